@@ -1,14 +1,53 @@
 use crate::{
     config,
-    config::{CommonConfig, Config},
+    config::{CommonConfig, Config, ImportPreview, Keymap},
     core::{
-        clipboard::{ClipBoardOprator, ImageDataDB},
-        database::{QueryReq, Record, SqliteDB},
-        handle::Handle,
+        actions::{self, ActionResult, CustomAction},
+        analytics::{self, EventCount, EventKind, EventStatsReq},
+        appearance::{self, Appearance, AppearancePatch},
+        audit::{AuditAction, AuditLog, AuditQueryReq},
+        backup::{self, BackupManifest, RestoreResult},
+        biometric,
+        clipboard::{ClipBoardOprator, ClipFormat, ImageDataDB, PasteStyle},
+        crash::{self, CrashReport},
+        database::{
+            HistoryPage, MergeStrategy, PageReq, QueryReq, Record, SqliteDB, TimelineDay,
+            TimelineReq, TrashItem,
+        },
+        deep_link,
+        diagnostics::{self, Diagnostics},
+        espanso::{self, EspansoSyncResult},
+        expansion::{self, ExpansionTrigger},
+        external_editor,
+        handle::{Handle, MsgTypeEnum},
+        integrity::{self, IntegrityReport, RepairResult},
+        macros::{self, ClipMacro, MacroStep},
+        onboarding::{self, OnboardingChoice, OnboardingStatus, PermissionStatus},
+        pairing::{self, PairingInfo},
+        palette, paste, pasteboard, phash, pin,
+        plugin::{self, PluginInfo},
+        preview::{self, ContentChunk},
+        purge::{self, PurgeResult},
+        rules::{self, CaptureRule, RuleInput, RuleOutcome},
+        screen_share,
+        screenshot::{self, ScreenshotMode},
+        script::{self, ScriptInfo},
+        snippet::{self, SnippetExportReq},
+        stats::{self, SourceCount, SourceStatsReq},
+        store::{self, ClipboardStore, StoreStats},
+        sync::{self, ApplyResult, SyncChange, SyncConflict, SyncRemote},
+        sync_client, sysopt, thumbnail, time_machine,
+        updater::{self, UpdateStatus},
+        vault::Vault,
+        window_manager::WindowType,
+        window_state,
     },
     log_err,
-    utils::json_util,
+    utils::{hotkey_util::HotkeyConflict, json_util},
+    wrap_err,
 };
+use serde_json::{Map, Value};
+use std::path::PathBuf;
 
 type CmdResult<T = ()> = Result<T, String>;
 
@@ -19,6 +58,7 @@ pub fn get_common_config() -> CmdResult<CommonConfig> {
 
 #[tauri::command]
 pub fn set_common_config(config: CommonConfig) -> CmdResult {
+    wrap_err!(config.validate())?;
     Config::common().draft().patch_config(config);
     Config::common().apply();
     log_err!(Config::common().data().save_file());
@@ -30,6 +70,24 @@ pub fn set_common_config(config: CommonConfig) -> CmdResult {
     Ok(())
 }
 
+#[tauri::command]
+pub fn get_settings() -> CmdResult<CommonConfig> {
+    Ok(Config::common().latest().clone())
+}
+
+#[tauri::command]
+pub fn get_keymap() -> CmdResult<Keymap> {
+    wrap_err!(config::current_keymap())
+}
+
+/// 校验通过后才写入配置，非法的值直接返回错误
+#[tauri::command]
+pub async fn patch_settings(patch: CommonConfig) -> CmdResult<CommonConfig> {
+    wrap_err!(patch.validate())?;
+    wrap_err!(config::modify_common_config(patch).await)?;
+    Ok(Config::common().latest().clone())
+}
+
 #[tauri::command]
 pub async fn change_language(language: String) -> CmdResult {
     let _ = config::modify_common_config(CommonConfig {
@@ -60,6 +118,23 @@ pub async fn change_auto_launch(enable: bool) -> CmdResult {
     Ok(())
 }
 
+/// 设置开机启动，失败时返回错误，配置保持不变
+#[tauri::command]
+pub async fn set_autostart(enable: bool) -> CmdResult {
+    wrap_err!(
+        config::modify_common_config(CommonConfig {
+            enable_auto_launch: Some(enable),
+            ..CommonConfig::default()
+        })
+        .await
+    )
+}
+
+#[tauri::command]
+pub fn is_autostart_enabled() -> CmdResult<bool> {
+    wrap_err!(sysopt::Sysopt::global().is_launch_enabled())
+}
+
 #[tauri::command]
 pub async fn change_theme_mode(theme_mode: String) -> CmdResult {
     let _ = config::modify_common_config(CommonConfig {
@@ -70,11 +145,66 @@ pub async fn change_theme_mode(theme_mode: String) -> CmdResult {
     Ok(())
 }
 
+#[tauri::command]
+pub fn get_appearance() -> CmdResult<Appearance> {
+    Ok(appearance::current())
+}
+
+#[tauri::command]
+pub async fn set_appearance(patch: AppearancePatch) -> CmdResult<Appearance> {
+    let patch = CommonConfig::from(patch);
+    wrap_err!(patch.validate())?;
+    wrap_err!(config::modify_common_config(patch).await)?;
+    Ok(appearance::current())
+}
+
 #[tauri::command]
 pub async fn change_hotkeys(hotkeys: Vec<String>) -> CmdResult {
-    let _ = config::modify_common_config(CommonConfig {
+    let patch = CommonConfig {
         hotkeys: Some(hotkeys),
         ..CommonConfig::default()
+    };
+    wrap_err!(patch.validate())?;
+    wrap_err!(config::modify_common_config(patch).await)
+}
+
+/// 最近一次注册失败的全局快捷键及可用的替代组合
+#[tauri::command]
+pub fn get_hotkey_conflicts() -> CmdResult<Vec<HotkeyConflict>> {
+    Ok(Handle::global().hotkey_conflicts.lock().clone())
+}
+
+/// 修改打开主窗口的全局快捷键，注册失败时返回错误并保留原快捷键
+#[tauri::command]
+pub async fn set_global_shortcut(keys: Vec<u32>) -> CmdResult {
+    let hotkey = format!(
+        "global-shortcut:{}",
+        keys.iter()
+            .map(|k| k.to_string())
+            .collect::<Vec<String>>()
+            .join("+")
+    );
+    let mut hotkeys = Config::common()
+        .latest()
+        .hotkeys
+        .clone()
+        .unwrap_or_default();
+    hotkeys.retain(|h| !h.starts_with("global-shortcut:"));
+    hotkeys.push(hotkey);
+    let patch = CommonConfig {
+        hotkeys: Some(hotkeys),
+        ..CommonConfig::default()
+    };
+    wrap_err!(patch.validate())?;
+    wrap_err!(config::modify_common_config(patch).await)
+}
+
+#[tauri::command]
+pub async fn change_audit_log(enable: bool, limit: Option<u32>) -> CmdResult {
+    let _ = config::modify_common_config(CommonConfig {
+        enable_audit_log: Some(enable),
+        audit_log_limit: limit,
+        ..CommonConfig::default()
     })
     .await;
     Ok(())
@@ -83,7 +213,10 @@ pub async fn change_hotkeys(hotkeys: Vec<String>) -> CmdResult {
 #[tauri::command]
 pub fn clear_data() -> bool {
     match SqliteDB::new().clear_data() {
-        Ok(()) => true,
+        Ok(()) => {
+            log_err!(thumbnail::clear());
+            true
+        }
         Err(_) => false,
     }
 }
@@ -91,7 +224,10 @@ pub fn clear_data() -> bool {
 #[tauri::command]
 pub fn insert_record(r: Record) -> bool {
     match SqliteDB::new().insert_record(r) {
-        Ok(_i) => true,
+        Ok(id) => {
+            Handle::notice_record(MsgTypeEnum::RecordAdded, id as u64);
+            true
+        }
         Err(e) => {
             println!("err:{}", e);
             false
@@ -102,7 +238,10 @@ pub fn insert_record(r: Record) -> bool {
 #[tauri::command]
 pub fn insert_if_not_exist(r: Record) -> bool {
     match SqliteDB::new().insert_if_not_exist(r) {
-        Ok(_i) => true,
+        Ok(res) => {
+            Handle::notice_insert_result(&res);
+            true
+        }
         Err(e) => {
             println!("err:{}", e);
             false
@@ -118,7 +257,10 @@ pub fn find_all_record() -> Vec<Record> {
 #[tauri::command]
 pub fn mark_favorite(id: u64) -> bool {
     match SqliteDB::new().mark_favorite(id) {
-        Ok(_i) => true,
+        Ok(_i) => {
+            Handle::notice_record(MsgTypeEnum::RecordUpdated, id);
+            true
+        }
         Err(e) => {
             println!("err:{}", e);
             false
@@ -126,10 +268,40 @@ pub fn mark_favorite(id: u64) -> bool {
     }
 }
 
+/// 锁定的记录不会被自动清理和清空，解锁后才能删除
+#[tauri::command]
+pub fn set_locked(ids: Vec<u64>, value: bool) -> CmdResult {
+    wrap_err!(SqliteDB::new().set_locked(&ids, value))?;
+    for id in ids {
+        Handle::notice_record(MsgTypeEnum::RecordUpdated, id);
+    }
+    Ok(())
+}
+
+/// 设置记录的备注，note 为空时清除，返回保存的备注
+#[tauri::command]
+pub fn set_note(id: u64, note: Option<String>) -> CmdResult<Option<String>> {
+    let note = wrap_err!(SqliteDB::new().set_note(id, note.as_deref()))?;
+    Handle::notice_record(MsgTypeEnum::RecordUpdated, id);
+    Ok(note)
+}
+
+#[tauri::command]
+pub fn set_favorite(ids: Vec<u64>, value: bool) -> CmdResult {
+    wrap_err!(SqliteDB::new().set_favorite(&ids, value))?;
+    for id in ids {
+        Handle::notice_record(MsgTypeEnum::RecordUpdated, id);
+    }
+    Ok(())
+}
+
 #[tauri::command]
 pub fn save_tags(id: u64, tags: String) -> bool {
     match SqliteDB::new().save_tags(id, tags) {
-        Ok(_i) => true,
+        Ok(_i) => {
+            Handle::notice_record(MsgTypeEnum::RecordUpdated, id);
+            true
+        }
         Err(e) => {
             println!("err:{}", e);
             false
@@ -137,10 +309,99 @@ pub fn save_tags(id: u64, tags: String) -> bool {
     }
 }
 
+/// 批量删除，宽限期内可以通过 undo_last_operation 恢复
+#[tauri::command]
+pub fn delete_by_ids(ids: Vec<u64>) -> CmdResult {
+    wrap_err!(SqliteDB::new().delete_by_ids(&ids))?;
+    log_err!(Handle::update_systray());
+    log_err!(Handle::notice_to_window(MsgTypeEnum::ChangeClipBoard, "ok"));
+    Ok(())
+}
+
+/// 粉碎删除：覆盖内容和缩略图后删除，无法撤销，返回删除的条数
+#[tauri::command]
+pub fn shred_records(ids: Vec<u64>) -> CmdResult<usize> {
+    let deleted = wrap_err!(SqliteDB::new().shred(&ids))?;
+    for id in &ids {
+        log_err!(thumbnail::shred(*id));
+    }
+    // VACUUM 较慢，放到后台执行
+    std::thread::spawn(|| log_err!(SqliteDB::new().compact()));
+    log_err!(Handle::update_systray());
+    log_err!(Handle::notice_to_window(MsgTypeEnum::ChangeClipBoard, "ok"));
+    Ok(deleted)
+}
+
+/// 粉碎内容匹配 pattern 的记录，regex 为 false 时按字符串匹配，dry_run 时只统计不删除
+#[tauri::command]
+pub fn purge_matching(pattern: String, regex: bool, dry_run: bool) -> CmdResult<PurgeResult> {
+    let res = wrap_err!(purge::purge_matching(&pattern, regex, dry_run))?;
+    if res.deleted > 0 {
+        std::thread::spawn(|| log_err!(SqliteDB::new().compact()));
+        log_err!(Handle::update_systray());
+        log_err!(Handle::notice_to_window(MsgTypeEnum::ChangeClipBoard, "ok"));
+    }
+    Ok(res)
+}
+
+/// 把剪切板恢复为 time（毫秒时间戳）时的内容，返回恢复的记录 id
+#[tauri::command]
+pub fn restore_clipboard_at(time: u64) -> CmdResult<u64> {
+    wrap_err!(time_machine::restore_at(time))
+}
+
+/// 恢复被 Lanaya 覆盖前的剪切板内容
+#[tauri::command]
+pub fn restore_previous_clipboard() -> CmdResult {
+    wrap_err!(time_machine::restore_previous())
+}
+
+/// 截图并放入剪切板和历史记录，取消时返回 false，截图工具运行期间不阻塞主线程
+#[tauri::command]
+pub async fn take_screenshot(mode: ScreenshotMode) -> CmdResult<bool> {
+    wrap_err!(screenshot::capture(mode))
+}
+
+/// 撤销最近一次删除或清空，返回恢复的条数
+#[tauri::command]
+pub fn undo_last_operation() -> CmdResult<usize> {
+    let restored = wrap_err!(SqliteDB::new().undo_last_delete())?;
+    if restored > 0 {
+        log_err!(Handle::update_systray());
+        log_err!(Handle::notice_to_window(MsgTypeEnum::ChangeClipBoard, "ok"));
+    }
+    Ok(restored)
+}
+
+/// 回收站中的记录
+#[tauri::command]
+pub fn list_trash() -> CmdResult<Vec<TrashItem>> {
+    wrap_err!(SqliteDB::new().list_trash())
+}
+
+/// 从回收站恢复记录，返回恢复的条数
+#[tauri::command]
+pub fn restore_from_trash(ids: Vec<u64>) -> CmdResult<usize> {
+    let restored = wrap_err!(SqliteDB::new().restore_from_trash(&ids))?;
+    if restored > 0 {
+        log_err!(Handle::update_systray());
+        log_err!(Handle::notice_to_window(MsgTypeEnum::ChangeClipBoard, "ok"));
+    }
+    Ok(restored)
+}
+
 #[tauri::command]
 pub fn delete_by_id(id: u64) -> bool {
-    match SqliteDB::new().delete_by_id(id) {
-        Ok(_i) => true,
+    let db = SqliteDB::new();
+    let record = db.find_list_item_by_id(id);
+    match db.delete_by_id(id) {
+        Ok(_i) => {
+            if let Ok(record) = record {
+                log_err!(Handle::notice_to_window(MsgTypeEnum::RecordDeleted, record));
+            }
+            log_err!(Handle::update_systray());
+            true
+        }
         Err(e) => {
             println!("err:{}", e);
             false
@@ -154,12 +415,36 @@ pub fn find_by_key(query: QueryReq) -> Vec<Record> {
 }
 
 #[tauri::command]
-pub fn delete_over_limit(limit: usize) -> bool {
+pub fn get_history_page(query: PageReq) -> CmdResult<HistoryPage> {
+    let db = SqliteDB::new();
+    // 翻页不算新的搜索
+    let searched = query.cursor.is_none() && query.key.as_ref().map_or(false, |k| !k.is_empty());
+    let page = wrap_err!(db.find_page(query))?;
+    if searched {
+        db.log_event(EventKind::Search);
+    }
+    Ok(page)
+}
+
+/// 按天分组的历史，用于时间线视图
+#[tauri::command]
+pub fn get_timeline(req: TimelineReq) -> CmdResult<Vec<TimelineDay>> {
+    wrap_err!(SqliteDB::new().timeline(req))
+}
+
+#[tauri::command]
+pub fn get_full_content(id: u64) -> CmdResult<String> {
+    let record = wrap_err!(SqliteDB::new().find_by_id(id))?;
+    wrap_err!(Vault::global().reveal(&record))
+}
+
+#[tauri::command]
+pub fn delete_over_limit(limit: usize) -> usize {
     match SqliteDB::new().delete_over_limit(limit) {
         Ok(res) => res,
         Err(e) => {
             println!("err:{}", e);
-            false
+            0
         }
     }
 }
@@ -168,13 +453,39 @@ pub fn delete_over_limit(limit: usize) -> bool {
 pub fn write_to_clip(id: u64) -> bool {
     let record = SqliteDB::new().find_by_id(id);
     match record {
-        Ok(r) => {
-            if r.data_type == "text" {
-                let _ = ClipBoardOprator::set_text(r.content);
+        Ok(mut r) => {
+            if r.is_secure {
+                match Vault::global().reveal(&r) {
+                    Ok(content) => r.content = content,
+                    Err(e) => {
+                        println!("err:{}", e);
+                        return false;
+                    }
+                }
+            }
+            time_machine::remember_current();
+            // 目标应用设置了纯文本时不写回 html、rtf 等格式
+            let plain = r.data_type == "text"
+                && paste::default_style(paste::target_app().as_deref()) == Some(PasteStyle::Plain);
+            let restored = !plain
+                && pasteboard::restore(&SqliteDB::new(), &r).unwrap_or_else(|e| {
+                    println!("err:{}", e);
+                    false
+                });
+            if restored {
+                // 已按复制时的原始类型写回
+            } else if r.data_type == "text" {
+                let _ = match r.is_secure {
+                    true => ClipBoardOprator::set_secure_text(r.content),
+                    false => ClipBoardOprator::set_text(r.content),
+                };
             } else if r.data_type == "image" {
                 let image_data: ImageDataDB = json_util::parse(&r.content).unwrap();
                 let _ = ClipBoardOprator::set_image(image_data);
             }
+            let db = SqliteDB::new();
+            db.audit(AuditAction::Pasted, id);
+            db.log_event(EventKind::Paste);
             true
         }
         Err(e) => {
@@ -183,3 +494,543 @@ pub fn write_to_clip(id: u64) -> bool {
         }
     }
 }
+
+#[tauri::command]
+pub fn copy_record(id: u64, format: ClipFormat) -> CmdResult {
+    wrap_err!(ClipBoardOprator::copy_by_id(id, Some(format)))
+}
+
+/// 按本次指定的粘贴格式复制，style 为空时使用目标应用的默认格式，返回实际使用的格式
+#[tauri::command]
+pub fn copy_record_as(id: u64, style: Option<PasteStyle>) -> CmdResult<PasteStyle> {
+    wrap_err!(ClipBoardOprator::copy_with_style(id, style))
+}
+
+/// 最近一次打开列表窗口前的前台应用，设置页中用于填写 paste_style_by_app
+#[tauri::command]
+pub fn get_paste_target_app() -> Option<String> {
+    paste::target_app()
+}
+
+#[tauri::command]
+pub fn unlock_vault(passphrase: String) -> CmdResult {
+    wrap_err!(Vault::global().unlock(&passphrase))
+}
+
+#[tauri::command]
+pub fn lock_vault() -> CmdResult {
+    Vault::global().lock();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn is_vault_unlocked() -> bool {
+    Vault::global().is_unlocked()
+}
+
+#[tauri::command]
+pub fn is_biometric_available() -> bool {
+    biometric::is_available()
+}
+
+#[tauri::command]
+pub async fn enable_biometric_unlock(window: tauri::Window) -> CmdResult {
+    wrap_err!(Vault::global().enable_biometric(&window).await)
+}
+
+#[tauri::command]
+pub async fn disable_biometric_unlock() -> CmdResult {
+    wrap_err!(Vault::global().disable_biometric().await)
+}
+
+#[tauri::command]
+pub async fn unlock_vault_with_biometric(window: tauri::Window) -> CmdResult {
+    wrap_err!(Vault::global().unlock_with_biometric(&window).await)
+}
+
+#[tauri::command]
+pub fn set_record_secure(id: u64, secure: bool) -> CmdResult {
+    match secure {
+        true => wrap_err!(Vault::global().secure_record(id))?,
+        false => wrap_err!(Vault::global().unsecure_record(id))?,
+    }
+    Handle::notice_record(MsgTypeEnum::RecordUpdated, id);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn reveal_record(id: u64) -> CmdResult<String> {
+    let record = wrap_err!(SqliteDB::new().find_by_id(id))?;
+    wrap_err!(Vault::global().reveal(&record))
+}
+
+#[tauri::command]
+pub fn find_audit_log(query: AuditQueryReq) -> CmdResult<Vec<AuditLog>> {
+    wrap_err!(SqliteDB::new().find_audit_log(query))
+}
+
+#[tauri::command]
+pub fn clear_audit_log() -> CmdResult {
+    wrap_err!(SqliteDB::new().clear_audit_log())
+}
+
+#[tauri::command]
+pub fn toggle_preview_window() {
+    Handle::open_window(WindowType::Preview);
+}
+
+#[tauri::command]
+pub fn preview_record(id: u64) {
+    preview::show_record(id);
+}
+
+#[tauri::command]
+pub async fn read_content_chunk(id: u64, offset: u64, len: u64) -> CmdResult<ContentChunk> {
+    wrap_err!(preview::read_chunk(id, offset, len))
+}
+
+#[tauri::command]
+pub async fn palette_search(key: String, request_id: u64) -> CmdResult {
+    wrap_err!(palette::search(key, request_id))
+}
+
+#[tauri::command]
+pub fn palette_execute(kind: String, id: String) -> CmdResult {
+    wrap_err!(palette::execute(&kind, &id))
+}
+
+#[tauri::command]
+pub fn get_plugins() -> CmdResult<Vec<PluginInfo>> {
+    Ok(plugin::plugin_infos())
+}
+
+#[tauri::command]
+pub fn get_capture_scripts() -> CmdResult<Vec<ScriptInfo>> {
+    wrap_err!(script::list_scripts())
+}
+
+/// 重新扫描插件目录
+#[tauri::command]
+pub fn reload_plugins() -> CmdResult<Vec<PluginInfo>> {
+    wrap_err!(plugin::load_all())?;
+    Ok(plugin::plugin_infos())
+}
+
+#[tauri::command]
+pub fn take_pending_search() -> Option<String> {
+    deep_link::take_pending_search()
+}
+
+/// 启动时数据库在后台初始化，就绪前前端不发起查询，避免阻塞主线程
+#[tauri::command]
+pub fn is_database_ready() -> bool {
+    SqliteDB::is_ready()
+}
+
+/// 首次启动时前端据此决定是否显示设置向导
+#[tauri::command]
+pub fn get_onboarding_status() -> OnboardingStatus {
+    onboarding::status()
+}
+
+#[tauri::command]
+pub fn check_permissions() -> Vec<PermissionStatus> {
+    onboarding::check_permissions()
+}
+
+#[tauri::command]
+pub fn open_permission_settings(name: String) -> CmdResult {
+    wrap_err!(onboarding::open_permission_settings(&name))
+}
+
+#[tauri::command]
+pub async fn complete_onboarding(choice: OnboardingChoice) -> CmdResult {
+    wrap_err!(onboarding::complete(choice).await)
+}
+
+/// 导出配置（不含访问令牌等密钥），用于迁移到其它机器
+#[tauri::command]
+pub fn export_settings(path: String) -> CmdResult {
+    wrap_err!(config::export_settings(&PathBuf::from(path)))
+}
+
+/// dry_run 时只返回会修改的配置项
+#[tauri::command]
+pub async fn import_settings(path: String, dry_run: bool) -> CmdResult<ImportPreview> {
+    wrap_err!(config::import_settings(&PathBuf::from(path), dry_run).await)
+}
+
+/// 时间范围内复制最多的来源应用
+#[tauri::command]
+pub fn get_top_apps(req: SourceStatsReq) -> CmdResult<Vec<SourceCount>> {
+    wrap_err!(stats::top_apps(&req))
+}
+
+/// 时间范围内复制最多的链接域名
+#[tauri::command]
+pub fn get_top_domains(req: SourceStatsReq) -> CmdResult<Vec<SourceCount>> {
+    wrap_err!(stats::top_domains(&req))
+}
+
+/// 弹窗打开时查询是否需要隐藏内容预览，之后的变化通过事件推送
+#[tauri::command]
+pub fn should_hide_previews() -> bool {
+    screen_share::should_hide_previews()
+}
+
+#[tauri::command]
+pub fn link_records(a: u64, b: u64) -> CmdResult {
+    wrap_err!(SqliteDB::new().link_records(a, b))?;
+    preview::show_record(a);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn unlink_records(a: u64, b: u64) -> CmdResult {
+    wrap_err!(SqliteDB::new().unlink_records(a, b))?;
+    preview::show_record(a);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_linked_records(id: u64) -> CmdResult<Vec<Record>> {
+    wrap_err!(SqliteDB::new().find_linked_records(id))
+}
+
+/// 把记录和关联的记录一起复制，文本之间用 separator 分隔
+#[tauri::command]
+pub fn copy_with_links(id: u64, separator: Option<String>) -> CmdResult {
+    wrap_err!(ClipBoardOprator::copy_with_links(id, separator))
+}
+
+/// 清理跟踪参数前的原始链接，没有清理过时返回 None，secure 记录需要先解锁
+#[tauri::command]
+pub fn get_original_content(id: u64) -> CmdResult<Option<String>> {
+    let record = wrap_err!(SqliteDB::new().find_by_id(id))?;
+    wrap_err!(Vault::global().reveal_original(&record))
+}
+
+/// 规则测试器：按 rules（为空时使用当前配置）匹配给定内容，不修改记录
+#[tauri::command]
+pub fn test_capture_rules(
+    input: RuleInput,
+    rules: Option<Vec<CaptureRule>>,
+) -> CmdResult<RuleOutcome> {
+    wrap_err!(rules::dry_run(&input, rules))
+}
+
+/// 把数据库、图片和配置打包成一个备份文件
+#[tauri::command]
+pub fn create_backup(path: PathBuf) -> CmdResult<BackupManifest> {
+    wrap_err!(backup::create_backup(&path))
+}
+
+/// strategy 为空时替换当前的全部记录
+#[tauri::command]
+pub fn restore_backup(path: PathBuf, strategy: Option<MergeStrategy>) -> CmdResult<RestoreResult> {
+    wrap_err!(backup::restore_backup(
+        &path,
+        strategy.unwrap_or(MergeStrategy::Replace)
+    ))
+}
+
+/// 合并其它设备发来的记录状态，返回新增、更新的数量和产生的冲突数
+#[tauri::command]
+pub fn apply_sync_changes(device: String, changes: Vec<SyncChange>) -> CmdResult<ApplyResult> {
+    wrap_err!(sync::apply_remote(changes, &device))
+}
+
+/// 需要发给该设备的记录，只包含设备同步范围内的记录，after 为上一页最后一条的 md5
+#[tauri::command]
+pub fn get_sync_changes(
+    device: String,
+    since: u64,
+    after: Option<String>,
+    limit: Option<usize>,
+) -> CmdResult<Vec<SyncChange>> {
+    wrap_err!(sync::changes_for(
+        &device,
+        since,
+        &after.unwrap_or_default(),
+        limit
+    ))
+}
+
+/// 待手动处理的同步冲突
+#[tauri::command]
+pub fn list_sync_conflicts() -> CmdResult<Vec<SyncConflict>> {
+    wrap_err!(sync::list_conflicts())
+}
+
+/// use_remote 为 true 时采用对方设备的值，否则保留本地的值
+#[tauri::command]
+pub fn resolve_sync_conflict(id: u64, use_remote: bool) -> CmdResult {
+    wrap_err!(sync::resolve_conflict(id, use_remote))
+}
+
+/// 生成配对二维码，需先开启同步接口
+#[tauri::command]
+pub fn start_pairing() -> CmdResult<PairingInfo> {
+    wrap_err!(pairing::start_pairing())
+}
+
+#[tauri::command]
+pub fn cancel_pairing() -> CmdResult {
+    pairing::cancel_pairing();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn unpair_sync_device(id: String) -> CmdResult {
+    wrap_err!(pairing::unpair(&id).await)
+}
+
+/// 把一条记录发送到指定设备的剪切板
+#[tauri::command]
+pub fn send_to_device(device_id: String, record_id: u64) -> CmdResult {
+    wrap_err!(pairing::send_to_device(&device_id, record_id))
+}
+
+/// 用另一台电脑设置页中的配对码配对，之后定时取对方发送到本机的记录
+#[tauri::command]
+pub async fn pair_with_remote(payload: String, name: String) -> CmdResult<SyncRemote> {
+    let res = tauri::async_runtime::spawn_blocking(move || sync_client::pair_with(&payload, &name))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|res| res);
+    wrap_err!(res)
+}
+
+#[tauri::command]
+pub async fn remove_sync_remote(device_id: String) -> CmdResult {
+    wrap_err!(sync_client::remove(&device_id).await)
+}
+
+/// 浏览器扩展推送时记录的页面地址
+#[tauri::command]
+pub fn get_source_url(id: u64) -> CmdResult<Option<String>> {
+    wrap_err!(SqliteDB::new().find_source_url(id))
+}
+
+/// 视觉上相同的其它图片，按相似程度排序
+#[tauri::command]
+pub fn find_similar_images(id: u64) -> CmdResult<Vec<Record>> {
+    wrap_err!(phash::similar_images(id))
+}
+
+/// 压缩旧版本保存的长文本，返回压缩的记录数
+#[tauri::command]
+pub fn compress_existing_content() -> CmdResult<usize> {
+    wrap_err!(SqliteDB::new().compress_existing())
+}
+
+/// 检查数据库的完整性和孤立的数据
+#[tauri::command]
+pub fn check_database_integrity() -> CmdResult<IntegrityReport> {
+    wrap_err!(integrity::check())
+}
+
+/// 修复数据库，损坏严重时把能读取的记录复制到新数据库
+#[tauri::command]
+pub fn repair_database() -> CmdResult<RepairResult> {
+    wrap_err!(integrity::repair())
+}
+
+/// 按日期或小时分组的复制、搜索、粘贴次数
+#[tauri::command]
+pub fn get_event_stats(req: EventStatsReq) -> CmdResult<Vec<EventCount>> {
+    wrap_err!(analytics::event_counts(&req))
+}
+
+/// 删除全部统计事件，返回删除的条数
+#[tauri::command]
+pub fn purge_analytics() -> CmdResult<usize> {
+    wrap_err!(analytics::purge())
+}
+
+/// 全部记录、收藏和图片的条数
+#[tauri::command]
+pub fn get_store_stats() -> CmdResult<StoreStats> {
+    wrap_err!(store::open().stats())
+}
+
+/// 本地保存的崩溃报告，最新的在前
+#[tauri::command]
+pub fn get_crash_reports() -> CmdResult<Vec<CrashReport>> {
+    wrap_err!(crash::list())
+}
+
+/// 删除本地的崩溃报告，返回删除的个数
+#[tauri::command]
+pub fn clear_crash_reports() -> CmdResult<usize> {
+    wrap_err!(crash::clear())
+}
+
+/// 当前的更新渠道和最近一次检查的结果
+#[tauri::command]
+pub fn get_update_status() -> UpdateStatus {
+    updater::status()
+}
+
+/// 在设置的渠道（update_channel）上检查更新
+#[tauri::command]
+pub async fn check_for_update() -> CmdResult<UpdateStatus> {
+    wrap_err!(updater::check().await)
+}
+
+/// 下载并安装更新，Windows 上会退出应用
+#[tauri::command]
+pub async fn install_update() -> CmdResult {
+    wrap_err!(updater::install().await)
+}
+
+/// 推迟提醒更新，hours 为 0 时取消推迟
+#[tauri::command]
+pub fn defer_update(hours: u64) {
+    updater::defer(hours)
+}
+
+/// 把收藏的文本导出为 VS Code、espanso 或 TextExpander 的片段，返回导出的条数
+#[tauri::command]
+pub fn export_snippets(req: SnippetExportReq) -> CmdResult<usize> {
+    wrap_err!(snippet::export(&req))
+}
+
+/// 立即和 espanso 同步一次，未设置 espanso_sync 时返回 None
+#[tauri::command]
+pub fn sync_espanso() -> CmdResult<Option<EspansoSyncResult>> {
+    wrap_err!(espanso::sync_now())
+}
+
+/// 设置记录的文本扩展触发词，trigger 为空时取消
+#[tauri::command]
+pub fn set_expansion_trigger(id: u64, trigger: String) -> CmdResult {
+    let trigger = trigger.trim();
+    if !trigger.is_empty() {
+        wrap_err!(expansion::validate_trigger(trigger))?;
+    }
+    wrap_err!(SqliteDB::new().set_expansion_trigger(id, trigger))?;
+    expansion::reload();
+    Ok(())
+}
+
+/// 已设置的文本扩展触发词
+#[tauri::command]
+pub fn get_expansion_triggers() -> CmdResult<Vec<ExpansionTrigger>> {
+    let triggers = wrap_err!(SqliteDB::new().find_expansion_triggers())?;
+    Ok(triggers
+        .into_iter()
+        .map(|(id, trigger)| ExpansionTrigger { id, trigger })
+        .collect())
+}
+
+/// 开始录制剪切板宏，之后复制的记录依次作为宏的步骤
+#[tauri::command]
+pub fn start_macro_recording(name: String) -> CmdResult {
+    wrap_err!(macros::start_recording(name))
+}
+
+/// 结束录制并保存宏
+#[tauri::command]
+pub fn stop_macro_recording() -> CmdResult<ClipMacro> {
+    wrap_err!(macros::stop_recording())
+}
+
+#[tauri::command]
+pub fn cancel_macro_recording() {
+    macros::cancel_recording()
+}
+
+/// 保存编辑后的宏（如调整顺序和每一步的等待时间），同名时覆盖
+#[tauri::command]
+pub fn save_macro(name: String, steps: Vec<MacroStep>) -> CmdResult<ClipMacro> {
+    wrap_err!(macros::save(&name, &steps))
+}
+
+#[tauri::command]
+pub fn get_macros() -> CmdResult<Vec<ClipMacro>> {
+    wrap_err!(SqliteDB::new().find_macros())
+}
+
+#[tauri::command]
+pub fn delete_macro(id: u64) -> CmdResult {
+    wrap_err!(SqliteDB::new().delete_macro(id))
+}
+
+/// 依次复制宏中的记录并粘贴到之前的应用
+#[tauri::command]
+pub fn replay_macro(id: u64) -> CmdResult {
+    wrap_err!(macros::replay(id))
+}
+
+/// 按选择的顺序合并多条文本记录后复制，separator 为空时使用 multi_paste_separator
+/// auto_paste 为 true 时隐藏窗口并粘贴到之前的应用，返回合并的条数
+#[tauri::command]
+pub fn paste_records(
+    ids: Vec<u64>,
+    separator: Option<String>,
+    auto_paste: Option<bool>,
+) -> CmdResult<usize> {
+    let count = wrap_err!(ClipBoardOprator::copy_joined(&ids, separator))?;
+    if auto_paste.unwrap_or(false) {
+        if let Some(window) = Handle::global().get_window() {
+            let _ = window.hide();
+        }
+        paste::paste_later();
+    }
+    Ok(count)
+}
+
+/// 适用于该类型记录的自定义操作，data_type 为空时返回全部
+#[tauri::command]
+pub fn get_custom_actions(data_type: Option<String>) -> CmdResult<Vec<CustomAction>> {
+    Ok(actions::list(data_type.as_deref()))
+}
+
+/// 对记录执行自定义操作，命令运行期间不阻塞主线程
+#[tauri::command]
+pub async fn run_custom_action(name: String, id: u64) -> CmdResult<ActionResult> {
+    wrap_err!(actions::run(&name, id))
+}
+
+/// 在外部编辑器中打开记录，保存后写回；as_new 为 true 时保存为新记录
+#[tauri::command]
+pub fn open_in_editor(id: u64, as_new: Option<bool>) -> CmdResult {
+    wrap_err!(external_editor::open(id, as_new.unwrap_or(false)))
+}
+
+/// 固定历史窗口，返回切换后的状态
+#[tauri::command]
+pub fn toggle_pin() -> CmdResult<bool> {
+    wrap_err!(pin::toggle())
+}
+
+#[tauri::command]
+pub fn is_window_pinned() -> CmdResult<bool> {
+    Ok(pin::is_pinned())
+}
+
+/// 前端保存的窗口布局，如最近的过滤条件
+#[tauri::command]
+pub fn get_window_layout() -> CmdResult<Map<String, Value>> {
+    Ok(window_state::layout())
+}
+
+/// 合并到已保存的布局中，值为 null 时删除该项
+#[tauri::command]
+pub fn save_window_layout(layout: Map<String, Value>) -> CmdResult {
+    window_state::save_layout(layout);
+    Ok(())
+}
+
+/// 运行环境、权限、数据库、快捷键和剪切板监听的状态，用于排查问题
+/// 传入 path 时同时保存为 JSON 文件，方便附在问题反馈中
+#[tauri::command]
+pub async fn run_diagnostics(path: Option<String>) -> CmdResult<Diagnostics> {
+    let report = diagnostics::run();
+    if let Some(path) = path {
+        let content = wrap_err!(json_util::stringfy(&report))?;
+        wrap_err!(std::fs::write(path, content))?;
+    }
+    Ok(report)
+}
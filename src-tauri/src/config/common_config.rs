@@ -1,21 +1,176 @@
-use crate::utils::{dirs, json_util};
-use anyhow::Result;
+use super::keymap;
+use crate::core::actions::{self, CustomAction};
+use crate::core::clipboard::PasteStyle;
+use crate::core::retention::RECORD_TYPES;
+use crate::core::rules::CaptureRule;
+use crate::core::sound::SOUND_EVENTS;
+use crate::core::sync::{SyncDevice, SyncRemote};
+use crate::core::url_clean;
+use crate::core::{
+    capture_guard, capture_notify, espanso::EspansoSync, expansion, handle::Handle, i18n, macros,
+    script, sync, trigger, updater, webhook::Webhook,
+};
+use crate::utils::{dirs, hotkey_util, json_util};
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct CommonConfig {
     // i18n
     pub language: Option<String>,
-    /// `light` or `dark` or  todo `system`
+    /// `light` or `dark` or `system`（跟随系统）
     pub theme_mode: Option<String>,
+    /// 强调色，`#rrggbb`
+    pub accent_color: Option<String>,
+    /// 列表字体大小（px）
+    pub font_size: Option<u32>,
+    /// 窗口不透明度，0.3 ~ 1.0
+    pub window_opacity: Option<f64>,
     /// can the app auto startup
     pub enable_auto_launch: Option<bool>,
+    /// 是否已完成首次启动的设置向导，只有新建配置文件时为 false
+    pub onboarding_completed: Option<bool>,
     /// hotkey map
-    /// format: {func},{key}
+    /// format: {func}:{key}+{key}
+    /// func: global-shortcut, quick-search, paste-plain-text, toggle-monitoring,
+    /// clear-clipboard, favorite-latest, open-snippets, restore-clipboard, screenshot-region,
+    /// screenshot-window, toggle-pin, clear-history
     pub hotkeys: Option<Vec<String>>,
     // pub font_family: Option<String>,
     // pub font_size: Option<String>,
     pub record_limit: Option<u32>,
+    /// 按类型单独限制条数，如 {"image": 100}，和 record_limit 同时生效
+    pub type_record_limits: Option<HashMap<String, u32>>,
+    /// 定时彻底删除回收站中的记录，关闭后删除的记录一直保留
+    pub auto_purge_trash: Option<bool>,
+    /// 删除的记录在回收站中保留的天数，为 0 时撤销期限（1 分钟）过后即彻底删除
+    pub trash_retention_days: Option<u32>,
+    /// 是否记录审计日志
+    pub enable_audit_log: Option<bool>,
+    /// 审计日志最多保留条数
+    pub audit_log_limit: Option<u32>,
+    /// 记录复制、搜索、粘贴的时间用于统计，只保存在本地
+    pub enable_analytics: Option<bool>,
+    /// 崩溃报告只保存在本地，设置后在下次启动时提交到该地址，为空时不提交
+    pub crash_report_url: Option<String>,
+    /// 更新渠道：`stable` / `beta`
+    pub update_channel: Option<String>,
+    /// 后台定时检查更新
+    pub auto_check_updates: Option<bool>,
+    /// 检查到更新时在后台下载安装，重启后生效（Windows 上只提示）
+    pub auto_download_updates: Option<bool>,
+    /// 安全输入（密码框、UAC）或锁屏时暂停记录
+    pub pause_on_secure_input: Option<bool>,
+    /// 共享或录制屏幕时暂停记录剪切板
+    pub pause_on_screen_share: Option<bool>,
+    /// 共享或录制屏幕时隐藏弹窗中的内容预览
+    pub hide_previews_on_screen_share: Option<bool>,
+    /// 额外视为正在共享屏幕的进程名
+    pub screen_share_processes: Option<Vec<String>>,
+    /// 是否允许使用 Touch ID / Windows Hello 解锁
+    pub enable_biometric_unlock: Option<bool>,
+    /// 托盘菜单中展示的最近记录条数，0 表示不展示
+    pub tray_recent_count: Option<u32>,
+    /// 主窗口弹出位置：`center` or `cursor` or `caret` or `remember`
+    pub popup_position: Option<String>,
+    /// 快速粘贴第 1~9 条记录的修饰键，如 [17, 16] 即 Ctrl+Shift+1~9，为空表示关闭
+    pub quick_paste_modifiers: Option<Vec<u32>>,
+    /// 快速粘贴时是否自动粘贴到当前应用，否则只复制
+    pub quick_paste_auto_paste: Option<bool>,
+    /// 高级触发方式：连按两次或和弦，format: double-tap:{func}:{code}+{code} / chord:{func}:{code}+{code}
+    pub triggers: Option<Vec<String>>,
+    /// 输入记录的触发词（如 ;addr）后替换为记录的内容，需要辅助功能权限
+    pub enable_text_expansion: Option<bool>,
+    /// 开启文本扩展的平台：`macos` / `windows` / `linux`，配置文件在多台电脑间同步时可以只在部分平台开启
+    pub text_expansion_platforms: Option<Vec<String>>,
+    /// 回放剪切板宏时每一步粘贴后等待的毫秒数
+    pub macro_step_delay: Option<u64>,
+    /// 同时粘贴多条记录时的分隔符，默认为换行
+    pub multi_paste_separator: Option<String>,
+    /// 各应用默认的粘贴格式，key 为应用名称（不区分大小写），如 {"iTerm2": "plain"}
+    pub paste_style_by_app: Option<HashMap<String, PasteStyle>>,
+    /// 列表窗口的按键方案：`default` or `vim` or `emacs`
+    pub keymap_profile: Option<String>,
+    /// 在按键方案基础上自定义的按键，format: {func}:{key}+{key}
+    pub keymap_overrides: Option<Vec<String>>,
+    /// 是否与 Windows 剪切板历史 / 云剪切板互通
+    pub enable_cloud_clipboard: Option<bool>,
+    /// 是否开启本地 HTTP 接口，仅监听 127.0.0.1
+    pub enable_http_api: Option<bool>,
+    /// 本地 HTTP 接口端口
+    pub http_api_port: Option<u16>,
+    /// 本地 HTTP 接口的访问令牌，为空时启动时自动生成
+    pub http_api_token: Option<String>,
+    /// 是否开启本地进程间通信（Unix socket / 命名管道），浏览器扩展、osc52 和 bridge 需要开启，修改后重启生效
+    pub enable_ipc: Option<bool>,
+    /// 新记录匹配过滤条件时推送的 webhook
+    pub webhooks: Option<Vec<Webhook>>,
+    /// 在记录的右键菜单中执行的自定义命令，不经过 shell，替换后以 - 开头的参数拒绝执行
+    pub custom_actions: Option<Vec<CustomAction>>,
+    /// 在外部编辑器中打开记录的命令，如 `code --wait`，{file} 为临时文件的路径，为空时使用系统默认的应用
+    pub external_editor: Option<String>,
+    /// 同步到 espanso 的收藏，为空时不同步
+    pub espanso_sync: Option<EspansoSync>,
+    /// 按顺序对新复制的文本执行的脚本（scripts 目录下的 {name}.rhai），不在列表中的脚本不执行
+    pub capture_scripts: Option<Vec<String>>,
+    /// 自动分类规则，按顺序匹配新复制的记录
+    pub capture_rules: Option<Vec<CaptureRule>>,
+    /// 复制和粘贴的次数合计达到多少次时提示收藏，0 表示不提示
+    pub favorite_suggestion_threshold: Option<u64>,
+    /// 提示收藏时是否同时发送系统通知
+    pub favorite_suggestion_notification: Option<bool>,
+    /// 复制链接时去掉 utm_* 等跟踪参数
+    pub enable_url_cleaning: Option<bool>,
+    /// 额外去掉的链接参数，以 * 结尾时按前缀匹配
+    pub url_clean_params: Option<Vec<String>>,
+    /// 单个脚本的执行时间上限（毫秒）
+    pub script_timeout_ms: Option<u32>,
+    /// 记录到新内容时是否发送系统通知
+    pub enable_capture_notification: Option<bool>,
+    /// 发送通知的记录类型：`text` / `image`
+    pub capture_notification_types: Option<Vec<String>>,
+    /// 文本包含任一关键字（不区分大小写）时才通知，为空时不限制
+    pub capture_notification_keywords: Option<Vec<String>>,
+    /// 免打扰时段，format: HH:MM-HH:MM，可以跨过零点，如 22:00-07:00
+    pub quiet_hours: Option<String>,
+    /// 记录到新内容和快速粘贴时是否播放提示音
+    pub enable_sound_feedback: Option<bool>,
+    /// 播放提示音的事件：`capture` / `paste`
+    pub sound_events: Option<Vec<String>>,
+    /// 提示音音量，0 ~ 100
+    pub sound_volume: Option<u32>,
+    /// 同步时收藏状态冲突的处理方式：`last_writer_wins` / `union` / `manual`
+    pub sync_favorite_policy: Option<String>,
+    /// 同步时标签冲突的处理方式，`union` 合并两边的标签
+    pub sync_tag_policy: Option<String>,
+    /// 其它设备删除了本地仍保留的记录时的处理方式，`union` 表示不删除
+    pub sync_deletion_policy: Option<String>,
+    /// 已配对的设备及各自的同步范围
+    pub sync_devices: Option<Vec<SyncDevice>>,
+    /// 本机配对过的其它电脑，定时取对方发送到本机剪切板的记录
+    pub sync_remotes: Option<Vec<SyncRemote>>,
+    /// 是否开启供手机等设备配对和同步的局域网接口
+    pub enable_sync_server: Option<bool>,
+    /// 同步接口的端口
+    pub sync_server_port: Option<u16>,
+    /// 同步接口监听的本机地址，为空时使用连接局域网的网卡地址，不监听所有网卡
+    pub sync_server_address: Option<String>,
+    /// 新复制的图片和已有图片的感知哈希相近时不新增记录，只把已有记录顶到最前
+    pub image_dedupe: Option<bool>,
+    /// 感知哈希相差的位数不超过该值时视为相同的图片，0 ~ 32
+    pub image_similarity_threshold: Option<u32>,
+    /// 单次复制内容的大小上限（MB），图片按未压缩的像素数据计算，0 表示不限制，最大 10240
+    pub max_capture_size_mb: Option<u64>,
+    /// 超过大小上限时的处理：`skip` 不记录 / `stub` 只记录一条占位文本
+    pub oversize_action: Option<String>,
+    /// 是否用 zstd 压缩保存较长的文本，读取时自动解压
+    pub compress_large_content: Option<bool>,
+    /// 数据库所在的目录，放在同步文件夹中可以和其它电脑共用，为空时在应用目录下，重启后生效
+    pub database_dir: Option<String>,
+    /// 历史窗口关闭多少分钟后释放缓存，0 为不释放
+    pub idle_release_minutes: Option<u64>,
 }
 
 impl CommonConfig {
@@ -28,20 +183,99 @@ impl CommonConfig {
                 template.merge(config);
                 template
             }
-            Err(_) => Self::template(),
+            Err(_) => Self::first_launch_template(),
+        }
+    }
+
+    /// 还没有配置文件时即为首次启动
+    pub fn first_launch_template() -> Self {
+        let exists = dirs::config_path().map(|p| p.exists()).unwrap_or(true);
+        Self {
+            onboarding_completed: Some(exists),
+            ..Self::template()
         }
     }
 
     pub fn template() -> Self {
         // todo windows 快捷键不一样，需要兼容
         Self {
-            language: match cfg!(feature = "default-meta") {
+            // 首次启动时跟随系统语言
+            language: i18n::system_language().or(match cfg!(feature = "default-meta") {
                 false => Some("zh".into()),
                 true => Some("en".into()),
-            },
+            }),
             theme_mode: Some("light".into()),
+            accent_color: Some("#3b82f6".into()),
+            font_size: Some(14),
+            window_opacity: Some(1.0),
             enable_auto_launch: Some(false),
+            onboarding_completed: None,
             record_limit: Some(100),
+            type_record_limits: None,
+            auto_purge_trash: Some(true),
+            trash_retention_days: Some(0),
+            enable_audit_log: Some(false),
+            audit_log_limit: Some(10000),
+            enable_analytics: Some(false),
+            crash_report_url: None,
+            update_channel: Some("stable".into()),
+            auto_check_updates: Some(true),
+            auto_download_updates: Some(false),
+            pause_on_secure_input: Some(true),
+            pause_on_screen_share: Some(false),
+            hide_previews_on_screen_share: Some(false),
+            screen_share_processes: Some(vec![]),
+            enable_biometric_unlock: Some(false),
+            tray_recent_count: Some(10),
+            popup_position: Some("cursor".into()),
+            quick_paste_modifiers: Some(vec![]),
+            quick_paste_auto_paste: Some(true),
+            triggers: Some(vec![]),
+            enable_text_expansion: Some(false),
+            text_expansion_platforms: Some(vec!["macos".into(), "windows".into(), "linux".into()]),
+            macro_step_delay: Some(300),
+            multi_paste_separator: Some("\n".into()),
+            paste_style_by_app: None,
+            keymap_profile: Some("default".into()),
+            keymap_overrides: Some(vec![]),
+            enable_cloud_clipboard: Some(false),
+            enable_http_api: Some(false),
+            http_api_port: Some(18090),
+            http_api_token: None,
+            enable_ipc: Some(false),
+            webhooks: Some(vec![]),
+            custom_actions: Some(vec![]),
+            external_editor: None,
+            espanso_sync: None,
+            capture_scripts: Some(vec![]),
+            capture_rules: Some(vec![]),
+            favorite_suggestion_threshold: Some(5),
+            favorite_suggestion_notification: Some(false),
+            enable_url_cleaning: Some(false),
+            url_clean_params: Some(vec![]),
+            script_timeout_ms: Some(100),
+            enable_capture_notification: Some(false),
+            capture_notification_types: Some(vec!["text".into(), "image".into()]),
+            capture_notification_keywords: Some(vec![]),
+            quiet_hours: None,
+            enable_sound_feedback: Some(false),
+            sound_events: Some(vec!["capture".into(), "paste".into()]),
+            sound_volume: Some(50),
+            sync_favorite_policy: Some("last_writer_wins".into()),
+            sync_tag_policy: Some("union".into()),
+            sync_deletion_policy: Some("manual".into()),
+            sync_devices: Some(vec![]),
+            sync_remotes: None,
+            enable_sync_server: Some(false),
+            sync_server_port: Some(18091),
+            sync_server_address: None,
+            image_dedupe: Some(false),
+            image_similarity_threshold: Some(6),
+            max_capture_size_mb: Some(100),
+            oversize_action: Some("stub".into()),
+            compress_large_content: Some(true),
+            database_dir: None,
+            idle_release_minutes: Some(10),
             hotkeys: Some(vec![
                 "clear-history:8+16+91".into(),
                 "global-shortcut:16+67+91".into(),
@@ -60,15 +294,473 @@ impl CommonConfig {
         if let Some(theme_mode) = other.theme_mode {
             self.theme_mode = Some(theme_mode);
         }
+        if let Some(accent_color) = other.accent_color {
+            self.accent_color = Some(accent_color);
+        }
+        if let Some(font_size) = other.font_size {
+            self.font_size = Some(font_size);
+        }
+        if let Some(window_opacity) = other.window_opacity {
+            self.window_opacity = Some(window_opacity);
+        }
         if let Some(enable_auto_launch) = other.enable_auto_launch {
             self.enable_auto_launch = Some(enable_auto_launch);
         }
+        if let Some(onboarding_completed) = other.onboarding_completed {
+            self.onboarding_completed = Some(onboarding_completed);
+        }
         if let Some(record_limit) = other.record_limit {
             self.record_limit = Some(record_limit);
         }
+        if let Some(type_record_limits) = other.type_record_limits {
+            self.type_record_limits = Some(type_record_limits);
+        }
+        if let Some(auto_purge_trash) = other.auto_purge_trash {
+            self.auto_purge_trash = Some(auto_purge_trash);
+        }
+        if let Some(trash_retention_days) = other.trash_retention_days {
+            self.trash_retention_days = Some(trash_retention_days);
+        }
         if let Some(hotkeys) = other.hotkeys {
             self.hotkeys = Some(hotkeys);
         }
+        if let Some(enable_audit_log) = other.enable_audit_log {
+            self.enable_audit_log = Some(enable_audit_log);
+        }
+        if let Some(audit_log_limit) = other.audit_log_limit {
+            self.audit_log_limit = Some(audit_log_limit);
+        }
+        if let Some(enable_analytics) = other.enable_analytics {
+            self.enable_analytics = Some(enable_analytics);
+        }
+        if let Some(crash_report_url) = other.crash_report_url {
+            self.crash_report_url = Some(crash_report_url);
+        }
+        if let Some(update_channel) = other.update_channel {
+            self.update_channel = Some(update_channel);
+        }
+        if let Some(auto_check_updates) = other.auto_check_updates {
+            self.auto_check_updates = Some(auto_check_updates);
+        }
+        if let Some(auto_download_updates) = other.auto_download_updates {
+            self.auto_download_updates = Some(auto_download_updates);
+        }
+        if let Some(pause_on_secure_input) = other.pause_on_secure_input {
+            self.pause_on_secure_input = Some(pause_on_secure_input);
+        }
+        if let Some(pause_on_screen_share) = other.pause_on_screen_share {
+            self.pause_on_screen_share = Some(pause_on_screen_share);
+        }
+        if let Some(hide_previews_on_screen_share) = other.hide_previews_on_screen_share {
+            self.hide_previews_on_screen_share = Some(hide_previews_on_screen_share);
+        }
+        if let Some(screen_share_processes) = other.screen_share_processes {
+            self.screen_share_processes = Some(screen_share_processes);
+        }
+        if let Some(enable_biometric_unlock) = other.enable_biometric_unlock {
+            self.enable_biometric_unlock = Some(enable_biometric_unlock);
+        }
+        if let Some(tray_recent_count) = other.tray_recent_count {
+            self.tray_recent_count = Some(tray_recent_count);
+        }
+        if let Some(popup_position) = other.popup_position {
+            self.popup_position = Some(popup_position);
+        }
+        if let Some(quick_paste_modifiers) = other.quick_paste_modifiers {
+            self.quick_paste_modifiers = Some(quick_paste_modifiers);
+        }
+        if let Some(quick_paste_auto_paste) = other.quick_paste_auto_paste {
+            self.quick_paste_auto_paste = Some(quick_paste_auto_paste);
+        }
+        if let Some(triggers) = other.triggers {
+            self.triggers = Some(triggers);
+        }
+        if let Some(enable_text_expansion) = other.enable_text_expansion {
+            self.enable_text_expansion = Some(enable_text_expansion);
+        }
+        if let Some(text_expansion_platforms) = other.text_expansion_platforms {
+            self.text_expansion_platforms = Some(text_expansion_platforms);
+        }
+        if let Some(macro_step_delay) = other.macro_step_delay {
+            self.macro_step_delay = Some(macro_step_delay);
+        }
+        if let Some(multi_paste_separator) = other.multi_paste_separator {
+            self.multi_paste_separator = Some(multi_paste_separator);
+        }
+        if let Some(paste_style_by_app) = other.paste_style_by_app {
+            self.paste_style_by_app = Some(paste_style_by_app);
+        }
+        if let Some(keymap_profile) = other.keymap_profile {
+            self.keymap_profile = Some(keymap_profile);
+        }
+        if let Some(keymap_overrides) = other.keymap_overrides {
+            self.keymap_overrides = Some(keymap_overrides);
+        }
+        if let Some(enable_cloud_clipboard) = other.enable_cloud_clipboard {
+            self.enable_cloud_clipboard = Some(enable_cloud_clipboard);
+        }
+        if let Some(enable_http_api) = other.enable_http_api {
+            self.enable_http_api = Some(enable_http_api);
+        }
+        if let Some(http_api_port) = other.http_api_port {
+            self.http_api_port = Some(http_api_port);
+        }
+        if let Some(http_api_token) = other.http_api_token {
+            self.http_api_token = Some(http_api_token);
+        }
+        if let Some(enable_ipc) = other.enable_ipc {
+            self.enable_ipc = Some(enable_ipc);
+        }
+        if let Some(webhooks) = other.webhooks {
+            self.webhooks = Some(webhooks);
+        }
+        if let Some(custom_actions) = other.custom_actions {
+            self.custom_actions = Some(custom_actions);
+        }
+        if let Some(external_editor) = other.external_editor {
+            self.external_editor = Some(external_editor);
+        }
+        if let Some(espanso_sync) = other.espanso_sync {
+            self.espanso_sync = Some(espanso_sync);
+        }
+        if let Some(capture_scripts) = other.capture_scripts {
+            self.capture_scripts = Some(capture_scripts);
+        }
+        if let Some(capture_rules) = other.capture_rules {
+            self.capture_rules = Some(capture_rules);
+        }
+        if let Some(favorite_suggestion_threshold) = other.favorite_suggestion_threshold {
+            self.favorite_suggestion_threshold = Some(favorite_suggestion_threshold);
+        }
+        if let Some(favorite_suggestion_notification) = other.favorite_suggestion_notification {
+            self.favorite_suggestion_notification = Some(favorite_suggestion_notification);
+        }
+        if let Some(enable_url_cleaning) = other.enable_url_cleaning {
+            self.enable_url_cleaning = Some(enable_url_cleaning);
+        }
+        if let Some(url_clean_params) = other.url_clean_params {
+            self.url_clean_params = Some(url_clean_params);
+        }
+        if let Some(script_timeout_ms) = other.script_timeout_ms {
+            self.script_timeout_ms = Some(script_timeout_ms);
+        }
+        if let Some(enable_capture_notification) = other.enable_capture_notification {
+            self.enable_capture_notification = Some(enable_capture_notification);
+        }
+        if let Some(capture_notification_types) = other.capture_notification_types {
+            self.capture_notification_types = Some(capture_notification_types);
+        }
+        if let Some(capture_notification_keywords) = other.capture_notification_keywords {
+            self.capture_notification_keywords = Some(capture_notification_keywords);
+        }
+        if let Some(quiet_hours) = other.quiet_hours {
+            self.quiet_hours = Some(quiet_hours);
+        }
+        if let Some(enable_sound_feedback) = other.enable_sound_feedback {
+            self.enable_sound_feedback = Some(enable_sound_feedback);
+        }
+        if let Some(sound_events) = other.sound_events {
+            self.sound_events = Some(sound_events);
+        }
+        if let Some(sound_volume) = other.sound_volume {
+            self.sound_volume = Some(sound_volume);
+        }
+        if let Some(sync_favorite_policy) = other.sync_favorite_policy {
+            self.sync_favorite_policy = Some(sync_favorite_policy);
+        }
+        if let Some(sync_tag_policy) = other.sync_tag_policy {
+            self.sync_tag_policy = Some(sync_tag_policy);
+        }
+        if let Some(sync_deletion_policy) = other.sync_deletion_policy {
+            self.sync_deletion_policy = Some(sync_deletion_policy);
+        }
+        if let Some(sync_devices) = other.sync_devices {
+            self.sync_devices = Some(sync_devices);
+        }
+        if let Some(sync_remotes) = other.sync_remotes {
+            self.sync_remotes = Some(sync_remotes);
+        }
+        if let Some(enable_sync_server) = other.enable_sync_server {
+            self.enable_sync_server = Some(enable_sync_server);
+        }
+        if let Some(sync_server_port) = other.sync_server_port {
+            self.sync_server_port = Some(sync_server_port);
+        }
+        if let Some(sync_server_address) = other.sync_server_address {
+            self.sync_server_address = Some(sync_server_address);
+        }
+        if let Some(image_dedupe) = other.image_dedupe {
+            self.image_dedupe = Some(image_dedupe);
+        }
+        if let Some(image_similarity_threshold) = other.image_similarity_threshold {
+            self.image_similarity_threshold = Some(image_similarity_threshold);
+        }
+        if let Some(max_capture_size_mb) = other.max_capture_size_mb {
+            self.max_capture_size_mb = Some(max_capture_size_mb);
+        }
+        if let Some(oversize_action) = other.oversize_action {
+            self.oversize_action = Some(oversize_action);
+        }
+        if let Some(compress_large_content) = other.compress_large_content {
+            self.compress_large_content = Some(compress_large_content);
+        }
+        if let Some(database_dir) = other.database_dir {
+            self.database_dir = Some(database_dir);
+        }
+        if let Some(idle_release_minutes) = other.idle_release_minutes {
+            self.idle_release_minutes = Some(idle_release_minutes);
+        }
+    }
+
+    /// 校验配置，只校验有值的字段，便于校验 patch
+    pub fn validate(&self) -> Result<()> {
+        if let Some(database_dir) = self.database_dir.as_ref().filter(|d| !d.is_empty()) {
+            if !Path::new(database_dir).is_absolute() {
+                bail!("database dir must be an absolute path");
+            }
+        }
+        if let Some(language) = &self.language {
+            if !i18n::available_languages().contains(language) {
+                bail!("unsupported language \"{}\"", language);
+            }
+        }
+        if let Some(theme_mode) = &self.theme_mode {
+            if !["light", "dark", "system"].contains(&theme_mode.as_str()) {
+                bail!("unsupported theme mode \"{}\"", theme_mode);
+            }
+        }
+        if let Some(accent_color) = &self.accent_color {
+            let hex = accent_color.strip_prefix('#').unwrap_or_default();
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                bail!("accent color must be like #3b82f6");
+            }
+        }
+        if let Some(font_size) = self.font_size {
+            if !(10..=24).contains(&font_size) {
+                bail!("font size must be between 10 and 24");
+            }
+        }
+        if let Some(window_opacity) = self.window_opacity {
+            if !(0.3..=1.0).contains(&window_opacity) {
+                bail!("window opacity must be between 0.3 and 1.0");
+            }
+        }
+        if let Some(popup_position) = &self.popup_position {
+            if !["center", "cursor", "caret", "remember"].contains(&popup_position.as_str()) {
+                bail!("unsupported popup position \"{}\"", popup_position);
+            }
+        }
+        if let Some(record_limit) = self.record_limit {
+            if record_limit == 0 || record_limit > 100000 {
+                bail!("record limit must be between 1 and 100000");
+            }
+        }
+        if let Some(type_record_limits) = &self.type_record_limits {
+            for (data_type, limit) in type_record_limits {
+                if !RECORD_TYPES.contains(&data_type.as_str()) {
+                    bail!("unsupported record type \"{}\"", data_type);
+                }
+                if *limit == 0 || *limit > 100000 {
+                    bail!("record limit must be between 1 and 100000");
+                }
+            }
+        }
+        if let Some(days) = self.trash_retention_days {
+            if days > 365 {
+                bail!("trash retention must be between 0 and 365 days");
+            }
+        }
+        if let Some(types) = &self.capture_notification_types {
+            if let Some(data_type) = types.iter().find(|t| !RECORD_TYPES.contains(&t.as_str())) {
+                bail!("unsupported record type \"{}\"", data_type);
+            }
+        }
+        if let Some(quiet_hours) = &self.quiet_hours {
+            if !quiet_hours.is_empty() {
+                capture_notify::parse_quiet_hours(quiet_hours)?;
+            }
+        }
+        if let Some(events) = &self.sound_events {
+            if let Some(event) = events.iter().find(|e| !SOUND_EVENTS.contains(&e.as_str())) {
+                bail!("unsupported sound event \"{}\"", event);
+            }
+        }
+        if let Some(sound_volume) = self.sound_volume {
+            if sound_volume > 100 {
+                bail!("sound volume must be between 0 and 100");
+            }
+        }
+        if let Some(audit_log_limit) = self.audit_log_limit {
+            if audit_log_limit == 0 {
+                bail!("audit log limit must be greater than 0");
+            }
+        }
+        if let Some(modifiers) = &self.quick_paste_modifiers {
+            if modifiers
+                .iter()
+                .any(|code| !hotkey_util::is_modifier(*code))
+            {
+                bail!("quick paste hotkeys only accept modifier keys");
+            }
+        }
+        if let Some(hotkeys) = &self.hotkeys {
+            // 只检查全局快捷键之间的冲突，窗口内快捷键由前端处理
+            let mut global_hotkeys = vec![];
+            for hotkey in hotkeys {
+                let (func, keys) = hotkey_util::parse_hotkey(hotkey)?;
+                if !keys.is_empty() && Handle::hotkey_action(&func).is_some() {
+                    global_hotkeys.push((func, keys));
+                }
+            }
+            if let Some(modifiers) = &self.quick_paste_modifiers {
+                if !modifiers.is_empty() {
+                    for n in 1..=9u32 {
+                        let mut keys = modifiers.clone();
+                        keys.push(48 + n);
+                        global_hotkeys.push((format!("quick-paste-{}", n), keys));
+                    }
+                }
+            }
+            if let Some((a, b)) = hotkey_util::find_conflict(&global_hotkeys) {
+                bail!("hotkey \"{}\" conflicts with \"{}\"", a, b);
+            }
+        }
+        if let Some(profile) = &self.keymap_profile {
+            if !keymap::PROFILES.contains(&profile.as_str()) {
+                bail!("unsupported keymap profile \"{}\"", profile);
+            }
+        }
+        if let Some(overrides) = &self.keymap_overrides {
+            keymap::validate_overrides(overrides)?;
+        }
+        if let Some(triggers) = &self.triggers {
+            for t in triggers {
+                trigger::parse_trigger(t)?;
+            }
+        }
+        if let Some(platforms) = &self.text_expansion_platforms {
+            if let Some(p) = platforms
+                .iter()
+                .find(|p| !expansion::PLATFORMS.contains(&p.as_str()))
+            {
+                bail!("unsupported platform \"{}\"", p);
+            }
+        }
+        if let Some(delay) = self.macro_step_delay {
+            if delay > macros::MAX_STEP_DELAY {
+                bail!(
+                    "macro step delay must not exceed {} ms",
+                    macros::MAX_STEP_DELAY
+                );
+            }
+        }
+        if let Some(port) = self.http_api_port {
+            if port < 1024 {
+                bail!("http api port must be between 1024 and 65535");
+            }
+        }
+        if let Some(port) = self.sync_server_port {
+            if port < 1024 {
+                bail!("sync server port must be between 1024 and 65535");
+            }
+        }
+        if let Some(address) = self.sync_server_address.as_ref().filter(|a| !a.is_empty()) {
+            match address.parse::<std::net::IpAddr>() {
+                Ok(ip) if !ip.is_unspecified() => {}
+                _ => bail!("sync server address must be the ip of one network interface"),
+            }
+        }
+        if let Some(size_mb) = self.max_capture_size_mb {
+            if size_mb > 10240 {
+                bail!("max capture size must be between 0 and 10240 MB");
+            }
+        }
+        if let Some(action) = &self.oversize_action {
+            if !capture_guard::OVERSIZE_ACTIONS.contains(&action.as_str()) {
+                bail!("unsupported oversize action \"{}\"", action);
+            }
+        }
+        if let Some(threshold) = self.image_similarity_threshold {
+            if threshold > 32 {
+                bail!("image similarity threshold must be between 0 and 32");
+            }
+        }
+        if let Some(token) = &self.http_api_token {
+            if token.len() < 16 {
+                bail!("http api token must be at least 16 characters");
+            }
+        }
+        if let Some(url) = self.crash_report_url.as_ref().filter(|url| !url.is_empty()) {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                bail!("crash report url must start with http:// or https://");
+            }
+        }
+        if let Some(channel) = &self.update_channel {
+            if !updater::CHANNELS.contains(&channel.as_str()) {
+                bail!("unsupported update channel \"{}\"", channel);
+            }
+        }
+        if let Some(webhooks) = &self.webhooks {
+            for webhook in webhooks {
+                webhook.validate()?;
+            }
+        }
+        if let Some(actions) = &self.custom_actions {
+            for (i, action) in actions.iter().enumerate() {
+                action.validate()?;
+                if actions[..i].iter().any(|a| a.name == action.name) {
+                    bail!("duplicate action name \"{}\"", action.name);
+                }
+            }
+        }
+        if let Some(editor) = &self.external_editor {
+            actions::split_command(editor)?;
+        }
+        if let Some(espanso_sync) = &self.espanso_sync {
+            espanso_sync.validate()?;
+        }
+        if let Some(rules) = &self.capture_rules {
+            for rule in rules {
+                rule.validate()?;
+            }
+        }
+        if let Some(params) = &self.url_clean_params {
+            for param in params {
+                url_clean::validate_param(param)?;
+            }
+        }
+        for remote in self.sync_remotes.iter().flatten() {
+            remote.validate()?;
+        }
+        if let Some(devices) = &self.sync_devices {
+            for (i, device) in devices.iter().enumerate() {
+                device.validate()?;
+                if devices[..i].iter().any(|d| d.id == device.id) {
+                    bail!("duplicate sync device id \"{}\"", device.id);
+                }
+            }
+        }
+        for policy in [
+            &self.sync_favorite_policy,
+            &self.sync_tag_policy,
+            &self.sync_deletion_policy,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            sync::validate_policy(policy)?;
+        }
+        if let Some(scripts) = &self.capture_scripts {
+            for name in scripts {
+                script::validate_name(name)?;
+            }
+        }
+        if let Some(timeout) = self.script_timeout_ms {
+            if timeout == 0 || timeout > 5000 {
+                bail!("script timeout must be between 1 and 5000 ms");
+            }
+        }
+        Ok(())
     }
 
     pub fn patch_config(&mut self, patch: CommonConfig) {
@@ -81,8 +773,77 @@ impl CommonConfig {
         }
         patch!(language);
         patch!(theme_mode);
+        patch!(accent_color);
+        patch!(font_size);
+        patch!(window_opacity);
         patch!(enable_auto_launch);
+        patch!(onboarding_completed);
         patch!(hotkeys);
         patch!(record_limit);
+        patch!(type_record_limits);
+        patch!(auto_purge_trash);
+        patch!(trash_retention_days);
+        patch!(enable_audit_log);
+        patch!(audit_log_limit);
+        patch!(enable_analytics);
+        patch!(crash_report_url);
+        patch!(update_channel);
+        patch!(auto_check_updates);
+        patch!(auto_download_updates);
+        patch!(pause_on_secure_input);
+        patch!(pause_on_screen_share);
+        patch!(hide_previews_on_screen_share);
+        patch!(screen_share_processes);
+        patch!(enable_biometric_unlock);
+        patch!(tray_recent_count);
+        patch!(popup_position);
+        patch!(quick_paste_modifiers);
+        patch!(quick_paste_auto_paste);
+        patch!(triggers);
+        patch!(enable_text_expansion);
+        patch!(text_expansion_platforms);
+        patch!(macro_step_delay);
+        patch!(multi_paste_separator);
+        patch!(paste_style_by_app);
+        patch!(keymap_profile);
+        patch!(keymap_overrides);
+        patch!(enable_cloud_clipboard);
+        patch!(enable_http_api);
+        patch!(http_api_port);
+        patch!(http_api_token);
+        patch!(enable_ipc);
+        patch!(webhooks);
+        patch!(custom_actions);
+        patch!(external_editor);
+        patch!(espanso_sync);
+        patch!(capture_scripts);
+        patch!(capture_rules);
+        patch!(favorite_suggestion_threshold);
+        patch!(favorite_suggestion_notification);
+        patch!(enable_url_cleaning);
+        patch!(url_clean_params);
+        patch!(script_timeout_ms);
+        patch!(enable_capture_notification);
+        patch!(capture_notification_types);
+        patch!(capture_notification_keywords);
+        patch!(quiet_hours);
+        patch!(enable_sound_feedback);
+        patch!(sound_events);
+        patch!(sound_volume);
+        patch!(sync_favorite_policy);
+        patch!(sync_tag_policy);
+        patch!(sync_deletion_policy);
+        patch!(sync_devices);
+        patch!(sync_remotes);
+        patch!(enable_sync_server);
+        patch!(sync_server_port);
+        patch!(sync_server_address);
+        patch!(image_dedupe);
+        patch!(image_similarity_threshold);
+        patch!(max_capture_size_mb);
+        patch!(oversize_action);
+        patch!(compress_large_content);
+        patch!(database_dir);
+        patch!(idle_release_minutes);
     }
 }
@@ -1,7 +1,14 @@
-use super::{CommonConfig, Draft};
+use super::{current_keymap, CommonConfig, Draft};
 use crate::{
+    core::appearance,
     core::handle,
+    core::http_api,
+    core::i18n,
+    core::pairing,
+    core::retention,
     core::sysopt,
+    core::trigger,
+    core::updater,
     log_err,
     utils::{dirs, json_util},
 };
@@ -45,7 +52,10 @@ impl Config {
         }));
         log_err!(dirs::config_path().map(|path| {
             if !path.exists() {
-                log_err!(json_util::save(&path, &CommonConfig::template()));
+                log_err!(json_util::save(
+                    &path,
+                    &CommonConfig::first_launch_template()
+                ));
             }
         }));
         Ok(())
@@ -58,29 +68,82 @@ pub async fn modify_common_config(patch: CommonConfig) -> Result<()> {
 
     let auto_launch = patch.enable_auto_launch;
     let language = patch.language;
-    let theme_mode = patch.theme_mode;
+    let appearance_changed = patch.theme_mode.is_some()
+        || patch.accent_color.is_some()
+        || patch.font_size.is_some()
+        || patch.window_opacity.is_some();
     let record_limit = patch.record_limit;
+    let type_record_limits_changed = patch.type_record_limits.is_some();
     let hotkeys = patch.hotkeys;
+    let hotkeys_changed = hotkeys.is_some() || patch.quick_paste_modifiers.is_some();
+    let triggers_changed = patch.triggers.is_some()
+        || patch.enable_text_expansion.is_some()
+        || patch.text_expansion_platforms.is_some();
+    let keymap_changed = patch.keymap_profile.is_some() || patch.keymap_overrides.is_some();
+    let http_api_changed = patch.enable_http_api.is_some()
+        || patch.http_api_port.is_some()
+        || patch.http_api_token.is_some();
+    let sync_server_changed = patch.enable_sync_server.is_some()
+        || patch.sync_server_port.is_some()
+        || patch.sync_server_address.is_some();
+    let update_channel_changed = patch.update_channel.is_some();
+
+    // patch 中只有一方时校验不到冲突，用合并后的快捷键和快捷粘贴修饰键再检查一次
+    if hotkeys_changed {
+        let merged = {
+            let latest = Config::common().latest();
+            CommonConfig {
+                hotkeys: latest.hotkeys.clone(),
+                quick_paste_modifiers: latest.quick_paste_modifiers.clone(),
+                ..CommonConfig::default()
+            }
+        };
+        if let Err(err) = merged.validate() {
+            Config::common().discard();
+            return Err(err);
+        }
+    }
 
     match {
         if auto_launch.is_some() {
             sysopt::Sysopt::global().update_launch()?;
         }
 
+        if hotkeys_changed {
+            handle::Handle::refresh_global_shortcut()?;
+        }
+
         if hotkeys.is_some() {
-            let _ = handle::Handle::refresh_global_shortcut();
             handle::Handle::notice_to_window(handle::MsgTypeEnum::ChangeHotKeys, hotkeys)?;
         }
 
+        if triggers_changed {
+            trigger::refresh();
+        }
+
+        if http_api_changed {
+            http_api::refresh()?;
+        }
+
+        if sync_server_changed {
+            pairing::refresh()?;
+        }
+
+        if update_channel_changed {
+            updater::on_channel_changed();
+        }
+
+        if keymap_changed {
+            handle::Handle::notice_to_window(handle::MsgTypeEnum::ChangeKeymap, current_keymap()?)?;
+        }
+
         if language.is_some() {
+            // 重新读取 locales 目录下的语言包
+            i18n::reload();
             handle::Handle::update_systray()?;
             handle::Handle::notice_to_window(handle::MsgTypeEnum::ChangeLanguage, language)?;
         }
 
-        if theme_mode.is_some() {
-            // todo send msg to frontend
-        }
-
         if record_limit.is_some() {
             handle::Handle::notice_to_window(handle::MsgTypeEnum::ChangeRecordLimit, record_limit)?;
         }
@@ -90,10 +153,29 @@ pub async fn modify_common_config(patch: CommonConfig) -> Result<()> {
         Ok(()) => {
             Config::common().apply();
             Config::common().data().save_file()?;
+            if appearance_changed {
+                appearance::notice_changed();
+            }
+            if type_record_limits_changed {
+                retention::enforce_now();
+            }
             Ok(())
         }
         Err(err) => {
             Config::common().discard();
+            // 注册失败时恢复原来的快捷键
+            if hotkeys_changed {
+                log_err!(handle::Handle::refresh_global_shortcut());
+            }
+            if triggers_changed {
+                trigger::refresh();
+            }
+            if http_api_changed {
+                log_err!(http_api::refresh());
+            }
+            if sync_server_changed {
+                log_err!(pairing::refresh());
+            }
             Err(err)
         }
     }
@@ -0,0 +1,182 @@
+use super::hot_reload::diff;
+use super::{modify_common_config, CommonConfig, Config};
+use crate::utils::json_util;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// 导出文件的格式版本，格式不兼容时加一
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsFile {
+    pub format_version: u32,
+    /// 导出时的应用版本，仅供参考
+    pub app_version: String,
+    pub exported_at: u64,
+    pub config: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingChange {
+    pub key: String,
+    pub current: Value,
+    pub incoming: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportPreview {
+    pub app_version: String,
+    pub changes: Vec<SettingChange>,
+    /// 当前版本不认识的配置项，导入时忽略
+    pub unknown_fields: Vec<String>,
+    /// dry run 时为 false
+    pub applied: bool,
+}
+
+/// 访问令牌、webhook 密钥、已配对的设备和向导状态只属于本机，不导出
+fn strip_secrets(mut config: CommonConfig) -> CommonConfig {
+    config.http_api_token = None;
+    config.onboarding_completed = None;
+    config.sync_devices = None;
+    config.sync_remotes = None;
+    if let Some(webhooks) = config.webhooks.as_mut() {
+        for webhook in webhooks.iter_mut() {
+            webhook.secret = None;
+        }
+    }
+    config
+}
+
+/// 导入的 webhook 和本机 url 相同时沿用本机的密钥
+fn restore_secrets(mut incoming: CommonConfig, current: &CommonConfig) -> CommonConfig {
+    let current_webhooks = current.webhooks.clone().unwrap_or_default();
+    if let Some(webhooks) = incoming.webhooks.as_mut() {
+        for webhook in webhooks.iter_mut() {
+            webhook.secret = current_webhooks
+                .iter()
+                .find(|w| w.url == webhook.url)
+                .and_then(|w| w.secret.clone());
+        }
+    }
+    incoming.http_api_token = None;
+    incoming.onboarding_completed = None;
+    // 保留本机配对的设备，其它电脑的设备令牌在这里无效
+    incoming.sync_devices = None;
+    incoming.sync_remotes = None;
+    incoming
+}
+
+pub fn export_settings(path: &PathBuf) -> Result<()> {
+    let config = strip_secrets(Config::common().data().clone());
+    let file = SettingsFile {
+        format_version: FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").into(),
+        exported_at: chrono::Local::now().timestamp_millis() as u64,
+        config: serde_json::to_value(config)?,
+    };
+    json_util::save(path, &file)
+}
+
+fn unknown_fields(config: &Value) -> Result<Vec<String>> {
+    let known = serde_json::to_value(CommonConfig::default())?;
+    let mut fields: Vec<String> = match (config, known) {
+        (Value::Object(config), Value::Object(known)) => config
+            .keys()
+            .filter(|key| !known.contains_key(*key))
+            .cloned()
+            .collect(),
+        _ => bail!("invalid settings file"),
+    };
+    fields.sort();
+    Ok(fields)
+}
+
+fn changes(current: &CommonConfig, patch: &CommonConfig) -> Result<Vec<SettingChange>> {
+    let current = serde_json::to_value(current)?;
+    let mut changes = vec![];
+    if let Value::Object(patch) = serde_json::to_value(patch)? {
+        for (key, incoming) in patch {
+            if incoming.is_null() {
+                continue;
+            }
+            changes.push(SettingChange {
+                current: current.get(&key).cloned().unwrap_or(Value::Null),
+                key,
+                incoming,
+            });
+        }
+    }
+    Ok(changes)
+}
+
+/// 读取导出文件并列出会修改的配置项，dry_run 为 false 时按设置页的流程应用
+pub async fn import_settings(path: &PathBuf, dry_run: bool) -> Result<ImportPreview> {
+    let file: SettingsFile = json_util::read(path)?;
+    if file.format_version > FORMAT_VERSION {
+        bail!(
+            "settings file was exported by a newer version ({}), please upgrade first",
+            file.app_version
+        );
+    }
+    let unknown_fields = unknown_fields(&file.config)?;
+    let current = Config::common().data().clone();
+    let incoming: CommonConfig = serde_json::from_value(file.config)?;
+    let incoming = restore_secrets(incoming, &current);
+    let patch = diff(&current, &incoming)?;
+    let changes = match &patch {
+        Some(patch) => changes(&current, patch)?,
+        None => vec![],
+    };
+    let mut applied = false;
+    if let (false, Some(patch)) = (dry_run, patch) {
+        patch.validate()?;
+        modify_common_config(patch).await?;
+        applied = true;
+    }
+    Ok(ImportPreview {
+        app_version: file.app_version,
+        changes,
+        unknown_fields,
+        applied,
+    })
+}
+
+#[test]
+fn test_settings_secrets() {
+    use crate::core::sync::SyncDevice;
+    use crate::core::webhook::Webhook;
+    let webhook = |secret: Option<&str>| Webhook {
+        url: "https://example.com/hook".into(),
+        secret: secret.map(String::from),
+        ..Webhook::default()
+    };
+    let current = CommonConfig {
+        http_api_token: Some("token".into()),
+        webhooks: Some(vec![webhook(Some("secret"))]),
+        sync_devices: Some(vec![SyncDevice {
+            id: "phone".into(),
+            token: Some("device-token".into()),
+            ..SyncDevice::default()
+        }]),
+        ..CommonConfig::default()
+    };
+    let exported = strip_secrets(current.clone());
+    assert_eq!(exported.http_api_token, None);
+    assert_eq!(exported.sync_devices, None);
+    assert_eq!(exported.webhooks, Some(vec![webhook(None)]));
+    // 导入到同一台机器时没有变化
+    let incoming = restore_secrets(exported, &current);
+    assert!(diff(&current, &incoming).unwrap().is_none());
+    let incoming = restore_secrets(
+        CommonConfig {
+            sync_devices: Some(vec![]),
+            ..CommonConfig::default()
+        },
+        &current,
+    );
+    assert!(diff(&current, &incoming).unwrap().is_none());
+    let unknown = unknown_fields(&serde_json::json!({"language": "en", "foo": 1})).unwrap();
+    assert_eq!(unknown, vec!["foo".to_string()]);
+}
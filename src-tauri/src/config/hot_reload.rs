@@ -0,0 +1,100 @@
+use super::{modify_common_config, CommonConfig, Config};
+use crate::core::handle::{Handle, MsgTypeEnum};
+use crate::log_err;
+use crate::utils::{dirs, json_util};
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::fs;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// 检查配置文件是否被修改的间隔
+const POLL_SECS: u64 = 2;
+
+/// 配置文件中和当前配置不同的字段，没有变化时返回 None
+/// 从文件中删掉的字段保持当前值，不会被重置
+pub(super) fn diff(current: &CommonConfig, file: &CommonConfig) -> Result<Option<CommonConfig>> {
+    let mut patch = Map::new();
+    if let (Value::Object(current), Value::Object(file)) =
+        (serde_json::to_value(current)?, serde_json::to_value(file)?)
+    {
+        for (key, value) in file {
+            if !value.is_null() && current.get(&key) != Some(&value) {
+                patch.insert(key, value);
+            }
+        }
+    }
+    if patch.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_value(Value::Object(patch))?))
+}
+
+fn modified() -> Option<SystemTime> {
+    fs::metadata(dirs::config_path().ok()?)
+        .ok()?
+        .modified()
+        .ok()
+}
+
+/// 按修改的字段走和设置页相同的流程，快捷键、webhook 等随之生效
+fn reload() -> Result<bool> {
+    let file: CommonConfig = json_util::read(&dirs::config_path()?)?;
+    let current = Config::common().data().clone();
+    let patch = match diff(&current, &file)? {
+        Some(patch) => patch,
+        None => return Ok(false),
+    };
+    patch.validate()?;
+    tauri::async_runtime::block_on(modify_common_config(patch))?;
+    Ok(true)
+}
+
+/// 监听 config.json 的外部修改并热加载
+/// 应用自己保存配置后文件和当前配置一致，不会重复加载
+pub fn watch_config_file() {
+    thread::spawn(|| {
+        let mut last_modified = modified();
+        loop {
+            thread::sleep(Duration::from_secs(POLL_SECS));
+            let now = modified();
+            if now == last_modified {
+                continue;
+            }
+            last_modified = now;
+            match reload() {
+                Ok(true) => {
+                    println!("config file reloaded");
+                    let config = Config::common().data().clone();
+                    log_err!(Handle::notice_to_window(
+                        MsgTypeEnum::ConfigReloaded,
+                        config
+                    ));
+                }
+                Ok(false) => {}
+                // 编辑到一半的文件可能不是合法的 JSON，等下次保存再加载
+                Err(err) => println!("reload config file error: {}", err),
+            }
+        }
+    });
+}
+
+#[test]
+fn test_diff() {
+    let current = CommonConfig {
+        language: Some("zh".into()),
+        record_limit: Some(300),
+        ..CommonConfig::default()
+    };
+    assert!(diff(&current, &current).unwrap().is_none());
+    let file = CommonConfig {
+        language: Some("en".into()),
+        record_limit: Some(300),
+        ..CommonConfig::default()
+    };
+    let patch = diff(&current, &file).unwrap().unwrap();
+    assert_eq!(patch.language, Some("en".into()));
+    assert_eq!(patch.record_limit, None);
+    // 文件中没有的字段不会清空当前配置
+    assert!(diff(&current, &CommonConfig::default()).unwrap().is_none());
+}
@@ -0,0 +1,128 @@
+use super::Config;
+use crate::utils::hotkey_util;
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+/// 内置的按键方案
+pub const PROFILES: [&str; 3] = ["default", "vim", "emacs"];
+
+/// 列表窗口内可重新绑定的操作
+pub const ACTIONS: [&str; 6] = [
+    "copy",
+    "quick-copy",
+    "move-up",
+    "move-down",
+    "delete",
+    "close-window",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeyBinding {
+    pub func: String,
+    pub keys: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Keymap {
+    pub profile: String,
+    pub bindings: Vec<KeyBinding>,
+}
+
+fn profile_bindings(profile: &str) -> Result<Vec<(&'static str, Vec<u32>)>> {
+    // 搜索框一直有焦点，除方向键外都要带修饰键，否则影响输入
+    let bindings = match profile {
+        "default" => vec![
+            ("copy", vec![13]),
+            ("quick-copy", vec![91]),
+            ("move-up", vec![38]),
+            ("move-down", vec![40]),
+            ("delete", vec![17, 46]),
+            ("close-window", vec![27]),
+        ],
+        "vim" => vec![
+            ("copy", vec![13]),
+            ("quick-copy", vec![91]),
+            ("move-up", vec![17, 75]),
+            ("move-down", vec![17, 74]),
+            ("delete", vec![17, 68]),
+            ("close-window", vec![27]),
+            ("close-window", vec![17, 219]),
+        ],
+        "emacs" => vec![
+            ("copy", vec![13]),
+            ("quick-copy", vec![91]),
+            ("move-up", vec![17, 80]),
+            ("move-down", vec![17, 78]),
+            ("delete", vec![17, 75]),
+            ("close-window", vec![27]),
+            ("close-window", vec![17, 71]),
+        ],
+        _ => bail!("unsupported keymap profile \"{}\"", profile),
+    };
+    Ok(bindings)
+}
+
+/// 校验自定义按键，format 与快捷键相同: {func}:{key}+{key}
+pub fn validate_overrides(overrides: &[String]) -> Result<()> {
+    for item in overrides {
+        let (func, _) = hotkey_util::parse_hotkey(item)?;
+        if !ACTIONS.contains(&func.as_str()) {
+            bail!("unsupported keymap action \"{}\"", func);
+        }
+    }
+    Ok(())
+}
+
+/// 在按键方案的基础上应用自定义按键
+/// 自定义了某个操作后，方案中该操作原来的按键全部失效；按键为空表示解绑
+pub fn resolve(profile: &str, overrides: &[String]) -> Result<Keymap> {
+    let mut custom = vec![];
+    for item in overrides {
+        custom.push(hotkey_util::parse_hotkey(item)?);
+    }
+    let mut bindings: Vec<KeyBinding> = profile_bindings(profile)?
+        .into_iter()
+        .filter(|(func, _)| !custom.iter().any(|(f, _)| f == func))
+        .map(|(func, keys)| KeyBinding {
+            func: func.to_string(),
+            keys,
+        })
+        .collect();
+    for (func, keys) in custom {
+        if !keys.is_empty() {
+            bindings.push(KeyBinding { func, keys });
+        }
+    }
+    Ok(Keymap {
+        profile: profile.to_string(),
+        bindings,
+    })
+}
+
+/// 当前配置对应的按键
+pub fn current_keymap() -> Result<Keymap> {
+    let common = Config::common().latest().clone();
+    let profile = common.keymap_profile.unwrap_or_else(|| "default".into());
+    resolve(&profile, &common.keymap_overrides.unwrap_or_default())
+}
+
+#[test]
+fn test_resolve_keymap() {
+    let keymap = resolve("vim", &[]).unwrap();
+    let close: Vec<&KeyBinding> = keymap
+        .bindings
+        .iter()
+        .filter(|b| b.func == "close-window")
+        .collect();
+    assert_eq!(close.len(), 2);
+
+    let overrides = vec!["delete:".to_string(), "move-up:16+38".to_string()];
+    let keymap = resolve("default", &overrides).unwrap();
+    assert!(keymap.bindings.iter().all(|b| b.func != "delete"));
+    assert!(keymap.bindings.contains(&KeyBinding {
+        func: "move-up".to_string(),
+        keys: vec![16, 38],
+    }));
+    assert!(resolve("helix", &[]).is_err());
+    assert!(validate_overrides(&["paste:13".to_string()]).is_err());
+}
@@ -1,7 +1,13 @@
 mod common_config;
 mod config;
 mod draft;
+mod export;
+mod hot_reload;
+mod keymap;
 
 pub use self::common_config::*;
 pub use self::config::*;
 pub use self::draft::*;
+pub use self::export::*;
+pub use self::hot_reload::*;
+pub use self::keymap::*;
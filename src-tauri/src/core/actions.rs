@@ -0,0 +1,268 @@
+use super::clipboard::ClipBoardOprator;
+use super::database::{Record, SqliteDB};
+use super::retention::RECORD_TYPES;
+use crate::config::Config;
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 命令运行的时间上限，超时后结束进程
+const ACTION_TIMEOUT: Duration = Duration::from_secs(30);
+/// 保留的输出长度上限
+const MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+const MAX_NAME_LEN: usize = 32;
+
+/// 设置中定义的操作，在记录的右键菜单中执行
+/// command 按空格拆分为程序和参数，不经过 shell，引号内的空格不拆分
+/// 参数中的 {content}、{id}、{data_type} 替换为记录的值，替换后仍是一个参数，内容不会被当作命令执行
+/// 替换后才以 - 开头的参数会被当作选项，这时拒绝执行，需要时在命令中写上 -- 或 --url={content}
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct CustomAction {
+    pub name: String,
+    /// 如 `open {content}`、`jq .`
+    pub command: String,
+    /// 为 true 时通过 stdin 传入记录的内容
+    #[serde(default)]
+    pub stdin: bool,
+    /// 为 true 时把命令的输出复制到剪切板
+    #[serde(default)]
+    pub copy_output: bool,
+    /// 只对这些类型的记录显示，为空时不限制
+    pub data_types: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ActionResult {
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub timed_out: bool,
+}
+
+impl CustomAction {
+    pub fn validate(&self) -> Result<()> {
+        let len = self.name.trim().chars().count();
+        if len == 0 || len > MAX_NAME_LEN {
+            bail!(
+                "the action name must be between 1 and {} characters",
+                MAX_NAME_LEN
+            );
+        }
+        if split_command(&self.command)?.is_empty() {
+            bail!("the command of action \"{}\" is empty", self.name);
+        }
+        for data_type in self.data_types.iter().flatten() {
+            if !RECORD_TYPES.contains(&data_type.as_str()) {
+                bail!(
+                    "unsupported data type \"{}\" for action \"{}\"",
+                    data_type,
+                    self.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn applies_to(&self, data_type: &str) -> bool {
+        self.data_types
+            .as_ref()
+            .filter(|types| !types.is_empty())
+            .map_or(true, |types| types.iter().any(|t| t == data_type))
+    }
+
+    /// 拆分后再替换占位符
+    fn argv(&self, record: &Record) -> Result<Vec<String>> {
+        let mut argv = vec![];
+        let mut after_separator = false;
+        for arg in split_command(&self.command)? {
+            let replaced = arg
+                .replace("{id}", &record.id.to_string())
+                .replace("{data_type}", &record.data_type)
+                .replace("{content}", &record.content);
+            if !after_separator && !arg.starts_with('-') && replaced.starts_with('-') {
+                bail!(
+                    "the record content starts with \"-\" and would be passed to action \"{}\" as an option, add -- before {{content}} in the command",
+                    self.name
+                );
+            }
+            after_separator |= arg == "--";
+            argv.push(replaced);
+        }
+        Ok(argv)
+    }
+}
+
+/// 按空白拆分，支持单引号、双引号和反斜杠转义
+pub fn split_command(command: &str) -> Result<Vec<String>> {
+    let mut args = vec![];
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                current.push(
+                    chars
+                        .next()
+                        .ok_or(anyhow!("unfinished escape in command"))?,
+                );
+                in_arg = true;
+            }
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        bail!("unclosed quote in command");
+    }
+    if in_arg {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+/// 适用于该类型记录的操作
+pub fn list(data_type: Option<&str>) -> Vec<CustomAction> {
+    let actions = { Config::common().latest().custom_actions.clone() };
+    actions
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|action| data_type.map_or(true, |t| action.applies_to(t)))
+        .collect()
+}
+
+fn read_limited(reader: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut buf = vec![];
+        if let Some(reader) = reader {
+            let _ = reader.take(MAX_OUTPUT_BYTES as u64).read_to_end(&mut buf);
+        }
+        String::from_utf8_lossy(&buf).to_string()
+    })
+}
+
+/// 对记录执行名为 name 的操作，加密的记录不执行
+pub fn run(name: &str, record_id: u64) -> Result<ActionResult> {
+    let action = list(None)
+        .into_iter()
+        .find(|action| action.name == name)
+        .ok_or(anyhow!("action \"{}\" not found", name))?;
+    let record = SqliteDB::new().find_by_id(record_id)?;
+    if record.is_secure {
+        bail!("actions cannot run on encrypted records");
+    }
+    if !action.applies_to(&record.data_type) {
+        bail!("action \"{}\" does not apply to this record", name);
+    }
+    let argv = action.argv(&record)?;
+    let mut child = Command::new(&argv[0])
+        .args(&argv[1..])
+        .stdin(match action.stdin {
+            true => Stdio::piped(),
+            false => Stdio::null(),
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let content = record.content.clone();
+        // 写入和读取输出同时进行，避免管道写满后互相等待
+        thread::spawn(move || stdin.write_all(content.as_bytes()));
+    }
+    let stdout = read_limited(child.stdout.take());
+    let stderr = read_limited(child.stderr.take());
+    let start = Instant::now();
+    let (code, timed_out) = loop {
+        if let Some(status) = child.try_wait()? {
+            break (status.code(), false);
+        }
+        if start.elapsed() > ACTION_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            break (None, true);
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    let res = ActionResult {
+        code,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+        timed_out,
+    };
+    if action.copy_output && code == Some(0) && !res.stdout.trim().is_empty() {
+        ClipBoardOprator::set_text(res.stdout.trim_end().to_string())?;
+    }
+    Ok(res)
+}
+
+#[test]
+fn test_custom_action_command() {
+    assert_eq!(
+        split_command(r#"jq  '.items[] | .name' -r"#).unwrap(),
+        vec!["jq", ".items[] | .name", "-r"]
+    );
+    assert_eq!(
+        split_command(r#"echo "a \"b\"" c\ d ''"#).unwrap(),
+        vec!["echo", "a \"b\"", "c d", ""]
+    );
+    assert!(split_command("echo 'open").is_err());
+    let action = CustomAction {
+        name: "Open".into(),
+        command: "open --url={content}".into(),
+        data_types: Some(vec!["text".into()]),
+        ..Default::default()
+    };
+    let record = Record {
+        id: 7,
+        content: "x; rm -rf ~".into(),
+        data_type: "text".into(),
+        ..Default::default()
+    };
+    // 内容始终是一个参数
+    assert_eq!(
+        action.argv(&record).unwrap(),
+        vec!["open", "--url=x; rm -rf ~"]
+    );
+    let option = Record {
+        content: "-rf".into(),
+        ..record.clone()
+    };
+    assert_eq!(action.argv(&option).unwrap(), vec!["open", "--url=-rf"]);
+    let bare = CustomAction {
+        command: "grep {content}".into(),
+        ..action.clone()
+    };
+    assert!(bare.argv(&option).is_err());
+    let separated = CustomAction {
+        command: "grep -- {content}".into(),
+        ..action.clone()
+    };
+    assert_eq!(separated.argv(&option).unwrap(), vec!["grep", "--", "-rf"]);
+    assert!(action.applies_to("text"));
+    assert!(!action.applies_to("image"));
+    assert!(action.validate().is_ok());
+    let empty = CustomAction {
+        name: "Empty".into(),
+        command: "  ".into(),
+        ..Default::default()
+    };
+    assert!(empty.validate().is_err());
+}
@@ -0,0 +1,87 @@
+use super::database::SqliteDB;
+use crate::config::Config;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// 最多保留的事件数，超过后删除最旧的
+pub const EVENT_LIMIT: u32 = 100_000;
+
+pub enum EventKind {
+    Capture,
+    Search,
+    Paste,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Capture => "capture",
+            EventKind::Search => "search",
+            EventKind::Paste => "paste",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EventStatsReq {
+    /// 毫秒时间戳，为空时不限制
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    /// day：按日期（YYYY-MM-DD）分组，hour：按一天中的小时（00-23）分组
+    pub group_by: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventCount {
+    pub bucket: String,
+    pub kind: String,
+    pub count: usize,
+}
+
+/// 统计默认关闭，只记录事件类型和时间，不记录内容和搜索词
+pub fn is_enabled() -> bool {
+    Config::common().latest().enable_analytics.unwrap_or(false)
+}
+
+/// 未开启统计时不打开数据库
+pub fn record(kind: EventKind) {
+    if is_enabled() {
+        SqliteDB::new().log_event(kind);
+    }
+}
+
+/// strftime 的格式，按本地时间分组
+fn bucket_format(group_by: Option<&str>) -> &'static str {
+    match group_by {
+        Some("hour") => "%H",
+        _ => "%Y-%m-%d",
+    }
+}
+
+pub fn event_counts(req: &EventStatsReq) -> Result<Vec<EventCount>> {
+    let start = req.start_time.unwrap_or(0);
+    let end = req.end_time.unwrap_or(i64::MAX as u64);
+    let format = bucket_format(req.group_by.as_deref());
+    let counts = SqliteDB::new().count_events(format, start, end)?;
+    Ok(counts
+        .into_iter()
+        .map(|(bucket, kind, count)| EventCount {
+            bucket,
+            kind,
+            count,
+        })
+        .collect())
+}
+
+/// 删除全部统计事件，返回删除的条数
+pub fn purge() -> Result<usize> {
+    SqliteDB::new().purge_events()
+}
+
+#[test]
+fn test_bucket_format() {
+    assert_eq!(bucket_format(None), "%Y-%m-%d");
+    assert_eq!(bucket_format(Some("day")), "%Y-%m-%d");
+    assert_eq!(bucket_format(Some("hour")), "%H");
+    assert_eq!(EventKind::Paste.as_str(), "paste");
+}
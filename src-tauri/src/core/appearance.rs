@@ -0,0 +1,104 @@
+use super::handle::{Handle, MsgTypeEnum};
+use crate::config::{CommonConfig, Config};
+use crate::log_err;
+use serde::{Deserialize, Serialize};
+use tauri::Theme;
+
+/// 前端应用的外观设置，theme 为 theme_mode 解析后的实际主题
+#[derive(Debug, Clone, Serialize)]
+pub struct Appearance {
+    pub theme_mode: String,
+    /// `light` or `dark`
+    pub theme: String,
+    pub accent_color: String,
+    pub font_size: u32,
+    pub window_opacity: f64,
+}
+
+/// 设置外观时只修改传入的字段
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AppearancePatch {
+    pub theme_mode: Option<String>,
+    pub accent_color: Option<String>,
+    pub font_size: Option<u32>,
+    pub window_opacity: Option<f64>,
+}
+
+impl From<AppearancePatch> for CommonConfig {
+    fn from(patch: AppearancePatch) -> Self {
+        CommonConfig {
+            theme_mode: patch.theme_mode,
+            accent_color: patch.accent_color,
+            font_size: patch.font_size,
+            window_opacity: patch.window_opacity,
+            ..CommonConfig::default()
+        }
+    }
+}
+
+fn theme_name(theme: Theme) -> &'static str {
+    match theme {
+        Theme::Dark => "dark",
+        _ => "light",
+    }
+}
+
+/// 系统当前的主题，取不到时按浅色处理
+fn system_theme() -> Theme {
+    Handle::global()
+        .get_window()
+        .and_then(|w| w.theme().ok())
+        .unwrap_or(Theme::Light)
+}
+
+fn resolve(theme_mode: &str, system: Theme) -> &'static str {
+    match theme_mode {
+        "dark" => "dark",
+        "system" => theme_name(system),
+        _ => "light",
+    }
+}
+
+pub fn current() -> Appearance {
+    with_system_theme(system_theme())
+}
+
+fn with_system_theme(system: Theme) -> Appearance {
+    let config = Config::common().latest().clone();
+    let theme_mode = config.theme_mode.unwrap_or("light".into());
+    Appearance {
+        theme: resolve(&theme_mode, system).to_string(),
+        theme_mode,
+        accent_color: config.accent_color.unwrap_or("#3b82f6".into()),
+        font_size: config.font_size.unwrap_or(14),
+        window_opacity: config.window_opacity.unwrap_or(1.0),
+    }
+}
+
+/// 外观配置修改后通知所有窗口
+pub fn notice_changed() {
+    log_err!(Handle::notice_to_window(
+        MsgTypeEnum::ChangeTheme,
+        current()
+    ));
+}
+
+/// 系统切换深色模式时，跟随系统的主题需要更新
+pub fn on_system_theme_changed(theme: Theme) {
+    let theme_mode = { Config::common().latest().theme_mode.clone() };
+    if theme_mode.as_deref() == Some("system") {
+        println!("system theme changed: {}", theme_name(theme));
+        log_err!(Handle::notice_to_window(
+            MsgTypeEnum::ChangeTheme,
+            with_system_theme(theme)
+        ));
+    }
+}
+
+#[test]
+fn test_resolve() {
+    assert_eq!(resolve("dark", Theme::Light), "dark");
+    assert_eq!(resolve("light", Theme::Dark), "light");
+    assert_eq!(resolve("system", Theme::Dark), "dark");
+    assert_eq!(resolve("system", Theme::Light), "light");
+}
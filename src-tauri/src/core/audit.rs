@@ -0,0 +1,50 @@
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+
+pub enum AuditAction {
+    Created,
+    Recopied,
+    Pasted,
+    Exported,
+    Deleted,
+    Edited,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Created => "created",
+            AuditAction::Recopied => "recopied",
+            AuditAction::Pasted => "pasted",
+            AuditAction::Exported => "exported",
+            AuditAction::Deleted => "deleted",
+            AuditAction::Edited => "edited",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct AuditLog {
+    pub id: u64,
+    pub record_id: u64,
+    pub action: String,
+    pub create_time: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct AuditQueryReq {
+    pub record_id: Option<u64>,
+    pub action: Option<String>,
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+/// 审计日志默认关闭，需要用户主动开启
+pub fn is_enabled() -> bool {
+    Config::common().latest().enable_audit_log.unwrap_or(false)
+}
+
+pub fn limit() -> u32 {
+    Config::common().latest().audit_log_limit.unwrap_or(10000)
+}
@@ -0,0 +1,178 @@
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+use tauri::utils::platform::current_exe;
+
+/// 开机启动项的名称与可执行文件名一致，兼容之前创建的启动项
+pub struct AutoStart {
+    name: String,
+    exe_path: PathBuf,
+}
+
+impl AutoStart {
+    pub fn current() -> Result<Self> {
+        let exe_path = dunce::canonicalize(current_exe()?)?;
+        let name = exe_path
+            .file_stem()
+            .and_then(|f| f.to_str())
+            .ok_or(anyhow!("failed to get file stem"))?
+            .to_string();
+        Ok(AutoStart { name, exe_path })
+    }
+
+    /// 登录时启动，已存在时会覆盖为当前的程序路径
+    pub fn enable(&self) -> Result<()> {
+        platform::enable(&self.name, &self.exe_path)
+    }
+
+    pub fn disable(&self) -> Result<()> {
+        platform::disable(&self.name)
+    }
+
+    /// 系统中实际的启动项状态
+    pub fn is_enabled(&self) -> Result<bool> {
+        platform::is_enabled(&self.name)
+    }
+}
+
+/// HKCU\...\Run 注册表项
+#[cfg(target_os = "windows")]
+mod platform {
+    use anyhow::Result;
+    use std::path::Path;
+    use winreg::enums::{HKEY_CURRENT_USER, KEY_QUERY_VALUE, KEY_SET_VALUE};
+    use winreg::RegKey;
+
+    const RUN_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+
+    fn run_key(flags: u32) -> Result<RegKey> {
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        Ok(hkcu.open_subkey_with_flags(RUN_KEY, flags)?)
+    }
+
+    pub fn enable(name: &str, exe_path: &Path) -> Result<()> {
+        let value = format!("\"{}\"", exe_path.display());
+        run_key(KEY_SET_VALUE)?.set_value(name, &value)?;
+        Ok(())
+    }
+
+    pub fn disable(name: &str) -> Result<()> {
+        match run_key(KEY_SET_VALUE)?.delete_value(name) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn is_enabled(name: &str) -> Result<bool> {
+        Ok(run_key(KEY_QUERY_VALUE)?
+            .get_value::<String, _>(name)
+            .is_ok())
+    }
+}
+
+/// ~/Library/LaunchAgents 下的 plist
+#[cfg(target_os = "macos")]
+mod platform {
+    use anyhow::{anyhow, Result};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use tauri::api::path::home_dir;
+
+    const LABEL: &str = "com.church.lanaya";
+
+    fn plist_path() -> Result<PathBuf> {
+        Ok(home_dir()
+            .ok_or(anyhow!("failed to get home dir"))?
+            .join("Library")
+            .join("LaunchAgents")
+            .join(format!("{}.plist", LABEL)))
+    }
+
+    fn escape(s: &str) -> String {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+
+    pub fn enable(_name: &str, exe_path: &Path) -> Result<()> {
+        let path = plist_path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let plist = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+</dict>
+</plist>
+"#,
+            LABEL,
+            escape(&exe_path.to_string_lossy())
+        );
+        fs::write(path, plist)?;
+        Ok(())
+    }
+
+    pub fn disable(_name: &str) -> Result<()> {
+        let path = plist_path()?;
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_enabled(_name: &str) -> Result<bool> {
+        Ok(plist_path()?.exists())
+    }
+}
+
+/// XDG autostart 的 desktop 文件
+#[cfg(target_os = "linux")]
+mod platform {
+    use anyhow::{anyhow, Result};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use tauri::api::path::config_dir;
+
+    fn desktop_path(name: &str) -> Result<PathBuf> {
+        Ok(config_dir()
+            .ok_or(anyhow!("failed to get config dir"))?
+            .join("autostart")
+            .join(format!("{}.desktop", name)))
+    }
+
+    pub fn enable(name: &str, exe_path: &Path) -> Result<()> {
+        let path = desktop_path(name)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let desktop = format!(
+            "[Desktop Entry]\nType=Application\nVersion=1.0\nName={}\nComment={} startup script\nExec=\"{}\"\nStartupNotify=false\nTerminal=false\nX-GNOME-Autostart-enabled=true\n",
+            name,
+            name,
+            exe_path.display()
+        );
+        fs::write(path, desktop)?;
+        Ok(())
+    }
+
+    pub fn disable(name: &str) -> Result<()> {
+        let path = desktop_path(name)?;
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_enabled(name: &str) -> Result<bool> {
+        Ok(desktop_path(name)?.exists())
+    }
+}
@@ -0,0 +1,233 @@
+use super::database::{MergeStrategy, SqliteDB, SQLITE_FILE};
+use super::handle::{Handle, MsgTypeEnum};
+use super::thumbnail;
+use super::vault::Vault;
+use crate::log_err;
+use crate::utils::dirs;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// 备份文件的格式版本，格式不兼容时加一
+const FORMAT_VERSION: u32 = 1;
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "database.sqlite";
+/// 配置、图片、插件等文件放在该目录下，路径相对于应用目录
+const FILES_PREFIX: &str = "files/";
+/// 不需要备份的目录，相对于应用目录
+const EXCLUDED_DIRS: [&str; 2] = ["logs", dirs::TEMP_DIR];
+/// 口令文件，相对于应用目录
+const VAULT_ENTRY: &str = "vault.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    /// 创建备份时的应用版本，仅供参考
+    pub app_version: String,
+    pub created_at: u64,
+    /// 备份中的文件数，不含数据库
+    pub file_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreResult {
+    pub app_version: String,
+    pub created_at: u64,
+    /// 新增或替换的记录数
+    pub records: usize,
+    pub files: usize,
+    /// 备份的口令和本机不同，合并时跳过的加密记录数
+    pub skipped_secure: usize,
+}
+
+/// 数据库文件和 -wal、-journal 等临时文件由快照代替
+/// 缩略图是缓存，恢复后按需重新生成，也不会带上加密记录的缩略图
+fn is_excluded(relative: &Path) -> bool {
+    if let Some(first) = relative.components().next() {
+        if EXCLUDED_DIRS.contains(&first.as_os_str().to_string_lossy().as_ref()) {
+            return true;
+        }
+    }
+    if relative.starts_with(Path::new("data").join("img").join(thumbnail::CACHE_DIR)) {
+        return true;
+    }
+    relative
+        .file_name()
+        .map(|name| name.to_string_lossy().starts_with(SQLITE_FILE))
+        .unwrap_or(false)
+}
+
+fn collect_files(base: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let relative = path.strip_prefix(base)?.to_path_buf();
+        if is_excluded(&relative) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(base, &path, files)?;
+        } else {
+            files.push(relative);
+        }
+    }
+    Ok(())
+}
+
+fn temp_db_path(kind: &str) -> PathBuf {
+    let now = chrono::Local::now().timestamp_millis();
+    std::env::temp_dir().join(format!("lanaya-{}-{}.sqlite", kind, now))
+}
+
+/// 把数据库快照、图片、配置等打包成一个 zip
+pub fn create_backup(path: &Path) -> Result<BackupManifest> {
+    let home = dirs::app_home_dir()?;
+    let mut files = vec![];
+    collect_files(&home, &home, &mut files)?;
+    let snapshot = temp_db_path("backup");
+    SqliteDB::new().snapshot_to(&snapshot)?;
+    let manifest = BackupManifest {
+        format_version: FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").into(),
+        created_at: chrono::Local::now().timestamp_millis() as u64,
+        file_count: files.len(),
+    };
+    let res = write_archive(path, &manifest, &snapshot, &home, &files);
+    log_err!(fs::remove_file(&snapshot));
+    res?;
+    Ok(manifest)
+}
+
+fn write_archive(
+    path: &Path,
+    manifest: &BackupManifest,
+    snapshot: &Path,
+    home: &Path,
+    files: &[PathBuf],
+) -> Result<()> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file(MANIFEST_ENTRY, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+    zip.start_file(DATABASE_ENTRY, options)?;
+    zip.write_all(&fs::read(snapshot)?)?;
+    for relative in files {
+        // zip 中统一使用 / 分隔
+        let name = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+        zip.start_file(format!("{}{}", FILES_PREFIX, name), options)?;
+        zip.write_all(&fs::read(home.join(relative))?)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+fn read_manifest(archive: &mut ZipArchive<File>) -> Result<BackupManifest> {
+    let mut content = String::new();
+    match archive.by_name(MANIFEST_ENTRY) {
+        Ok(mut entry) => entry.read_to_string(&mut content)?,
+        Err(_) => bail!("not a lanaya backup"),
+    };
+    let manifest: BackupManifest = serde_json::from_str(&content)?;
+    if manifest.format_version > FORMAT_VERSION {
+        bail!(
+            "backup was created by a newer version ({}), please upgrade first",
+            manifest.app_version
+        );
+    }
+    Ok(manifest)
+}
+
+/// 校验版本后恢复数据库和文件，strategy 决定如何处理当前的记录
+pub fn restore_backup(path: &Path, strategy: MergeStrategy) -> Result<RestoreResult> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let manifest = read_manifest(&mut archive)?;
+    let snapshot = temp_db_path("restore");
+    match archive.by_name(DATABASE_ENTRY) {
+        Ok(mut entry) => {
+            let mut file = File::create(&snapshot)?;
+            std::io::copy(&mut entry, &mut file)?;
+        }
+        Err(_) => bail!("backup does not contain a database"),
+    }
+    // 合并时本机的口令文件不会被替换，口令不同的加密记录无法解密
+    let skip_secure = strategy != MergeStrategy::Replace
+        && read_entry(&mut archive, &format!("{}{}", FILES_PREFIX, VAULT_ENTRY))
+            .map_or(false, |vault| !Vault::is_same_vault(&vault));
+    let res = SqliteDB::new().restore_from(&snapshot, strategy, skip_secure);
+    log_err!(fs::remove_file(&snapshot));
+    let (records, skipped_secure) = res?;
+    // 缩略图按 id 缓存，恢复后 id 对应的记录已经不同
+    thumbnail::clear()?;
+    let files = extract_files(&mut archive, strategy == MergeStrategy::Replace)?;
+    // 口令文件可能已被替换，需要重新解锁
+    Vault::global().lock();
+    log_err!(Handle::update_systray());
+    log_err!(Handle::notice_to_window(MsgTypeEnum::ChangeClipBoard, "ok"));
+    Ok(RestoreResult {
+        app_version: manifest.app_version,
+        created_at: manifest.created_at,
+        records,
+        files,
+        skipped_secure,
+    })
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Option<String> {
+    let mut content = String::new();
+    archive
+        .by_name(name)
+        .ok()?
+        .read_to_string(&mut content)
+        .ok()?;
+    Some(content)
+}
+
+/// 配置文件写回后由配置热加载生效，合并时不覆盖本机已有的文件
+fn extract_files(archive: &mut ZipArchive<File>, overwrite: bool) -> Result<usize> {
+    let home = dirs::app_home_dir()?;
+    let mut count = 0;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        // enclosed_name 会拒绝 ../ 等逃出目录的路径
+        let relative = match entry
+            .enclosed_name()
+            .and_then(|name| name.strip_prefix(FILES_PREFIX).ok())
+        {
+            Some(relative) => relative.to_path_buf(),
+            None => continue,
+        };
+        if entry.is_dir() || is_excluded(&relative) {
+            continue;
+        }
+        let target = home.join(&relative);
+        if !overwrite && target.exists() {
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        std::io::copy(&mut entry, &mut File::create(&target)?)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[test]
+fn test_backup_excluded() {
+    assert!(is_excluded(Path::new("logs/app.log")));
+    assert!(is_excluded(&Path::new("data").join(SQLITE_FILE)));
+    assert!(is_excluded(
+        &Path::new("data").join(format!("{}-wal", SQLITE_FILE))
+    ));
+    assert!(!is_excluded(Path::new("data/img/1.png")));
+    assert!(is_excluded(Path::new("data/img/thumbs/1_512.jpg")));
+    assert!(is_excluded(Path::new("tmp/edit-1-0a1b.txt")));
+    assert!(!is_excluded(Path::new("config.json")));
+}
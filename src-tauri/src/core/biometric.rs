@@ -0,0 +1,133 @@
+use anyhow::{bail, Result};
+use tauri::Window;
+
+const KEYRING_SERVICE: &str = "com.church.lanaya";
+const KEYRING_USER: &str = "vault-key";
+
+/// 当前设备是否支持生物识别（Touch ID / Windows Hello）
+pub fn is_available() -> bool {
+    platform::is_available()
+}
+
+/// 弹出系统的生物识别验证，验证通过返回 Ok(())
+/// window 为发起验证的窗口，Windows 上验证对话框显示在它的前面
+pub async fn verify(window: &Window, reason: &str) -> Result<()> {
+    if !is_available() {
+        bail!("biometric authentication is not available");
+    }
+    let window = window.clone();
+    let reason = reason.to_string();
+    // 一直等到用户完成验证，放到阻塞线程中，不占用异步运行时
+    let verified =
+        tauri::async_runtime::spawn_blocking(move || platform::verify(&window, &reason)).await??;
+    match verified {
+        true => Ok(()),
+        false => bail!("biometric authentication failed"),
+    }
+}
+
+/// 密钥保存在系统钥匙串 / 凭据管理器中
+pub fn save_secret(secret: &str) -> Result<()> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)?.set_password(secret)?;
+    Ok(())
+}
+
+pub fn load_secret() -> Result<String> {
+    Ok(keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)?.get_password()?)
+}
+
+pub fn delete_secret() -> Result<()> {
+    match keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)?.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use anyhow::Result;
+    use block::ConcreteBlock;
+    use objc::runtime::{Object, BOOL, YES};
+    use objc::{class, msg_send, sel, sel_impl};
+    use std::ffi::CString;
+    use std::sync::mpsc;
+    use tauri::Window;
+
+    // LAPolicyDeviceOwnerAuthenticationWithBiometrics
+    const LA_POLICY_BIOMETRICS: isize = 1;
+
+    #[link(name = "LocalAuthentication", kind = "framework")]
+    extern "C" {}
+
+    pub fn is_available() -> bool {
+        unsafe {
+            let context: *mut Object = msg_send![class!(LAContext), new];
+            let error: *mut Object = std::ptr::null_mut();
+            let can: BOOL =
+                msg_send![context, canEvaluatePolicy: LA_POLICY_BIOMETRICS error: error];
+            let _: () = msg_send![context, release];
+            can == YES
+        }
+    }
+
+    pub fn verify(_window: &Window, reason: &str) -> Result<bool> {
+        let (tx, rx) = mpsc::channel();
+        let reason = CString::new(reason)?;
+        unsafe {
+            let context: *mut Object = msg_send![class!(LAContext), new];
+            let reason: *mut Object =
+                msg_send![class!(NSString), stringWithUTF8String: reason.as_ptr()];
+            let reply = ConcreteBlock::new(move |success: BOOL, _error: *mut Object| {
+                let _ = tx.send(success == YES);
+            })
+            .copy();
+            let _: () = msg_send![context, evaluatePolicy: LA_POLICY_BIOMETRICS localizedReason: reason reply: &*reply];
+            let res = rx.recv().unwrap_or(false);
+            let _: () = msg_send![context, release];
+            Ok(res)
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use anyhow::Result;
+    use tauri::Window;
+    use windows::core::{factory, HSTRING};
+    use windows::Foundation::IAsyncOperation;
+    use windows::Security::Credentials::UI::{
+        UserConsentVerificationResult, UserConsentVerifier, UserConsentVerifierAvailability,
+    };
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::WinRT::IUserConsentVerifierInterop;
+
+    pub fn is_available() -> bool {
+        UserConsentVerifier::CheckAvailabilityAsync()
+            .and_then(|op| op.get())
+            .map(|res| res == UserConsentVerifierAvailability::Available)
+            .unwrap_or(false)
+    }
+
+    /// 桌面应用没有 CoreWindow，需要通过 interop 接口指定所有者窗口，否则对话框会出现在窗口后面
+    pub fn verify(window: &Window, reason: &str) -> Result<bool> {
+        let owner = HWND(window.hwnd()?.0);
+        let interop = factory::<UserConsentVerifier, IUserConsentVerifierInterop>()?;
+        let op: IAsyncOperation<UserConsentVerificationResult> =
+            unsafe { interop.RequestVerificationForWindowAsync(owner, &HSTRING::from(reason))? };
+        Ok(op.get()? == UserConsentVerificationResult::Verified)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use anyhow::Result;
+    use tauri::Window;
+
+    pub fn is_available() -> bool {
+        false
+    }
+
+    pub fn verify(_window: &Window, _reason: &str) -> Result<bool> {
+        Ok(false)
+    }
+}
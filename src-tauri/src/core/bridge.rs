@@ -0,0 +1,145 @@
+use super::ipc::{self, Stream};
+use anyhow::{bail, Result};
+use arboard::Clipboard;
+use serde_json::json;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// 隧道断开后重连的间隔
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 远程服务器上的 lanaya bridge，不启动界面，只把剪切板的变化转发给本机的实例
+/// 本机开启 enable_ipc 后：ssh -R /tmp/lanaya-$USER.sock:$HOME/.config/lanaya/lanaya.sock dev
+/// 远程：LANAYA_IPC_TOKEN=<本机 ~/.config/lanaya/ipc.token 的内容> lanaya bridge --socket /tmp/lanaya-$USER.sock
+/// 转发过来的 socket 在远程服务器上，连接时必须出示本机实例的令牌
+#[derive(Debug, PartialEq, Clone)]
+pub struct BridgeOptions {
+    /// None 时连接本机的实例
+    pub socket: Option<PathBuf>,
+    /// 保存令牌的文件，未设置时读取 LANAYA_IPC_TOKEN，不从命令行参数传入避免出现在进程列表中
+    pub token_file: Option<PathBuf>,
+    pub interval_ms: u64,
+    /// 作为来源应用显示，默认为 ssh:<主机名>
+    pub name: Option<String>,
+}
+
+impl Default for BridgeOptions {
+    fn default() -> Self {
+        BridgeOptions {
+            socket: None,
+            token_file: None,
+            interval_ms: 500,
+            name: None,
+        }
+    }
+}
+
+/// 多实例占用数据库时也用于区分不同的电脑
+pub fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "remote".into())
+}
+
+fn token(options: &BridgeOptions) -> Result<String> {
+    if let Some(path) = options.token_file.as_ref() {
+        return Ok(std::fs::read_to_string(path)?.trim().to_string());
+    }
+    match std::env::var(ipc::TOKEN_ENV) {
+        Ok(token) if !token.trim().is_empty() => Ok(token.trim().to_string()),
+        _ => bail!("missing token, set {} or pass --token-file", ipc::TOKEN_ENV),
+    }
+}
+
+fn connect(options: &BridgeOptions) -> Result<Stream> {
+    match options.socket.as_ref() {
+        Some(path) => ipc::connect_at(path, &token(options)?),
+        None => ipc::connect(),
+    }
+}
+
+/// 和上次的内容比较，变化时返回需要转发的文本，启动时已有的内容不转发
+fn changed(last: &mut Option<String>, current: Option<String>) -> Option<String> {
+    let current = current.filter(|text| !text.trim().is_empty())?;
+    if last.as_ref() == Some(&current) {
+        return None;
+    }
+    let first = last.is_none();
+    *last = Some(current.clone());
+    match first {
+        true => None,
+        false => Some(current),
+    }
+}
+
+pub fn run(options: BridgeOptions) -> Result<()> {
+    let mut clipboard = match Clipboard::new() {
+        Ok(clipboard) => clipboard,
+        Err(err) => bail!(
+            "no clipboard available on this host ({}), pipe terminal output into \"lanaya osc52\" instead",
+            err
+        ),
+    };
+    let source = options
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("ssh:{}", hostname()));
+    let mut stream: Option<Stream> = None;
+    let mut last = None;
+    // 转发失败的内容在重连后重发
+    let mut unsent: Option<String> = None;
+    loop {
+        if let Some(text) = changed(&mut last, clipboard.get_text().ok()) {
+            unsent = Some(text);
+        }
+        if let Some(text) = unsent.as_ref() {
+            if stream.is_none() {
+                match connect(&options) {
+                    Ok(s) => {
+                        eprintln!("bridge connected");
+                        stream = Some(s);
+                    }
+                    Err(err) => eprintln!("bridge connect error: {}", err),
+                }
+            }
+            if let Some(s) = stream.as_mut() {
+                let req = json!({ "op": "capture", "text": text, "browser": source });
+                match ipc::request(s, &req) {
+                    Ok(_) => unsent = None,
+                    // 连接已断开时重连，其它错误（如暂停记录）丢弃该条
+                    Err(err) => match err.downcast_ref::<std::io::Error>() {
+                        Some(_) => {
+                            eprintln!("bridge disconnected: {}", err);
+                            stream = None;
+                        }
+                        None => {
+                            eprintln!("bridge: {}", err);
+                            unsent = None;
+                        }
+                    },
+                }
+            }
+        }
+        let wait = match stream.is_none() && unsent.is_some() {
+            true => RECONNECT_INTERVAL,
+            false => Duration::from_millis(options.interval_ms),
+        };
+        thread::sleep(wait);
+    }
+}
+
+#[test]
+fn test_bridge_changed() {
+    let mut last = None;
+    assert_eq!(changed(&mut last, Some("old".into())), None);
+    assert_eq!(changed(&mut last, Some("old".into())), None);
+    assert_eq!(changed(&mut last, None), None);
+    assert_eq!(changed(&mut last, Some("  ".into())), None);
+    assert_eq!(changed(&mut last, Some("new".into())), Some("new".into()));
+    assert_eq!(last, Some("new".into()));
+}
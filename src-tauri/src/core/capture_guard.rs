@@ -0,0 +1,72 @@
+use super::capture_notify;
+use super::i18n;
+use crate::config::Config;
+
+pub const OVERSIZE_ACTIONS: [&str; 2] = ["skip", "stub"];
+
+/// 超过大小上限时的处理结果
+#[derive(Debug, PartialEq)]
+pub enum Guard {
+    Accept,
+    Skip,
+    /// 用占位文本代替原内容
+    Stub(String),
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1024 * 1024 => format!("{:.1} MB", b as f64 / 1024.0 / 1024.0),
+        b if b >= 1024 => format!("{:.1} KB", b as f64 / 1024.0),
+        b => format!("{} B", b),
+    }
+}
+
+/// limit_mb 为 0 时不限制，description 为内容的简短描述，如 "text"、"image 7680x4320"
+fn decide(size: u64, limit_mb: u64, action: &str, description: &str) -> Guard {
+    if limit_mb == 0 || size <= limit_mb.saturating_mul(1024 * 1024) {
+        return Guard::Accept;
+    }
+    match action {
+        "skip" => Guard::Skip,
+        _ => Guard::Stub(format!(
+            "[Oversize {}: {} not stored]",
+            description,
+            format_size(size)
+        )),
+    }
+}
+
+/// 在编码、插件处理之前检查，超过上限时发送通知
+pub fn check(size: u64, description: &str) -> Guard {
+    let (limit_mb, action) = {
+        let config = Config::common().latest();
+        (
+            config.max_capture_size_mb.unwrap_or(100),
+            config
+                .oversize_action
+                .clone()
+                .unwrap_or_else(|| "stub".into()),
+        )
+    };
+    let guard = decide(size, limit_mb, &action, description);
+    if guard != Guard::Accept {
+        capture_notify::notify(i18n::t_args(
+            "notification.oversize",
+            &[("size", &format_size(size))],
+        ));
+    }
+    guard
+}
+
+#[test]
+fn test_capture_guard() {
+    assert_eq!(decide(10, 1, "skip", "text"), Guard::Accept);
+    assert_eq!(decide(u64::MAX, 0, "skip", "text"), Guard::Accept);
+    assert_eq!(decide(u64::MAX, u64::MAX, "skip", "text"), Guard::Accept);
+    assert_eq!(decide(2 * 1024 * 1024, 1, "skip", "text"), Guard::Skip);
+    assert_eq!(
+        decide(3 * 1024 * 1024 / 2, 1, "stub", "image 100x100"),
+        Guard::Stub("[Oversize image 100x100: 1.5 MB not stored]".into())
+    );
+    assert_eq!(format_size(2048), "2.0 KB");
+}
@@ -0,0 +1,129 @@
+use super::database::{Record, SqliteDB};
+use super::handle::Handle;
+use super::i18n;
+use super::tray::Tray;
+use crate::config::{CommonConfig, Config};
+use crate::log_err;
+use anyhow::{bail, Result};
+use chrono::Timelike;
+use tauri::api::notification::Notification;
+use tauri::Manager;
+
+/// "HH:MM-HH:MM" 转成一天中的 (开始, 结束) 分钟数
+pub fn parse_quiet_hours(spec: &str) -> Result<(u32, u32)> {
+    let parse = |time: &str| -> Option<u32> {
+        let (hour, minute) = time.trim().split_once(':')?;
+        let (hour, minute) = (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?);
+        if hour > 23 || minute > 59 {
+            return None;
+        }
+        Some(hour * 60 + minute)
+    };
+    match spec
+        .split_once('-')
+        .and_then(|(s, e)| Some((parse(s)?, parse(e)?)))
+    {
+        Some(range) => Ok(range),
+        None => bail!("quiet hours must be like 22:00-07:00"),
+    }
+}
+
+/// 开始时间大于结束时间时表示跨过零点
+fn in_range((start, end): (u32, u32), minutes: u32) -> bool {
+    if start <= end {
+        start <= minutes && minutes < end
+    } else {
+        minutes >= start || minutes < end
+    }
+}
+
+/// 当前是否处于免打扰时段，未设置时返回 false
+pub fn is_quiet_now() -> bool {
+    let quiet_hours = { Config::common().latest().quiet_hours.clone() };
+    let range = match quiet_hours.filter(|q| !q.is_empty()) {
+        Some(quiet_hours) => parse_quiet_hours(&quiet_hours),
+        None => return false,
+    };
+    let now = chrono::Local::now();
+    match range {
+        Ok(range) => in_range(range, now.hour() * 60 + now.minute()),
+        Err(_) => false,
+    }
+}
+
+fn should_notify(config: &CommonConfig, record: &Record) -> bool {
+    let types = config
+        .capture_notification_types
+        .clone()
+        .unwrap_or_default();
+    if !types.contains(&record.data_type) {
+        return false;
+    }
+    let keywords = config
+        .capture_notification_keywords
+        .clone()
+        .unwrap_or_default();
+    if record.data_type != "text" || keywords.is_empty() {
+        return true;
+    }
+    // 加密记录无法匹配关键字
+    if record.is_secure {
+        return false;
+    }
+    let content = record.content.to_lowercase();
+    keywords
+        .iter()
+        .any(|keyword| !keyword.is_empty() && content.contains(&keyword.to_lowercase()))
+}
+
+/// 新记录插入后调用，按配置决定是否发送系统通知
+pub fn on_inserted(id: u64) {
+    let config = { Config::common().latest().clone() };
+    if !config.enable_capture_notification.unwrap_or(false) || is_quiet_now() {
+        return;
+    }
+    let record = match SqliteDB::new().find_by_id(id) {
+        Ok(record) => record,
+        Err(err) => {
+            println!("capture notification error: {}", err);
+            return;
+        }
+    };
+    if !should_notify(&config, &record) {
+        return;
+    }
+    show(
+        &i18n::t("notification.captured"),
+        &Tray::record_title(&record),
+    );
+}
+
+/// 不受记录通知开关影响的提醒，免打扰时段内不发送
+pub fn notify(body: String) {
+    if is_quiet_now() {
+        return;
+    }
+    show("Lanaya", &body);
+}
+
+fn show(title: &str, body: &str) {
+    let identifier = match Handle::global().app_handle.lock().as_ref() {
+        Some(app_handle) => app_handle.config().tauri.bundle.identifier.clone(),
+        None => return,
+    };
+    log_err!(Notification::new(identifier).title(title).body(body).show());
+}
+
+#[test]
+fn test_quiet_hours() {
+    assert_eq!(parse_quiet_hours("22:00-07:30").unwrap(), (1320, 450));
+    assert!(parse_quiet_hours("25:00-07:00").is_err());
+    assert!(parse_quiet_hours("22:00").is_err());
+    let overnight = parse_quiet_hours("22:00-07:00").unwrap();
+    assert!(in_range(overnight, 23 * 60));
+    assert!(in_range(overnight, 6 * 60));
+    assert!(!in_range(overnight, 12 * 60));
+    let daytime = parse_quiet_hours("09:00-18:00").unwrap();
+    assert!(in_range(daytime, 9 * 60));
+    assert!(!in_range(daytime, 18 * 60));
+}
@@ -0,0 +1,465 @@
+use super::bridge::{self, BridgeOptions};
+use super::clipboard::{ClipBoardOprator, ImageDataDB};
+use super::database::{PageReq, Record, SqliteDB, READ_ONLY_ARG};
+use super::deep_link;
+use super::ipc;
+use super::native_messaging;
+use super::osc52;
+use super::retention;
+use super::vault::SECURE_MASK;
+use crate::utils::json_util;
+use anyhow::{bail, Result};
+use serde_json::{json, Value};
+use std::io::Read;
+
+/// lanaya add 单条内容的大小上限
+const MAX_ADD_LEN: usize = 10 * 1024 * 1024;
+
+const USAGE: &str = "usage:
+  lanaya list [--limit <n>] [--json] [--format <fmt>]   列出最近的记录
+  lanaya query <text> [--limit <n>] [--format <fmt>]    搜索记录
+  lanaya get <id>                                       输出记录的完整内容
+  lanaya copy <id>                                      复制记录到剪切板
+  lanaya add <text>                                     新增一条记录
+  lanaya add -                                          从标准输入读取并新增记录
+  lanaya native-host install <extension-id>...          为浏览器扩展注册 native messaging host
+  lanaya osc52 [--passthrough]                          把标准输入中的 OSC 52 剪切板内容发给 Lanaya
+  lanaya bridge [--socket <path>] [--token-file <path>] [--interval <ms>] [--name <name>]
+                                                        在远程服务器上把剪切板的变化转发给本机的 Lanaya
+                                                        令牌为本机 ipc.token 的内容，也可以通过 LANAYA_IPC_TOKEN 传入
+  fmt: plain, json, alfred (Alfred / Raycast script filter), wox";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OutputFormat {
+    Plain,
+    Json,
+    /// Alfred script filter，Raycast 的 script filter 兼容该格式
+    Alfred,
+    Wox,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    List {
+        key: Option<String>,
+        limit: usize,
+        format: OutputFormat,
+    },
+    Get(u64),
+    Copy(u64),
+    /// None 表示从标准输入读取
+    Add(Option<String>),
+    /// 扩展 id，Chrome 系和 Firefox 的可以混在一起
+    InstallNativeHost(Vec<String>),
+    /// passthrough 时同时把输入原样输出
+    Osc52 {
+        passthrough: bool,
+    },
+    Bridge(BridgeOptions),
+}
+
+fn parse_id(arg: Option<&&str>) -> Result<u64> {
+    match arg.map(|id| id.parse::<u64>()) {
+        Some(Ok(id)) => Ok(id),
+        _ => bail!("missing or invalid record id"),
+    }
+}
+
+fn parse_list(args: &[&str], key: Option<String>) -> Result<Command> {
+    let mut limit = 20;
+    let mut format = OutputFormat::Plain;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match *arg {
+            "--json" => format = OutputFormat::Json,
+            "--limit" => match iter.next().map(|n| n.parse::<usize>()) {
+                Some(Ok(n)) if n > 0 => limit = n,
+                _ => bail!("invalid value for --limit"),
+            },
+            "--format" => {
+                format = match iter.next() {
+                    Some(&"plain") => OutputFormat::Plain,
+                    Some(&"json") => OutputFormat::Json,
+                    Some(&"alfred") | Some(&"raycast") => OutputFormat::Alfred,
+                    Some(&"wox") => OutputFormat::Wox,
+                    _ => bail!("invalid value for --format"),
+                }
+            }
+            _ => bail!("unknown option \"{}\"", arg),
+        }
+    }
+    Ok(Command::List { key, limit, format })
+}
+
+fn parse_bridge(args: &[&str]) -> Result<Command> {
+    let mut options = BridgeOptions::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match *arg {
+            "--socket" => match iter.next() {
+                Some(path) => options.socket = Some(path.into()),
+                None => bail!("missing value for --socket"),
+            },
+            "--token-file" => match iter.next() {
+                Some(path) => options.token_file = Some(path.into()),
+                None => bail!("missing value for --token-file"),
+            },
+            "--interval" => match iter.next().map(|n| n.parse::<u64>()) {
+                Some(Ok(n)) if n >= 100 => options.interval_ms = n,
+                _ => bail!("invalid value for --interval, at least 100 ms"),
+            },
+            "--name" => match iter.next() {
+                Some(name) => options.name = Some(name.to_string()),
+                None => bail!("missing value for --name"),
+            },
+            _ => bail!("unknown option \"{}\"", arg),
+        }
+    }
+    Ok(Command::Bridge(options))
+}
+
+/// 解析命令行参数（不含程序名），不是命令行模式时返回 None
+pub fn parse(args: &[String]) -> Option<Result<Command>> {
+    // --portable、--read-only 等启动参数不属于命令
+    let args: Vec<&str> = args
+        .iter()
+        .map(|a| a.as_str())
+        .filter(|a| *a != "--portable" && *a != READ_ONLY_ARG)
+        .collect();
+    let command = match args.first() {
+        Some(command) => *command,
+        None => return None,
+    };
+    let res = match command {
+        "list" => parse_list(&args[1..], None),
+        "query" => {
+            // 选项之前的参数都是搜索词，启动器会把整段输入作为一个或多个参数传入
+            let split = args[1..]
+                .iter()
+                .position(|a| a.starts_with("--"))
+                .map_or(args.len(), |i| i + 1);
+            let key = args[1..split].join(" ");
+            parse_list(&args[split..], Some(key))
+        }
+        "get" => parse_id(args.get(1)).map(Command::Get),
+        "copy" => parse_id(args.get(1)).map(Command::Copy),
+        "add" => match &args[1..] {
+            [] => Err(anyhow::anyhow!(
+                "missing text, use \"add -\" to read from stdin"
+            )),
+            ["-"] => Ok(Command::Add(None)),
+            // 未加引号的多个参数按空格拼接
+            words => Ok(Command::Add(Some(words.join(" ")))),
+        },
+        "native-host" => match &args[1..] {
+            ["install", ids @ ..] if !ids.is_empty() => Ok(Command::InstallNativeHost(
+                ids.iter().map(|id| id.to_string()).collect(),
+            )),
+            _ => Err(anyhow::anyhow!(
+                "usage: lanaya native-host install <extension-id>..."
+            )),
+        },
+        "osc52" => match &args[1..] {
+            [] => Ok(Command::Osc52 { passthrough: false }),
+            ["--passthrough"] => Ok(Command::Osc52 { passthrough: true }),
+            _ => Err(anyhow::anyhow!("usage: lanaya osc52 [--passthrough]")),
+        },
+        "bridge" => parse_bridge(&args[1..]),
+        "help" | "--help" | "-h" => Err(anyhow::anyhow!("")),
+        // 其它参数（如 lanaya:// 链接）交给正常启动流程
+        _ => return None,
+    };
+    Some(res)
+}
+
+/// 在界面启动前调用：不需要界面的命令直接执行并退出进程
+/// copy 需要正在运行的实例持有剪切板，交给单实例插件转发
+pub fn run() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match parse(&args) {
+        None | Some(Ok(Command::Copy(_))) => return,
+        Some(res) => res,
+    };
+    platform::attach_console();
+    let res = command.and_then(execute);
+    match res {
+        Ok(()) => std::process::exit(0),
+        Err(err) => {
+            if !err.to_string().is_empty() {
+                eprintln!("error: {}", err);
+            }
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 处理转发过来的命令行参数，已处理返回 true
+pub fn handle_args(argv: &[String]) -> bool {
+    let args = argv.get(1..).unwrap_or_default();
+    match parse(args) {
+        Some(Ok(Command::Copy(id))) => {
+            if let Err(err) = ClipBoardOprator::copy_by_id(id, None) {
+                println!("cli copy error: {}", err);
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+fn execute(command: Command) -> Result<()> {
+    match command {
+        Command::List { key, limit, format } => {
+            let db = SqliteDB::open_read_only()?;
+            let page = db.find_page(PageReq {
+                page_size: Some(limit),
+                key: key.filter(|k| !k.trim().is_empty()),
+                ..PageReq::default()
+            })?;
+            match format {
+                OutputFormat::Plain => {
+                    for r in page.records.iter() {
+                        println!("{}\t{}\t{}", r.id, r.data_type, summary(r));
+                    }
+                }
+                OutputFormat::Json => println!("{}", serde_json::to_string(&page.records)?),
+                format => println!("{}", launcher_items(&page.records, format)),
+            }
+            Ok(())
+        }
+        Command::Get(id) => {
+            let record = SqliteDB::open_read_only()?.find_by_id(id)?;
+            if record.is_secure {
+                bail!("record {} is secure, unlock it in Lanaya first", id);
+            }
+            if record.data_type != "text" {
+                bail!(
+                    "record {} is an {}, use \"lanaya copy {}\"",
+                    id,
+                    record.data_type,
+                    id
+                );
+            }
+            print!("{}", record.content);
+            Ok(())
+        }
+        Command::Add(text) => {
+            let content = match text {
+                Some(text) => text,
+                None => {
+                    // 多读一个字节用于判断是否超出上限
+                    let mut content = String::new();
+                    std::io::stdin()
+                        .take(MAX_ADD_LEN as u64 + 1)
+                        .read_to_string(&mut content)?;
+                    content
+                }
+            };
+            if content.trim().is_empty() {
+                bail!("nothing to add");
+            }
+            if content.len() > MAX_ADD_LEN {
+                bail!("content is larger than {} MB", MAX_ADD_LEN / 1024 / 1024);
+            }
+            // 有运行中的实例时由它写入，两个进程不同时写数据库
+            if let Ok(mut stream) = ipc::connect() {
+                let res = ipc::request(&mut stream, &json!({ "op": "add", "text": content }))?;
+                println!("{}", res["id"]);
+                return Ok(());
+            }
+            SqliteDB::init_shared();
+            println!("{}", add_text(&SqliteDB::new(), content)?);
+            Ok(())
+        }
+        Command::InstallNativeHost(ids) => {
+            for path in native_messaging::install(&ids)? {
+                println!("{}", path);
+            }
+            Ok(())
+        }
+        Command::Osc52 { passthrough } => osc52::forward_stdin(passthrough),
+        Command::Bridge(options) => bridge::run(options),
+        Command::Copy(_) => bail!("copy is handled by the running instance"),
+    }
+}
+
+/// 内容相同的记录只更新时间，不会重复插入
+pub fn add_text(db: &SqliteDB, content: String) -> Result<u64> {
+    if SqliteDB::is_read_only() {
+        bail!("the database is opened read-only");
+    }
+    let res = db.insert_if_not_exist(Record {
+        content,
+        data_type: "text".to_string(),
+        ..Default::default()
+    })?;
+    retention::enforce(db)?;
+    Ok(res.id())
+}
+
+/// 启动器的结果列表，Alfred 选中后打开 lanaya://copy/<id> 复制到剪切板
+fn launcher_items(records: &[Record], format: OutputFormat) -> Value {
+    let items: Vec<Value> = records
+        .iter()
+        .map(|r| {
+            let title = summary(r);
+            let subtitle = match r.tags.is_empty() {
+                true => r.data_type.clone(),
+                false => format!("{} · {}", r.data_type, r.tags),
+            };
+            let arg = format!("{}://copy/{}", deep_link::SCHEME, r.id);
+            let icon = match r.data_type.as_str() {
+                "image" => "public.image",
+                _ => "public.plain-text",
+            };
+            match format {
+                OutputFormat::Wox => json!({
+                    "Title": title,
+                    "SubTitle": subtitle,
+                    // 由 Wox 插件的 copy 方法调用 lanaya copy <id>
+                    "JsonRPCAction": { "method": "copy", "parameters": [r.id] },
+                }),
+                _ => json!({
+                    "uid": r.id.to_string(),
+                    "title": title,
+                    "subtitle": subtitle,
+                    "arg": arg,
+                    "icon": {
+                        "type": "filetype",
+                        "path": icon,
+                    },
+                }),
+            }
+        })
+        .collect();
+    match format {
+        OutputFormat::Wox => json!({ "result": items }),
+        _ => json!({ "items": items }),
+    }
+}
+
+/// 单行摘要
+fn summary(r: &Record) -> String {
+    if r.is_secure {
+        return SECURE_MASK.to_string();
+    }
+    if r.data_type == "image" || r.data_type == "svg" {
+        let image: ImageDataDB = json_util::parse(&r.content).unwrap_or_default();
+        return format!("[Image] {}x{}", image.width, image.height);
+    }
+    let line = r
+        .content
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if line.chars().count() > 80 {
+        format!("{}…", line.chars().take(80).collect::<String>())
+    } else {
+        line
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+
+    /// release 版本没有控制台窗口，挂到启动它的终端上才能输出
+    pub fn attach_console() {
+        const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+        unsafe {
+            AttachConsole(ATTACH_PARENT_PROCESS);
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod platform {
+    pub fn attach_console() {}
+}
+
+#[test]
+fn test_parse() {
+    let args = |s: &str| -> Vec<String> { s.split(' ').map(String::from).collect() };
+    assert_eq!(
+        parse(&args("list --limit 5 --json")).unwrap().unwrap(),
+        Command::List {
+            key: None,
+            limit: 5,
+            format: OutputFormat::Json
+        }
+    );
+    assert_eq!(
+        parse(&args("query hello world --format alfred"))
+            .unwrap()
+            .unwrap(),
+        Command::List {
+            key: Some("hello world".into()),
+            limit: 20,
+            format: OutputFormat::Alfred
+        }
+    );
+    assert!(parse(&args("list --format xml")).unwrap().is_err());
+    assert_eq!(parse(&args("get 12")).unwrap().unwrap(), Command::Get(12));
+    assert_eq!(
+        parse(&args("--portable copy 3")).unwrap().unwrap(),
+        Command::Copy(3)
+    );
+    assert_eq!(
+        parse(&args("--read-only copy 3")).unwrap().unwrap(),
+        Command::Copy(3)
+    );
+    assert_eq!(parse(&args("add -")).unwrap().unwrap(), Command::Add(None));
+    assert_eq!(
+        parse(&args("add build passed")).unwrap().unwrap(),
+        Command::Add(Some("build passed".into()))
+    );
+    assert!(parse(&args("add")).unwrap().is_err());
+    assert_eq!(
+        parse(&args("native-host install abc lanaya@example.com"))
+            .unwrap()
+            .unwrap(),
+        Command::InstallNativeHost(vec!["abc".into(), "lanaya@example.com".into()])
+    );
+    assert!(parse(&args("native-host install")).unwrap().is_err());
+    assert_eq!(
+        parse(&args("osc52 --passthrough")).unwrap().unwrap(),
+        Command::Osc52 { passthrough: true }
+    );
+    assert!(parse(&args("osc52 -v")).unwrap().is_err());
+    assert_eq!(
+        parse(&args(
+            "bridge --socket /tmp/lanaya.sock --token-file /tmp/token --name dev"
+        ))
+        .unwrap()
+        .unwrap(),
+        Command::Bridge(BridgeOptions {
+            socket: Some("/tmp/lanaya.sock".into()),
+            token_file: Some("/tmp/token".into()),
+            name: Some("dev".into()),
+            ..BridgeOptions::default()
+        })
+    );
+    assert!(parse(&args("bridge --interval 10")).unwrap().is_err());
+    assert!(parse(&args("get abc")).unwrap().is_err());
+    assert!(parse(&args("lanaya://copy/1")).is_none());
+    assert!(parse(&[]).is_none());
+}
+
+#[test]
+fn test_launcher_items() {
+    let records = vec![Record {
+        id: 7,
+        content: "hello\nworld".into(),
+        data_type: "text".into(),
+        ..Default::default()
+    }];
+    let alfred = launcher_items(&records, OutputFormat::Alfred);
+    assert_eq!(alfred["items"][0]["title"], "hello world");
+    assert_eq!(alfred["items"][0]["arg"], "lanaya://copy/7");
+    let wox = launcher_items(&records, OutputFormat::Wox);
+    assert_eq!(wox["result"][0]["Title"], "hello world");
+}
@@ -1,13 +1,41 @@
+use super::analytics::{self, EventKind};
+use super::audit::AuditAction;
+use super::capture_guard::{self, Guard};
+use super::capture_notify;
+use super::cloud_clipboard;
 use super::database;
 use super::handle::{self, MsgTypeEnum};
+use super::idle;
+use super::macros;
+use super::native_messaging;
+use super::paste;
+use super::pasteboard;
+use super::phash;
+use super::plugin;
+use super::retention;
+use super::rules;
+use super::screen_share;
+use super::script;
+use super::secure_input;
+use super::sound;
+use super::source_app;
+use super::suggestion;
+use super::svg;
+use super::time_machine;
+use super::url_clean;
+use super::vault::Vault;
 use crate::config::Config;
-use crate::core::database::Record;
+use crate::core::database::{InsertResult, Record};
+use crate::log_err;
 use crate::utils::{img_util, json_util, string_util};
-use anyhow::Result;
+use anyhow::{bail, Result};
 use arboard::Clipboard;
 use chrono::Duration;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::thread;
 const CHANGE_DEFAULT_MSG: &str = "ok";
 
@@ -15,29 +43,372 @@ pub struct ClipboardWatcher;
 
 pub struct ClipBoardOprator;
 
-#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ImageDataDB {
+    /// 像素尺寸
     pub width: usize,
     pub height: usize,
+    /// 复制时的屏幕缩放比例，逻辑尺寸 = 像素尺寸 / scale，旧记录没有该字段按 1 处理
+    #[serde(default = "default_scale")]
+    pub scale: f64,
     pub base64: String,
+    /// 复制的是 GIF 动图时保存原始的 GIF（base64），base64 中只是第一帧
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gif: Option<String>,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+impl Default for ImageDataDB {
+    fn default() -> Self {
+        ImageDataDB {
+            width: 0,
+            height: 0,
+            scale: default_scale(),
+            base64: String::new(),
+            gif: None,
+        }
+    }
+}
+
+/// 重新复制记录时可选的格式
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipFormat {
+    Plain,
+    Html,
+    Rtf,
+    Image,
+    Files,
+}
+
+/// 粘贴时带格式还是只粘贴纯文本，只对文本记录生效
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PasteStyle {
+    /// 按复制时的原始类型写回，包括 html、rtf 等
+    Formatted,
+    Plain,
 }
 
 impl ClipBoardOprator {
     pub fn set_text(text: String) -> Result<()> {
+        Self::write_text(text, false)
+    }
+
+    /// 加密记录的内容不进入系统剪切板历史，也不同步到其它设备
+    pub fn set_secure_text(text: String) -> Result<()> {
+        Self::write_text(text, true)
+    }
+
+    fn write_text(text: String, secure: bool) -> Result<()> {
+        if cloud_clipboard::is_supported() && (secure || cloud_clipboard::is_enabled()) {
+            return cloud_clipboard::set_text(&text, !secure);
+        }
         let mut clipboard = Clipboard::new()?;
         clipboard.set_text(text)?;
         Ok(())
     }
 
     pub fn set_image(data: ImageDataDB) -> Result<()> {
+        // 动图按 GIF 写回，同时带上第一帧的 PNG 供不支持 GIF 的应用使用
+        if let Some(gif) = &data.gif {
+            if pasteboard::write_gif(&string_util::base64_decode(gif), &data)? {
+                return Ok(());
+            }
+        }
+        // 高分屏的图片需要带上逻辑尺寸写回，否则粘贴后会放大一倍变模糊
+        if data.scale > 1.0 && pasteboard::write_image(&data)? {
+            return Ok(());
+        }
         let mut clipboard = Clipboard::new()?;
         let img_data = img_util::base64_to_rgba8(&data.base64).unwrap();
         clipboard.set_image(img_data)?;
         Ok(())
     }
+
+    /// 清空系统剪切板，不影响历史记录
+    pub fn clear() -> Result<()> {
+        time_machine::remember_current();
+        let mut clipboard = Clipboard::new()?;
+        clipboard.clear()?;
+        Ok(())
+    }
+
+    pub fn set_html(html: String, alt_text: String) -> Result<()> {
+        let mut clipboard = Clipboard::new()?;
+        clipboard.set_html(html, Some(alt_text))?;
+        Ok(())
+    }
+
+    /// 按 id 重新复制记录，format 为空时按记录类型选择默认格式
+    pub fn copy_by_id(id: u64, format: Option<ClipFormat>) -> Result<()> {
+        let db = database::SqliteDB::new();
+        Self::write_by_id(&db, id, format)?;
+        if database::SqliteDB::is_read_only() {
+            return Ok(());
+        }
+        db.touch_record(id)?;
+        db.audit(AuditAction::Pasted, id);
+        db.log_event(EventKind::Paste);
+        handle::Handle::notice_record(MsgTypeEnum::RecordUpdated, id);
+        suggestion::on_used(id);
+        Ok(())
+    }
+
+    /// 恢复剪切板时写回记录，不算作粘贴，也不更新复制时间和次数
+    pub fn restore_by_id(id: u64) -> Result<()> {
+        let record = Self::write_by_id(&database::SqliteDB::new(), id, None)?;
+        if record.data_type == "text" {
+            *transient_md5().lock() = Some(string_util::md5(&record.content));
+        }
+        Ok(())
+    }
+
+    fn write_by_id(db: &database::SqliteDB, id: u64, format: Option<ClipFormat>) -> Result<Record> {
+        let mut record = db.find_by_id(id)?;
+        record.content = Vault::global().reveal(&record)?;
+        time_machine::remember_current();
+        // 未指定格式时优先按复制时的原始类型写回
+        let restored = format.is_none() && pasteboard::restore(db, &record)?;
+        if !restored {
+            // SVG 默认复制源码
+            let format = format.unwrap_or(match record.data_type.as_str() {
+                "image" => ClipFormat::Image,
+                _ => ClipFormat::Plain,
+            });
+            Self::write_record(&record, format)?;
+        }
+        Ok(record)
+    }
+
+    /// 按粘贴格式复制，style 为空时使用 paste_style_by_app 中目标应用的设置，都没有时带格式
+    pub fn copy_with_style(id: u64, style: Option<PasteStyle>) -> Result<PasteStyle> {
+        let style = style
+            .or_else(|| paste::default_style(paste::target_app().as_deref()))
+            .unwrap_or(PasteStyle::Formatted);
+        let data_type = database::SqliteDB::new()
+            .find_list_item_by_id(id)?
+            .data_type;
+        let format = match (style, data_type.as_str()) {
+            (PasteStyle::Plain, "text" | "svg") => Some(ClipFormat::Plain),
+            _ => None,
+        };
+        Self::copy_by_id(id, format)?;
+        Ok(style)
+    }
+
+    /// 把记录和关联的文本记录按关联顺序合并后复制，separator 默认为换行
+    pub fn copy_with_links(id: u64, separator: Option<String>) -> Result<()> {
+        let db = database::SqliteDB::new();
+        let mut ids = vec![id];
+        ids.extend(db.find_linked_records(id)?.into_iter().map(|r| r.id));
+        let mut contents = vec![];
+        let mut secure = false;
+        for id in ids {
+            let record = db.find_by_id(id)?;
+            if record.data_type == "text" {
+                secure |= record.is_secure;
+                contents.push(Vault::global().reveal(&record)?);
+            }
+        }
+        if contents.is_empty() {
+            bail!("no text record to copy");
+        }
+        time_machine::remember_current();
+        Self::write_text(contents.join(separator.as_deref().unwrap_or("\n")), secure)?;
+        if !database::SqliteDB::is_read_only() {
+            db.audit(AuditAction::Pasted, id);
+            db.log_event(EventKind::Paste);
+        }
+        Ok(())
+    }
+
+    /// 按选择的顺序合并多条文本记录后复制，不会产生合并后的新记录，返回合并的条数
+    pub fn copy_joined(ids: &[u64], separator: Option<String>) -> Result<usize> {
+        let db = database::SqliteDB::new();
+        let mut contents = vec![];
+        let mut secure = false;
+        for id in ids {
+            let record = db.find_by_id(*id)?;
+            if record.data_type == "text" {
+                secure |= record.is_secure;
+                contents.push(Vault::global().reveal(&record)?);
+            }
+        }
+        if contents.is_empty() {
+            bail!("no text record to copy");
+        }
+        time_machine::remember_current();
+        let separator = separator.unwrap_or_else(|| {
+            Config::common()
+                .latest()
+                .multi_paste_separator
+                .clone()
+                .unwrap_or_else(|| "\n".into())
+        });
+        let text = contents.join(&separator);
+        *transient_md5().lock() = Some(string_util::md5(&text));
+        Self::write_text(text, secure)?;
+        if !database::SqliteDB::is_read_only() {
+            for id in ids {
+                db.audit(AuditAction::Pasted, *id);
+            }
+            db.log_event(EventKind::Paste);
+        }
+        Ok(contents.len())
+    }
+
+    /// 写入剪切板但不记录，剪切板监听读到相同内容时跳过
+    pub fn set_transient_text(text: String) -> Result<()> {
+        *transient_md5().lock() = Some(string_util::md5(&text));
+        Self::set_text(text)
+    }
+
+    /// 按指定格式把记录写回剪切板，content 需为明文
+    pub fn write_record(record: &Record, format: ClipFormat) -> Result<()> {
+        match (record.data_type.as_str(), format) {
+            ("text", ClipFormat::Plain) => {
+                Self::write_text(record.content.clone(), record.is_secure)
+            }
+            ("text", ClipFormat::Html) => Self::set_html(
+                string_util::text_to_html(&record.content),
+                record.content.clone(),
+            ),
+            ("image", ClipFormat::Image) => Self::set_image(json_util::parse(&record.content)?),
+            ("svg", ClipFormat::Plain) => {
+                Self::write_text(record.content.clone(), record.is_secure)
+            }
+            ("svg", ClipFormat::Image) => Self::set_image(svg::to_image(&record.content)?),
+            (data_type, format) => {
+                bail!(
+                    "format {:?} is not available for {} record",
+                    format,
+                    data_type
+                )
+            }
+        }
+    }
 }
 
+/// set_transient_text 写入的内容，剪切板监听读到后清空
+fn transient_md5() -> &'static Mutex<Option<String>> {
+    static TRANSIENT: OnceCell<Mutex<Option<String>>> = OnceCell::new();
+    TRANSIENT.get_or_init(|| Mutex::new(None))
+}
+
+/// 用户手动暂停监听剪切板
+static MONITORING: AtomicBool = AtomicBool::new(true);
+/// 监听最后一次检查剪切板的时间（毫秒），用于诊断监听是否还在运行
+static LAST_POLL: AtomicU64 = AtomicU64::new(0);
+
 impl ClipboardWatcher {
+    /// 剪切板监听记录到内容后的通知、提示音，导入等批量插入不走这里
+    pub fn notice_captured(res: &InsertResult) {
+        handle::Handle::notice_insert_result(res);
+        match res {
+            InsertResult::Inserted(id) => capture_notify::on_inserted(*id),
+            InsertResult::Updated(id) => suggestion::on_used(*id),
+        }
+        macros::on_captured(res.id());
+        // 重复复制也算记录成功
+        sound::play("capture");
+        analytics::record(EventKind::Capture);
+    }
+
+    pub fn is_monitoring() -> bool {
+        MONITORING.load(Ordering::SeqCst)
+    }
+
+    /// 手动暂停、只读打开数据库、输入密码或共享屏幕时不记录新内容
+    pub fn should_pause() -> bool {
+        !Self::is_monitoring()
+            || database::SqliteDB::is_read_only()
+            || secure_input::should_pause()
+            || screen_share::should_pause()
+    }
+
+    /// 新的文本依次经过大小检查、插件和脚本、链接清理后入库，再应用规则，被跳过时返回 None
+    /// from_clipboard 为 false 时（如导入系统剪切板历史）不读取剪切板中的其它格式和前台应用
+    pub fn capture_text(
+        db: &database::SqliteDB,
+        content_origin: String,
+        from_clipboard: bool,
+    ) -> Option<InsertResult> {
+        // 过大的内容在插件处理前拦下，避免卡住界面和撑大数据库
+        let (content_origin, stubbed) =
+            match capture_guard::check(content_origin.len() as u64, "text") {
+                Guard::Accept => (content_origin, false),
+                Guard::Skip => return None,
+                Guard::Stub(stub) => (stub, true),
+            };
+        // 插件和脚本可以跳过或改写新复制的文本
+        let content_origin =
+            plugin::process_capture(content_origin).and_then(script::process_capture)?;
+        if content_origin.trim().is_empty() {
+            return None;
+        }
+        // 保存清理跟踪参数后的链接，原始链接另外保存
+        let (content_origin, original) = match url_clean::process_capture(&content_origin) {
+            Some(cleaned) => (cleaned, Some(content_origin)),
+            None => (content_origin, None),
+        };
+        // 浏览器扩展刚推送过相同内容
+        if !native_messaging::claim_capture(&string_util::md5(&content_origin)) {
+            return None;
+        }
+        let source = match from_clipboard {
+            true => source_app::frontmost_app(),
+            false => None,
+        };
+        // SVG 单独作为一种类型，列表中显示渲染后的图片
+        let (data_type, content_preview) = match svg::is_svg(&content_origin) {
+            true => match svg::preview(&content_origin) {
+                Ok(preview) => ("svg", Some(preview)),
+                Err(_) => ("text", None),
+            },
+            false => ("text", None),
+        };
+        let res = db.insert_if_not_exist(Record {
+            content: content_origin,
+            content_preview,
+            data_type: data_type.to_string(),
+            is_favorite: false,
+            ..Default::default()
+        });
+        let res = match res {
+            Ok(res) => res,
+            Err(e) => {
+                println!("insert record error: {}", e);
+                return None;
+            }
+        };
+        // 占位记录不保存剪切板中的其它格式，否则原内容还是会存进数据库
+        if from_clipboard && !stubbed {
+            log_err!(pasteboard::capture(db, res.id()));
+        }
+        if let Some(app) = &source {
+            log_err!(db.set_source_app(res.id(), app));
+        }
+        if let Some(original) = &original {
+            log_err!(db.set_original_content(res.id(), original));
+        }
+        log_err!(rules::apply(db, res.id(), source.as_deref()));
+        Some(res)
+    }
+
+    /// 监听还没有开始时为 None
+    pub fn last_poll() -> Option<u64> {
+        Some(LAST_POLL.load(Ordering::SeqCst)).filter(|t| *t > 0)
+    }
+
+    /// 切换监听状态，返回切换后的状态
+    pub fn toggle_monitoring() -> bool {
+        !MONITORING.fetch_xor(true, Ordering::SeqCst)
+    }
+
     pub fn start() {
         tauri::async_runtime::spawn(async {
             // 1000毫秒检测一次剪切板变化
@@ -45,38 +416,72 @@ impl ClipboardWatcher {
             let mut last_content_md5 = String::new();
             let mut last_img_md5 = String::new();
             let mut clipboard = Clipboard::new().unwrap();
+            let mut paused = false;
+            let mut hide_previews = false;
+            // 连接在监听期间复用，缓存的预编译语句才有效
+            // 修复数据库或空闲释放时关闭，之后重新打开
+            let mut long_lived = database::LongLivedDB::default();
             println!("start clipboard watcher");
             loop {
+                LAST_POLL.store(
+                    chrono::Local::now().timestamp_millis() as u64,
+                    Ordering::SeqCst,
+                );
+                // 只读打开数据库时只浏览和重新复制，不记录
+                let should_pause = Self::should_pause();
+                if should_pause != paused {
+                    paused = !paused;
+                    println!("clipboard watcher paused: {}", paused);
+                    log_err!(handle::Handle::notice_to_window(
+                        MsgTypeEnum::ChangeCapturePaused,
+                        paused
+                    ));
+                }
+                if screen_share::should_hide_previews() != hide_previews {
+                    hide_previews = !hide_previews;
+                    log_err!(handle::Handle::notice_to_window(
+                        MsgTypeEnum::ChangeScreenSharing,
+                        hide_previews
+                    ));
+                }
+                // 空闲释放后不保留连接，有新内容时再打开
+                if paused || idle::is_released() {
+                    long_lived.release_stale();
+                }
+                if paused {
+                    // 暂停期间复制的内容（如密码）只记下 md5，恢复后也不会补录
+                    if let Ok(text) = clipboard.get_text() {
+                        last_content_md5 = string_util::md5(&text);
+                    }
+                    if let Ok(img) = clipboard.get_image() {
+                        last_img_md5 = string_util::md5_by_bytes(&img.bytes);
+                    }
+                    thread::sleep(Duration::milliseconds(wait_millis).to_std().unwrap());
+                    continue;
+                }
                 let mut need_notify = false;
-                let db = database::SqliteDB::new();
                 let text = clipboard.get_text();
                 let _ = text.map(|text| {
                     let content_origin = text.clone();
                     let content = text.trim();
                     let md5 = string_util::md5(&content_origin);
                     if !content.is_empty() && md5 != last_content_md5 {
-                        // 说明有新内容
-                        let content_preview = if content.len() > 1000 {
-                            Some(content.chars().take(1000).collect())
-                        } else {
-                            Some(content.to_string())
+                        // 正在替换数据库文件时先不记录，替换完成后再处理
+                        let db = match long_lived.get() {
+                            Some(db) => db,
+                            None => return,
                         };
-                        let res = db.insert_if_not_exist(Record {
-                            content: content_origin,
-                            content_preview,
-                            data_type: "text".to_string(),
-                            is_favorite: false,
-                            ..Default::default()
-                        });
-                        match res {
-                            Ok(_) => {
-                                need_notify = true;
-                            }
-                            Err(e) => {
-                                println!("insert record error: {}", e);
-                            }
-                        }
+                        // 说明有新内容
+                        // 不是 set_transient_text 写入的内容时也清空，避免之后误跳过
+                        let transient = transient_md5().lock().take() == Some(md5.clone());
                         last_content_md5 = md5;
+                        if transient {
+                            return;
+                        }
+                        if let Some(res) = Self::capture_text(db, content_origin, true) {
+                            Self::notice_captured(&res);
+                            need_notify = true;
+                        }
                     }
                 });
 
@@ -84,31 +489,89 @@ impl ClipboardWatcher {
                 let _ = img.map(|img| {
                     let img_md5 = string_util::md5_by_bytes(&img.bytes);
                     if img_md5 != last_img_md5 {
+                        let db = match long_lived.get() {
+                            Some(db) => db,
+                            None => return,
+                        };
+                        last_img_md5 = img_md5;
+                        // 在编码为 PNG 之前检查，占位记录为文本
+                        let description = format!("image {}x{}", img.width, img.height);
+                        match capture_guard::check(img.bytes.len() as u64, &description) {
+                            Guard::Accept => {}
+                            Guard::Skip => return,
+                            Guard::Stub(stub) => {
+                                // 和文本一样记录来源并应用规则，只是不保存其它格式
+                                match db.insert_if_not_exist(Record {
+                                    content: stub,
+                                    data_type: "text".to_string(),
+                                    ..Default::default()
+                                }) {
+                                    Ok(res) => {
+                                        let source = source_app::frontmost_app();
+                                        if let Some(app) = &source {
+                                            log_err!(db.set_source_app(res.id(), app));
+                                        }
+                                        log_err!(rules::apply(&db, res.id(), source.as_deref()));
+                                        Self::notice_captured(&res);
+                                        need_notify = true;
+                                    }
+                                    Err(e) => println!("insert record error: {}", e),
+                                }
+                                return;
+                            }
+                        }
                         // 有新图片产生
                         let base64 = img_util::rgba8_to_base64(&img);
+                        let scale = pasteboard::image_scale().unwrap_or(1.0);
                         let content_db = ImageDataDB {
                             width: img.width,
                             height: img.height,
+                            scale,
                             base64,
+                            gif: pasteboard::read_gif().map(|gif| string_util::base64_encode(&gif)),
                         };
                         // 压缩画质作为预览图，防止渲染时非常卡顿
                         let jpeg_base64 = img_util::rgba8_to_jpeg_base64(&img, 75);
                         let content_preview_db = ImageDataDB {
                             width: img.width,
                             height: img.height,
+                            scale,
                             base64: jpeg_base64,
+                            gif: None,
                         };
                         let content = json_util::stringfy(&content_db).unwrap();
                         let content_preview = json_util::stringfy(&content_preview_db).unwrap();
-                        let res = db.insert_if_not_exist(Record {
-                            content,
-                            content_preview: Some(content_preview),
-                            data_type: "image".to_string(),
-                            is_favorite: false,
-                            ..Default::default()
-                        });
+                        let source = source_app::frontmost_app();
+                        let hash = img_util::dhash(&img).ok();
+                        // 重新编码的截图等视觉上相同的图片只把已有记录顶到最前
+                        let similar = match hash.filter(|_| phash::is_dedupe_enabled()) {
+                            Some(hash) => phash::find_similar(&db, hash).unwrap_or(None),
+                            None => None,
+                        };
+                        let res = match similar {
+                            Some(id) => db.record_recopied(id).map(|_| {
+                                db.audit(AuditAction::Recopied, id);
+                                InsertResult::Updated(id)
+                            }),
+                            None => db.insert_if_not_exist(Record {
+                                content,
+                                content_preview: Some(content_preview),
+                                data_type: "image".to_string(),
+                                is_favorite: false,
+                                ..Default::default()
+                            }),
+                        };
                         match res {
-                            Ok(_) => {
+                            Ok(res) => {
+                                if let (InsertResult::Inserted(id), Some(hash)) = (&res, hash) {
+                                    log_err!(db.set_phash(*id, &phash::to_hex(hash)));
+                                }
+                                log_err!(pasteboard::capture(&db, res.id()));
+                                if let Some(app) = &source {
+                                    log_err!(db.set_source_app(res.id(), app));
+                                }
+                                log_err!(rules::apply(&db, res.id(), source.as_deref()));
+                                Self::notice_captured(&res);
                                 drop(img);
                                 need_notify = true;
                             }
@@ -116,19 +579,14 @@ impl ClipboardWatcher {
                                 println!("insert record error: {}", e);
                             }
                         }
-                        last_img_md5 = img_md5;
                     }
                 });
-                let limit = Config::common().latest().record_limit.clone();
-                if let Some(l) = limit {
-                    let res = db.delete_over_limit(l as usize);
-                    if let Ok(success) = res {
-                        if success {
-                            need_notify = true;
-                        }
-                    }
-                }
+                // 只在记录了新内容后检查条数，修改上限时由 enforce_now 处理
                 if need_notify {
+                    if let Some(db) = long_lived.get() {
+                        log_err!(retention::enforce(db));
+                    }
+                    log_err!(handle::Handle::update_systray());
                     handle::Handle::notice_to_window(
                         MsgTypeEnum::ChangeClipBoard,
                         CHANGE_DEFAULT_MSG,
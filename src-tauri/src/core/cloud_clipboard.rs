@@ -0,0 +1,145 @@
+use super::clipboard::ClipboardWatcher;
+use super::database::SqliteDB;
+use super::handle::{Handle, MsgTypeEnum};
+use crate::config::Config;
+use crate::log_err;
+use crate::utils::string_util;
+use anyhow::Result;
+use std::collections::HashSet;
+use std::thread;
+use std::time::Duration;
+
+/// 是否与系统剪切板历史（Windows Win+V / 云剪切板）互通，加密记录不会同步出去
+pub fn is_enabled() -> bool {
+    platform::is_supported()
+        && Config::common()
+            .latest()
+            .enable_cloud_clipboard
+            .unwrap_or(false)
+}
+
+/// 定时把系统剪切板历史中的新文本导入记录，如从手机同步过来的内容
+/// 开启后第一次读到的历史只作为基准，在 Lanaya 中删除或粉碎的记录不会再被导入
+pub fn start() {
+    if !platform::is_supported() {
+        return;
+    }
+    thread::spawn(|| {
+        let mut seen: Option<HashSet<String>> = None;
+        loop {
+            thread::sleep(Duration::from_secs(5));
+            if !is_enabled() {
+                seen = None;
+                continue;
+            }
+            if let Err(err) = import_history(&mut seen) {
+                println!("import clipboard history error: {}", err);
+            }
+        }
+    });
+}
+
+/// 和复制的内容一样经过插件、规则等处理，暂停记录时只记下 md5
+fn import_history(seen: &mut Option<HashSet<String>>) -> Result<()> {
+    let texts = platform::history_texts()?;
+    let seen = match seen {
+        Some(seen) => seen,
+        None => {
+            *seen = Some(texts.iter().map(|text| string_util::md5(text)).collect());
+            return Ok(());
+        }
+    };
+    let paused = ClipboardWatcher::should_pause();
+    let db = SqliteDB::new();
+    let mut imported = false;
+    // 系统历史最新的在前面，倒序导入保证顺序一致
+    for text in texts.into_iter().rev() {
+        let md5 = string_util::md5(&text);
+        if !seen.insert(md5.clone()) || paused || text.trim().is_empty() {
+            continue;
+        }
+        // 本机复制的内容已由剪切板监听记录
+        if db.md5_is_exist(md5)? {
+            continue;
+        }
+        if let Some(res) = ClipboardWatcher::capture_text(&db, text, false) {
+            Handle::notice_insert_result(&res);
+            imported = true;
+        }
+    }
+    if imported {
+        log_err!(Handle::update_systray());
+        log_err!(Handle::notice_to_window(MsgTypeEnum::ChangeClipBoard, "ok"));
+    }
+    Ok(())
+}
+
+/// 系统剪切板历史可用
+pub fn is_supported() -> bool {
+    platform::is_supported()
+}
+
+/// 写入剪切板，roam 为 true 时允许进入系统历史、同步到其它设备
+pub fn set_text(text: &str, roam: bool) -> Result<()> {
+    platform::set_text(text, roam)
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use anyhow::{bail, Result};
+    use windows::core::HSTRING;
+    use windows::ApplicationModel::DataTransfer::{
+        Clipboard, ClipboardContentOptions, ClipboardHistoryItemsResultStatus, DataPackage,
+        StandardDataFormats,
+    };
+
+    pub fn is_supported() -> bool {
+        Clipboard::IsHistoryEnabled().unwrap_or(false)
+    }
+
+    pub fn history_texts() -> Result<Vec<String>> {
+        let res = Clipboard::GetHistoryItemsAsync()?.get()?;
+        if res.Status()? != ClipboardHistoryItemsResultStatus::Success {
+            bail!("clipboard history is not accessible");
+        }
+        let text_format = StandardDataFormats::Text()?;
+        let mut texts = vec![];
+        for item in res.Items()? {
+            let view = item.Content()?;
+            if view.Contains(&text_format)? {
+                texts.push(view.GetTextAsync()?.get()?.to_string());
+            }
+        }
+        Ok(texts)
+    }
+
+    pub fn set_text(text: &str, roam: bool) -> Result<()> {
+        let package = DataPackage::new()?;
+        package.SetText(&HSTRING::from(text))?;
+        let options = ClipboardContentOptions::new()?;
+        options.SetIsAllowedInHistory(roam)?;
+        options.SetIsRoamable(roam)?;
+        if !Clipboard::SetContentWithOptions(&package, &options)? {
+            bail!("failed to set clipboard content");
+        }
+        Clipboard::Flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod platform {
+    use anyhow::{bail, Result};
+
+    pub fn is_supported() -> bool {
+        false
+    }
+
+    pub fn history_texts() -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    pub fn set_text(_text: &str, _roam: bool) -> Result<()> {
+        bail!("cloud clipboard is only supported on windows")
+    }
+}
@@ -0,0 +1,60 @@
+use crate::config::Config;
+use anyhow::Result;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+
+/// 超过该长度（字节）的文本才压缩，较短的内容压缩收益不大
+pub const THRESHOLD: usize = 32 * 1024;
+const LEVEL: i32 = 3;
+
+pub fn is_enabled() -> bool {
+    Config::common()
+        .latest()
+        .compress_large_content
+        .unwrap_or(true)
+}
+
+/// 只压缩未加密的长文本，图片已是 PNG 压缩过的数据
+pub fn should_compress(data_type: &str, content: &str) -> bool {
+    data_type == "text" && content.len() > THRESHOLD && is_enabled()
+}
+
+pub fn compress(content: &str) -> Result<Vec<u8>> {
+    Ok(zstd::encode_all(content.as_bytes(), LEVEL)?)
+}
+
+pub fn decompress(data: &[u8]) -> Result<String> {
+    Ok(String::from_utf8(zstd::decode_all(data)?)?)
+}
+
+/// content 字段中压缩的内容以 BLOB 保存，文本照常读取：row.get::<_, Content>(i)?.0
+pub struct Content(pub String);
+
+impl FromSql for Content {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        match value {
+            ValueRef::Blob(data) => decompress(data)
+                .map(Content)
+                .map_err(|err| FromSqlError::Other(err.into())),
+            ValueRef::Null => Ok(Content(String::new())),
+            value => Ok(Content(value.as_str()?.to_string())),
+        }
+    }
+}
+
+#[test]
+fn test_compress_content() {
+    let content = "2024-01-01 INFO request handled\n".repeat(4096);
+    let compressed = compress(&content).unwrap();
+    assert!(compressed.len() < content.len() / 10);
+    assert_eq!(
+        Content::column_result(ValueRef::Blob(&compressed))
+            .unwrap()
+            .0,
+        content
+    );
+    assert_eq!(
+        Content::column_result(ValueRef::Text(b"hi")).unwrap().0,
+        "hi"
+    );
+    assert!(Content::column_result(ValueRef::Blob(b"not zstd")).is_err());
+}
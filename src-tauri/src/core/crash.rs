@@ -0,0 +1,246 @@
+use crate::config::Config;
+use crate::log_err;
+use crate::utils::dirs::app_logs_dir;
+use crate::utils::json_util;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic::{self, PanicInfo};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// 本地最多保留的崩溃报告数
+const MAX_REPORTS: usize = 20;
+/// 报告中最多保留的调用栈帧数
+const MAX_FRAMES: usize = 64;
+/// panic 信息最多保留的字符数
+const MAX_MESSAGE_LEN: usize = 200;
+const TIMEOUT_SECS: u64 = 10;
+
+/// 崩溃报告，不包含剪切板内容：panic 信息中引号内的部分会被去掉
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct CrashReport {
+    pub id: String,
+    pub time: u64,
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub thread: String,
+    pub message: String,
+    /// 源码位置，file:line:column
+    pub location: Option<String>,
+    pub backtrace: Vec<String>,
+    /// 提交到 crash_report_url 的时间，为空时未提交
+    pub submitted_at: Option<u64>,
+}
+
+fn crash_dir() -> Result<PathBuf> {
+    Ok(app_logs_dir()?.join("crashes"))
+}
+
+/// 在 main 的最开始调用，CLI、浏览器扩展消息等不启动界面时的 panic 也会记录
+pub fn install() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // panic 可能发生在持有配置的锁时，这里只写本地文件，不读取配置
+        log_err!(save(&build_report(info)));
+        default_hook(info);
+    }));
+}
+
+fn build_report(info: &PanicInfo) -> CrashReport {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+    let home = tauri::api::path::home_dir().map(|home| home.to_string_lossy().to_string());
+    let now = chrono::Local::now();
+    CrashReport {
+        id: now.format("%Y%m%d%H%M%S%3f").to_string(),
+        time: now.timestamp_millis() as u64,
+        app_version: env!("CARGO_PKG_VERSION").into(),
+        os: std::env::consts::OS.into(),
+        arch: std::env::consts::ARCH.into(),
+        thread: std::thread::current()
+            .name()
+            .unwrap_or("unnamed")
+            .to_string(),
+        message: scrub_message(&message),
+        location: info
+            .location()
+            .map(|l| scrub_path(&format!("{}:{}:{}", l.file(), l.line(), l.column()), &home)),
+        backtrace: scrub_backtrace(&Backtrace::force_capture().to_string(), &home),
+        submitted_at: None,
+    }
+}
+
+/// 去掉引号中的内容（通常是 {:?} 输出的字符串）并截断，避免把剪切板内容写入报告
+fn scrub_message(message: &str) -> String {
+    let first_line = message.lines().next().unwrap_or_default();
+    let mut res = String::new();
+    let mut quote = None;
+    for c in first_line.chars() {
+        match quote {
+            Some(q) if c == q => {
+                res.push(c);
+                quote = None;
+            }
+            Some(_) => {}
+            None if c == '"' || c == '\'' || c == '`' => {
+                res.push(c);
+                res.push('…');
+                quote = Some(c);
+            }
+            None => res.push(c),
+        }
+    }
+    // 引号未闭合时后面都是被去掉的内容
+    if let Some(q) = quote {
+        res.push(q);
+    }
+    match res.chars().count() > MAX_MESSAGE_LEN {
+        true => res.chars().take(MAX_MESSAGE_LEN).chain(['…']).collect(),
+        false => res,
+    }
+}
+
+/// 用户目录替换为 ~，避免报告中出现用户名
+fn scrub_path(path: &str, home: &Option<String>) -> String {
+    match home.as_ref().filter(|home| !home.is_empty()) {
+        Some(home) => path.replace(home.as_str(), "~"),
+        None => path.to_string(),
+    }
+}
+
+/// 只保留函数名和源码位置
+fn scrub_backtrace(backtrace: &str, home: &Option<String>) -> Vec<String> {
+    let mut frames = 0;
+    let mut res = vec![];
+    for line in backtrace.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        match line.strip_prefix("at ") {
+            Some(location) => {
+                if !res.is_empty() {
+                    res.push(format!("  at {}", scrub_path(location, home)));
+                }
+            }
+            None => {
+                frames += 1;
+                if frames > MAX_FRAMES {
+                    break;
+                }
+                res.push(line.to_string());
+            }
+        }
+    }
+    res
+}
+
+fn save(report: &CrashReport) -> Result<()> {
+    let dir = crash_dir()?;
+    fs::create_dir_all(&dir)?;
+    json_util::save(&dir.join(format!("{}.json", report.id)), report)?;
+    // 文件名按时间排序，删除最旧的
+    let mut files = report_files()?;
+    while files.len() > MAX_REPORTS {
+        log_err!(fs::remove_file(files.remove(0)));
+    }
+    Ok(())
+}
+
+fn report_files() -> Result<Vec<PathBuf>> {
+    let dir = crash_dir()?;
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// 本地保存的崩溃报告，最新的在前
+pub fn list() -> Result<Vec<CrashReport>> {
+    let mut reports: Vec<CrashReport> = report_files()?
+        .iter()
+        .filter_map(|path| json_util::read(path).ok())
+        .collect();
+    reports.reverse();
+    Ok(reports)
+}
+
+pub fn clear() -> Result<usize> {
+    let files = report_files()?;
+    for file in &files {
+        fs::remove_file(file)?;
+    }
+    Ok(files.len())
+}
+
+/// 用户设置了 crash_report_url 时，在后台提交还未提交的报告
+pub fn submit_pending() {
+    let url = match Config::common()
+        .latest()
+        .crash_report_url
+        .clone()
+        .filter(|url| !url.is_empty())
+    {
+        Some(url) => url,
+        None => return,
+    };
+    std::thread::spawn(move || {
+        for path in report_files().unwrap_or_default() {
+            let mut report: CrashReport = match json_util::read(&path) {
+                Ok(report) => report,
+                Err(_) => continue,
+            };
+            if report.submitted_at.is_some() {
+                continue;
+            }
+            if let Err(err) = submit(&url, &report) {
+                println!("submit crash report error: {}", err);
+                return;
+            }
+            report.submitted_at = Some(chrono::Local::now().timestamp_millis() as u64);
+            log_err!(json_util::save(&path, &report));
+        }
+    });
+}
+
+fn submit(url: &str, report: &CrashReport) -> Result<()> {
+    let res = ureq::post(url)
+        .timeout(Duration::from_secs(TIMEOUT_SECS))
+        .send_json(serde_json::to_value(report)?);
+    match res {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(status, _)) => bail!("server responded {}", status),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[test]
+fn test_scrub_crash_report() {
+    assert_eq!(
+        scrub_message("failed to parse \"secret token\" at 'x'"),
+        "failed to parse \"…\" at '…'"
+    );
+    assert_eq!(scrub_message("unclosed `abc\nsecond line"), "unclosed `…`");
+    assert_eq!(
+        scrub_message(&"a".repeat(300)).chars().count(),
+        MAX_MESSAGE_LEN + 1
+    );
+    let home = Some("/home/alice".to_string());
+    let backtrace = "   0: lanaya::core::crash::install\n             at /home/alice/lanaya/src/core/crash.rs:10:5\n   1: std::rt::lang_start\n";
+    assert_eq!(
+        scrub_backtrace(backtrace, &home),
+        vec![
+            "0: lanaya::core::crash::install",
+            "  at ~/lanaya/src/core/crash.rs:10:5",
+            "1: std::rt::lang_start",
+        ]
+    );
+}
@@ -1,11 +1,55 @@
+use super::analytics::{self, EventKind};
+use super::audit::{self, AuditAction, AuditLog, AuditQueryReq};
+use super::bridge::hostname;
+use super::capture_notify;
+use super::clipboard::ImageDataDB;
+use super::compress::{self, Content};
+use super::i18n;
+use super::job_queue::{self, Job};
+use super::macros::{ClipMacro, MacroStep};
+use super::query_builder::{int, text, QueryBuilder};
+use super::record_cache;
+use super::retention;
+use super::sync::{SyncChange, SyncConflict, SyncState};
+use crate::config::Config;
+use crate::log_err;
 use crate::utils::dirs::app_data_dir;
-use crate::utils::string_util;
-use anyhow::Result;
-use rusqlite::{Connection, OpenFlags};
+use crate::utils::{json_util, process_util, string_util};
+use anyhow::{bail, Result};
+use chrono::TimeZone;
+use once_cell::sync::OnceCell;
+use parking_lot::{Condvar, Mutex};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, TransactionBehavior};
+use std::collections::BTreeSet;
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Default, PartialEq)]
+/// 删除的记录可以撤销的时间
+pub const UNDO_GRACE_SECS: u64 = 60;
+/// trash 表中和 record 表相同的字段
+const TRASH_COLUMNS: &str =
+    "id, content, content_preview, data_type, md5, create_time, is_favorite, tags, is_secure, source_app, expire_at, original_content, copy_count, first_seen_at, updated_at, source_url, phash, content_len, expansion_trigger, paste_count, favorite_suggested, is_locked, note";
+/// 文本记录预览的最大字符数
+pub const PREVIEW_LEN: usize = 1000;
+/// 搜索命中的位置不在预览中时，截取命中位置附近的字符数
+const SNIPPET_LEN: usize = 400;
+/// 截取时保留命中位置之前的字符数
+const SNIPPET_BEFORE: usize = 60;
+/// 匹配含非 ASCII 字符的内容
+const NON_ASCII_GLOB: &str = "*[^\u{1}-\u{7f}]*";
+/// 自动清理（条数上限、过期）和清空可以删除的记录，收藏和锁定的记录总是保留
+const DISPOSABLE: &str = "is_favorite = 0 and is_locked = 0";
+/// 超出总条数上限不多时不删除，避免每次复制都删除一条，按类型的上限不留余量
+const OVER_LIMIT_SLACK: usize = 10;
+/// 备注的最大字符数
+const MAX_NOTE_LEN: usize = 500;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, PartialEq, Clone)]
 pub struct Record {
     pub id: u64,
     pub content: String,
@@ -16,8 +60,21 @@ pub struct Record {
     pub create_time: u64,
     pub is_favorite: bool,
     pub tags: String,
+    // 加密存储，列表中只返回占位内容
+    pub is_secure: bool,
+    // 内容总长度（字符数），仅在列表查询时返回
+    pub content_length: Option<u64>,
     // 仅在搜索返回时使用
     pub content_highlight: Option<String>,
+    // 复制次数和第一次复制的时间，create_time 为最近一次复制的时间
+    pub copy_count: Option<u64>,
+    pub first_seen_at: Option<u64>,
+    // 锁定的记录不会被自动清理和清空，解锁后才能删除
+    #[serde(default)]
+    pub is_locked: bool,
+    // 备注，明文保存并参与搜索，加密的记录也可以按备注找到
+    #[serde(default)]
+    pub note: Option<String>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
@@ -26,28 +83,360 @@ pub struct QueryReq {
     pub limit: Option<usize>,
     pub is_favorite: Option<bool>,
     pub tags: Option<Vec<String>>,
+    // 列表内容只返回前 N 个字符
+    pub preview_length: Option<usize>,
+    // 图片只返回尺寸，内容通过 thumb:// 协议按需加载
+    pub lazy_images: Option<bool>,
+    // 只返回复制次数不少于 N 次的记录
+    pub min_copy_count: Option<u64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, PartialEq)]
+pub struct PageReq {
+    pub page_size: Option<usize>,
+    pub cursor: Option<String>,
+    pub key: Option<String>,
+    pub is_favorite: Option<bool>,
+    pub tags: Option<Vec<String>>,
+    pub preview_length: Option<usize>,
+    pub lazy_images: Option<bool>,
+    pub min_copy_count: Option<u64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+pub struct HistoryPage {
+    pub records: Vec<Record>,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+pub struct TimelineReq {
+    /// 需要返回记录的日期（YYYY-MM-DD），为空时只返回最近一天的记录
+    pub days: Option<Vec<String>>,
+    /// 最多统计最近多少天（有记录的天）
+    pub max_days: Option<usize>,
+    /// 每天最多返回的记录数
+    pub limit_per_day: Option<usize>,
+    pub key: Option<String>,
+    pub is_favorite: Option<bool>,
+    pub tags: Option<Vec<String>>,
+    pub preview_length: Option<usize>,
+    pub lazy_images: Option<bool>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+pub struct TimelineDay {
+    /// 本地时间的日期，YYYY-MM-DD
+    pub day: String,
+    pub count: usize,
+    /// 只有请求的日期才包含记录
+    pub records: Vec<Record>,
+}
+
+/// 恢复备份时如何处理当前已有的记录
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeStrategy {
+    /// 用备份替换当前的全部记录
+    Replace,
+    /// 合并两边的记录，相同内容保留最近复制的一边
+    KeepNewest,
+    /// 合并两边的记录，相同内容保留当前的记录
+    UnionByHash,
+}
+
+/// insert_if_not_exist 的结果：新插入的记录或已存在被更新时间的记录 id
+#[derive(Debug, PartialEq)]
+pub enum InsertResult {
+    Inserted(u64),
+    Updated(u64),
+}
+
+impl InsertResult {
+    pub fn id(&self) -> u64 {
+        match self {
+            InsertResult::Inserted(id) | InsertResult::Updated(id) => *id,
+        }
+    }
 }
 
 pub struct SqliteDB {
     conn: Connection,
 }
 
-const SQLITE_FILE: &str = "data_v1_1_8.sqlite";
+/// 每个连接缓存的预编译语句数量，足够覆盖记录和任务队列的常用语句
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+pub const SQLITE_FILE: &str = "data_v1_1_8.sqlite";
+/// 初始化异常时最多等待的时间，之后的查询照常执行并返回错误
+const READY_TIMEOUT_SECS: u64 = 30;
+
+/// 以只读方式打开数据库的启动参数，也可以设置环境变量 READ_ONLY_ENV=1
+pub const READ_ONLY_ARG: &str = "--read-only";
+pub const READ_ONLY_ENV: &str = "LANAYA_READ_ONLY";
+/// 其它进程正在写入时最多等待的时间，超时后返回 database is locked
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// 持有数据库的实例定时更新心跳，超过 OWNER_STALE_SECS 没有更新视为已退出
+/// 同一台电脑上的实例按进程是否存在判断，心跳只用于其它电脑，间隔较长以免同步文件夹频繁上传
+const HEARTBEAT_SECS: u64 = 60;
+const OWNER_STALE_SECS: u64 = 180;
+
+/// 数据库已被其它实例（如另一台电脑）持有，本实例只读
+static SECONDARY: AtomicBool = AtomicBool::new(false);
+
+/// 长期持有的连接（剪切板监听、后台任务）的代数，加一后在下次使用时关闭重开
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+/// 打开中的长期连接数
+static LONG_LIVED_OPEN: AtomicUsize = AtomicUsize::new(0);
+/// 为 true 时正在替换数据库文件，长期连接不再打开
+fn swapping() -> &'static Mutex<bool> {
+    static SWAPPING: OnceCell<Mutex<bool>> = OnceCell::new();
+    SWAPPING.get_or_init(|| Mutex::new(false))
+}
+
+/// 长期持有的连接，代数变化后关闭，需要时再重新打开
+/// 替换数据库文件期间 get 返回 None，调用方跳过这一轮
+#[derive(Default)]
+pub struct LongLivedDB {
+    db: Option<(u64, SqliteDB)>,
+}
+
+impl LongLivedDB {
+    pub fn get(&mut self) -> Option<&SqliteDB> {
+        self.release_stale();
+        if self.db.is_none() {
+            let swapping = swapping().lock();
+            if *swapping {
+                return None;
+            }
+            LONG_LIVED_OPEN.fetch_add(1, Ordering::SeqCst);
+            drop(swapping);
+            self.db = Some((GENERATION.load(Ordering::SeqCst), SqliteDB::new()));
+        }
+        self.db.as_ref().map(|(_, db)| db)
+    }
+
+    /// 代数变化时关闭连接，不重新打开
+    pub fn release_stale(&mut self) {
+        let generation = GENERATION.load(Ordering::SeqCst);
+        if self.db.as_ref().map_or(false, |(g, _)| *g != generation) {
+            self.close();
+        }
+    }
+
+    fn close(&mut self) {
+        if self.db.take().is_some() {
+            LONG_LIVED_OPEN.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Drop for LongLivedDB {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// 建表和迁移在启动后的后台线程完成，完成前打开的连接先等待
+fn readiness() -> &'static (Mutex<bool>, Condvar) {
+    static READY: OnceCell<(Mutex<bool>, Condvar)> = OnceCell::new();
+    READY.get_or_init(|| (Mutex::new(false), Condvar::new()))
+}
 
 #[allow(unused)]
 impl SqliteDB {
+    /// 只读时数据库文件不存在会在 init 中返回错误并退出，之后不会走到这里
     pub fn new() -> Self {
-        let data_dir = app_data_dir().unwrap().join(SQLITE_FILE);
-        let c = Connection::open_with_flags(data_dir, OpenFlags::SQLITE_OPEN_READ_WRITE).unwrap();
-        SqliteDB { conn: c }
+        Self::wait_ready();
+        Self::try_new().unwrap()
+    }
+
+    fn try_new() -> Result<Self> {
+        let flags = match Self::is_read_only() {
+            true => OpenFlags::SQLITE_OPEN_READ_ONLY,
+            false => OpenFlags::SQLITE_OPEN_READ_WRITE,
+        };
+        let c = Self::open(&Self::path()?, flags)?;
+        c.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+        Ok(SqliteDB { conn: c })
+    }
+
+    /// 只读打开，供命令行等不需要写入的场景使用
+    pub fn open_read_only() -> Result<Self> {
+        let c = Self::open(&Self::path()?, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        Ok(SqliteDB { conn: c })
+    }
+
+    /// 数据库文件的路径，设置了 database_dir 时放在该目录下，启动时确定
+    pub fn path() -> Result<PathBuf> {
+        static DIR: OnceCell<Option<PathBuf>> = OnceCell::new();
+        let dir = DIR.get_or_init(|| {
+            Config::common()
+                .latest()
+                .database_dir
+                .clone()
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+        });
+        match dir {
+            Some(dir) => Ok(dir.join(SQLITE_FILE)),
+            None => Ok(app_data_dir()?.join(SQLITE_FILE)),
+        }
+    }
+
+    /// 其它进程写入时等待一会儿再重试，不直接返回 SQLITE_BUSY
+    fn open(path: &Path, flags: OpenFlags) -> Result<Connection> {
+        if flags.contains(OpenFlags::SQLITE_OPEN_READ_ONLY) && !path.exists() {
+            bail!("database file {} does not exist", path.display());
+        }
+        let c = Connection::open_with_flags(path, flags)?;
+        c.busy_timeout(BUSY_TIMEOUT)?;
+        Self::register_functions(&c)?;
+        Ok(c)
+    }
+
+    /// fold(text)：小写并去掉变音符号，搜索时两边都按该形式比较，Café 和 cafe 相同
+    /// decompress(content)：解压 BLOB 保存的长文本，文本原样返回，解压失败时为 NULL
+    fn register_functions(c: &Connection) -> Result<()> {
+        c.create_scalar_function(
+            "fold",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                Ok(match ctx.get_raw(0) {
+                    ValueRef::Text(t) => Some(string_util::fold(&String::from_utf8_lossy(t))),
+                    _ => None,
+                })
+            },
+        )?;
+        c.create_scalar_function(
+            "decompress",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                Ok(match ctx.get_raw(0) {
+                    ValueRef::Blob(data) => compress::decompress(data).ok(),
+                    ValueRef::Text(t) => Some(String::from_utf8_lossy(t).to_string()),
+                    _ => None,
+                })
+            },
+        )?;
+        Ok(())
+    }
+
+    /// 让长期持有的连接在下次使用时重新打开
+    pub fn release_long_lived() {
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+        job_queue::wake();
+    }
+
+    /// 等长期持有的连接都关闭后执行 f，期间不再打开，用于替换数据库文件
+    /// timeout 内没有全部关闭时返回错误
+    pub fn with_long_lived_closed<T>(
+        timeout: Duration,
+        f: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        *swapping().lock() = true;
+        Self::release_long_lived();
+        let start = Instant::now();
+        while LONG_LIVED_OPEN.load(Ordering::SeqCst) > 0 && start.elapsed() < timeout {
+            thread::sleep(Duration::from_millis(50));
+        }
+        let res = match LONG_LIVED_OPEN.load(Ordering::SeqCst) {
+            0 => f(),
+            _ => Err(anyhow::anyhow!(
+                "database is still in use, restart the app and try again"
+            )),
+        };
+        *swapping().lock() = false;
+        res
+    }
+
+    pub fn file_modified() -> Option<std::time::SystemTime> {
+        let path = Self::path().ok()?;
+        std::fs::metadata(path).ok()?.modified().ok()
+    }
+
+    /// 数据库放在同步文件夹中由多台电脑共用时，其它电脑只读打开，只浏览和重新复制
+    /// 由每个实例的启动参数或环境变量决定，不放在会一起同步的配置中
+    /// 数据库已被其它实例持有时也只读
+    pub fn is_read_only() -> bool {
+        static READ_ONLY: OnceCell<bool> = OnceCell::new();
+        SECONDARY.load(Ordering::SeqCst)
+            || *READ_ONLY.get_or_init(|| {
+                std::env::args().any(|arg| arg == READ_ONLY_ARG)
+                    || std::env::var(READ_ONLY_ENV).map_or(false, |v| v == "1")
+            })
+    }
+
+    pub fn is_ready() -> bool {
+        *readiness().0.lock()
+    }
+
+    fn wait_ready() {
+        let (ready, cvar) = readiness();
+        let mut ready = ready.lock();
+        if !*ready {
+            cvar.wait_while_for(
+                &mut ready,
+                |ready| !*ready,
+                std::time::Duration::from_secs(READY_TIMEOUT_SECS),
+            );
+        }
+    }
+
+    /// 建表、迁移，并占用数据库，已被其它实例占用时本实例只读
+    /// 只读打开时数据库文件还不存在（如同步文件夹还没有同步完成）返回错误
+    pub fn init() -> Result<()> {
+        // 只读时不建表也不迁移，由负责写入的那台电脑完成
+        if Self::is_read_only() {
+            Self::try_new()?;
+            Self::set_ready();
+            return Ok(());
+        }
+        Self::init_schema();
+        match Self::claim_owner() {
+            Ok(true) => Self::start_heartbeat(),
+            Ok(false) => Self::become_secondary(),
+            Err(err) => println!("claim database owner error: {}", err),
+        }
+        Self::set_ready();
+        Ok(())
+    }
+
+    /// 只建表和迁移，不占用数据库，供命令行等短暂写入的进程使用
+    pub fn init_shared() {
+        if !Self::is_read_only() {
+            Self::init_schema();
+        }
+        Self::set_ready();
     }
 
-    pub fn init() {
-        let data_dir = app_data_dir().unwrap().join(SQLITE_FILE);
+    fn init_schema() {
+        let data_dir = Self::path().unwrap();
         if !Path::new(&data_dir).exists() {
+            if let Some(parent) = data_dir.parent() {
+                std::fs::create_dir_all(parent).unwrap();
+            }
             File::create(&data_dir).unwrap();
         }
-        let c = Connection::open_with_flags(data_dir, OpenFlags::SQLITE_OPEN_READ_WRITE).unwrap();
+        let mut c = Self::open(&data_dir, OpenFlags::SQLITE_OPEN_READ_WRITE).unwrap();
+        // 两个进程同时启动时，后一个等前一个迁移完成
+        let tx = c
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .unwrap();
+        Self::create_schema(&tx).unwrap();
+        tx.commit().unwrap();
+    }
+
+    fn set_ready() {
+        let (ready, cvar) = readiness();
+        *ready.lock() = true;
+        cvar.notify_all();
+    }
+
+    /// 建表并补齐新增的字段，修复数据库时也用它创建新的数据库
+    fn create_schema(c: &Connection) -> Result<()> {
         let sql = r#"
         create table if not exists record
         (
@@ -58,34 +447,305 @@ impl SqliteDB {
             md5         VARCHAR(200) DEFAULT '',
             create_time INTEGER,
             is_favorite INTEGER DEFAULT 0,
-            tags        VARCHAR(256) DEFAULT ''
+            tags        VARCHAR(256) DEFAULT '',
+            is_secure   INTEGER DEFAULT 0
+        );
+        "#;
+        c.execute(sql, ())?;
+        let sql = r#"
+        create table if not exists audit_log
+        (
+            id          INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+            record_id   INTEGER,
+            action      VARCHAR(20) DEFAULT '',
+            create_time INTEGER
+        );
+        "#;
+        c.execute(sql, ())?;
+        let sql = r#"
+        create table if not exists pasteboard_type
+        (
+            record_id   INTEGER NOT NULL,
+            type        VARCHAR(100) NOT NULL,
+            data        BLOB,
+            PRIMARY KEY (record_id, type)
+        );
+        "#;
+        c.execute(sql, ())?;
+        let sql = r#"
+        create table if not exists job
+        (
+            id          INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+            kind        VARCHAR(32) NOT NULL,
+            record_id   INTEGER NOT NULL,
+            payload     TEXT NOT NULL DEFAULT '',
+            attempts    INTEGER DEFAULT 0,
+            next_run_at INTEGER,
+            last_error  TEXT,
+            UNIQUE (kind, record_id, payload)
+        );
+        "#;
+        c.execute(sql, ())?;
+        // 记录之间的关联，source_id 为较小的 id
+        let sql = r#"
+        create table if not exists record_link
+        (
+            source_id   INTEGER NOT NULL,
+            target_id   INTEGER NOT NULL,
+            create_time INTEGER,
+            PRIMARY KEY (source_id, target_id)
+        );
+        "#;
+        c.execute(sql, ())?;
+        // 同步时需要手动处理的冲突，同一记录的同一字段只保留最新的一条
+        let sql = r#"
+        create table if not exists sync_conflict
+        (
+            id          INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+            record_id   INTEGER NOT NULL,
+            field       VARCHAR(20) NOT NULL,
+            local       TEXT,
+            remote      TEXT,
+            device      VARCHAR(256) DEFAULT '',
+            create_time INTEGER,
+            UNIQUE (record_id, field)
+        );
+        "#;
+        c.execute(sql, ())?;
+        // 删除的记录留下的标记，同步时通知其它设备删除，同一内容重新复制后移除
+        let sql = r#"
+        create table if not exists sync_tombstone
+        (
+            md5         VARCHAR(200) NOT NULL PRIMARY KEY,
+            data_type   VARCHAR(20) DEFAULT '',
+            is_favorite INTEGER DEFAULT 0,
+            tags        VARCHAR(256) DEFAULT '',
+            deleted_at  INTEGER NOT NULL
+        );
+        "#;
+        c.execute(sql, ())?;
+        // 所有删除记录的地方都经过这里，加密记录不同步也不留标记
+        let sql = r#"
+        create trigger if not exists record_sync_tombstone after delete on record
+        when old.is_secure = 0
+        begin
+            insert or replace into sync_tombstone (md5, data_type, is_favorite, tags, deleted_at)
+            values (old.md5, old.data_type, old.is_favorite, old.tags, cast((julianday('now') - 2440587.5) * 86400000 as integer));
+        end;
+        "#;
+        c.execute(sql, ())?;
+        let sql = r#"
+        create trigger if not exists record_sync_revive after insert on record
+        begin
+            delete from sync_tombstone where md5 = new.md5;
+        end;
+        "#;
+        c.execute(sql, ())?;
+        // 每次复制（新记录、重复复制、粘贴）的时间，create_time 只保存最近一次
+        let sql = r#"
+        create table if not exists copy_history
+        (
+            id          INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+            record_id   INTEGER NOT NULL,
+            copied_at   INTEGER NOT NULL
+        );
+        "#;
+        c.execute(sql, ())?;
+        c.execute(
+            "create index if not exists copy_history_copied_at on copy_history (copied_at)",
+            (),
+        )?;
+        // 所有修改 create_time 的地方都经过这里
+        let sql = r#"
+        create trigger if not exists record_copy_history_insert after insert on record
+        begin
+            insert into copy_history (record_id, copied_at) values (new.id, new.create_time);
+        end;
+        "#;
+        c.execute(sql, ())?;
+        let sql = r#"
+        create trigger if not exists record_copy_history_update after update of create_time on record
+        when new.create_time is not old.create_time
+        begin
+            insert into copy_history (record_id, copied_at) values (new.id, new.create_time);
+        end;
+        "#;
+        c.execute(sql, ())?;
+        let sql = r#"
+        create trigger if not exists record_copy_history_delete after delete on record
+        begin
+            delete from copy_history where record_id = old.id;
+        end;
+        "#;
+        c.execute(sql, ())?;
+        // 删除和清空的记录先移到这里，撤销时恢复
+        let sql = r#"
+        create table if not exists trash
+        (
+            batch_id        INTEGER NOT NULL,
+            deleted_at      INTEGER NOT NULL,
+            id              INTEGER NOT NULL,
+            content         TEXT,
+            content_preview TEXT,
+            data_type       VARCHAR(20) DEFAULT '',
+            md5             VARCHAR(200) DEFAULT '',
+            create_time     INTEGER,
+            is_favorite     INTEGER DEFAULT 0,
+            tags            VARCHAR(256) DEFAULT '',
+            is_secure       INTEGER DEFAULT 0,
+            source_app      VARCHAR(256) DEFAULT '',
+            expire_at       INTEGER DEFAULT 0,
+            original_content TEXT DEFAULT '',
+            copy_count      INTEGER DEFAULT 1,
+            first_seen_at   INTEGER DEFAULT 0,
+            updated_at      INTEGER DEFAULT 0,
+            source_url      TEXT DEFAULT '',
+            phash           VARCHAR(16) DEFAULT '',
+            content_len     INTEGER DEFAULT 0
+        );
+        "#;
+        c.execute(sql, ())?;
+        let sql = r#"
+        create table if not exists trash_pasteboard_type
+        (
+            batch_id    INTEGER NOT NULL,
+            record_id   INTEGER NOT NULL,
+            type        VARCHAR(100) NOT NULL,
+            data        BLOB
+        );
+        "#;
+        c.execute(sql, ())?;
+        // 开启统计后记录的复制、搜索、粘贴事件，只保存在本地
+        let sql = r#"
+        create table if not exists event_log
+        (
+            id          INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+            kind        VARCHAR(20) NOT NULL,
+            create_time INTEGER
+        );
+        "#;
+        c.execute(sql, ())?;
+        // 剪切板宏，steps 为 JSON 数组
+        let sql = r#"
+        create table if not exists clip_macro
+        (
+            id          INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+            name        VARCHAR(100) NOT NULL UNIQUE,
+            steps       TEXT NOT NULL,
+            create_time INTEGER
+        );
+        "#;
+        c.execute(sql, ())?;
+        // 正在写入数据库的实例，只有一行
+        let sql = r#"
+        create table if not exists instance_owner
+        (
+            id           INTEGER NOT NULL PRIMARY KEY CHECK (id = 1),
+            pid          INTEGER NOT NULL,
+            host         VARCHAR(256) DEFAULT '',
+            heartbeat_at INTEGER
         );
         "#;
-        c.execute(sql, ()).unwrap();
+        c.execute(sql, ())?;
+        Self::migrate(c)
+    }
+
+    // 旧版本创建的数据库缺少新增的字段，这里补齐
+    fn migrate(c: &Connection) -> Result<()> {
+        Self::add_column_if_not_exist(c, "record", "is_secure", "INTEGER DEFAULT 0")?;
+        Self::add_column_if_not_exist(c, "record", "source_app", "VARCHAR(256) DEFAULT ''")?;
+        // 规则设置的过期时间（毫秒），0 表示不过期
+        Self::add_column_if_not_exist(c, "record", "expire_at", "INTEGER DEFAULT 0")?;
+        Self::add_column_if_not_exist(c, "trash", "expire_at", "INTEGER DEFAULT 0")?;
+        // 清理跟踪参数前的原始链接，没有清理时为空
+        Self::add_column_if_not_exist(c, "record", "original_content", "TEXT DEFAULT ''")?;
+        Self::add_column_if_not_exist(c, "trash", "original_content", "TEXT DEFAULT ''")?;
+        // 复制次数和第一次复制的时间，旧记录只知道最近一次复制的时间
+        Self::add_column_if_not_exist(c, "record", "copy_count", "INTEGER DEFAULT 1")?;
+        if Self::add_column_if_not_exist(c, "record", "first_seen_at", "INTEGER DEFAULT 0")? {
+            c.execute("update record set first_seen_at = create_time", ())?;
+        }
+        Self::add_column_if_not_exist(c, "trash", "copy_count", "INTEGER DEFAULT 1")?;
+        Self::add_column_if_not_exist(c, "trash", "first_seen_at", "INTEGER DEFAULT 0")?;
+        // 收藏和标签最后修改的时间，同步时用于判断哪边较新
+        Self::add_column_if_not_exist(c, "record", "updated_at", "INTEGER DEFAULT 0")?;
+        Self::add_column_if_not_exist(c, "trash", "updated_at", "INTEGER DEFAULT 0")?;
+        // 浏览器扩展推送的页面地址
+        Self::add_column_if_not_exist(c, "record", "source_url", "TEXT DEFAULT ''")?;
+        Self::add_column_if_not_exist(c, "trash", "source_url", "TEXT DEFAULT ''")?;
+        // 图片的感知哈希（16 位十六进制），为空表示还未计算
+        Self::add_column_if_not_exist(c, "record", "phash", "VARCHAR(16) DEFAULT ''")?;
+        Self::add_column_if_not_exist(c, "trash", "phash", "VARCHAR(16) DEFAULT ''")?;
+        // 压缩（content 为 BLOB）时原文的字符数，未压缩时为 0
+        Self::add_column_if_not_exist(c, "record", "content_len", "INTEGER DEFAULT 0")?;
+        Self::add_column_if_not_exist(c, "trash", "content_len", "INTEGER DEFAULT 0")?;
+        // 文本扩展的触发词，如 ;addr，为空表示没有设置
+        Self::add_column_if_not_exist(c, "record", "expansion_trigger", "TEXT DEFAULT ''")?;
+        Self::add_column_if_not_exist(c, "trash", "expansion_trigger", "TEXT DEFAULT ''")?;
+        // 粘贴次数，和 copy_count 一起用于提示收藏，提示过后不再提示
+        Self::add_column_if_not_exist(c, "record", "paste_count", "INTEGER DEFAULT 0")?;
+        Self::add_column_if_not_exist(c, "trash", "paste_count", "INTEGER DEFAULT 0")?;
+        Self::add_column_if_not_exist(c, "record", "favorite_suggested", "INTEGER DEFAULT 0")?;
+        Self::add_column_if_not_exist(c, "trash", "favorite_suggested", "INTEGER DEFAULT 0")?;
+        // 锁定的记录，见 DISPOSABLE
+        Self::add_column_if_not_exist(c, "record", "is_locked", "INTEGER DEFAULT 0")?;
+        Self::add_column_if_not_exist(c, "trash", "is_locked", "INTEGER DEFAULT 0")?;
+        // 用户填写的备注，为空表示没有备注
+        Self::add_column_if_not_exist(c, "record", "note", "TEXT DEFAULT ''")?;
+        Self::add_column_if_not_exist(c, "trash", "note", "TEXT DEFAULT ''")?;
+        Ok(())
+    }
+
+    fn add_column_if_not_exist(
+        c: &Connection,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> Result<bool> {
+        let sql = "SELECT count(*) FROM pragma_table_info(?1) WHERE name = ?2";
+        let count: u32 = c.query_row(sql, [table, column], |row| row.get(0))?;
+        if count > 0 {
+            return Ok(false);
+        }
+        let sql = format!("alter table {} add column {} {}", table, column, definition);
+        c.execute(&sql, ())?;
+        Ok(true)
     }
 
     pub fn insert_record(&self, r: Record) -> Result<i64> {
-        let sql = "insert into record (content,md5,create_time,is_favorite,data_type,content_preview) values (?1,?2,?3,?4,?5,?6)";
+        let sql = "insert into record (content,md5,create_time,is_favorite,data_type,content_preview,first_seen_at,content_len) values (?1,?2,?3,?4,?5,?6,?3,?7)";
         let md5 = string_util::md5(r.content.as_str());
         let now = chrono::Local::now().timestamp_millis() as u64;
-        let content_preview = r.content_preview.unwrap_or("".to_string());
-        let res = self.conn.execute(
-            sql,
-            (
-                &r.content,
-                md5,
-                now,
-                &r.is_favorite,
-                &r.data_type,
-                content_preview,
-            ),
-        )?;
+        // 文本记录在插入时生成预览，列表不需要读取完整内容
+        let content_preview = match r.content_preview {
+            Some(preview) => preview,
+            None if r.data_type == "text" => string_util::smart_preview(&r.content, PREVIEW_LEN),
+            None => String::new(),
+        };
+        // 长文本压缩后以 BLOB 保存，读取时由 Content 解压
+        let (content, content_len) = match compress::should_compress(&r.data_type, &r.content) {
+            true => {
+                let len = r.content.chars().count() as u64;
+                (Value::Blob(compress::compress(&r.content)?), len)
+            }
+            false => (Value::Text(r.content), 0),
+        };
+        self.conn.prepare_cached(sql)?.execute((
+            &content,
+            md5,
+            now,
+            &r.is_favorite,
+            &r.data_type,
+            content_preview,
+            content_len,
+        ))?;
+        record_cache::invalidate();
         Ok(self.conn.last_insert_rowid())
     }
 
-    fn find_record_by_md5(&self, md5: String) -> Result<Record> {
+    pub fn find_record_by_md5(&self, md5: String) -> Result<Record> {
         let sql = "SELECT id, content, md5, create_time, is_favorite FROM record WHERE md5 = ?1";
-        let r = self.conn.query_row(sql, [md5], |row| {
+        let r = self.conn.prepare_cached(sql)?.query_row([md5], |row| {
             Ok(Record {
                 id: row.get(0)?,
                 ..Default::default()
@@ -94,128 +754,715 @@ impl SqliteDB {
         Ok(r)
     }
 
-    // 更新时间
-    fn update_record_create_time(&self, r: Record) -> Result<()> {
-        let sql = "update record set create_time = ?2 where id = ?1";
-        // 获取当前毫秒级时间戳
+    // 重新复制时把记录顶到最前，并增加粘贴次数
+    pub fn touch_record(&self, id: u64) -> Result<()> {
+        let sql = "update record set create_time = ?2, paste_count = paste_count + 1 where id = ?1";
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        self.conn.prepare_cached(sql)?.execute([&id, &now])?;
+        record_cache::invalidate();
+        Ok(())
+    }
+
+    // 重复复制时增加次数，create_time 更新为最近一次复制的时间
+    pub fn record_recopied(&self, id: u64) -> Result<()> {
+        let sql = "update record set create_time = ?2, copy_count = copy_count + 1 where id = ?1";
         let now = chrono::Local::now().timestamp_millis() as u64;
-        self.conn.execute(sql, [&r.id, &now])?;
+        self.conn.prepare_cached(sql)?.execute([&id, &now])?;
+        record_cache::invalidate();
         Ok(())
     }
 
-    pub fn insert_if_not_exist(&self, r: Record) -> Result<()> {
+    // 查找和插入/更新在同一个事务中完成，避免并发写入时产生重复记录
+    pub fn insert_if_not_exist(&self, r: Record) -> Result<InsertResult> {
+        let tx = self.conn.unchecked_transaction()?;
+        let res = self.upsert_record(r)?;
+        tx.commit()?;
+        record_cache::invalidate();
+        Ok(res)
+    }
+
+    /// 批量导入，整批在一个事务中完成，返回每条记录的结果
+    /// 单条失败时只回滚该条，不影响其它记录
+    pub fn insert_records(&self, records: Vec<Record>) -> Result<Vec<Result<InsertResult>>> {
+        let mut tx = self.conn.unchecked_transaction()?;
+        let mut results = Vec::with_capacity(records.len());
+        for r in records {
+            let sp = tx.savepoint()?;
+            let res = self.upsert_record(r);
+            if res.is_ok() {
+                sp.commit()?;
+            }
+            results.push(res);
+        }
+        tx.commit()?;
+        record_cache::invalidate();
+        Ok(results)
+    }
+
+    fn upsert_record(&self, r: Record) -> Result<InsertResult> {
         let md5 = string_util::md5(r.content.as_str());
-        match self.find_record_by_md5(md5) {
+        let res = match self.find_record_by_md5(md5) {
             Ok(res) => {
-                self.update_record_create_time(res)?;
+                let id = res.id;
+                self.record_recopied(id)?;
+                self.audit(AuditAction::Recopied, id);
+                InsertResult::Updated(id)
             }
             Err(_e) => {
-                self.insert_record(r)?;
+                let id = self.insert_record(r)? as u64;
+                self.audit(AuditAction::Created, id);
+                InsertResult::Inserted(id)
             }
-        }
-        Ok(())
+        };
+        Ok(res)
+    }
+
+    pub fn find_id_by_md5(&self, md5: &str) -> Result<Option<u64>> {
+        let sql = "SELECT id FROM record WHERE md5 = ?1";
+        let id = self
+            .conn
+            .prepare_cached(sql)?
+            .query_row([md5], |row| row.get(0))
+            .optional()?;
+        Ok(id)
     }
 
     pub fn md5_is_exist(&self, md5: String) -> Result<bool> {
         let sql = "SELECT count(*) FROM record WHERE md5 = ?1";
-        let count: u32 = self.conn.query_row(sql, [md5], |row| row.get(0))?;
+        let count: u32 = self
+            .conn
+            .prepare_cached(sql)?
+            .query_row([md5], |row| row.get(0))?;
         Ok(count > 0)
     }
 
     // 清除数据
     pub fn clear_data(&self) -> Result<()> {
-        let sql = "delete from record where is_favorite = 0";
-        self.conn.execute(sql, ())?;
+        let tx = self.conn.unchecked_transaction()?;
+        let sql = format!("select id from record where {}", DISPOSABLE);
+        self.audit_selected(AuditAction::Deleted, &sql, [])?;
+        self.move_to_trash(|q| {
+            q.and(DISPOSABLE, vec![]);
+        })?;
+        tx.commit()?;
+        record_cache::invalidate();
         Ok(())
     }
 
     pub fn delete_by_id(&self, id: u64) -> Result<()> {
-        let sql = "delete from record where id = ?1";
-        self.conn.execute(sql, [&id])?;
+        self.delete_by_ids(&[id])
+    }
+
+    pub fn delete_by_ids(&self, ids: &[u64]) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        self.ensure_unlocked(ids)?;
+        self.move_to_trash(|q| {
+            Self::and_ids(q, "id", ids);
+        })?;
+        for id in ids {
+            self.audit(AuditAction::Deleted, *id);
+        }
+        tx.commit()?;
+        record_cache::invalidate();
         Ok(())
     }
 
     // 标记为收藏,如有已经收藏了的则取消收藏
     pub fn mark_favorite(&self, id: u64) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
         let record = self.find_by_id(id)?;
-        let sql = "update record set is_favorite = ?2 where id = ?1";
+        let sql = "update record set is_favorite = ?2, updated_at = ?3 where id = ?1";
         let is_favorite = if record.is_favorite { 0 } else { 1 };
-        self.conn.execute(sql, [&id, &is_favorite])?;
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        self.conn
+            .prepare_cached(sql)?
+            .execute([&id, &is_favorite, &now])?;
+        tx.commit()?;
+        record_cache::invalidate();
         Ok(())
     }
 
-    pub fn save_tags(&self, id: u64, tags: String) -> Result<()> {
-        let sql = "update record set tags = ?2 where id = ?1";
-        self.conn.execute(sql, (&id, &tags))?;
-        Ok(())
+    /// 一次读出 record 或 trash 表中文本记录的内容、备注和清理跟踪参数前的原始链接
+    pub fn find_text_contents(&self, in_trash: bool) -> Result<Vec<(Record, Option<String>)>> {
+        let sql = format!(
+            "SELECT id, content, is_secure, is_locked, nullif(note, ''), nullif(original_content, '') FROM {} where data_type in ('text', 'svg') order by create_time desc, id desc",
+            if in_trash { "trash" } else { "record" }
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let res = stmt
+            .query_map([], |row| {
+                let record = Record {
+                    id: row.get(0)?,
+                    content: row.get::<_, Content>(1)?.0,
+                    is_secure: row.get(2)?,
+                    is_locked: row.get(3)?,
+                    note: row.get(4)?,
+                    ..Record::default()
+                };
+                Ok((record, row.get(5)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(res)
     }
 
-    pub fn find_all(&self) -> Result<Vec<Record>> {
-        let sql = "SELECT id, content_preview, data_type, md5, create_time, is_favorite, tags FROM record order by create_time desc";
-        let mut stmt = self.conn.prepare(sql)?;
-        let mut rows = stmt.query([])?;
-        let mut res = vec![];
-        while let Some(row) = rows.next()? {
-            let data_type: String = row.get(2)?;
-            let content: String = row.get(1)?;
-            let tags: String = row.get(6)?;
-            let r = Record {
-                id: row.get(0)?,
-                content,
-                content_preview: None,
-                data_type,
-                md5: row.get(3)?,
-                create_time: row.get(4)?,
-                is_favorite: row.get(5)?,
-                content_highlight: None,
-                tags,
-            };
-            res.push(r);
-        }
-        Ok(res)
+    /// column in (ids)，id 也作为参数绑定
+    fn and_ids(q: &mut QueryBuilder, column: &str, ids: &[u64]) {
+        q.and_in(column, ids.iter().map(|id| int(*id)).collect());
     }
 
-    pub fn find_by_key(&self, req: QueryReq) -> Result<Vec<Record>> {
-        let mut sql: String = String::new();
-        sql.push_str(
-            "SELECT id, content_preview, md5, create_time, is_favorite, data_type, tags FROM record where 1=1",
+    /// ids 中有锁定的记录时返回错误
+    fn ensure_unlocked(&self, ids: &[u64]) -> Result<()> {
+        let mut q = QueryBuilder::new();
+        Self::and_ids(&mut q, "id", ids);
+        let sql = format!(
+            "SELECT id FROM record where is_locked = 1{} limit 1",
+            q.where_clause()
         );
-        let mut limit: usize = 300;
-        let mut params: Vec<String> = vec![];
-        if let Some(l) = req.limit {
-            limit = l;
+        let locked: Option<u64> = self
+            .conn
+            .query_row(&sql, q.params(), |row| row.get(0))
+            .optional()?;
+        if let Some(id) = locked {
+            bail!("record {} is locked, unlock it before deleting", id);
         }
-        params.push(limit.to_string());
-        if let Some(k) = &req.key {
-            params.push(format!("%{}%", k));
-            sql.push_str(
-                format!(" and data_type='text' and content like ?{}", params.len()).as_str(),
-            );
+        Ok(())
+    }
+
+    pub fn is_locked(&self, id: u64) -> Result<bool> {
+        let sql = "SELECT is_locked FROM record where id = ?1";
+        let locked: Option<bool> = self
+            .conn
+            .query_row(sql, [id], |row| row.get(0))
+            .optional()?;
+        Ok(locked.unwrap_or(false))
+    }
+
+    /// 锁定或解锁记录
+    pub fn set_locked(&self, ids: &[u64], value: bool) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let sql = "update record set is_locked = ?2, updated_at = ?3 where id = ?1";
+            let mut stmt = tx.prepare_cached(sql)?;
+            let now = chrono::Local::now().timestamp_millis() as u64;
+            for id in ids {
+                stmt.execute((id, &value, &now))?;
+            }
         }
-        if let Some(is_fav) = req.is_favorite {
-            let is_fav_int = if is_fav { 1 } else { 0 };
-            params.push(is_fav_int.to_string());
-            sql.push_str(format!(" and is_favorite = ?{}", params.len()).as_str());
-        }
-        if let Some(tags) = req.tags {
-            for tag in tags.iter() {
-                params.push(format!("%{}%", tag));
-                sql.push_str(format!(" and tags like ?{}", params.len()).as_str());
+        tx.commit()?;
+        record_cache::invalidate();
+        Ok(())
+    }
+
+    // 批量设置收藏状态，在同一个事务中完成
+    pub fn set_favorite(&self, ids: &[u64], value: bool) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let sql = "update record set is_favorite = ?2, updated_at = ?3 where id = ?1";
+            let mut stmt = tx.prepare_cached(sql)?;
+            let now = chrono::Local::now().timestamp_millis() as u64;
+            for id in ids {
+                stmt.execute((id, &value, &now))?;
             }
         }
-        let sql = format!("{} order by create_time desc limit ?1", sql);
-        let mut stmt = self.conn.prepare(&sql)?;
-        let mut rows = stmt.query(rusqlite::params_from_iter(params))?;
-        let mut res = vec![];
-        while let Some(row) = rows.next()? {
-            let data_type: String = row.get(5)?;
-            let content: String = row.get(1)?;
-            let tags: String = row.get(6)?;
-            let content_highlight = req
-                .key
-                .as_ref()
-                .map(|key| string_util::highlight(key.as_str(), content.as_str()));
-            let r = Record {
+        tx.commit()?;
+        record_cache::invalidate();
+        Ok(())
+    }
+
+    /// 设置文本扩展的触发词，为空时取消，设置了触发词的记录同时收藏，避免被自动清理
+    pub fn set_expansion_trigger(&self, id: u64, trigger: &str) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        if !trigger.is_empty() {
+            let sql = "SELECT count(*) FROM record where expansion_trigger = ?1 and id != ?2";
+            let used: usize = self.conn.query_row(sql, (trigger, id), |row| row.get(0))?;
+            if used > 0 {
+                bail!("the trigger \"{}\" is already used", trigger);
+            }
+        }
+        let sql = "update record set expansion_trigger = ?2, is_favorite = (is_favorite or ?2 != ''), updated_at = ?3 where id = ?1 and is_secure = 0 and data_type = 'text'";
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        if self.conn.execute(sql, (id, trigger, now))? == 0 {
+            bail!("only plain text records can have a trigger");
+        }
+        tx.commit()?;
+        record_cache::invalidate();
+        Ok(())
+    }
+
+    /// 所有设置了触发词的记录 id 和触发词
+    pub fn find_expansion_triggers(&self) -> Result<Vec<(u64, String)>> {
+        let sql = "SELECT id, expansion_trigger FROM record where expansion_trigger != '' and is_secure = 0";
+        let mut stmt = self.conn.prepare(sql)?;
+        let res = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(u64, String)>>>()?;
+        Ok(res)
+    }
+
+    /// 未收藏、还没有提示过，且复制和粘贴次数合计达到 threshold 时标记为已提示，返回合计次数
+    pub fn take_favorite_suggestion(&self, id: u64, threshold: u64) -> Result<Option<u64>> {
+        let sql = "SELECT copy_count + paste_count FROM record where id = ?1 and is_favorite = 0 and favorite_suggested = 0";
+        let uses: Option<u64> = self
+            .conn
+            .query_row(sql, [id], |row| row.get(0))
+            .optional()?;
+        match uses.filter(|uses| *uses >= threshold) {
+            Some(uses) => {
+                let sql = "update record set favorite_suggested = 1 where id = ?1";
+                self.conn.prepare_cached(sql)?.execute([id])?;
+                record_cache::invalidate();
+                Ok(Some(uses))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn save_tags(&self, id: u64, tags: String) -> Result<()> {
+        let sql = "update record set tags = ?2, updated_at = ?3 where id = ?1";
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        self.conn.prepare_cached(sql)?.execute((&id, &tags, &now))?;
+        record_cache::invalidate();
+        Ok(())
+    }
+
+    /// 设置备注，为空时清除，返回保存的备注
+    pub fn set_note(&self, id: u64, note: Option<&str>) -> Result<Option<String>> {
+        let note = normalize_note(note)?;
+        let sql = "update record set note = ?2, updated_at = ?3 where id = ?1";
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        let updated =
+            self.conn
+                .prepare_cached(sql)?
+                .execute((&id, note.as_deref().unwrap_or(""), &now))?;
+        if updated == 0 {
+            bail!("record {} not found", id);
+        }
+        record_cache::invalidate();
+        Ok(note)
+    }
+
+    // 记录复制时的前台应用，重复复制时更新为最近一次的来源
+    pub fn set_source_app(&self, id: u64, source_app: &str) -> Result<()> {
+        let sql = "update record set source_app = ?2 where id = ?1";
+        self.conn.prepare_cached(sql)?.execute((&id, source_app))?;
+        record_cache::invalidate();
+        Ok(())
+    }
+
+    pub fn set_source_url(&self, id: u64, url: &str) -> Result<()> {
+        let sql = "update record set source_url = ?2 where id = ?1";
+        self.conn.prepare_cached(sql)?.execute((&id, url))?;
+        record_cache::invalidate();
+        Ok(())
+    }
+
+    pub fn find_source_url(&self, id: u64) -> Result<Option<String>> {
+        let sql = "SELECT source_url FROM record where id = ?1";
+        let url: Option<String> = self.conn.query_row(sql, [&id], |row| row.get(0))?;
+        Ok(url.filter(|url| !url.is_empty()))
+    }
+
+    pub fn set_phash(&self, id: u64, phash: &str) -> Result<()> {
+        let sql = "update record set phash = ?2 where id = ?1";
+        self.conn.prepare_cached(sql)?.execute((&id, phash))?;
+        record_cache::invalidate();
+        Ok(())
+    }
+
+    /// 已计算感知哈希的图片
+    pub fn find_image_hashes(&self) -> Result<Vec<(u64, String)>> {
+        let sql = "SELECT id, phash FROM record where data_type = 'image' and phash != ''";
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// 为旧版本保存的、还未计算感知哈希的图片添加后台任务，返回添加的任务数
+    pub fn enqueue_phash_backfill(&self, kind: &str) -> Result<usize> {
+        let sql = "insert or ignore into job (kind,record_id,payload,attempts,next_run_at) select ?1, id, '', 0, ?2 from record where data_type = 'image' and is_secure = 0 and (phash is null or phash = '')";
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        Ok(self.conn.execute(sql, (kind, &now))?)
+    }
+
+    pub fn set_original_content(&self, id: u64, original: &str) -> Result<()> {
+        let sql = "update record set original_content = ?2 where id = ?1";
+        self.conn.prepare_cached(sql)?.execute((&id, original))?;
+        record_cache::invalidate();
+        Ok(())
+    }
+
+    // 记录被清理过时返回原始内容
+    pub fn find_original_content(&self, id: u64) -> Result<Option<String>> {
+        let sql = "SELECT original_content FROM record WHERE id = ?1";
+        let original: Option<String> = self
+            .conn
+            .prepare_cached(sql)?
+            .query_row([id], |row| row.get(0))?;
+        Ok(original.filter(|original| !original.is_empty()))
+    }
+
+    pub fn set_expire_at(&self, id: u64, expire_at: u64) -> Result<()> {
+        let sql = "update record set expire_at = ?2 where id = ?1";
+        self.conn.prepare_cached(sql)?.execute((&id, &expire_at))?;
+        record_cache::invalidate();
+        Ok(())
+    }
+
+    // 删除规则设置了过期时间且已过期的记录，收藏的记录保留
+    pub fn delete_expired(&self, now: u64) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let condition = format!("{} and expire_at > 0 and expire_at <= ?1", DISPOSABLE);
+        let sql = format!("select id from record where {}", condition);
+        self.audit_selected(AuditAction::Deleted, &sql, [now])?;
+        let sql = format!("DELETE FROM record WHERE {}", condition);
+        let deleted = self.conn.prepare_cached(&sql)?.execute([now])?;
+        if deleted == 0 {
+            return Ok(0);
+        }
+        self.delete_orphan_pasteboard_types()?;
+        tx.commit()?;
+        record_cache::invalidate();
+        Ok(deleted)
+    }
+
+    /// 全部记录、收藏和图片的条数
+    pub fn count_records(&self) -> Result<(usize, usize, usize)> {
+        let sql = "SELECT count(*), coalesce(sum(is_favorite = 1), 0), coalesce(sum(data_type = 'image'), 0) FROM record";
+        let counts = self
+            .conn
+            .query_row(sql, [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        Ok(counts)
+    }
+
+    // 时间范围内各来源应用的记录数，按数量倒序
+    pub fn count_source_apps(
+        &self,
+        start: u64,
+        end: u64,
+        limit: usize,
+    ) -> Result<Vec<(String, usize)>> {
+        let sql = "SELECT source_app, count(*) as c FROM record where source_app != '' and create_time >= ?1 and create_time < ?2 group by source_app order by c desc limit ?3";
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let rows = stmt.query_map((start, end, limit), |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    // 时间范围内像是链接的文本记录内容，过长的不可能是单个链接
+    pub fn find_url_contents(&self, start: u64, end: u64) -> Result<Vec<String>> {
+        let sql = "SELECT content FROM record where data_type = 'text' and is_secure = 0 and typeof(content) = 'text' and content like 'http%' and length(content) < 2048 and create_time >= ?1 and create_time < ?2";
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let rows = stmt.query_map((start, end), |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<String>>>()?)
+    }
+
+    /// time 时最后一次复制或粘贴的记录 id
+    /// create_time 只保存最近一次复制的时间，之后又复制过的记录从 copy_history 中找到
+    pub fn find_active_at(&self, time: u64) -> Result<Option<u64>> {
+        let sql = "SELECT create_time, id FROM record where create_time <= ?1 order by create_time desc, id desc limit 1";
+        let latest: Option<(u64, u64)> = self
+            .conn
+            .query_row(sql, [time], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()?;
+        let sql = "SELECT h.copied_at, h.record_id FROM copy_history h join record r on r.id = h.record_id where h.copied_at <= ?1 order by h.copied_at desc, h.id desc limit 1";
+        let logged: Option<(u64, u64)> = self
+            .conn
+            .query_row(sql, [time], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()?;
+        Ok(latest.max(logged).map(|(_, id)| id))
+    }
+
+    pub fn find_latest_id(&self) -> Result<Option<u64>> {
+        let sql = "SELECT id FROM record order by create_time desc, id desc limit 1";
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let mut rows = stmt.query([])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 收藏的文本记录（不含加密记录），tags 不为空时只返回带有其中任一标签的记录
+    pub fn find_favorite_texts(&self, tags: &[String]) -> Result<Vec<Record>> {
+        let mut q = QueryBuilder::new();
+        q.and_any(
+            "(',' || tags || ',') like {0}",
+            tags.iter()
+                .map(|tag| text(format!("%,{},%", tag)))
+                .collect(),
+        );
+        let sql = format!(
+            "SELECT id, content, md5, create_time, tags FROM record where is_favorite = 1 and is_secure = 0 and data_type = 'text'{} order by create_time desc",
+            q.where_clause()
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut rows = stmt.query(q.params())?;
+        let mut res = vec![];
+        while let Some(row) = rows.next()? {
+            res.push(Record {
+                id: row.get(0)?,
+                content: row.get::<_, Content>(1)?.0,
+                data_type: "text".to_string(),
+                md5: row.get(2)?,
+                create_time: row.get(3)?,
+                is_favorite: true,
+                tags: row.get(4)?,
+                ..Default::default()
+            });
+        }
+        Ok(res)
+    }
+
+    /// 所有用过的标签，按首次出现的顺序去重
+    pub fn find_all_tags(&self) -> Result<Vec<String>> {
+        let sql = "SELECT tags FROM record where tags != '' order by create_time desc";
+        let mut stmt = self.conn.prepare(sql)?;
+        let mut rows = stmt.query([])?;
+        let mut res: Vec<String> = vec![];
+        while let Some(row) = rows.next()? {
+            let tags: String = row.get(0)?;
+            for tag in tags.split(',').filter(|t| !t.is_empty()) {
+                if !res.iter().any(|t| t == tag) {
+                    res.push(tag.to_string());
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    pub fn find_all(&self) -> Result<Vec<Record>> {
+        let sql = "SELECT id, content_preview, data_type, md5, create_time, is_favorite, tags, is_secure, is_locked, nullif(note, '') FROM record order by create_time desc";
+        let mut stmt = self.conn.prepare(sql)?;
+        let mut rows = stmt.query([])?;
+        let mut res = vec![];
+        while let Some(row) = rows.next()? {
+            let data_type: String = row.get(2)?;
+            let content: String = row.get(1)?;
+            let tags: String = row.get(6)?;
+            let r = Record {
+                id: row.get(0)?,
+                content,
+                content_preview: None,
+                data_type,
+                md5: row.get(3)?,
+                create_time: row.get(4)?,
+                is_favorite: row.get(5)?,
+                content_highlight: None,
+                tags,
+                is_secure: row.get(7)?,
+                content_length: None,
+                copy_count: None,
+                first_seen_at: None,
+                is_locked: row.get(8)?,
+                note: row.get(9)?,
+            };
+            res.push(r);
+        }
+        Ok(res)
+    }
+
+    pub fn find_by_key(&self, req: QueryReq) -> Result<Vec<Record>> {
+        self.query_records(&req, None)
+    }
+
+    // 按 create_time、id 倒序分页，cursor 为上一页最后一条的 (create_time, id)
+    pub fn find_page(&self, req: PageReq) -> Result<HistoryPage> {
+        let page_size = req.page_size.unwrap_or(50).max(1);
+        let cursor = match &req.cursor {
+            Some(c) => Some(Self::parse_cursor(c)?),
+            None => None,
+        };
+        let query = QueryReq {
+            key: req.key,
+            // 多取一条用于判断是否还有下一页
+            limit: Some(page_size + 1),
+            is_favorite: req.is_favorite,
+            tags: req.tags,
+            preview_length: req.preview_length,
+            lazy_images: req.lazy_images,
+            min_copy_count: req.min_copy_count,
+        };
+        let mut records = self.query_records(&query, cursor)?;
+        let has_more = records.len() > page_size;
+        records.truncate(page_size);
+        let next_cursor = match has_more {
+            true => records
+                .last()
+                .map(|r| format!("{}_{}", r.create_time, r.id)),
+            false => None,
+        };
+        Ok(HistoryPage {
+            records,
+            next_cursor,
+            has_more,
+        })
+    }
+
+    fn parse_cursor(cursor: &str) -> Result<(u64, u64)> {
+        let (create_time, id) = cursor
+            .split_once('_')
+            .ok_or(anyhow::anyhow!("invalid cursor \"{}\"", cursor))?;
+        Ok((create_time.parse()?, id.parse()?))
+    }
+
+    // 先查内存缓存，打开弹窗时的首页查询不用等待数据库
+    fn query_records(&self, req: &QueryReq, cursor: Option<(u64, u64)>) -> Result<Vec<Record>> {
+        let key = format!("{}|{:?}", serde_json::to_string(req)?, cursor);
+        record_cache::check_modified(Self::file_modified());
+        if let Some(records) = record_cache::get_query(&key) {
+            return Ok(records);
+        }
+        let generation = record_cache::generation();
+        let records = self.query_records_from_db(req, cursor)?;
+        record_cache::put_query(generation, key, &records);
+        Ok(records)
+    }
+
+    // 按本地日期分组的历史，每天的条数和请求日期的记录
+    pub fn timeline(&self, req: TimelineReq) -> Result<Vec<TimelineDay>> {
+        let query = QueryReq {
+            key: req.key,
+            limit: Some(req.limit_per_day.unwrap_or(50)),
+            is_favorite: req.is_favorite,
+            tags: req.tags,
+            preview_length: req.preview_length,
+            lazy_images: req.lazy_images,
+            min_copy_count: None,
+        };
+        let mut q = QueryBuilder::new();
+        Self::filter_clause(&query, &mut q);
+        let limit = q.bind(int(req.max_days.unwrap_or(90) as u64));
+        let sql = format!(
+            "SELECT date(create_time / 1000, 'unixepoch', 'localtime') as day, count(*) FROM record where 1=1{} group by day order by day desc limit {}",
+            q.where_clause(),
+            limit
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut rows = stmt.query(q.params())?;
+        let mut days = vec![];
+        while let Some(row) = rows.next()? {
+            days.push(TimelineDay {
+                day: row.get(0)?,
+                count: row.get(1)?,
+                records: vec![],
+            });
+        }
+        let requested = match req.days {
+            Some(requested) => requested,
+            None => days.iter().take(1).map(|d| d.day.clone()).collect(),
+        };
+        for day in days.iter_mut().filter(|d| requested.contains(&d.day)) {
+            let (start, end) = Self::day_range(&day.day)?;
+            // 从当天结束时往前取，再去掉前一天的记录
+            day.records = self
+                .query_records(&query, Some((end, 0)))?
+                .into_iter()
+                .filter(|r| r.create_time >= start)
+                .collect();
+        }
+        Ok(days)
+    }
+
+    // 本地日期对应的 [start, end) 毫秒时间戳
+    fn day_range(day: &str) -> Result<(u64, u64)> {
+        let date = chrono::NaiveDate::parse_from_str(day, "%Y-%m-%d")?;
+        let start_of = |date: chrono::NaiveDate| -> Result<u64> {
+            let midnight = date.and_hms_opt(0, 0, 0);
+            match midnight.and_then(|t| chrono::Local.from_local_datetime(&t).earliest()) {
+                Some(time) => Ok(time.timestamp_millis() as u64),
+                None => bail!("invalid day {}", date),
+            }
+        };
+        let next = date.succ_opt().unwrap_or(date);
+        Ok((start_of(date)?, start_of(next)?))
+    }
+
+    // 搜索关键字、收藏和标签的过滤条件
+    fn filter_clause(req: &QueryReq, q: &mut QueryBuilder) {
+        if let Some(k) = &req.key {
+            // 忽略大小写和变音符号，压缩的内容解压后搜索
+            // 只含 ASCII 的内容用 like 比较即可，只对含其它字符的内容调用 fold，搜索时不必逐条折叠
+            // 备注对所有类型的记录都可以搜索
+            q.and(
+                "((data_type='text' and is_secure = 0 and ((typeof(content) = 'text' and (content like {0} or (content glob {1} and fold(content) like {0}))) or (typeof(content) = 'blob' and fold(decompress(content)) like {0}))) or fold(note) like {0})",
+                vec![
+                    text(format!("%{}%", string_util::fold(k))),
+                    text(NON_ASCII_GLOB.to_string()),
+                ],
+            );
+        }
+        if let Some(is_fav) = req.is_favorite {
+            q.and("is_favorite = {0}", vec![is_fav.into()]);
+        }
+        for tag in req.tags.iter().flatten() {
+            q.and("tags like {0}", vec![text(format!("%{}%", tag))]);
+        }
+        if let Some(min_copy_count) = req.min_copy_count {
+            q.and("copy_count >= {0}", vec![int(min_copy_count)]);
+        }
+    }
+
+    fn query_records_from_db(
+        &self,
+        req: &QueryReq,
+        cursor: Option<(u64, u64)>,
+    ) -> Result<Vec<Record>> {
+        let limit = req.limit.unwrap_or(300);
+        let mut q = QueryBuilder::new();
+        Self::filter_clause(req, &mut q);
+        if let Some((create_time, id)) = cursor {
+            q.and(
+                "(create_time < {0} or (create_time = {0} and id < {1}))",
+                vec![int(create_time), int(id)],
+            );
+        }
+        let limit = q.bind(int(limit as u64));
+        let sql = format!(
+            "SELECT id, content_preview, md5, create_time, is_favorite, data_type, tags, is_secure, case when typeof(content) = 'blob' then content_len else length(content) end, copy_count, first_seen_at, is_locked, nullif(note, '') FROM record where 1=1{} order by create_time desc, id desc limit {}",
+            q.where_clause(),
+            limit
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut rows = stmt.query(q.params())?;
+        let mut res = vec![];
+        while let Some(row) = rows.next()? {
+            let data_type: String = row.get(5)?;
+            let mut content: String = row.get(1)?;
+            let tags: String = row.get(6)?;
+            // 命中的位置在预览之后时，读出全文（压缩的内容解压后）截取命中位置附近的内容代替预览
+            let is_secure: bool = row.get(7)?;
+            if let (Some(key), true) = (&req.key, !is_secure && data_type == "text") {
+                if !string_util::fold(&content).contains(&string_util::fold(key)) {
+                    let id: u64 = row.get(0)?;
+                    let full = self.conn.query_row(
+                        "SELECT content FROM record where id = ?1",
+                        [id],
+                        |r| Ok(r.get::<_, Content>(0)?.0),
+                    )?;
+                    if let Some(snippet) =
+                        string_util::snippet(key, &full, SNIPPET_BEFORE, SNIPPET_LEN)
+                    {
+                        content = format!("…{}", snippet.trim_start());
+                    }
+                }
+            }
+            if let Some(len) = req.preview_length {
+                if data_type == "text" && content.chars().count() > len {
+                    content = content.chars().take(len).collect();
+                }
+            }
+            if req.lazy_images == Some(true) && data_type == "image" {
+                let mut image: ImageDataDB = json_util::parse(&content).unwrap_or_default();
+                image.base64 = String::new();
+                content = json_util::stringfy(&image)?;
+            }
+            let content_highlight = req
+                .key
+                .as_ref()
+                .map(|key| string_util::highlight(key.as_str(), content.as_str()));
+            let r = Record {
                 id: row.get(0)?,
                 content,
                 content_preview: None,
@@ -225,32 +1472,103 @@ impl SqliteDB {
                 is_favorite: row.get(4)?,
                 content_highlight,
                 tags,
+                is_secure,
+                content_length: row.get(8)?,
+                copy_count: row.get(9)?,
+                first_seen_at: row.get(10)?,
+                is_locked: row.get(11)?,
+                note: row.get(12)?,
             };
             res.push(r);
         }
         Ok(res)
     }
 
-    //删除超过limit的记录
-    pub fn delete_over_limit(&self, limit: usize) -> Result<bool> {
-        // 先查询count，如果count - limit > 50 就删除 超出limit部分记录 主要是防止频繁重建数据库
-        let mut stmt = self
+    /// 删除超过 limit 的最旧记录，返回删除的条数，收藏的记录不计入条数也不会删除
+    pub fn delete_over_limit(&self, limit: usize) -> Result<usize> {
+        self.delete_oldest_over(None, limit, OVER_LIMIT_SLACK)
+    }
+
+    /// 删除某一类型中超过 limit 的最旧记录，和 delete_over_limit 一样不删除收藏
+    /// 如图片的上限用于控制占用的空间，超出一条也删除
+    pub fn delete_over_limit_by_type(&self, data_type: &str, limit: usize) -> Result<usize> {
+        self.delete_oldest_over(Some(data_type), limit, 0)
+    }
+
+    fn delete_oldest_over(
+        &self,
+        data_type: Option<&str>,
+        limit: usize,
+        slack: usize,
+    ) -> Result<usize> {
+        let mut q = QueryBuilder::new();
+        if let Some(data_type) = data_type {
+            q.and("data_type = {0}", vec![text(data_type)]);
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        let sql = format!(
+            "SELECT count(*) FROM record where {}{}",
+            DISPOSABLE,
+            q.where_clause()
+        );
+        let count: usize = self
             .conn
-            .prepare("SELECT count(*) FROM record where is_favorite = 0")?;
-        let mut rows = stmt.query([])?;
-        let count: usize = rows.next()?.unwrap().get(0).unwrap();
-        if count < 10 + limit {
-            return Ok(false);
+            .prepare_cached(&sql)?
+            .query_row(q.params(), |row| row.get(0))?;
+        let remove_num = excess(count, limit, slack);
+        if remove_num == 0 {
+            return Ok(0);
         }
-        let remove_num = count - limit;
-        let sql = "DELETE FROM record WHERE is_favorite = 0 and id in (SELECT id FROM record where is_favorite = 0 order by create_time asc limit ?1)";
-        self.conn.execute(sql, [remove_num])?;
-        Ok(true)
+        let limit = q.bind(int(remove_num as u64));
+        let oldest = format!(
+            "SELECT id FROM record where {}{} order by create_time asc, id asc limit {}",
+            DISPOSABLE,
+            q.where_clause(),
+            limit
+        );
+        self.audit_selected(AuditAction::Deleted, &oldest, q.params())?;
+        let sql = format!("DELETE FROM record WHERE id in ({})", oldest);
+        let deleted = self.conn.prepare_cached(&sql)?.execute(q.params())?;
+        self.delete_orphan_pasteboard_types()?;
+        tx.commit()?;
+        record_cache::invalidate();
+        Ok(deleted)
     }
 
     pub fn find_by_id(&self, id: u64) -> Result<Record> {
-        let sql = "SELECT id, content, data_type, md5, create_time, is_favorite, tags FROM record where id = ?1";
-        let r = self.conn.query_row(sql, [&id], |row| {
+        record_cache::check_modified(Self::file_modified());
+        if let Some(record) = record_cache::get_record(id) {
+            return Ok(record);
+        }
+        let generation = record_cache::generation();
+        let sql = "SELECT id, content, data_type, md5, create_time, is_favorite, tags, is_secure, copy_count, first_seen_at, is_locked, nullif(note, '') FROM record where id = ?1";
+        let r = self.conn.prepare_cached(sql)?.query_row([&id], |row| {
+            Ok(Record {
+                id: row.get(0)?,
+                content: row.get::<_, Content>(1)?.0,
+                content_preview: None,
+                data_type: row.get(2)?,
+                md5: row.get(3)?,
+                create_time: row.get(4)?,
+                is_favorite: row.get(5)?,
+                content_highlight: None,
+                tags: row.get(6)?,
+                is_secure: row.get(7)?,
+                content_length: None,
+                copy_count: row.get(8)?,
+                first_seen_at: row.get(9)?,
+                is_locked: row.get(10)?,
+                note: row.get(11)?,
+            })
+        })?;
+        record_cache::put_record(generation, &r);
+        Ok(r)
+    }
+
+    // 列表展示用的记录，content 为预览内容
+    pub fn find_list_item_by_id(&self, id: u64) -> Result<Record> {
+        let sql = "SELECT id, content_preview, data_type, md5, create_time, is_favorite, tags, is_secure, copy_count, first_seen_at, is_locked, nullif(note, '') FROM record where id = ?1";
+        let r = self.conn.prepare_cached(sql)?.query_row([&id], |row| {
             Ok(Record {
                 id: row.get(0)?,
                 content: row.get(1)?,
@@ -261,20 +1579,1327 @@ impl SqliteDB {
                 is_favorite: row.get(5)?,
                 content_highlight: None,
                 tags: row.get(6)?,
+                is_secure: row.get(7)?,
+                content_length: None,
+                copy_count: row.get(8)?,
+                first_seen_at: row.get(9)?,
+                is_locked: row.get(10)?,
+                note: row.get(11)?,
             })
         })?;
         Ok(r)
     }
+
+    pub fn find_preview_by_id(&self, id: u64) -> Result<Option<String>> {
+        let sql = "SELECT content_preview FROM record where id = ?1";
+        let r = self
+            .conn
+            .prepare_cached(sql)?
+            .query_row([&id], |row| row.get(0))?;
+        Ok(r)
+    }
+
+    /// 按字符读取内容的一段，返回 (内容, 总字符数)，加密记录读取到的是密文
+    pub fn read_content_chunk(&self, id: u64, offset: u64, len: u64) -> Result<(String, u64)> {
+        let sql = "SELECT typeof(content) = 'blob', content_len FROM record where id = ?1";
+        let (compressed, total): (bool, u64) = self
+            .conn
+            .prepare_cached(sql)?
+            .query_row([&id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        // 压缩的内容需要整段解压后再截取
+        if compressed {
+            let content = self.find_by_id(id)?.content;
+            let chunk = content
+                .chars()
+                .skip(offset as usize)
+                .take(len as usize)
+                .collect();
+            return Ok((chunk, total));
+        }
+        let sql = "SELECT substr(content, ?2 + 1, ?3), length(content) FROM record where id = ?1";
+        let r = self
+            .conn
+            .prepare_cached(sql)?
+            .query_row([&id, &offset, &len], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(r)
+    }
+
+    /// 在外部编辑器中修改后写回，md5、预览一起更新，复制时的富文本等原始格式不再保留
+    pub fn update_content(
+        &self,
+        id: u64,
+        content: String,
+        content_preview: Option<String>,
+    ) -> Result<()> {
+        let md5 = string_util::md5(&content);
+        let sql = "SELECT count(*) FROM record WHERE md5 = ?1 and id != ?2";
+        let count: u32 = self
+            .conn
+            .prepare_cached(sql)?
+            .query_row((&md5, &id), |row| row.get(0))?;
+        if count > 0 {
+            bail!("another record already has the same content");
+        }
+        let sql = "SELECT data_type FROM record WHERE id = ?1";
+        let data_type: String = self
+            .conn
+            .prepare_cached(sql)?
+            .query_row([&id], |row| row.get(0))?;
+        let content_preview = match content_preview {
+            Some(preview) => preview,
+            None if data_type == "text" => string_util::smart_preview(&content, PREVIEW_LEN),
+            None => String::new(),
+        };
+        let (content, content_len) = match compress::should_compress(&data_type, &content) {
+            true => {
+                let len = content.chars().count() as u64;
+                (Value::Blob(compress::compress(&content)?), len)
+            }
+            false => (Value::Text(content), 0),
+        };
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        let tx = self.conn.unchecked_transaction()?;
+        let sql = "update record set content = ?2, md5 = ?3, content_preview = ?4, content_len = ?5, original_content = NULL, phash = NULL, updated_at = ?6 where id = ?1";
+        self.conn.execute(
+            sql,
+            (&id, &content, &md5, &content_preview, &content_len, &now),
+        )?;
+        self.delete_pasteboard_types(id)?;
+        tx.commit()?;
+        record_cache::invalidate();
+        self.audit(AuditAction::Edited, id);
+        Ok(())
+    }
+
+    pub fn update_secure(
+        &self,
+        id: u64,
+        is_secure: bool,
+        content: String,
+        content_preview: String,
+        original_content: Option<&str>,
+    ) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        // 加密时原始链接已放入加密内容，列中不再保留明文
+        let sql = "update record set is_secure = ?2, content = ?3, content_preview = ?4, original_content = ?5 where id = ?1";
+        self.conn.execute(
+            sql,
+            (
+                &id,
+                &is_secure,
+                &content,
+                &content_preview,
+                original_content,
+            ),
+        )?;
+        // 原始剪切板数据中也有明文，不再保留
+        if is_secure {
+            self.delete_pasteboard_types(id)?;
+        }
+        tx.commit()?;
+        record_cache::invalidate();
+        Ok(())
+    }
+
+    /// 压缩旧版本保存的长文本，返回压缩的记录数，之后 VACUUM 回收空间
+    pub fn compress_existing(&self) -> Result<usize> {
+        let sql = "SELECT id, content FROM record where data_type = 'text' and is_secure = 0 and typeof(content) = 'text' and length(CAST(content AS BLOB)) > ?1";
+        let rows = self
+            .conn
+            .prepare(sql)?
+            .query_map([compress::THRESHOLD], |row| {
+                Ok((row.get::<_, u64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let tx = self.conn.unchecked_transaction()?;
+        let sql = "update record set content = ?2, content_len = ?3 where id = ?1";
+        for (id, content) in rows.iter() {
+            let len = content.chars().count() as u64;
+            tx.execute(sql, (id, compress::compress(content)?, len))?;
+        }
+        tx.commit()?;
+        if !rows.is_empty() {
+            self.conn.execute("VACUUM", ())?;
+        }
+        record_cache::invalidate();
+        Ok(rows.len())
+    }
 }
 
-#[test]
-fn test_sqlite_insert() {
-    SqliteDB::init();
-    let r = Record {
-        content: "123456".to_string(),
-        md5: "e10adc3949ba59abbe56e057f20f883e".to_string(),
-        create_time: 1234568,
-        ..Default::default()
-    };
-    assert_eq!(SqliteDB::new().insert_record(r).unwrap(), 1_i64)
+/// 超过上限需要删除的条数，超出不到 slack 条时不删除
+fn excess(count: usize, limit: usize, slack: usize) -> usize {
+    match count > limit && count >= limit + slack {
+        true => count - limit,
+        false => 0,
+    }
+}
+
+/// 去掉首尾空白，空白的备注视为清除
+fn normalize_note(note: Option<&str>) -> Result<Option<String>> {
+    let note = note.map(str::trim).filter(|n| !n.is_empty());
+    if let Some(note) = note {
+        if note.chars().count() > MAX_NOTE_LEN {
+            bail!("the note must be at most {} characters", MAX_NOTE_LEN);
+        }
+    }
+    Ok(note.map(String::from))
+}
+
+/// 没有实例占用、占用的是本进程或对方已超过 OWNER_STALE_SECS 没有心跳时可以占用
+/// owner_alive 判断同一台电脑上持有数据库的进程是否还在运行，如崩溃后重启时 pid 已不同
+fn can_claim(
+    owner: Option<&(u32, String, u64)>,
+    pid: u32,
+    host: &str,
+    now: u64,
+    owner_alive: impl Fn(u32) -> bool,
+) -> bool {
+    match owner {
+        None => true,
+        Some((owner_pid, owner_host, _)) if owner_host == host => {
+            *owner_pid == pid || !owner_alive(*owner_pid)
+        }
+        Some((_, _, heartbeat_at)) => now.saturating_sub(*heartbeat_at) > OWNER_STALE_SECS * 1000,
+    }
+}
+
+// 多个实例（如同步文件夹中共用数据库的其它电脑）之间约定只有一个写入
+impl SqliteDB {
+    fn identity() -> (u32, String) {
+        (std::process::id(), hostname())
+    }
+
+    fn claim_owner() -> Result<bool> {
+        let mut c = Self::open(&Self::path()?, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+        let tx = c.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let sql = "SELECT pid, host, heartbeat_at FROM instance_owner where id = 1";
+        let owner = tx
+            .query_row(sql, [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .optional()?;
+        let (pid, host) = Self::identity();
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        if !can_claim(owner.as_ref(), pid, &host, now, process_util::is_alive) {
+            return Ok(false);
+        }
+        let sql = "insert or replace into instance_owner (id, pid, host, heartbeat_at) values (1, ?1, ?2, ?3)";
+        tx.execute(sql, (pid, &host, now))?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// 心跳被其它实例接管（如休眠后超时）时重新尝试占用，失败后转为只读
+    fn start_heartbeat() {
+        thread::spawn(|| loop {
+            thread::sleep(Duration::from_secs(HEARTBEAT_SECS));
+            let (pid, host) = Self::identity();
+            let now = chrono::Local::now().timestamp_millis() as u64;
+            let sql = "update instance_owner set heartbeat_at = ?3 where id = 1 and pid = ?1 and host = ?2";
+            let updated = SqliteDB::new().conn.execute(sql, (pid, &host, now));
+            match updated {
+                Ok(0) => {
+                    if let Ok(false) = Self::claim_owner() {
+                        Self::become_secondary();
+                        return;
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => println!("database heartbeat error: {}", err),
+            }
+        });
+    }
+
+    fn become_secondary() {
+        println!("database is used by another instance, opened read-only");
+        SECONDARY.store(true, Ordering::SeqCst);
+        capture_notify::notify(i18n::t("notification.database_in_use"));
+    }
+
+    /// 退出时释放，其它实例不用等心跳超时
+    pub fn release_owner() {
+        if Self::is_read_only() {
+            return;
+        }
+        let (pid, host) = Self::identity();
+        let sql = "delete from instance_owner where id = 1 and pid = ?1 and host = ?2";
+        log_err!(SqliteDB::new().conn.execute(sql, (pid, &host)));
+    }
+}
+
+// 原始剪切板类型（macOS 的 rtf / html / png / 文件等）
+impl SqliteDB {
+    pub fn save_pasteboard_types(&self, record_id: u64, types: &[(String, Vec<u8>)]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        let sql = "delete from pasteboard_type where record_id = ?1";
+        tx.execute(sql, [&record_id])?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "insert into pasteboard_type (record_id,type,data) values (?1,?2,?3)",
+            )?;
+            for (t, data) in types {
+                stmt.execute((&record_id, t, data))?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn find_pasteboard_types(&self, record_id: u64) -> Result<Vec<(String, Vec<u8>)>> {
+        let sql = "SELECT type, data FROM pasteboard_type where record_id = ?1";
+        let mut stmt = self.conn.prepare(sql)?;
+        let mut rows = stmt.query([&record_id])?;
+        let mut res = vec![];
+        while let Some(row) = rows.next()? {
+            res.push((row.get(0)?, row.get(1)?));
+        }
+        Ok(res)
+    }
+
+    pub fn delete_pasteboard_types(&self, record_id: u64) -> Result<()> {
+        let sql = "delete from pasteboard_type where record_id = ?1";
+        self.conn.prepare_cached(sql)?.execute([&record_id])?;
+        Ok(())
+    }
+
+    fn delete_orphan_pasteboard_types(&self) -> Result<()> {
+        let sql = "delete from pasteboard_type where record_id not in (select id from record)";
+        self.conn.execute(sql, ())?;
+        Ok(())
+    }
+}
+
+/// 回收站中的记录，加密记录不显示内容
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct TrashItem {
+    pub id: u64,
+    pub data_type: String,
+    pub content: Option<String>,
+    pub is_secure: bool,
+    pub deleted_at: u64,
+}
+
+/// 回收站列表最多返回的条数
+const TRASH_LIST_LIMIT: u64 = 500;
+
+// 撤销删除：删除的记录先移到 trash 表，宽限期内可以恢复
+impl SqliteDB {
+    /// 把满足 condition 的记录和原始剪切板类型移到 trash，需在事务中调用
+    /// filter 向空的 QueryBuilder 中追加要删除的记录的条件，每条语句各自绑定一次参数
+    fn move_to_trash(&self, filter: impl Fn(&mut QueryBuilder)) -> Result<u64> {
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        self.purge_trash(retention::trash_purge_before(now).unwrap_or(0))?;
+        let batch_id: u64 = self.conn.query_row(
+            "SELECT coalesce(max(batch_id), 0) + 1 FROM trash",
+            [],
+            |row| row.get(0),
+        )?;
+        let mut q = QueryBuilder::new();
+        let (batch, deleted_at) = (q.bind(int(batch_id)), q.bind(int(now)));
+        filter(&mut q);
+        let sql = format!(
+            "insert into trash (batch_id, deleted_at, {0}) select {1}, {2}, {0} from record where 1=1{3}",
+            TRASH_COLUMNS,
+            batch,
+            deleted_at,
+            q.where_clause()
+        );
+        self.conn.execute(&sql, q.params())?;
+        let mut q = QueryBuilder::new();
+        let batch = q.bind(int(batch_id));
+        filter(&mut q);
+        let sql = format!(
+            "insert into trash_pasteboard_type (batch_id, record_id, type, data) select {}, record_id, type, data from pasteboard_type where record_id in (select id from record where 1=1{})",
+            batch,
+            q.where_clause()
+        );
+        self.conn.execute(&sql, q.params())?;
+        let mut q = QueryBuilder::new();
+        filter(&mut q);
+        let sql = format!("delete from record where 1=1{}", q.where_clause());
+        self.conn.execute(&sql, q.params())?;
+        self.delete_orphan_pasteboard_types()?;
+        Ok(batch_id)
+    }
+
+    /// 恢复宽限期内最近一次删除的记录，返回恢复的条数
+    /// 删除后又复制了相同内容的记录不再恢复，避免重复
+    pub fn undo_last_delete(&self) -> Result<usize> {
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        let since = now.saturating_sub(UNDO_GRACE_SECS * 1000);
+        let tx = self.conn.unchecked_transaction()?;
+        let batch_id: Option<u64> = self.conn.query_row(
+            "SELECT max(batch_id) FROM trash where deleted_at >= ?1",
+            [since],
+            |row| row.get(0),
+        )?;
+        let batch_id = match batch_id {
+            Some(batch_id) => batch_id,
+            None => return Ok(0),
+        };
+        let sql = format!(
+            "insert into record ({0}) select {0} from trash where batch_id = ?1 and md5 not in (select md5 from record)",
+            TRASH_COLUMNS
+        );
+        let restored = self.conn.execute(&sql, [batch_id])?;
+        let sql = "insert or ignore into pasteboard_type (record_id, type, data) select record_id, type, data from trash_pasteboard_type where batch_id = ?1 and record_id in (select id from record)";
+        self.conn.execute(sql, [batch_id])?;
+        self.delete_trash_batch(batch_id)?;
+        tx.commit()?;
+        record_cache::invalidate();
+        Ok(restored)
+    }
+
+    /// 最近删除的记录，新删除的在前
+    pub fn list_trash(&self) -> Result<Vec<TrashItem>> {
+        let sql = "SELECT id, data_type, case when is_secure = 1 then null else content_preview end, is_secure, deleted_at FROM trash order by deleted_at desc, id desc limit ?1";
+        let mut stmt = self.conn.prepare(sql)?;
+        let items = stmt
+            .query_map([TRASH_LIST_LIMIT], |row| {
+                Ok(TrashItem {
+                    id: row.get(0)?,
+                    data_type: row.get(1)?,
+                    content: row.get(2)?,
+                    is_secure: row.get(3)?,
+                    deleted_at: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(items)
+    }
+
+    /// 从回收站恢复指定的记录，返回恢复的条数
+    /// 删除后又复制了相同内容的记录不再恢复，和撤销一样
+    pub fn restore_from_trash(&self, ids: &[u64]) -> Result<usize> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let mut q = QueryBuilder::new();
+        q.and_in("id", ids.iter().map(|id| int(*id)).collect());
+        let tx = self.conn.unchecked_transaction()?;
+        let sql = format!(
+            "insert into record ({0}) select {0} from trash where 1=1{1} and md5 not in (select md5 from record) and id not in (select id from record)",
+            TRASH_COLUMNS,
+            q.where_clause()
+        );
+        let restored = self.conn.execute(&sql, q.params())?;
+        let mut q = QueryBuilder::new();
+        q.and_in("record_id", ids.iter().map(|id| int(*id)).collect());
+        let sql = format!(
+            "insert or ignore into pasteboard_type (record_id, type, data) select record_id, type, data from trash_pasteboard_type where 1=1{} and record_id in (select id from record)",
+            q.where_clause()
+        );
+        self.conn.execute(&sql, q.params())?;
+        let sql = format!(
+            "delete from trash_pasteboard_type where 1=1{}",
+            q.where_clause()
+        );
+        self.conn.execute(&sql, q.params())?;
+        let mut q = QueryBuilder::new();
+        q.and_in("id", ids.iter().map(|id| int(*id)).collect());
+        let sql = format!("delete from trash where 1=1{}", q.where_clause());
+        self.conn.execute(&sql, q.params())?;
+        tx.commit()?;
+        record_cache::invalidate();
+        Ok(restored)
+    }
+
+    /// 彻底删除 before 之前移到 trash 的记录，返回删除的条数
+    /// 加密的记录过了撤销期限就删除，不随回收站保留
+    pub fn purge_trash(&self, before: u64) -> Result<usize> {
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        let secure_before = now.saturating_sub(UNDO_GRACE_SECS * 1000);
+        let condition = "deleted_at < ?1 or (is_secure = 1 and deleted_at < ?2)";
+        let sql = format!("delete from trash_pasteboard_type where batch_id in (select batch_id from trash where {})", condition);
+        self.conn.execute(&sql, [before, secure_before])?;
+        let purged = self.conn.execute(
+            &format!("delete from trash where {}", condition),
+            [before, secure_before],
+        )?;
+        // 撤销时关联跟着记录恢复，两边都不存在时才删除
+        let sql = "delete from record_link where source_id not in (select id from record union select id from trash) or target_id not in (select id from record union select id from trash)";
+        self.conn.execute(sql, ())?;
+        let sql = "delete from sync_conflict where record_id not in (select id from record union select id from trash)";
+        self.conn.execute(sql, ())?;
+        Ok(purged)
+    }
+
+    fn delete_trash_batch(&self, batch_id: u64) -> Result<()> {
+        self.conn.execute(
+            "delete from trash_pasteboard_type where batch_id = ?1",
+            [batch_id],
+        )?;
+        self.conn
+            .execute("delete from trash where batch_id = ?1", [batch_id])?;
+        Ok(())
+    }
+}
+
+// 粉碎：内容先用 0 覆盖再删除，不进入回收站，无法撤销
+impl SqliteDB {
+    /// 返回删除的条数，之后应在后台调用 compact，数据库文件中才不会留下旧的内容
+    pub fn shred(&self, ids: &[u64]) -> Result<usize> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        self.ensure_unlocked(ids)?;
+        // 每条语句在 sql 后拼接 column in (ids) 的条件
+        let execute = |sql: &str, column: &str| -> Result<usize> {
+            let mut q = QueryBuilder::new();
+            Self::and_ids(&mut q, column, ids);
+            Ok(self.conn.execute(
+                &format!("{} where 1=1{}", sql, q.where_clause()),
+                q.params(),
+            )?)
+        };
+        let deleted = self.with_secure_delete(|| {
+            let tx = self.conn.unchecked_transaction()?;
+            execute(
+                "update record set content = zeroblob(length(CAST(content AS BLOB))), content_preview = zeroblob(length(CAST(content_preview AS BLOB))), original_content = zeroblob(length(CAST(original_content AS BLOB))), note = ''",
+                "id",
+            )?;
+            execute(
+                "update pasteboard_type set data = zeroblob(length(data))",
+                "record_id",
+            )?;
+            let deleted = execute("delete from record", "id")?;
+            for table in ["pasteboard_type", "job", "sync_conflict"] {
+                execute(&format!("delete from {}", table), "record_id")?;
+            }
+            execute("delete from record_link", "source_id")?;
+            execute("delete from record_link", "target_id")?;
+            for id in ids {
+                self.audit(AuditAction::Deleted, *id);
+            }
+            tx.commit()?;
+            Ok(deleted)
+        })?;
+        record_cache::invalidate();
+        Ok(deleted)
+    }
+
+    /// 删除时释放的页也用 0 覆盖，出错时同样恢复，长期使用的连接不会一直开着
+    fn with_secure_delete<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        self.conn.pragma_update(None, "secure_delete", true)?;
+        let res = f();
+        let reset = self.conn.pragma_update(None, "secure_delete", false);
+        let res = res?;
+        reset?;
+        Ok(res)
+    }
+
+    /// 粉碎回收站中的记录，返回删除的条数
+    pub fn shred_trash(&self, ids: &[u64]) -> Result<usize> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let mut q = QueryBuilder::new();
+        q.and_in("id", ids.iter().map(|id| int(*id)).collect());
+        let mut types = QueryBuilder::new();
+        types.and_in("record_id", ids.iter().map(|id| int(*id)).collect());
+        self.with_secure_delete(|| {
+            let tx = self.conn.unchecked_transaction()?;
+            let sql = format!(
+                "update trash set content = zeroblob(length(CAST(content AS BLOB))), content_preview = zeroblob(length(CAST(content_preview AS BLOB))), original_content = zeroblob(length(CAST(original_content AS BLOB))), note = '' where 1=1{}",
+                q.where_clause()
+            );
+            self.conn.execute(&sql, q.params())?;
+            let sql = format!(
+                "update trash_pasteboard_type set data = zeroblob(length(data)) where 1=1{}",
+                types.where_clause()
+            );
+            self.conn.execute(&sql, types.params())?;
+            let sql = format!("delete from trash where 1=1{}", q.where_clause());
+            let deleted = self.conn.execute(&sql, q.params())?;
+            let sql = format!(
+                "delete from trash_pasteboard_type where 1=1{}",
+                types.where_clause()
+            );
+            self.conn.execute(&sql, types.params())?;
+            tx.commit()?;
+            Ok(deleted)
+        })
+    }
+
+    /// VACUUM 重写数据库文件，去掉已释放的页
+    pub fn compact(&self) -> Result<()> {
+        self.conn.execute("VACUUM", ())?;
+        Ok(())
+    }
+}
+
+// 记录关联：如用户名和对应的网址，预览时显示相关记录
+impl SqliteDB {
+    /// 关联是双向的，按 (较小 id, 较大 id) 保存
+    pub fn link_records(&self, a: u64, b: u64) -> Result<()> {
+        if a == b {
+            bail!("cannot link a record to itself");
+        }
+        self.find_list_item_by_id(a)?;
+        self.find_list_item_by_id(b)?;
+        let sql = "insert or ignore into record_link (source_id, target_id, create_time) values (?1, ?2, ?3)";
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        self.conn
+            .prepare_cached(sql)?
+            .execute((a.min(b), a.max(b), now))?;
+        Ok(())
+    }
+
+    pub fn unlink_records(&self, a: u64, b: u64) -> Result<()> {
+        let sql = "delete from record_link where source_id = ?1 and target_id = ?2";
+        self.conn
+            .prepare_cached(sql)?
+            .execute((a.min(b), a.max(b)))?;
+        Ok(())
+    }
+
+    /// 与 id 关联的记录，按关联的先后排序，content 为预览内容
+    pub fn find_linked_records(&self, id: u64) -> Result<Vec<Record>> {
+        // 已删除（在 trash 中）的记录不返回
+        let sql = "SELECT r.id FROM record_link l join record r on r.id = (case when l.source_id = ?1 then l.target_id else l.source_id end) where l.source_id = ?1 or l.target_id = ?1 order by l.create_time";
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let ids = stmt
+            .query_map([id], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<u64>>>()?;
+        ids.into_iter()
+            .map(|id| self.find_list_item_by_id(id))
+            .collect()
+    }
+}
+
+// 备份和恢复
+impl SqliteDB {
+    /// 导出一份一致的数据库快照，导出期间其它连接照常读写
+    pub fn snapshot_to(&self, path: &Path) -> Result<()> {
+        self.conn
+            .execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// 按 strategy 把备份的数据库合并到当前的记录，返回新增或替换的记录数和跳过的加密记录数
+    /// 备份出自旧版本时只复制两边都有的字段
+    /// skip_secure 为 true 时（备份的口令和本机不同）合并时跳过加密的记录，本机的口令无法解密
+    pub fn restore_from(
+        &self,
+        path: &Path,
+        strategy: MergeStrategy,
+        skip_secure: bool,
+    ) -> Result<(usize, usize)> {
+        self.conn
+            .execute("ATTACH DATABASE ?1 AS backup", [path.to_string_lossy()])?;
+        let res = match strategy {
+            MergeStrategy::Replace => self.replace_from_backup().map(|restored| (restored, 0)),
+            MergeStrategy::KeepNewest => self.merge_from_backup(true, skip_secure),
+            MergeStrategy::UnionByHash => self.merge_from_backup(false, skip_secure),
+        };
+        log_err!(self.conn.execute("DETACH DATABASE backup", ()));
+        res
+    }
+
+    fn backup_has_table(&self, table: &str) -> Result<bool> {
+        let sql = "SELECT count(*) FROM backup.sqlite_master WHERE type = 'table' and name = ?1";
+        let count: u32 = self.conn.query_row(sql, [table], |row| row.get(0))?;
+        Ok(count > 0)
+    }
+
+    fn common_columns(&self, table: &str) -> Result<String> {
+        let sql = "SELECT name FROM pragma_table_info(?1, 'main') WHERE name IN (SELECT name FROM pragma_table_info(?1, 'backup'))";
+        let mut stmt = self.conn.prepare(sql)?;
+        let columns = stmt
+            .query_map([table], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(columns.join(", "))
+    }
+
+    /// 两边按 md5 合并，备份中的新记录重新分配 id
+    /// keep_newest 为 true 时两边都有的记录保留 create_time 较新的一边
+    fn merge_from_backup(&self, keep_newest: bool, skip_secure: bool) -> Result<(usize, usize)> {
+        if !self.backup_has_table("record")? {
+            bail!("backup does not contain a record table");
+        }
+        let columns: Vec<String> = self
+            .common_columns("record")?
+            .split(", ")
+            .filter(|c| *c != "id")
+            .map(String::from)
+            .collect();
+        // 旧版本的备份没有加密记录
+        let skip_secure = skip_secure && columns.iter().any(|c| c == "is_secure");
+        let secure_filter = match skip_secure {
+            true => " and b.is_secure = 0",
+            false => "",
+        };
+        let tx = self.conn.unchecked_transaction()?;
+        let max_id: u64 =
+            self.conn
+                .query_row("SELECT coalesce(max(id), 0) FROM main.record", [], |row| {
+                    row.get(0)
+                })?;
+        let mut merged = 0;
+        let skipped: usize = match skip_secure {
+            true => self.conn.query_row(
+                "SELECT count(*) FROM backup.record b where b.is_secure = 1 and b.md5 not in (select md5 from main.record)",
+                [],
+                |row| row.get(0),
+            )?,
+            false => 0,
+        };
+        if keep_newest {
+            let updated: Vec<&str> = columns
+                .iter()
+                .map(String::as_str)
+                .filter(|c| *c != "md5")
+                .collect();
+            let values: Vec<String> = updated.iter().map(|c| format!("b.{}", c)).collect();
+            let sql = format!(
+                "update main.record set ({}) = (select {} from backup.record b where b.md5 = record.md5) where exists (select 1 from backup.record b where b.md5 = record.md5 and b.create_time > record.create_time{})",
+                updated.join(", "),
+                values.join(", "),
+                secure_filter
+            );
+            merged += self.conn.execute(&sql, ())?;
+        }
+        let sql = format!(
+            "insert into main.record ({0}) select {0} from backup.record b where md5 not in (select md5 from main.record){1}",
+            columns.join(", "),
+            secure_filter
+        );
+        merged += self.conn.execute(&sql, ())?;
+        // 原始剪切板类型只补给新增的记录，关联按 md5 对应到当前的 id
+        if self.backup_has_table("pasteboard_type")? {
+            let sql = "insert or ignore into main.pasteboard_type (record_id, type, data) select m.id, p.type, p.data from backup.pasteboard_type p join backup.record b on b.id = p.record_id join main.record m on m.md5 = b.md5 where m.id > ?1";
+            self.conn.execute(sql, [max_id])?;
+        }
+        if self.backup_has_table("record_link")? {
+            let sql = "insert or ignore into main.record_link (source_id, target_id, create_time) select min(ms.id, mt.id), max(ms.id, mt.id), l.create_time from backup.record_link l join backup.record bs on bs.id = l.source_id join backup.record bt on bt.id = l.target_id join main.record ms on ms.md5 = bs.md5 join main.record mt on mt.md5 = bt.md5 where ms.id != mt.id";
+            self.conn.execute(sql, ())?;
+        }
+        tx.commit()?;
+        record_cache::invalidate();
+        Ok((merged, skipped))
+    }
+
+    fn replace_from_backup(&self) -> Result<usize> {
+        if !self.backup_has_table("record")? {
+            bail!("backup does not contain a record table");
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        let mut restored = 0;
+        for table in ["record", "pasteboard_type", "record_link"] {
+            self.conn
+                .execute(&format!("delete from main.{}", table), ())?;
+            if !self.backup_has_table(table)? {
+                continue;
+            }
+            let columns = self.common_columns(table)?;
+            let sql = format!(
+                "insert into main.{0} ({1}) select {1} from backup.{0}",
+                table, columns
+            );
+            let count = self.conn.execute(&sql, ())?;
+            if table == "record" {
+                restored = count;
+            }
+        }
+        tx.commit()?;
+        record_cache::invalidate();
+        Ok(restored)
+    }
+}
+
+/// 修复时复制到新数据库的表
+const SALVAGE_TABLES: [&str; 9] = [
+    "record",
+    "pasteboard_type",
+    "record_link",
+    "job",
+    "sync_conflict",
+    "audit_log",
+    "trash",
+    "trash_pasteboard_type",
+    "clip_macro",
+];
+
+/// 对应的记录已不存在的行：表名和条件，回收站中的记录撤销后还会用到关联
+const ORPHAN_CONDITIONS: [(&str, &str); 5] = [
+    ("pasteboard_type", "record_id not in (select id from record)"),
+    (
+        "record_link",
+        "source_id not in (select id from record union select id from trash) or target_id not in (select id from record union select id from trash)",
+    ),
+    ("job", "record_id not in (select id from record)"),
+    ("sync_conflict", "record_id not in (select id from record)"),
+    (
+        "trash_pasteboard_type",
+        "batch_id not in (select batch_id from trash)",
+    ),
+];
+
+// 完整性检查和修复
+impl SqliteDB {
+    /// PRAGMA integrity_check 发现的问题，没有问题时为空
+    pub fn integrity_check(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("PRAGMA integrity_check(100)")?;
+        let messages = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(messages.into_iter().filter(|m| m != "ok").collect())
+    }
+
+    /// 比 integrity_check 快，不检查索引和内容是否一致，最多返回 10 条
+    pub fn quick_check(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("PRAGMA quick_check(10)")?;
+        let messages = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(messages.into_iter().filter(|m| m != "ok").collect())
+    }
+
+    /// 各表中孤立的行数，没有孤立行的表不返回
+    pub fn count_orphans(&self) -> Result<Vec<(String, usize)>> {
+        let mut res = vec![];
+        for (table, condition) in ORPHAN_CONDITIONS {
+            let sql = format!("SELECT count(*) FROM {} where {}", table, condition);
+            let count: usize = self.conn.query_row(&sql, [], |row| row.get(0))?;
+            if count > 0 {
+                res.push((table.to_string(), count));
+            }
+        }
+        Ok(res)
+    }
+
+    pub fn delete_orphans(&self) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut removed = 0;
+        for (table, condition) in ORPHAN_CONDITIONS {
+            removed += tx.execute(&format!("delete from {} where {}", table, condition), ())?;
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    /// 压缩的内容无法解压的记录
+    pub fn find_unreadable_records(&self) -> Result<Vec<u64>> {
+        let sql = "SELECT id, content FROM record where typeof(content) = 'blob'";
+        let mut stmt = self.conn.prepare(sql)?;
+        let mut rows = stmt.query([])?;
+        let mut res = vec![];
+        while let Some(row) = rows.next()? {
+            if row.get::<_, Content>(1).is_err() {
+                res.push(row.get(0)?);
+            }
+        }
+        Ok(res)
+    }
+
+    pub fn reindex(&self) -> Result<()> {
+        self.conn.execute("REINDEX", ())?;
+        Ok(())
+    }
+
+    /// 把能读取的行复制到 path 处新建的数据库，返回复制和跳过的行数
+    /// 整表复制失败时逐行复制，损坏页上的行会被跳过
+    pub fn salvage_to(&self, path: &Path) -> Result<(usize, usize)> {
+        {
+            let c = Connection::open(path)?;
+            Self::create_schema(&c)?;
+        }
+        self.conn
+            .execute("ATTACH DATABASE ?1 AS salvage", [path.to_string_lossy()])?;
+        let res = self.salvage_tables();
+        log_err!(self.conn.execute("DETACH DATABASE salvage", ()));
+        res
+    }
+
+    fn salvage_tables(&self) -> Result<(usize, usize)> {
+        let (mut copied, mut skipped) = (0, 0);
+        for table in SALVAGE_TABLES {
+            let sql = "SELECT name FROM pragma_table_info(?1, 'main') WHERE name IN (SELECT name FROM pragma_table_info(?1, 'salvage'))";
+            let columns = self
+                .conn
+                .prepare(sql)?
+                .query_map([table], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?
+                .join(", ");
+            if columns.is_empty() {
+                continue;
+            }
+            let sql = format!(
+                "insert or ignore into salvage.{0} ({1}) select {1} from main.{0}",
+                table, columns
+            );
+            let tx = self.conn.unchecked_transaction()?;
+            if let Ok(count) = tx.execute(&sql, ()) {
+                tx.commit()?;
+                copied += count;
+                continue;
+            }
+            // 整表复制失败时回滚已复制的部分，改为逐行复制
+            tx.rollback()?;
+            let sql = format!("{} where rowid = ?1", sql);
+            for rowid in self.readable_rowids(table) {
+                match self.conn.execute(&sql, [rowid]) {
+                    Ok(count) => copied += count,
+                    Err(_) => skipped += 1,
+                }
+            }
+        }
+        Ok((copied, skipped))
+    }
+
+    /// 从两端分别扫描，遇到损坏的页时停止，尽量多找出能读取的行
+    fn readable_rowids(&self, table: &str) -> Vec<i64> {
+        let mut rowids = BTreeSet::new();
+        for order in ["asc", "desc"] {
+            let sql = format!("SELECT rowid FROM main.{} order by rowid {}", table, order);
+            let mut stmt = match self.conn.prepare(&sql) {
+                Ok(stmt) => stmt,
+                Err(_) => continue,
+            };
+            let mut rows = match stmt.query([]) {
+                Ok(rows) => rows,
+                Err(_) => continue,
+            };
+            while let Ok(Some(row)) = rows.next() {
+                match row.get(0) {
+                    Ok(rowid) => rowids.insert(rowid),
+                    Err(_) => break,
+                };
+            }
+        }
+        rowids.into_iter().collect()
+    }
+}
+
+// 审计日志
+impl SqliteDB {
+    /// 记录一次操作，未开启审计日志时直接忽略
+    pub fn audit(&self, action: AuditAction, record_id: u64) {
+        if !audit::is_enabled() {
+            return;
+        }
+        log_err!(self.insert_audit_log(action, record_id));
+    }
+
+    fn insert_audit_log(&self, action: AuditAction, record_id: u64) -> Result<()> {
+        let sql = "insert into audit_log (record_id,action,create_time) values (?1,?2,?3)";
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        self.conn
+            .prepare_cached(sql)?
+            .execute((&record_id, action.as_str(), &now))?;
+        self.trim_audit_log()
+    }
+
+    /// 批量删除等操作一次写入 select 出的全部记录 id，未开启审计时不查询
+    fn audit_selected(
+        &self,
+        action: AuditAction,
+        select_ids: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<()> {
+        if !audit::is_enabled() {
+            return Ok(());
+        }
+        let sql = format!(
+            "insert into audit_log (record_id,action,create_time) select id, '{}', {} from ({})",
+            action.as_str(),
+            chrono::Local::now().timestamp_millis(),
+            select_ids
+        );
+        self.conn.prepare_cached(&sql)?.execute(params)?;
+        self.trim_audit_log()
+    }
+
+    // 超过保留条数的旧日志直接删除
+    fn trim_audit_log(&self) -> Result<()> {
+        let sql = "delete from audit_log where id <= (select max(id) from audit_log) - ?1";
+        self.conn.prepare_cached(sql)?.execute([audit::limit()])?;
+        Ok(())
+    }
+
+    pub fn find_audit_log(&self, req: AuditQueryReq) -> Result<Vec<AuditLog>> {
+        let sql = r#"
+        SELECT id, record_id, action, create_time FROM audit_log
+        where (?1 is null or record_id = ?1)
+          and (?2 is null or action = ?2)
+          and (?3 is null or create_time >= ?3)
+          and (?4 is null or create_time <= ?4)
+        order by id desc limit ?5
+        "#;
+        let limit = req.limit.unwrap_or(300);
+        let mut stmt = self.conn.prepare(sql)?;
+        let mut rows = stmt.query((
+            &req.record_id,
+            &req.action,
+            &req.start_time,
+            &req.end_time,
+            &limit,
+        ))?;
+        let mut res = vec![];
+        while let Some(row) = rows.next()? {
+            res.push(AuditLog {
+                id: row.get(0)?,
+                record_id: row.get(1)?,
+                action: row.get(2)?,
+                create_time: row.get(3)?,
+            });
+        }
+        Ok(res)
+    }
+
+    pub fn clear_audit_log(&self) -> Result<()> {
+        self.conn.execute("delete from audit_log", ())?;
+        Ok(())
+    }
+}
+
+// 本地统计事件
+impl SqliteDB {
+    /// 记录一次事件，未开启统计或只读时直接忽略
+    pub fn log_event(&self, kind: EventKind) {
+        if !analytics::is_enabled() || Self::is_read_only() {
+            return;
+        }
+        log_err!(self.insert_event(kind));
+    }
+
+    fn insert_event(&self, kind: EventKind) -> Result<()> {
+        let sql = "insert into event_log (kind,create_time) values (?1,?2)";
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        self.conn
+            .prepare_cached(sql)?
+            .execute((kind.as_str(), &now))?;
+        let sql = "delete from event_log where id <= (select max(id) from event_log) - ?1";
+        self.conn
+            .prepare_cached(sql)?
+            .execute([analytics::EVENT_LIMIT])?;
+        Ok(())
+    }
+
+    /// 按 strftime 格式分组的各类事件数，返回 (分组, 类型, 次数)
+    pub fn count_events(
+        &self,
+        format: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<(String, String, usize)>> {
+        let sql = "SELECT strftime(?1, create_time / 1000, 'unixepoch', 'localtime') as bucket, kind, count(*) FROM event_log where create_time >= ?2 and create_time < ?3 group by bucket, kind order by bucket, kind";
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let res = stmt
+            .query_map((format, &start, &end), |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(res)
+    }
+
+    pub fn purge_events(&self) -> Result<usize> {
+        Ok(self.conn.execute("delete from event_log", ())?)
+    }
+}
+
+// 同步
+impl SqliteDB {
+    /// 未修改过收藏和标签的记录以最近一次复制的时间为准
+    pub fn find_sync_state(&self, md5: &str) -> Result<Option<SyncState>> {
+        let sql = "SELECT id, is_favorite, tags, max(create_time, updated_at) FROM record where md5 = ?1 order by id desc limit 1";
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let mut rows = stmt.query([md5])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(SyncState {
+                id: row.get(0)?,
+                is_favorite: row.get(1)?,
+                tags: row.get(2)?,
+                updated_at: row.get(3)?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// 按 (修改时间, md5) 升序，从游标 (since, after) 之后开始，删除的记录带上 deleted
+    /// 修改时间相同的记录不会因为分页被跳过，tags 中任一标签完全匹配即可
+    pub fn find_sync_changes(
+        &self,
+        since: u64,
+        after: &str,
+        data_types: Option<&[String]>,
+        favorites_only: bool,
+        tags: &[&str],
+        limit: usize,
+    ) -> Result<Vec<SyncChange>> {
+        let mut q = QueryBuilder::new();
+        q.and(
+            "(modified > {0} or (modified = {0} and md5 > {1}))",
+            vec![int(since), text(after)],
+        );
+        let types = data_types.unwrap_or_default();
+        q.and_in(
+            "data_type",
+            types.iter().map(|t| text(t.as_str())).collect(),
+        );
+        if favorites_only {
+            q.and("is_favorite = 1", vec![]);
+        }
+        let tags = tags
+            .iter()
+            .map(|tag| text(format!("%,{},%", tag)))
+            .collect();
+        q.and_any("(',' || tags || ',') like {0}", tags);
+        let limit = q.bind(int(limit as u64));
+        let sql = format!(
+            "SELECT md5, data_type, content, is_favorite, tags, modified, deleted FROM (SELECT md5, data_type, content, is_favorite, tags, max(create_time, updated_at) as modified, 0 as deleted FROM record where is_secure = 0 union all SELECT md5, data_type, null, is_favorite, tags, deleted_at, 1 FROM sync_tombstone) where 1=1{} order by modified asc, md5 asc limit {}",
+            q.where_clause(),
+            limit
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut rows = stmt.query(q.params())?;
+        let mut res = vec![];
+        while let Some(row) = rows.next()? {
+            let deleted: bool = row.get(6)?;
+            res.push(SyncChange {
+                md5: row.get(0)?,
+                data_type: row.get(1)?,
+                content: match deleted {
+                    true => None,
+                    false => Some(row.get::<_, Content>(2)?.0),
+                },
+                is_favorite: row.get(3)?,
+                tags: row.get(4)?,
+                updated_at: row.get(5)?,
+                deleted,
+            });
+        }
+        Ok(res)
+    }
+
+    /// 删除 before 之前的复制历史，返回删除的条数
+    pub fn purge_copy_history(&self, before: u64) -> Result<usize> {
+        let sql = "delete from copy_history where copied_at < ?1";
+        Ok(self.conn.execute(sql, [before])?)
+    }
+
+    /// 删除 before 之前留下的删除标记，返回删除的条数
+    pub fn purge_sync_tombstones(&self, before: u64) -> Result<usize> {
+        let sql = "delete from sync_tombstone where deleted_at < ?1";
+        Ok(self.conn.prepare_cached(sql)?.execute([before])?)
+    }
+
+    pub fn set_updated_at(&self, id: u64, updated_at: u64) -> Result<()> {
+        let sql = "update record set updated_at = ?2 where id = ?1";
+        self.conn.prepare_cached(sql)?.execute((&id, &updated_at))?;
+        record_cache::invalidate();
+        Ok(())
+    }
+
+    pub fn add_sync_conflict(&self, c: &SyncConflict) -> Result<()> {
+        let sql = "insert or replace into sync_conflict (record_id,field,local,remote,device,create_time) values (?1,?2,?3,?4,?5,?6)";
+        self.conn.prepare_cached(sql)?.execute((
+            &c.record_id,
+            &c.field,
+            c.local.to_string(),
+            c.remote.to_string(),
+            &c.device,
+            &c.create_time,
+        ))?;
+        Ok(())
+    }
+
+    /// 记录已被删除的冲突不再返回
+    pub fn find_sync_conflicts(&self) -> Result<Vec<SyncConflict>> {
+        let sql = "SELECT c.id, c.record_id, c.field, c.local, c.remote, c.device, c.create_time FROM sync_conflict c join record r on r.id = c.record_id order by c.id desc";
+        let mut stmt = self.conn.prepare(sql)?;
+        let mut rows = stmt.query([])?;
+        let mut res = vec![];
+        while let Some(row) = rows.next()? {
+            res.push(Self::sync_conflict_from_row(row)?);
+        }
+        Ok(res)
+    }
+
+    pub fn find_sync_conflict(&self, id: u64) -> Result<SyncConflict> {
+        let sql = "SELECT id, record_id, field, local, remote, device, create_time FROM sync_conflict where id = ?1";
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let mut rows = stmt.query([&id])?;
+        match rows.next()? {
+            Some(row) => Self::sync_conflict_from_row(row),
+            None => bail!("sync conflict {} not found", id),
+        }
+    }
+
+    fn sync_conflict_from_row(row: &rusqlite::Row) -> Result<SyncConflict> {
+        let local: String = row.get(3)?;
+        let remote: String = row.get(4)?;
+        Ok(SyncConflict {
+            id: row.get(0)?,
+            record_id: row.get(1)?,
+            field: row.get(2)?,
+            local: serde_json::from_str(&local)?,
+            remote: serde_json::from_str(&remote)?,
+            device: row.get(5)?,
+            create_time: row.get(6)?,
+        })
+    }
+
+    pub fn delete_sync_conflict(&self, id: u64) -> Result<()> {
+        self.conn
+            .prepare_cached("delete from sync_conflict where id = ?1")?
+            .execute([&id])?;
+        Ok(())
+    }
+}
+
+// 后台任务队列
+impl SqliteDB {
+    /// 相同的任务已在队列中时忽略
+    pub fn enqueue_job(&self, kind: &str, record_id: u64, payload: &str) -> Result<()> {
+        let sql = "insert or ignore into job (kind,record_id,payload,attempts,next_run_at) values (?1,?2,?3,0,?4)";
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        self.conn
+            .prepare_cached(sql)?
+            .execute((kind, &record_id, payload, &now))?;
+        Ok(())
+    }
+
+    /// 取出最早到期的任务
+    pub fn next_due_job(&self) -> Result<Option<Job>> {
+        let sql = "SELECT id, kind, record_id, payload, attempts FROM job where next_run_at <= ?1 order by next_run_at asc, id asc limit 1";
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let mut rows = stmt.query([&now])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(Job {
+                id: row.get(0)?,
+                kind: row.get(1)?,
+                record_id: row.get(2)?,
+                payload: row.get(3)?,
+                attempts: row.get(4)?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// 失败后延迟重试
+    pub fn reschedule_job(&self, id: u64, delay_millis: u64, error: &str) -> Result<()> {
+        let sql = "update job set attempts = attempts + 1, next_run_at = ?2, last_error = ?3 where id = ?1";
+        let next_run_at = chrono::Local::now().timestamp_millis() as u64 + delay_millis;
+        self.conn
+            .prepare_cached(sql)?
+            .execute((&id, &next_run_at, error))?;
+        Ok(())
+    }
+
+    pub fn delete_job(&self, id: u64) -> Result<()> {
+        self.conn
+            .prepare_cached("delete from job where id = ?1")?
+            .execute([&id])?;
+        Ok(())
+    }
+
+    pub fn count_jobs(&self) -> Result<u64> {
+        let count = self
+            .conn
+            .query_row("SELECT count(*) FROM job", [], |row| row.get(0))?;
+        Ok(count)
+    }
+}
+
+// 剪切板宏
+impl SqliteDB {
+    /// 同名的宏覆盖步骤，返回宏的 id
+    pub fn save_macro(&self, name: &str, steps: &[MacroStep]) -> Result<u64> {
+        let sql = "insert into clip_macro (name,steps,create_time) values (?1,?2,?3) on conflict(name) do update set steps = excluded.steps";
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        self.conn
+            .prepare_cached(sql)?
+            .execute((name, serde_json::to_string(steps)?, &now))?;
+        let sql = "SELECT id FROM clip_macro where name = ?1";
+        Ok(self.conn.query_row(sql, [name], |row| row.get(0))?)
+    }
+
+    fn macro_from_row(row: &rusqlite::Row) -> rusqlite::Result<(u64, String, String, u64)> {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+    }
+
+    fn to_macro((id, name, steps, create_time): (u64, String, String, u64)) -> Result<ClipMacro> {
+        Ok(ClipMacro {
+            id,
+            name,
+            steps: serde_json::from_str(&steps)?,
+            create_time,
+        })
+    }
+
+    pub fn find_macros(&self) -> Result<Vec<ClipMacro>> {
+        let sql = "SELECT id, name, steps, create_time FROM clip_macro order by name";
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt
+            .query_map([], Self::macro_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter().map(Self::to_macro).collect()
+    }
+
+    pub fn find_macro(&self, id: u64) -> Result<ClipMacro> {
+        let sql = "SELECT id, name, steps, create_time FROM clip_macro where id = ?1";
+        let row = self
+            .conn
+            .query_row(sql, [id], Self::macro_from_row)
+            .optional()?;
+        match row {
+            Some(row) => Self::to_macro(row),
+            None => bail!("macro {} not found", id),
+        }
+    }
+
+    pub fn delete_macro(&self, id: u64) -> Result<()> {
+        self.conn
+            .prepare_cached("delete from clip_macro where id = ?1")?
+            .execute([&id])?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_sqlite_insert() {
+    SqliteDB::init().unwrap();
+    let r = Record {
+        content: "123456".to_string(),
+        md5: "e10adc3949ba59abbe56e057f20f883e".to_string(),
+        create_time: 1234568,
+        ..Default::default()
+    };
+    assert_eq!(SqliteDB::new().insert_record(r).unwrap(), 1_i64)
+}
+
+#[test]
+fn test_can_claim_owner() {
+    let now = 1_700_000_000_000;
+    let alive = |_| true;
+    let exited = |_| false;
+    assert!(can_claim(None, 1, "mac", now, alive));
+    let owner = (1, "mac".to_string(), now - 1000);
+    assert!(can_claim(Some(&owner), 1, "mac", now, alive));
+    assert!(!can_claim(Some(&owner), 2, "mac", now, alive));
+    assert!(can_claim(Some(&owner), 2, "mac", now, exited));
+    assert!(!can_claim(Some(&owner), 1, "linux", now, exited));
+    let stale = (1, "mac".to_string(), now - (OWNER_STALE_SECS + 1) * 1000);
+    assert!(can_claim(Some(&stale), 2, "linux", now, alive));
+}
+
+#[test]
+fn test_excess() {
+    assert_eq!(excess(0, 100, OVER_LIMIT_SLACK), 0);
+    assert_eq!(excess(109, 100, OVER_LIMIT_SLACK), 0);
+    assert_eq!(excess(110, 100, OVER_LIMIT_SLACK), 10);
+    assert_eq!(excess(500, 0, OVER_LIMIT_SLACK), 500);
+    assert_eq!(excess(100, 100, 0), 0);
+    assert_eq!(excess(101, 100, 0), 1);
+}
+
+#[test]
+fn test_normalize_note() {
+    assert_eq!(
+        normalize_note(Some("  staging API key, rotates monthly\n")).unwrap(),
+        Some("staging API key, rotates monthly".to_string())
+    );
+    assert_eq!(normalize_note(Some(" \t")).unwrap(), None);
+    assert_eq!(normalize_note(None).unwrap(), None);
+    assert!(normalize_note(Some(&"a".repeat(MAX_NOTE_LEN + 1))).is_err());
 }
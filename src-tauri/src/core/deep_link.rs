@@ -0,0 +1,119 @@
+use super::cli;
+use super::clipboard::ClipBoardOprator;
+use super::database::SqliteDB;
+use super::handle::{Handle, MsgTypeEnum};
+use super::window_manager::WindowType;
+use crate::utils::string_util;
+use anyhow::{bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+
+pub const SCHEME: &str = "lanaya";
+
+#[derive(Debug, PartialEq)]
+pub enum DeepLink {
+    /// lanaya://copy/<id>
+    Copy(u64),
+    /// lanaya://search?q=...
+    Search(String),
+}
+
+/// 新建的窗口还没来得及监听事件，先把搜索词存起来，由前端加载完成后取走
+fn pending_search() -> &'static Mutex<Option<String>> {
+    static PENDING_SEARCH: OnceCell<Mutex<Option<String>>> = OnceCell::new();
+    PENDING_SEARCH.get_or_init(|| Mutex::new(None))
+}
+
+pub fn take_pending_search() -> Option<String> {
+    pending_search().lock().take()
+}
+
+pub fn parse(url: &str) -> Result<DeepLink> {
+    let rest = match url.strip_prefix(&format!("{}://", SCHEME)) {
+        Some(rest) => rest.trim_end_matches('/'),
+        None => bail!("not a {} url \"{}\"", SCHEME, url),
+    };
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let mut segments = path.split('/');
+    match segments.next() {
+        Some("copy") => match segments.next().map(|id| id.parse::<u64>()) {
+            Some(Ok(id)) => Ok(DeepLink::Copy(id)),
+            _ => bail!("invalid record id in \"{}\"", url),
+        },
+        Some("search") => {
+            let key = query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(k, _)| *k == "q")
+                .map(|(_, v)| string_util::url_decode(v))
+                .unwrap_or_default();
+            Ok(DeepLink::Search(key))
+        }
+        _ => bail!("unsupported url \"{}\"", url),
+    }
+}
+
+pub fn handle(url: &str) -> Result<()> {
+    match parse(url)? {
+        DeepLink::Copy(id) => {
+            // 任何网页都能打开链接，解锁期间也不能借此把加密记录的明文写入剪切板
+            if SqliteDB::new().find_by_id(id)?.is_secure {
+                bail!("secure records cannot be copied from a link");
+            }
+            ClipBoardOprator::copy_by_id(id, None)
+        }
+        DeepLink::Search(key) => {
+            *pending_search().lock() = Some(key.clone());
+            Handle::show_window(WindowType::Main);
+            Handle::notice_to_window(MsgTypeEnum::Search, key)
+        }
+    }
+}
+
+/// 处理第二个实例转发过来的启动参数：命令行命令和链接分别处理，否则把主窗口带到前台
+pub fn handle_args(argv: Vec<String>) {
+    if cli::handle_args(&argv) {
+        return;
+    }
+    let urls: Vec<&String> = argv
+        .iter()
+        .filter(|arg| arg.starts_with(&format!("{}://", SCHEME)))
+        .collect();
+    if urls.is_empty() {
+        Handle::show_window(WindowType::Main);
+        return;
+    }
+    for url in urls {
+        if let Err(err) = handle(url) {
+            println!("handle deep link error: {}", err);
+        }
+    }
+}
+
+/// 注册 lanaya:// 协议，需在 tauri 初始化后调用
+pub fn register() {
+    let res = tauri_plugin_deep_link::register(SCHEME, |url| {
+        if let Err(err) = handle(&url) {
+            println!("handle deep link error: {}", err);
+        }
+    });
+    if let Err(err) = res {
+        println!("register deep link error: {}", err);
+    }
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(parse("lanaya://copy/12").unwrap(), DeepLink::Copy(12));
+    assert_eq!(parse("lanaya://copy/12/").unwrap(), DeepLink::Copy(12));
+    assert_eq!(
+        parse("lanaya://search?q=hello%20world").unwrap(),
+        DeepLink::Search("hello world".into())
+    );
+    assert_eq!(
+        parse("lanaya://search").unwrap(),
+        DeepLink::Search("".into())
+    );
+    assert!(parse("lanaya://copy/abc").is_err());
+    assert!(parse("https://copy/12").is_err());
+}
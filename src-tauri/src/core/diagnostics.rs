@@ -0,0 +1,272 @@
+use super::clipboard::ClipboardWatcher;
+use super::database::SqliteDB;
+use super::handle::Handle;
+use super::onboarding::{self, PermissionStatus};
+use super::{screen_share, secure_input};
+use crate::utils::hotkey_util::HotkeyConflict;
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::process::Command;
+
+/// 监听每秒检查一次剪切板，超过该时间没有检查视为已停止
+const WATCHER_STALE_MILLIS: u64 = 10_000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlatformInfo {
+    pub os: String,
+    pub arch: String,
+    /// 如 14.2.1、Ubuntu 22.04.3 LTS，取不到时为空
+    pub os_version: Option<String>,
+    pub app_version: String,
+    /// 仅 Linux：x11 或 wayland
+    pub display_server: Option<String>,
+    /// 仅 Wayland：是否可以通过 XWayland 访问剪切板
+    pub xwayland: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DatabaseHealth {
+    pub path: String,
+    pub size_bytes: Option<u64>,
+    pub ready: bool,
+    pub read_only: bool,
+    pub records: Option<usize>,
+    /// PRAGMA quick_check 发现的问题，完整的检查见 check_database_integrity
+    pub errors: Vec<String>,
+    /// 无法打开或查询时的错误
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HotkeyStatus {
+    pub func: String,
+    pub shortcut: String,
+    pub registered: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WatcherStatus {
+    pub alive: bool,
+    /// 最后一次检查剪切板的时间（毫秒）
+    pub last_poll: Option<u64>,
+    /// 暂停记录的原因：paused、read_only、secure_input、screen_share
+    pub paused_by: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostics {
+    pub generated_at: u64,
+    pub platform: PlatformInfo,
+    pub permissions: Vec<PermissionStatus>,
+    pub database: DatabaseHealth,
+    pub hotkeys: Vec<HotkeyStatus>,
+    pub hotkey_conflicts: Vec<HotkeyConflict>,
+    pub watcher: WatcherStatus,
+    /// 发现的问题，没有问题时为空
+    pub problems: Vec<String>,
+}
+
+/// /etc/os-release 中的 PRETTY_NAME
+fn parse_os_release(content: &str) -> Option<String> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+        .map(|value| value.trim().trim_matches('"').to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn os_version() -> Option<String> {
+    let output = |program: &str, args: &[&str]| {
+        let output = Command::new(program).args(args).output().ok()?;
+        let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Some(text).filter(|text| !text.is_empty())
+    };
+    match std::env::consts::OS {
+        "macos" => output("sw_vers", &["-productVersion"]),
+        "windows" => output("cmd", &["/C", "ver"]),
+        _ => parse_os_release(&fs::read_to_string("/etc/os-release").ok()?),
+    }
+}
+
+/// 由 XDG_SESSION_TYPE、WAYLAND_DISPLAY、DISPLAY 判断显示服务，返回 (显示服务, 是否有 XWayland)
+fn display_server(
+    session_type: Option<&str>,
+    wayland_display: Option<&str>,
+    display: Option<&str>,
+) -> (Option<String>, Option<bool>) {
+    let wayland =
+        session_type == Some("wayland") || wayland_display.map_or(false, |d| !d.is_empty());
+    let has_display = display.map_or(false, |d| !d.is_empty());
+    match (wayland, has_display) {
+        (true, xwayland) => (Some("wayland".into()), Some(xwayland)),
+        (false, true) => (Some("x11".into()), None),
+        (false, false) => (None, None),
+    }
+}
+
+fn platform_info() -> PlatformInfo {
+    let (display_server, xwayland) = match cfg!(target_os = "linux") {
+        true => {
+            let var = |name: &str| std::env::var(name).ok();
+            display_server(
+                var("XDG_SESSION_TYPE").as_deref(),
+                var("WAYLAND_DISPLAY").as_deref(),
+                var("DISPLAY").as_deref(),
+            )
+        }
+        false => (None, None),
+    };
+    let app_version = {
+        let binding = Handle::global().app_handle.lock();
+        binding
+            .as_ref()
+            .map(|app_handle| app_handle.package_info().version.to_string())
+            .unwrap_or_default()
+    };
+    PlatformInfo {
+        os: std::env::consts::OS.into(),
+        arch: std::env::consts::ARCH.into(),
+        os_version: os_version(),
+        app_version,
+        display_server,
+        xwayland,
+    }
+}
+
+fn database_health() -> DatabaseHealth {
+    let mut health = DatabaseHealth {
+        ready: SqliteDB::is_ready(),
+        read_only: SqliteDB::is_read_only(),
+        ..Default::default()
+    };
+    if let Ok(path) = SqliteDB::path() {
+        health.size_bytes = fs::metadata(&path).ok().map(|m| m.len());
+        health.path = path.to_string_lossy().to_string();
+    }
+    // 未就绪时打开连接会等待，这里不等
+    if !health.ready {
+        return health;
+    }
+    let res = (|| -> Result<(usize, Vec<String>)> {
+        let db = SqliteDB::new();
+        Ok((db.count_records()?.0, db.quick_check()?))
+    })();
+    match res {
+        Ok((records, errors)) => {
+            health.records = Some(records);
+            health.errors = errors;
+        }
+        Err(err) => health.error = Some(err.to_string()),
+    }
+    health
+}
+
+fn is_alive(last_poll: Option<u64>, now: u64) -> bool {
+    last_poll.map_or(false, |t| now.saturating_sub(t) <= WATCHER_STALE_MILLIS)
+}
+
+fn watcher_status(now: u64) -> WatcherStatus {
+    let last_poll = ClipboardWatcher::last_poll();
+    let paused_by = [
+        ("paused", !ClipboardWatcher::is_monitoring()),
+        ("read_only", SqliteDB::is_read_only()),
+        ("secure_input", secure_input::should_pause()),
+        ("screen_share", screen_share::should_pause()),
+    ]
+    .iter()
+    .filter(|(_, paused)| *paused)
+    .map(|(reason, _)| reason.to_string())
+    .collect();
+    WatcherStatus {
+        alive: is_alive(last_poll, now),
+        last_poll,
+        paused_by,
+    }
+}
+
+fn problems(diagnostics: &Diagnostics) -> Vec<String> {
+    let mut problems = vec![];
+    if diagnostics.platform.xwayland == Some(false) {
+        problems.push("wayland session without XWayland, the clipboard cannot be read".into());
+    }
+    for permission in &diagnostics.permissions {
+        if permission.status == "denied" {
+            problems.push(format!("{} permission denied", permission.name));
+        }
+    }
+    let database = &diagnostics.database;
+    if !database.ready {
+        problems.push("database is not ready".into());
+    }
+    if let Some(err) = &database.error {
+        problems.push(format!("database error: {}", err));
+    }
+    if !database.errors.is_empty() {
+        problems.push(format!(
+            "database quick check found {} problems",
+            database.errors.len()
+        ));
+    }
+    for hotkey in diagnostics.hotkeys.iter().filter(|h| !h.registered) {
+        problems.push(format!(
+            "hotkey {} ({}) is not registered",
+            hotkey.func, hotkey.shortcut
+        ));
+    }
+    if !diagnostics.watcher.alive {
+        problems.push("clipboard watcher is not running".into());
+    }
+    problems
+}
+
+/// 汇总运行环境、权限、数据库、快捷键和剪切板监听的状态，用于排查问题和反馈
+pub fn run() -> Diagnostics {
+    let now = chrono::Local::now().timestamp_millis() as u64;
+    let hotkeys = Handle::hotkey_registrations()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(func, shortcut, registered)| HotkeyStatus {
+            func,
+            shortcut,
+            registered,
+        })
+        .collect();
+    let mut diagnostics = Diagnostics {
+        generated_at: now,
+        platform: platform_info(),
+        permissions: onboarding::check_permissions(),
+        database: database_health(),
+        hotkeys,
+        hotkey_conflicts: Handle::global().hotkey_conflicts.lock().clone(),
+        watcher: watcher_status(now),
+        problems: vec![],
+    };
+    diagnostics.problems = problems(&diagnostics);
+    diagnostics
+}
+
+#[test]
+fn test_diagnostics_platform() {
+    let os_release = "NAME=\"Ubuntu\"\nPRETTY_NAME=\"Ubuntu 22.04.3 LTS\"\nID=ubuntu\n";
+    assert_eq!(
+        parse_os_release(os_release),
+        Some("Ubuntu 22.04.3 LTS".to_string())
+    );
+    assert_eq!(parse_os_release("ID=arch\n"), None);
+    assert_eq!(
+        display_server(Some("wayland"), Some("wayland-0"), None),
+        (Some("wayland".to_string()), Some(false))
+    );
+    assert_eq!(
+        display_server(None, Some("wayland-0"), Some(":0")),
+        (Some("wayland".to_string()), Some(true))
+    );
+    assert_eq!(
+        display_server(Some("x11"), None, Some(":0")),
+        (Some("x11".to_string()), None)
+    );
+    assert!(is_alive(Some(995_000), 1_000_000));
+    assert!(!is_alive(Some(980_000), 1_000_000));
+    assert!(!is_alive(None, 1_000_000));
+}
@@ -0,0 +1,357 @@
+use super::audit::AuditAction;
+use super::database::{InsertResult, Record, SqliteDB};
+use super::handle::{Handle, MsgTypeEnum};
+use super::snippet::{self, SnippetFormat};
+use crate::config::Config;
+use crate::log_err;
+use crate::utils::{dirs, string_util};
+use anyhow::{anyhow, bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// 检查记录和 match 文件是否变化的间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+const MATCH_FILE: &str = "lanaya.yml";
+/// 上次写入 match 文件的内容
+const LAST_EXPORT_FILE: &str = "espanso_export.yml";
+
+/// 把收藏同步到 espanso 的 match 文件，espanso 会自动重新加载
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct EspansoSync {
+    /// 同步带有这些标签的收藏，为空时同步全部收藏的文本
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// match 文件的路径，默认为 espanso 配置目录下的 match/lanaya.yml
+    pub path: Option<String>,
+    /// 在 match 文件中新增的匹配导入为收藏，删除仍需要在 Lanaya 中进行
+    #[serde(default)]
+    pub import: bool,
+}
+
+impl EspansoSync {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(path) = self.path.as_ref().filter(|p| !p.is_empty()) {
+            if !path.ends_with(".yml") && !path.ends_with(".yaml") {
+                bail!("the espanso match file must be a .yml file");
+            }
+        }
+        Ok(())
+    }
+
+    fn match_path(&self) -> Result<PathBuf> {
+        if let Some(path) = self.path.as_ref().filter(|p| !p.is_empty()) {
+            return Ok(PathBuf::from(path));
+        }
+        let dir = tauri::api::path::config_dir()
+            .ok_or(anyhow!("failed to get the espanso config dir"))?;
+        Ok(dir.join("espanso").join("match").join(MATCH_FILE))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EspansoSyncResult {
+    pub path: String,
+    pub exported: usize,
+    pub imported: usize,
+}
+
+/// 解析 trigger、replace，支持单行的值和 |、> 多行块，即 Lanaya 写入的格式和常见的手写格式
+fn parse_matches(content: &str) -> Vec<(String, String)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut matches = vec![];
+    let mut trigger: Option<String> = None;
+    let mut i = 0;
+    while i < lines.len() {
+        let raw = lines[i];
+        i += 1;
+        let line = raw.trim_start().trim_start_matches("- ").trim();
+        if let Some(value) = line.strip_prefix("trigger:") {
+            trigger = parse_scalar(value);
+        } else if let Some(value) = line.strip_prefix("replace:") {
+            let value = value.trim();
+            let replace = match value.starts_with('|') || value.starts_with('>') {
+                true => {
+                    let key_indent = raw.find("replace:").unwrap_or(0);
+                    let (block, used) = parse_block(&lines[i..], key_indent, value);
+                    i += used;
+                    Some(block).filter(|block| !block.trim().is_empty())
+                }
+                false => parse_scalar(value),
+            };
+            if let (Some(t), Some(replace)) = (trigger.take(), replace) {
+                matches.push((t, replace));
+            }
+        }
+    }
+    matches
+}
+
+/// 缩进比 replace 更深的行属于块，返回块的内容和占用的行数
+/// | 保留换行，> 把相邻的行用空格连接，带 - 时去掉末尾的换行
+fn parse_block(lines: &[&str], key_indent: usize, style: &str) -> (String, usize) {
+    let mut body: Vec<&str> = vec![];
+    let mut indent = None;
+    for line in lines {
+        let current = line.len() - line.trim_start().len();
+        if !line.trim().is_empty() && current <= key_indent {
+            break;
+        }
+        if line.trim().is_empty() {
+            body.push("");
+            continue;
+        }
+        let indent = *indent.get_or_insert(current);
+        body.push(&line[indent.min(current)..]);
+    }
+    let used = body.len();
+    // 块后面的空行不属于内容
+    while body.last() == Some(&"") {
+        body.pop();
+    }
+    let mut text = String::new();
+    if style.starts_with('>') {
+        for (n, line) in body.iter().enumerate() {
+            if line.is_empty() {
+                text.push('\n');
+                continue;
+            }
+            if n > 0 && !body[n - 1].is_empty() {
+                text.push(' ');
+            }
+            text.push_str(line);
+        }
+    } else {
+        text = body.join("\n");
+    }
+    if !style.ends_with('-') {
+        text.push('\n');
+    }
+    (text, used)
+}
+
+fn parse_scalar(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.starts_with('"') {
+        return serde_json::from_str(value).ok();
+    }
+    let value = match value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        true => value[1..value.len() - 1].replace("''", "'"),
+        false => value.to_string(),
+    };
+    match value.is_empty() {
+        true => None,
+        false => Some(value),
+    }
+}
+
+/// 上次写入 match 文件的内容，之后在 Lanaya 中删除的收藏不会再从文件中导入
+/// 同时保存到数据目录，重启后仍能区分新增和修改的匹配
+fn last_export() -> &'static Mutex<Option<String>> {
+    static LAST_EXPORT: OnceCell<Mutex<Option<String>>> = OnceCell::new();
+    LAST_EXPORT.get_or_init(|| {
+        let saved = last_export_path().and_then(|path| Ok(fs::read_to_string(path)?));
+        Mutex::new(saved.ok())
+    })
+}
+
+fn last_export_path() -> Result<PathBuf> {
+    Ok(dirs::app_data_dir()?.join(LAST_EXPORT_FILE))
+}
+
+fn save_last_export(content: String) {
+    if last_export().lock().as_ref() == Some(&content) {
+        return;
+    }
+    if let Ok(path) = last_export_path() {
+        log_err!(fs::write(path, &content));
+    }
+    *last_export().lock() = Some(content);
+}
+
+/// match 文件相比上次写入的变化
+#[derive(Debug, PartialEq)]
+enum MatchChange {
+    /// 新增的匹配：触发词和内容
+    Added(String, String),
+    /// 修改了内容的匹配：原来的内容和新的内容
+    Edited(String, String),
+}
+
+/// match 文件中新增或修改了内容的匹配，触发词和上次写入的相同时为修改
+fn changed_matches(content: &str, exported: &str) -> Vec<MatchChange> {
+    let exported = parse_matches(exported);
+    parse_matches(content)
+        .into_iter()
+        .filter(|m| !exported.contains(m))
+        .map(
+            |(trigger, replace)| match exported.iter().find(|(t, _)| *t == trigger) {
+                Some((_, old)) => MatchChange::Edited(old.clone(), replace),
+                None => MatchChange::Added(trigger, replace),
+            },
+        )
+        .collect()
+}
+
+/// 导入 match 文件中新增的内容，修改的匹配更新对应的记录，返回导入和更新的条数
+fn import(db: &SqliteDB, config: &EspansoSync, content: &str) -> Result<usize> {
+    let exported = last_export().lock().clone().unwrap_or_default();
+    let mut imported = 0;
+    for change in changed_matches(content, &exported) {
+        let replace = match change {
+            MatchChange::Added(_, replace) => replace,
+            MatchChange::Edited(old, replace) => {
+                // 原来的记录还在时直接修改，避免新旧两条都同步回去
+                if let Some(id) = db.find_id_by_md5(&string_util::md5(&old))? {
+                    if !db.md5_is_exist(string_util::md5(&replace))? {
+                        db.update_content(id, replace, None)?;
+                        imported += 1;
+                    }
+                    continue;
+                }
+                replace
+            }
+        };
+        // 已有的记录不更新复制时间
+        if db.md5_is_exist(string_util::md5(&replace))? {
+            continue;
+        }
+        let res = db.insert_if_not_exist(Record {
+            content: replace,
+            data_type: "text".to_string(),
+            ..Default::default()
+        })?;
+        if let InsertResult::Inserted(id) = res {
+            db.set_favorite(&[id], true)?;
+            if let Some(tag) = config.tags.first() {
+                db.save_tags(id, tag.clone())?;
+            }
+            imported += 1;
+        }
+    }
+    Ok(imported)
+}
+
+/// 先导入（开启 import 时），再把收藏写入 match 文件，内容未变化时不写入
+/// 只管理 Lanaya 生成的文件，不会覆盖用户自己的 match 文件
+pub fn sync(config: &EspansoSync) -> Result<EspansoSyncResult> {
+    let path = config.match_path()?;
+    let db = SqliteDB::new();
+    let current = fs::read_to_string(&path).unwrap_or_default();
+    if !current.trim().is_empty() && !current.starts_with(snippet::ESPANSO_HEADER) {
+        bail!(
+            "{} was not generated by Lanaya, choose a dedicated match file such as match/{}",
+            path.to_string_lossy(),
+            MATCH_FILE
+        );
+    }
+    let mut imported = 0;
+    if config.import && !SqliteDB::is_read_only() {
+        imported = import(&db, config, &current)?;
+        if imported > 0 {
+            log_err!(Handle::notice_to_window(MsgTypeEnum::ChangeClipBoard, "ok"));
+        }
+    }
+    let records = db.find_favorite_texts(&config.tags)?;
+    let mut snippets = snippet::to_snippets(&records);
+    // 文件中已有的匹配保留原来的触发词，不按内容重新生成
+    let triggers = parse_matches(&current);
+    for snippet in snippets.iter_mut() {
+        snippet.espanso_trigger = triggers
+            .iter()
+            .find(|(_, replace)| *replace == snippet.body)
+            .map(|(trigger, _)| trigger.clone());
+    }
+    let content = snippet::render(SnippetFormat::Espanso, &snippets)?;
+    if content != current {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, &content)?;
+        // 文件内容不变时不重复记录
+        for record in &records {
+            db.audit(AuditAction::Exported, record.id);
+        }
+    }
+    save_last_export(content);
+    Ok(EspansoSyncResult {
+        path: path.to_string_lossy().to_string(),
+        exported: snippets.len(),
+        imported,
+    })
+}
+
+/// espanso_sync 中设置的同步，未设置时返回 None
+pub fn sync_now() -> Result<Option<EspansoSyncResult>> {
+    let config = Config::common().latest().espanso_sync.clone();
+    config.map(|config| sync(&config)).transpose()
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).ok()?.modified().ok()
+}
+
+/// 数据库、match 文件或同步设置变化时同步一次
+pub fn start() {
+    thread::spawn(|| {
+        // 上次同步时的设置、数据库和 match 文件的修改时间
+        let mut last: Option<(EspansoSync, Option<SystemTime>, Option<SystemTime>)> = None;
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let config = match Config::common().latest().espanso_sync.clone() {
+                Some(config) => config,
+                None => {
+                    last = None;
+                    continue;
+                }
+            };
+            let path = match config.match_path() {
+                Ok(path) => path,
+                Err(_) => continue,
+            };
+            let state = (config, SqliteDB::file_modified(), modified(&path));
+            if last.as_ref() == Some(&state) {
+                continue;
+            }
+            match sync(&state.0) {
+                // 同步本身会修改数据库和 match 文件，以同步后的时间为准
+                Ok(_) => last = Some((state.0, SqliteDB::file_modified(), modified(&path))),
+                Err(err) => {
+                    println!("espanso sync error: {}", err);
+                    last = Some(state);
+                }
+            }
+        }
+    });
+}
+
+#[test]
+fn test_parse_espanso_matches() {
+    let content = "# generated by Lanaya\nmatches:\n  - trigger: \":addr\"\n    replace: \"1 Main St\\nSpringfield\"\n  - trigger: :sig\n    replace: 'Best, Ann''s team'\n  - trigger: \":multi\"\n    replace: |\n      line one\n        indented\n\n  - trigger: \":fold\"\n    replace: >-\n      a\n      b\n";
+    assert_eq!(
+        parse_matches(content),
+        vec![
+            (":addr".to_string(), "1 Main St\nSpringfield".to_string()),
+            (":sig".to_string(), "Best, Ann's team".to_string()),
+            (":multi".to_string(), "line one\n  indented\n".to_string()),
+            (":fold".to_string(), "a b".to_string()),
+        ]
+    );
+    let exported = "matches:\n  - trigger: \":addr\"\n    replace: \"1 Main\"\n  - trigger: \":sig\"\n    replace: \"Best, Ann's team\"\n";
+    assert_eq!(
+        changed_matches(content, exported)[..2],
+        [
+            MatchChange::Edited("1 Main".into(), "1 Main St\nSpringfield".into()),
+            MatchChange::Added(":multi".into(), "line one\n  indented\n".into()),
+        ]
+    );
+    let config = EspansoSync {
+        path: Some("/tmp/base.txt".into()),
+        ..Default::default()
+    };
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,216 @@
+use super::database::SqliteDB;
+use super::secure_input;
+use crate::config::Config;
+use crate::log_err;
+use anyhow::{bail, Result};
+use enigo::{Enigo, Key, KeyboardControllable};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// 可以单独开启文本扩展的平台，与 std::env::consts::OS 一致
+pub const PLATFORMS: [&str; 3] = ["macos", "windows", "linux"];
+const MIN_TRIGGER_LEN: usize = 2;
+const MAX_TRIGGER_LEN: usize = 32;
+/// 模拟输入前等待触发词的最后一个键松开
+const EXPAND_DELAY: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExpansionTrigger {
+    pub id: u64,
+    pub trigger: String,
+}
+
+/// 最近输入的字符，按下回车、方向键或点击鼠标后清空
+#[derive(Debug, Default)]
+pub struct TypedBuffer {
+    typed: String,
+}
+
+impl TypedBuffer {
+    /// 输入以某个触发词结尾时返回该触发词（有多个时取最长的），并清空已输入的字符
+    pub fn push(&mut self, text: &str, triggers: &[ExpansionTrigger]) -> Option<ExpansionTrigger> {
+        self.typed.push_str(text);
+        let count = self.typed.chars().count();
+        if count > MAX_TRIGGER_LEN {
+            self.typed = self.typed.chars().skip(count - MAX_TRIGGER_LEN).collect();
+        }
+        let found = triggers
+            .iter()
+            .filter(|t| self.typed.ends_with(&t.trigger))
+            .max_by_key(|t| t.trigger.len())?
+            .clone();
+        self.typed.clear();
+        Some(found)
+    }
+
+    pub fn backspace(&mut self) {
+        self.typed.pop();
+    }
+
+    pub fn reset(&mut self) {
+        self.typed.clear();
+    }
+}
+
+#[derive(Default)]
+struct Expansions {
+    buffer: TypedBuffer,
+    triggers: Vec<ExpansionTrigger>,
+}
+
+fn expansions() -> &'static Mutex<Expansions> {
+    static EXPANSIONS: OnceCell<Mutex<Expansions>> = OnceCell::new();
+    EXPANSIONS.get_or_init(|| Mutex::new(Expansions::default()))
+}
+
+/// 正在模拟输入，期间的按键事件是自己产生的，忽略
+static EXPANDING: AtomicBool = AtomicBool::new(false);
+
+pub fn validate_trigger(trigger: &str) -> Result<()> {
+    let len = trigger.chars().count();
+    if !(MIN_TRIGGER_LEN..=MAX_TRIGGER_LEN).contains(&len) {
+        bail!(
+            "the trigger must be between {} and {} characters",
+            MIN_TRIGGER_LEN,
+            MAX_TRIGGER_LEN
+        );
+    }
+    if trigger.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        bail!("the trigger must not contain whitespace");
+    }
+    Ok(())
+}
+
+/// 开启了 enable_text_expansion，且当前平台在 text_expansion_platforms 中
+pub fn is_enabled() -> bool {
+    let config = Config::common().latest();
+    config.enable_text_expansion.unwrap_or(false)
+        && config
+            .text_expansion_platforms
+            .as_ref()
+            .map_or(true, |platforms| {
+                platforms.iter().any(|p| p == std::env::consts::OS)
+            })
+}
+
+/// 重新读取记录的触发词，未开启时清空，按键监听中不再匹配
+pub fn reload() {
+    if !is_enabled() {
+        *expansions().lock() = Expansions::default();
+        return;
+    }
+    // 启动时数据库可能还未就绪，不阻塞调用方
+    thread::spawn(|| match SqliteDB::new().find_expansion_triggers() {
+        Ok(triggers) => {
+            let mut expansions = expansions().lock();
+            expansions.buffer.reset();
+            expansions.triggers = triggers
+                .into_iter()
+                .map(|(id, trigger)| ExpansionTrigger { id, trigger })
+                .collect();
+        }
+        Err(err) => println!("load expansion triggers error: {}", err),
+    });
+}
+
+/// 由全局按键监听调用，输入触发词后在其它线程中替换为记录的内容
+pub fn on_event(event: &rdev::Event) {
+    use rdev::Key::*;
+    if EXPANDING.load(Ordering::SeqCst) {
+        return;
+    }
+    let mut expansions = expansions().lock();
+    if expansions.triggers.is_empty() {
+        return;
+    }
+    let fired = match &event.event_type {
+        rdev::EventType::KeyPress(Backspace) => {
+            expansions.buffer.backspace();
+            None
+        }
+        rdev::EventType::KeyPress(
+            Return | Tab | Escape | LeftArrow | RightArrow | UpArrow | DownArrow | Home | End
+            | PageUp | PageDown,
+        )
+        | rdev::EventType::ButtonPress(_) => {
+            expansions.buffer.reset();
+            None
+        }
+        rdev::EventType::KeyPress(_) => match event.name.as_deref() {
+            // 按住 Ctrl 等组合键时为控制字符
+            Some(name) if name.chars().any(|c| c.is_control()) => {
+                expansions.buffer.reset();
+                None
+            }
+            Some(name) if !name.is_empty() => {
+                let Expansions { buffer, triggers } = &mut *expansions;
+                buffer.push(name, triggers)
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(trigger) = fired {
+        thread::spawn(move || log_err!(expand(&trigger)));
+    }
+}
+
+/// 删除输入的触发词，再模拟输入记录的内容
+fn expand(trigger: &ExpansionTrigger) -> Result<()> {
+    // 密码框中不替换
+    if secure_input::is_secure_input() {
+        return Ok(());
+    }
+    let record = SqliteDB::new().find_by_id(trigger.id)?;
+    if record.is_secure || record.data_type != "text" {
+        return Ok(());
+    }
+    EXPANDING.store(true, Ordering::SeqCst);
+    thread::sleep(EXPAND_DELAY);
+    let mut enigo = Enigo::new();
+    for _ in 0..trigger.trigger.chars().count() {
+        enigo.key_click(Key::Backspace);
+    }
+    enigo.key_sequence(&record.content);
+    // 等模拟的按键事件都被监听收到后再恢复
+    thread::sleep(EXPAND_DELAY);
+    EXPANDING.store(false, Ordering::SeqCst);
+    Ok(())
+}
+
+#[test]
+fn test_typed_buffer() {
+    let triggers = vec![
+        ExpansionTrigger {
+            id: 1,
+            trigger: ";addr".into(),
+        },
+        ExpansionTrigger {
+            id: 2,
+            trigger: "addr".into(),
+        },
+    ];
+    let mut buffer = TypedBuffer::default();
+    for c in ["x", ";", "a", "d", "d"] {
+        assert_eq!(buffer.push(c, &triggers), None);
+    }
+    // 同时匹配时取最长的
+    assert_eq!(buffer.push("r", &triggers).map(|t| t.id), Some(1));
+    // 替换后清空，不会和之前的输入拼接
+    assert_eq!(buffer.push("r", &triggers), None);
+    for c in ["a", "d", "d", "x"] {
+        buffer.push(c, &triggers);
+    }
+    buffer.backspace();
+    assert_eq!(buffer.push("r", &triggers).map(|t| t.id), Some(2));
+    buffer.push(";add", &triggers);
+    buffer.reset();
+    assert_eq!(buffer.push("r", &triggers), None);
+    assert!(validate_trigger(";a").is_ok());
+    assert!(validate_trigger(";").is_err());
+    assert!(validate_trigger("; a").is_err());
+}
@@ -0,0 +1,275 @@
+use super::actions;
+use super::capture_notify;
+use super::clipboard::{ClipboardWatcher, ImageDataDB};
+use super::database::{Record, SqliteDB};
+use super::handle::{Handle, MsgTypeEnum};
+use super::i18n;
+use super::svg;
+use super::thumbnail;
+use crate::config::Config;
+use crate::utils::{dirs, img_util, json_util, string_util};
+use anyhow::{bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// 检查文件是否保存过的间隔
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+/// 编辑器在这段时间内退出时，视为只负责打开文件的启动器，改为按时间结束监听
+const QUICK_EXIT: Duration = Duration::from_secs(2);
+/// 无法得知编辑器何时关闭时，最后一次保存后继续监听的时间
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// 正在编辑的记录，同一条记录只打开一次
+fn editing() -> &'static Mutex<HashSet<u64>> {
+    static EDITING: OnceCell<Mutex<HashSet<u64>>> = OnceCell::new();
+    EDITING.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+fn temp_path(record: &Record) -> Result<PathBuf> {
+    let ext = match record.data_type.as_str() {
+        "image" => "png",
+        "svg" => "svg",
+        _ => "txt",
+    };
+    dirs::temp_file_path(&format!("edit-{}", record.id), ext)
+}
+
+/// 只新建文件，路径已存在（包括符号链接）时失败，Unix 上只有本用户可以读写
+fn write_new(path: &Path, content: &[u8]) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(content)?;
+    Ok(())
+}
+
+/// external_editor 中的命令，{file} 替换为临时文件的路径，没有 {file} 时加在最后
+/// 未设置或图片记录使用系统默认的应用打开
+fn editor_command(editor: Option<&str>, data_type: &str, path: &str) -> Result<Vec<String>> {
+    if let Some(editor) = editor.filter(|e| !e.trim().is_empty() && data_type != "image") {
+        let mut argv = actions::split_command(editor)?;
+        if argv.iter().any(|arg| arg.contains("{file}")) {
+            argv = argv.iter().map(|arg| arg.replace("{file}", path)).collect();
+        } else {
+            argv.push(path.to_string());
+        }
+        return Ok(argv);
+    }
+    let argv: Vec<&str> = match std::env::consts::OS {
+        // -t 使用默认的文本编辑器
+        "macos" if data_type == "image" => vec!["open", path],
+        "macos" => vec!["open", "-t", path],
+        "windows" => vec!["cmd", "/C", "start", "", path],
+        _ => vec!["xdg-open", path],
+    };
+    Ok(argv.into_iter().map(String::from).collect())
+}
+
+/// 写入临时文件并用编辑器打开，保存后写回记录；as_new 为 true 时保存为新记录
+pub fn open(id: u64, as_new: bool) -> Result<()> {
+    let record = SqliteDB::new().find_by_id(id)?;
+    if record.is_secure {
+        bail!("encrypted records cannot be opened in an external editor");
+    }
+    if !["text", "image", "svg"].contains(&record.data_type.as_str()) {
+        bail!("records of type {} cannot be edited", record.data_type);
+    }
+    if SqliteDB::is_read_only() {
+        bail!("the database is read only");
+    }
+    if !editing().lock().insert(id) {
+        bail!("the record is already open in an editor");
+    }
+    let res = start(record, as_new);
+    if res.is_err() {
+        editing().lock().remove(&id);
+    }
+    res
+}
+
+fn start(record: Record, as_new: bool) -> Result<()> {
+    let path = temp_path(&record)?;
+    match record.data_type.as_str() {
+        "image" => {
+            let image: ImageDataDB = json_util::parse(&record.content)?;
+            write_new(&path, &string_util::try_base64_decode(&image.base64)?)?;
+        }
+        _ => write_new(&path, record.content.as_bytes())?,
+    }
+    let editor = { Config::common().latest().external_editor.clone() };
+    let argv = editor_command(
+        editor.as_deref(),
+        &record.data_type,
+        &path.to_string_lossy(),
+    )?;
+    let child = Command::new(&argv[0]).args(&argv[1..]).spawn()?;
+    thread::spawn(move || {
+        // 最后一次保存没有写回时保留临时文件，编辑的内容不会丢失
+        match watch(&record, as_new, &path, child) {
+            true => {
+                let _ = fs::remove_file(&path);
+            }
+            false => capture_notify::notify(i18n::t_args(
+                "notification.edit_kept",
+                &[("path", &path.to_string_lossy())],
+            )),
+        }
+        editing().lock().remove(&record.id);
+    });
+    Ok(())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).ok()?.modified().ok()
+}
+
+/// 每次保存都写回，编辑器退出或长时间没有保存后结束，最后一次保存写回失败时返回 false
+fn watch(record: &Record, as_new: bool, path: &Path, mut child: Child) -> bool {
+    let started = Instant::now();
+    let mut last_saved = Instant::now();
+    let mut last_modified = modified(path);
+    let mut editor_running = true;
+    let mut written = true;
+    // 保存为新记录时，之后的保存更新这条新记录
+    let mut target = match as_new {
+        true => None,
+        false => Some(record.id),
+    };
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        if editor_running {
+            if let Ok(Some(_)) = child.try_wait() {
+                editor_running = false;
+                if started.elapsed() > QUICK_EXIT {
+                    if let Some(saved) = check_saved(record, path, &mut last_modified, &mut target)
+                    {
+                        written = saved;
+                    }
+                    return written;
+                }
+            }
+        }
+        match check_saved(record, path, &mut last_modified, &mut target) {
+            Some(true) => {
+                last_saved = Instant::now();
+                written = true;
+            }
+            Some(false) => written = false,
+            None => {}
+        }
+        if !editor_running && last_saved.elapsed() > IDLE_TIMEOUT {
+            return written;
+        }
+    }
+}
+
+/// 文件保存过时写回，返回是否写回成功，没有保存过或内容没有变化时返回 None
+/// 写回失败时提示用户，之后再次保存会重试
+fn check_saved(
+    record: &Record,
+    path: &Path,
+    last_modified: &mut Option<SystemTime>,
+    target: &mut Option<u64>,
+) -> Option<bool> {
+    let current = modified(path);
+    if current.is_none() || current == *last_modified {
+        return None;
+    }
+    *last_modified = current;
+    match save(record, path, *target) {
+        Ok(Some(id)) => {
+            *target = Some(id);
+            Some(true)
+        }
+        Ok(None) => None,
+        Err(err) => {
+            capture_notify::notify(i18n::t_args(
+                "notification.edit_save_failed",
+                &[("error", &err.to_string())],
+            ));
+            Some(false)
+        }
+    }
+}
+
+/// 把文件内容写回，target 为空时新建记录，返回写入的记录 id，内容没有变化时返回 None
+fn save(record: &Record, path: &Path, target: Option<u64>) -> Result<Option<u64>> {
+    let (content, preview) = match record.data_type.as_str() {
+        "image" => {
+            let base64 = string_util::base64_encode(&fs::read(path)?);
+            let img = img_util::base64_to_rgba8(&base64)?;
+            let old: ImageDataDB = json_util::parse(&record.content).unwrap_or_default();
+            let image = ImageDataDB {
+                width: img.width,
+                height: img.height,
+                scale: old.scale,
+                base64,
+                gif: None,
+            };
+            let preview = ImageDataDB {
+                base64: img_util::rgba8_to_jpeg_base64(&img, 75),
+                ..image.clone()
+            };
+            (
+                json_util::stringfy(&image)?,
+                Some(json_util::stringfy(&preview)?),
+            )
+        }
+        // 和复制时一样重新渲染预览，否则列表中会显示为空
+        "svg" => {
+            let content = fs::read_to_string(path)?;
+            let preview = svg::preview(&content)?;
+            (content, Some(preview))
+        }
+        _ => (fs::read_to_string(path)?, None),
+    };
+    let db = SqliteDB::new();
+    match target {
+        Some(id) => {
+            if db.find_by_id(id)?.content == content {
+                return Ok(None);
+            }
+            db.update_content(id, content, preview)?;
+            // 缩略图按 id 缓存，内容改变后重新生成
+            thumbnail::shred(id)?;
+            Handle::notice_record(MsgTypeEnum::RecordUpdated, id);
+            Ok(Some(id))
+        }
+        // 没有修改就保存时不新建，避免之后的保存改到原记录上
+        None if content == record.content => Ok(None),
+        None => {
+            let res = db.insert_if_not_exist(Record {
+                content,
+                content_preview: preview,
+                data_type: record.data_type.clone(),
+                ..Default::default()
+            })?;
+            ClipboardWatcher::notice_captured(&res);
+            Ok(Some(res.id()))
+        }
+    }
+}
+
+#[test]
+fn test_editor_command() {
+    let argv = editor_command(Some("code --wait"), "text", "/tmp/a b.txt").unwrap();
+    assert_eq!(argv, vec!["code", "--wait", "/tmp/a b.txt"]);
+    let argv = editor_command(Some("emacsclient -c '{file}'"), "text", "/tmp/a.txt").unwrap();
+    assert_eq!(argv, vec!["emacsclient", "-c", "/tmp/a.txt"]);
+    // 图片使用系统默认的应用
+    let argv = editor_command(Some("code --wait"), "image", "/tmp/a.png").unwrap();
+    assert_eq!(argv.last().map(|a| a.as_str()), Some("/tmp/a.png"));
+    assert_ne!(argv[0], "code");
+    assert!(editor_command(Some("vim '"), "text", "/tmp/a.txt").is_err());
+}
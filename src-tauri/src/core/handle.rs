@@ -1,8 +1,18 @@
 use super::{
+    clipboard::{ClipBoardOprator, ClipboardWatcher},
+    database::{InsertResult, SqliteDB},
+    idle, job_queue, paste, pin, position,
+    screenshot::{self, ScreenshotMode},
+    time_machine,
     tray::Tray,
     window_manager::{WindowInfo, WindowType},
+    window_state,
+};
+use crate::{
+    config::Config,
+    log_err,
+    utils::hotkey_util::{self, HotkeyConflict, HotkeyRegisterError},
 };
-use crate::{config::Config, log_err, utils::hotkey_util};
 use anyhow::{bail, Result};
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
@@ -14,13 +24,31 @@ use window_shadows::set_shadow;
 #[derive(Debug, Default, Clone)]
 pub struct Handle {
     pub app_handle: Arc<Mutex<Option<AppHandle>>>,
+    /// 最近一次注册全局快捷键失败的记录
+    pub hotkey_conflicts: Arc<Mutex<Vec<HotkeyConflict>>>,
 }
 
 pub enum MsgTypeEnum {
     ChangeLanguage,
     ChangeRecordLimit,
     ChangeHotKeys,
+    ChangeKeymap,
     ChangeClipBoard,
+    ChangeCapturePaused,
+    ChangeScreenSharing,
+    RecordAdded,
+    RecordUpdated,
+    RecordDeleted,
+    Search,
+    HotkeyConflict,
+    DatabaseReady,
+    ConfigReloaded,
+    ChangeTheme,
+    SyncDevicePaired,
+    UpdateAvailable,
+    TrashPurged,
+    FavoriteSuggested,
+    ChangePinned,
 }
 
 impl Handle {
@@ -29,6 +57,7 @@ impl Handle {
 
         HANDLE.get_or_init(|| Handle {
             app_handle: Arc::new(Mutex::new(None)),
+            hotkey_conflicts: Arc::new(Mutex::new(vec![])),
         })
     }
 
@@ -43,6 +72,13 @@ impl Handle {
             .and_then(|a| a.get_window("main"))
     }
 
+    pub fn get_window_by_label(&self, label: &str) -> Option<Window> {
+        self.app_handle
+            .lock()
+            .as_ref()
+            .and_then(|a| a.get_window(label))
+    }
+
     fn get_manager(&self) -> Result<impl GlobalShortcutManager> {
         let app_handle = self.app_handle.lock();
         if app_handle.is_none() {
@@ -66,7 +102,6 @@ impl Handle {
         Ok(())
     }
 
-    #[allow(unused)]
     pub fn update_systray_select_item() -> Result<()> {
         let app_handle = Self::global().app_handle.lock();
         if app_handle.is_none() {
@@ -106,6 +141,14 @@ impl Handle {
                     };
                 }
             }
+            MsgTypeEnum::ChangeKeymap => {
+                let window = app_handle.as_ref().unwrap().get_window("main");
+                if window.is_some() {
+                    if let Some(win) = window {
+                        win.emit("lanaya://change-keymap", msg)?;
+                    };
+                }
+            }
             MsgTypeEnum::ChangeClipBoard => {
                 let window = app_handle.as_ref().unwrap().get_window("main");
                 if window.is_some() {
@@ -114,63 +157,325 @@ impl Handle {
                     };
                 }
             }
+            MsgTypeEnum::ChangeCapturePaused => {
+                let window = app_handle.as_ref().unwrap().get_window("main");
+                if window.is_some() {
+                    if let Some(win) = window {
+                        win.emit("lanaya://change-capture-paused", msg)?;
+                    };
+                }
+            }
+            MsgTypeEnum::ChangeScreenSharing => {
+                let window = app_handle.as_ref().unwrap().get_window("main");
+                if window.is_some() {
+                    if let Some(win) = window {
+                        win.emit("lanaya://change-screen-sharing", msg)?;
+                    };
+                }
+            }
+            MsgTypeEnum::RecordAdded => {
+                let window = app_handle.as_ref().unwrap().get_window("main");
+                if window.is_some() {
+                    if let Some(win) = window {
+                        win.emit("lanaya://record-added", msg)?;
+                    };
+                }
+            }
+            MsgTypeEnum::RecordUpdated => {
+                let window = app_handle.as_ref().unwrap().get_window("main");
+                if window.is_some() {
+                    if let Some(win) = window {
+                        win.emit("lanaya://record-updated", msg)?;
+                    };
+                }
+            }
+            MsgTypeEnum::RecordDeleted => {
+                let window = app_handle.as_ref().unwrap().get_window("main");
+                if window.is_some() {
+                    if let Some(win) = window {
+                        win.emit("lanaya://record-deleted", msg)?;
+                    };
+                }
+            }
+            MsgTypeEnum::Search => {
+                let window = app_handle.as_ref().unwrap().get_window("main");
+                if window.is_some() {
+                    if let Some(win) = window {
+                        win.emit("lanaya://search", msg)?;
+                    };
+                }
+            }
+            MsgTypeEnum::HotkeyConflict => {
+                // 设置窗口和主窗口都需要提示
+                app_handle
+                    .as_ref()
+                    .unwrap()
+                    .emit_all("lanaya://hotkey-conflict", msg)?;
+            }
+            MsgTypeEnum::DatabaseReady => {
+                app_handle
+                    .as_ref()
+                    .unwrap()
+                    .emit_all("lanaya://database-ready", msg)?;
+            }
+            MsgTypeEnum::ConfigReloaded => {
+                // 设置窗口和主窗口都需要刷新
+                app_handle
+                    .as_ref()
+                    .unwrap()
+                    .emit_all("lanaya://config-reloaded", msg)?;
+            }
+            MsgTypeEnum::ChangeTheme => {
+                app_handle
+                    .as_ref()
+                    .unwrap()
+                    .emit_all("lanaya://change-theme", msg)?;
+            }
+            MsgTypeEnum::SyncDevicePaired => {
+                // 设置窗口中的配对二维码需要关闭
+                app_handle
+                    .as_ref()
+                    .unwrap()
+                    .emit_all("lanaya://sync-device-paired", msg)?;
+            }
+            MsgTypeEnum::UpdateAvailable => {
+                app_handle
+                    .as_ref()
+                    .unwrap()
+                    .emit_all("lanaya://update-available", msg)?;
+            }
+            MsgTypeEnum::TrashPurged => {
+                app_handle
+                    .as_ref()
+                    .unwrap()
+                    .emit_all("lanaya://trash-purged", msg)?;
+            }
+            MsgTypeEnum::FavoriteSuggested => {
+                app_handle
+                    .as_ref()
+                    .unwrap()
+                    .emit_all("lanaya://favorite-suggested", msg)?;
+            }
+            MsgTypeEnum::ChangePinned => {
+                app_handle
+                    .as_ref()
+                    .unwrap()
+                    .emit_all("lanaya://change-pinned", msg)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 把记录变更推送给前端，payload 为列表展示用的记录
+    pub fn notice_record(msg_type: MsgTypeEnum, id: u64) {
+        match SqliteDB::new().find_list_item_by_id(id) {
+            Ok(record) => log_err!(Self::notice_to_window(msg_type, record)),
+            Err(err) => println!("err: {}", err),
+        }
+        log_err!(Self::update_systray());
+    }
+
+    pub fn notice_insert_result(res: &InsertResult) {
+        match res {
+            InsertResult::Inserted(id) => {
+                Self::notice_record(MsgTypeEnum::RecordAdded, *id);
+                job_queue::enqueue_post_processing(*id);
+            }
+            InsertResult::Updated(id) => Self::notice_record(MsgTypeEnum::RecordUpdated, *id),
+        }
+    }
+
+    /// 配置中的全局快捷键是否已注册，返回 (功能, 快捷键, 是否已注册)
+    pub fn hotkey_registrations() -> Result<Vec<(String, String, bool)>> {
+        let hotkeys = { Config::common().latest().hotkeys.clone() };
+        let manager = Self::global().get_manager()?;
+        let mut res = vec![];
+        for hotkey in hotkeys.unwrap_or_default() {
+            let (func, keys) = match hotkey_util::parse_hotkey(&hotkey) {
+                Ok(res) => res,
+                Err(_) => continue,
+            };
+            if keys.is_empty() || Self::hotkey_action(&func).is_none() {
+                continue;
+            }
+            let shortcut = hotkey_util::get_short_cut_name(keys, true);
+            let registered = manager.is_registered(&shortcut).unwrap_or(false);
+            res.push((func, shortcut, registered));
+        }
+        Ok(res)
+    }
+
+    /// 全局快捷键对应的动作
+    /// clear-history 等快捷键只在窗口内生效，由前端处理
+    pub fn hotkey_action(func: &str) -> Option<fn()> {
+        let action: fn() = match func {
+            "global-shortcut" => || Self::open_window(WindowType::Main),
+            "quick-search" => || Self::open_window(WindowType::Palette),
+            "paste-plain-text" => || log_err!(paste::paste_plain_text()),
+            "toggle-monitoring" => || {
+                ClipboardWatcher::toggle_monitoring();
+            },
+            "clear-clipboard" => || log_err!(ClipBoardOprator::clear()),
+            "favorite-latest" => || log_err!(Self::favorite_latest()),
+            "restore-clipboard" => || log_err!(time_machine::restore_previous()),
+            "screenshot-region" => || screenshot::capture_in_background(ScreenshotMode::Region),
+            "screenshot-window" => || screenshot::capture_in_background(ScreenshotMode::Window),
+            "toggle-pin" => || {
+                log_err!(pin::toggle());
+            },
+            "open-snippets" => || {
+                Self::show_window(WindowType::Main);
+                log_err!(Self::notice_to_window(MsgTypeEnum::Search, "f:"));
+            },
+            _ => return None,
+        };
+        Some(action)
+    }
+
+    /// 收藏最近的一条记录
+    fn favorite_latest() -> Result<()> {
+        let db = SqliteDB::new();
+        if let Some(id) = db.find_latest_id()? {
+            db.set_favorite(&[id], true)?;
+            Self::notice_record(MsgTypeEnum::RecordUpdated, id);
         }
         Ok(())
     }
 
     pub fn refresh_global_shortcut() -> Result<()> {
-        let hotkeys_new = Config::common().latest().hotkeys.clone();
-        if let Some(hotkeys) = hotkeys_new {
-            // hotkeys 中找到 global-shortcut 开头的第一个
-            let mut global_shortcut = None;
-            for hotkey in hotkeys {
-                if hotkey.starts_with("global-shortcut") {
-                    global_shortcut = Some(hotkey);
-                    break;
-                }
-            }
-            // 目前只有一个 global-shortcut，此处可以改为循环
-            if let Some(global_shortcut) = global_shortcut {
-                let mut shortcut_manager = Self::global().get_manager()?;
-                let _ = shortcut_manager.unregister_all();
-                let hot_key_arr: Vec<&str> = global_shortcut.split(':').collect();
-                if hot_key_arr[1].is_empty() {
-                    // 如果没有配置 global-shortcut，则等于清空快捷键，直接返回
-                    return Ok(());
-                }
-                let hot_key_arr: Vec<&str> = hot_key_arr[1].split('+').collect();
-                let hot_key_arr: Vec<u32> = hot_key_arr
-                    .iter()
-                    .map(|x| x.parse::<u32>().unwrap())
-                    .collect();
-                let short_cut_name = hotkey_util::get_short_cut_name(hot_key_arr, true);
-                let _ = shortcut_manager.register(short_cut_name.as_str(), || {
-                    Self::open_window(WindowType::Main)
+        let hotkeys = Config::common().latest().hotkeys.clone();
+        let hotkeys = match hotkeys {
+            Some(hotkeys) => hotkeys,
+            None => return Ok(()),
+        };
+        let mut shortcut_manager = Self::global().get_manager()?;
+        let _ = shortcut_manager.unregister_all();
+        let mut failed: Vec<(String, Vec<u32>, String)> = vec![];
+        for hotkey in hotkeys {
+            let (func, hot_key_arr) = match hotkey_util::parse_hotkey(&hotkey) {
+                Ok(res) => res,
+                Err(err) => {
+                    println!("err: {}", err);
+                    continue;
+                }
+            };
+            // 没有配置按键的等于清空该快捷键
+            if hot_key_arr.is_empty() {
+                continue;
+            }
+            let action = match Self::hotkey_action(&func) {
+                Some(action) => action,
+                None => continue,
+            };
+            let short_cut_name = hotkey_util::get_short_cut_name(hot_key_arr.clone(), true);
+            if let Err(err) = shortcut_manager.register(short_cut_name.as_str(), action) {
+                failed.push((func, hot_key_arr, err.to_string()));
+            }
+        }
+        // 快速粘贴：修饰键 + 1~9
+        let modifiers = Config::common().latest().quick_paste_modifiers.clone();
+        let modifiers = modifiers.unwrap_or_default();
+        if !modifiers.is_empty() {
+            for n in 1..=9usize {
+                let mut keys = modifiers.clone();
+                keys.push(48 + n as u32);
+                let short_cut_name = hotkey_util::get_short_cut_name(keys.clone(), true);
+                let res = shortcut_manager.register(short_cut_name.as_str(), move || {
+                    log_err!(paste::paste_nth(n));
                 });
+                if let Err(err) = res {
+                    failed.push((format!("quick-paste-{}", n), keys, err.to_string()));
+                }
             }
         }
-        Ok(())
+        // 被系统或其它应用占用的快捷键，试着找几个还能注册的组合
+        let conflicts: Vec<HotkeyConflict> = failed
+            .into_iter()
+            .map(|(func, keys, reason)| {
+                let suggestions = hotkey_util::alternative_combos(&keys)
+                    .into_iter()
+                    .filter(|combo| {
+                        let name = hotkey_util::get_short_cut_name(combo.clone(), true);
+                        if shortcut_manager.is_registered(&name).unwrap_or(true) {
+                            return false;
+                        }
+                        let free = shortcut_manager.register(&name, || {}).is_ok();
+                        if free {
+                            let _ = shortcut_manager.unregister(&name);
+                        }
+                        free
+                    })
+                    .take(3)
+                    .collect();
+                HotkeyConflict {
+                    func,
+                    shortcut: hotkey_util::get_short_cut_name(keys.clone(), true),
+                    keys,
+                    reason,
+                    suggestions,
+                }
+            })
+            .collect();
+        *Self::global().hotkey_conflicts.lock() = conflicts.clone();
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+        log_err!(Self::notice_to_window(
+            MsgTypeEnum::HotkeyConflict,
+            conflicts.clone()
+        ));
+        Err(HotkeyRegisterError(conflicts).into())
     }
 
+    /// 打开窗口，已经显示时关闭
     pub fn open_window(window_type: WindowType) {
+        Self::create_or_show_window(window_type, true);
+    }
+
+    /// 显示窗口，已经显示时只聚焦
+    pub fn show_window(window_type: WindowType) {
+        Self::create_or_show_window(window_type, false);
+    }
+
+    fn create_or_show_window(window_type: WindowType, toggle: bool) {
         let binding = Self::global().app_handle.lock();
         let app_handle = binding.as_ref().unwrap();
 
         let window_info = match window_type {
             WindowType::Config => WindowInfo::config(),
             WindowType::Main => WindowInfo::main(),
+            WindowType::Preview => WindowInfo::preview(),
+            WindowType::Palette => WindowInfo::palette(),
         };
 
+        idle::mark_active();
         let label = window_info.label.as_str();
         let title = window_info.title.as_str();
         let url = window_info.url.as_str();
 
+        // 窗口已显示时前台应用是自己
+        if let WindowType::Main | WindowType::Palette = window_type {
+            let visible = app_handle
+                .get_window(label)
+                .map_or(false, |w| w.is_visible().unwrap_or(false));
+            if !visible {
+                paste::remember_target();
+            }
+        }
+
         if let Some(window) = app_handle.get_window(label) {
-            if window.is_visible().unwrap() {
+            if toggle && window.is_visible().unwrap() {
                 let _ = window.close();
                 return;
             }
             let _ = window.unminimize();
+            if let WindowType::Main = window_type {
+                log_err!(position::place_main_window(
+                    &window,
+                    window_info.width,
+                    window_info.height
+                ));
+            }
             let _ = window.show();
             let _ = window.set_focus();
             return;
@@ -186,7 +491,10 @@ impl Handle {
         .visible(false)
         .resizable(window_info.resizable)
         .fullscreen(window_info.fullscreenable)
-        .always_on_top(window_info.always_on_top)
+        .always_on_top(
+            window_info.always_on_top
+                || (matches!(window_type, WindowType::Main) && pin::is_pinned()),
+        )
         .inner_size(window_info.width, window_info.height)
         .transparent(window_info.transparent)
         .decorations(window_info.decorations)
@@ -195,6 +503,14 @@ impl Handle {
         .build();
         match new_window {
             Ok(window) => {
+                if let WindowType::Main = window_type {
+                    window_state::track(&window);
+                    log_err!(position::place_main_window(
+                        &window,
+                        window_info.width,
+                        window_info.height
+                    ));
+                }
                 let _ = window.show();
                 let _ = window.set_focus();
                 if let WindowType::Main = window_type {
@@ -0,0 +1,216 @@
+use super::clipboard::ClipBoardOprator;
+use super::database::PageReq;
+use super::store::{self, ClipboardStore};
+use super::vault::Vault;
+use crate::config::Config;
+use crate::utils::{crypto_util, json_util, string_util};
+use anyhow::{anyhow, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+fn server() -> &'static Mutex<Option<Arc<Server>>> {
+    static SERVER: OnceCell<Mutex<Option<Arc<Server>>>> = OnceCell::new();
+    SERVER.get_or_init(|| Mutex::new(None))
+}
+
+/// 供其它工具、浏览器扩展调用的本地接口，只监听 127.0.0.1，需带令牌访问
+/// 本地 socket / 命名管道（ipc.rs）也复用这些操作
+/// GET  /api/records?q=&limit=&cursor=&favorite=  搜索历史记录
+/// GET  /api/records/{id}                         获取记录完整内容
+/// POST /api/records/{id}/copy                    复制记录到剪切板
+#[derive(Debug, PartialEq)]
+pub enum Route {
+    Search(PageReq),
+    Get(u64),
+    Copy(u64),
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+/// 没有令牌时生成一个并写入配置文件，需在配置初始化后调用
+pub fn init_token() -> Result<()> {
+    let empty = Config::common()
+        .data()
+        .http_api_token
+        .as_ref()
+        .map_or(true, |t| t.is_empty());
+    if !empty {
+        return Ok(());
+    }
+    let token = string_util::to_hex(&crypto_util::random_bytes(24));
+    Config::common().data().http_api_token = Some(token);
+    Config::common().data().save_file()
+}
+
+/// 按最新配置启动或关闭服务，端口、令牌变化时重启
+pub fn refresh() -> Result<()> {
+    if let Some(old) = server().lock().take() {
+        old.unblock();
+    }
+    let (enable, port) = {
+        let config = Config::common().latest();
+        (
+            config.enable_http_api.unwrap_or(false),
+            config.http_api_port.unwrap_or(18090),
+        )
+    };
+    if !enable {
+        return Ok(());
+    }
+    let srv = Server::http(("127.0.0.1", port))
+        .map_err(|err| anyhow!("failed to start http api on port {}: {}", port, err))?;
+    let srv = Arc::new(srv);
+    *server().lock() = Some(srv.clone());
+    println!("http api listening on 127.0.0.1:{}", port);
+    thread::spawn(move || {
+        // unblock 后 incoming_requests 结束，线程退出
+        for req in srv.incoming_requests() {
+            handle_request(req);
+        }
+    });
+    Ok(())
+}
+
+fn handle_request(req: Request) {
+    // 浏览器扩展跨域访问前的预检请求
+    if *req.method() == Method::Options {
+        let _ = req.respond(with_cors(Response::from_string("").with_status_code(204)));
+        return;
+    }
+    let (status, body) = if !is_authorized(&req) {
+        (401, error_body("invalid or missing token"))
+    } else {
+        match route(req.method(), req.url()) {
+            Some(route) => match execute(route) {
+                Ok(body) => (200, body.to_string()),
+                Err(err) => (400, error_body(&err.to_string())),
+            },
+            None => (404, error_body("not found")),
+        }
+    };
+    let content_type = Header::from_bytes(
+        &b"Content-Type"[..],
+        &b"application/json; charset=utf-8"[..],
+    )
+    .unwrap();
+    let res = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(content_type);
+    if let Err(err) = req.respond(with_cors(res)) {
+        println!("http api respond error: {}", err);
+    }
+}
+
+fn with_cors<R: std::io::Read>(res: Response<R>) -> Response<R> {
+    res.with_header(Header::from_bytes(&b"Access-Control-Allow-Origin"[..], &b"*"[..]).unwrap())
+        .with_header(
+            Header::from_bytes(
+                &b"Access-Control-Allow-Headers"[..],
+                &b"Authorization, Content-Type"[..],
+            )
+            .unwrap(),
+        )
+        .with_header(
+            Header::from_bytes(&b"Access-Control-Allow-Methods"[..], &b"GET, POST"[..]).unwrap(),
+        )
+}
+
+fn error_body(msg: &str) -> String {
+    json_util::stringfy(&ErrorBody {
+        error: msg.to_string(),
+    })
+    .unwrap_or_default()
+}
+
+/// Authorization: Bearer <token>
+fn is_authorized(req: &Request) -> bool {
+    let token = match Config::common().latest().http_api_token.clone() {
+        Some(token) if !token.is_empty() => token,
+        _ => return false,
+    };
+    req.headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .and_then(|h| h.value.as_str().strip_prefix("Bearer "))
+        .map_or(false, |given| constant_time_eq(given.trim(), &token))
+}
+
+/// 逐字节比较不提前返回，避免通过响应时间猜出令牌
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.bytes()
+        .zip(b.bytes())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+fn route(method: &Method, url: &str) -> Option<Route> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match (method, segments.as_slice()) {
+        (Method::Get, ["api", "records"]) => {
+            let mut req = PageReq::default();
+            for (k, v) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+                let v = string_util::url_decode(v);
+                match k {
+                    "q" => req.key = Some(v),
+                    "limit" => req.page_size = v.parse().ok(),
+                    "cursor" => req.cursor = Some(v),
+                    "favorite" => req.is_favorite = v.parse().ok(),
+                    _ => {}
+                }
+            }
+            Some(Route::Search(req))
+        }
+        (Method::Get, ["api", "records", id]) => id.parse().ok().map(Route::Get),
+        (Method::Post, ["api", "records", id, "copy"]) => id.parse().ok().map(Route::Copy),
+        _ => None,
+    }
+}
+
+pub fn execute(route: Route) -> Result<Value> {
+    match route {
+        Route::Search(req) => Ok(serde_json::to_value(store::open().query(req)?)?),
+        Route::Get(id) => {
+            let mut record = store::open().get(id)?;
+            // 保险箱未解锁时返回错误，不会泄露内容
+            record.content = Vault::global().reveal(&record)?;
+            Ok(serde_json::to_value(record)?)
+        }
+        Route::Copy(id) => {
+            ClipBoardOprator::copy_by_id(id, None)?;
+            Ok(Value::Object(Default::default()))
+        }
+    }
+}
+
+#[test]
+fn test_route() {
+    assert_eq!(route(&Method::Get, "/api/records/12"), Some(Route::Get(12)));
+    assert_eq!(
+        route(&Method::Post, "/api/records/12/copy"),
+        Some(Route::Copy(12))
+    );
+    assert_eq!(route(&Method::Get, "/api/records/12/copy"), None);
+    assert_eq!(route(&Method::Get, "/api/records/abc"), None);
+    match route(&Method::Get, "/api/records?q=hello%20world&limit=5") {
+        Some(Route::Search(req)) => {
+            assert_eq!(req.key, Some("hello world".to_string()));
+            assert_eq!(req.page_size, Some(5));
+        }
+        other => panic!("unexpected route {:?}", other),
+    }
+    assert!(constant_time_eq("abc", "abc"));
+    assert!(!constant_time_eq("abc", "abd"));
+    assert!(!constant_time_eq("abc", "abcd"));
+}
@@ -0,0 +1,191 @@
+use crate::config::Config;
+use crate::utils::{dirs, json_util};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fs;
+
+/// 缺少翻译时使用的语言
+pub const FALLBACK_LANGUAGE: &str = "en";
+/// 内置语言包，locales 目录下的同名文件可以覆盖其中的条目
+const BUILTIN: [(&str, &str); 2] = [
+    ("en", include_str!("../../locales/en.json")),
+    ("zh", include_str!("../../locales/zh.json")),
+];
+
+type Messages = HashMap<String, String>;
+
+fn cache() -> &'static Mutex<HashMap<String, Messages>> {
+    static CACHE: OnceCell<Mutex<HashMap<String, Messages>>> = OnceCell::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn load(language: &str) -> Messages {
+    let mut messages: Messages = BUILTIN
+        .iter()
+        .find(|(code, _)| *code == language)
+        .and_then(|(_, json)| json_util::parse(json).ok())
+        .unwrap_or_default();
+    let custom = dirs::locales_dir().map(|dir| dir.join(format!("{}.json", language)));
+    if let Ok(path) = custom {
+        if path.exists() {
+            match json_util::read::<Messages>(&path) {
+                Ok(custom) => messages.extend(custom),
+                Err(err) => println!("load locale {} error: {}", path.display(), err),
+            }
+        }
+    }
+    messages
+}
+
+/// 语言包文件修改后调用
+pub fn reload() {
+    cache().lock().clear();
+}
+
+/// 内置语言和 locales 目录下的 <language>.json
+pub fn available_languages() -> Vec<String> {
+    let mut languages: Vec<String> = BUILTIN.iter().map(|(code, _)| code.to_string()).collect();
+    if let Ok(entries) = dirs::locales_dir().and_then(|dir| Ok(fs::read_dir(dir)?)) {
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            if let Some(code) = path.file_stem().and_then(|s| s.to_str()) {
+                if !languages.iter().any(|l| l == code) {
+                    languages.push(code.to_string());
+                }
+            }
+        }
+    }
+    languages
+}
+
+/// 配置中的语言，未设置时跟随系统
+pub fn current_language() -> String {
+    let language = { Config::common().latest().language.clone() };
+    language
+        .or_else(system_language)
+        .unwrap_or(FALLBACK_LANGUAGE.into())
+}
+
+/// 按当前语言翻译
+pub fn t(key: &str) -> String {
+    tr(&current_language(), key)
+}
+
+/// 带参数的翻译，文案中的 {name} 替换为对应的值
+pub fn t_args(key: &str, args: &[(&str, &str)]) -> String {
+    format(&t(key), args)
+}
+
+/// 先查指定语言，再查 FALLBACK_LANGUAGE，都没有时返回 key
+pub fn tr(language: &str, key: &str) -> String {
+    let mut cache = cache().lock();
+    for language in [language, FALLBACK_LANGUAGE] {
+        let messages = cache
+            .entry(language.to_string())
+            .or_insert_with(|| load(language));
+        if let Some(message) = messages.get(key) {
+            return message.clone();
+        }
+    }
+    key.to_string()
+}
+
+fn format(template: &str, args: &[(&str, &str)]) -> String {
+    args.iter()
+        .fold(template.to_string(), |res, (name, value)| {
+            res.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+/// zh_CN.UTF-8、zh-Hans-CN 等取语言部分
+fn normalize_locale(locale: &str) -> Option<String> {
+    let code: String = locale
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_lowercase();
+    match code.as_str() {
+        "" | "c" | "posix" => None,
+        _ => Some(code),
+    }
+}
+
+/// 系统语言，没有对应的语言包时返回 None
+pub fn system_language() -> Option<String> {
+    let code = normalize_locale(&platform::system_locale()?)?;
+    available_languages()
+        .into_iter()
+        .find(|language| *language == code)
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    const LOCALE_NAME_MAX_LENGTH: usize = 85;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetUserDefaultLocaleName(name: *mut u16, len: i32) -> i32;
+    }
+
+    pub fn system_locale() -> Option<String> {
+        let mut buf = [0u16; LOCALE_NAME_MAX_LENGTH];
+        let len = unsafe { GetUserDefaultLocaleName(buf.as_mut_ptr(), buf.len() as i32) };
+        if len <= 1 {
+            return None;
+        }
+        String::from_utf16(&buf[..len as usize - 1]).ok()
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod platform {
+    fn from_env() -> Option<String> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+    }
+
+    /// 从 Finder 启动时通常没有 LANG，读取系统偏好设置
+    #[cfg(target_os = "macos")]
+    pub fn system_locale() -> Option<String> {
+        from_env().or_else(|| {
+            let output = std::process::Command::new("defaults")
+                .args(["read", "-g", "AppleLocale"])
+                .output()
+                .ok()?;
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    pub fn system_locale() -> Option<String> {
+        from_env()
+    }
+}
+
+#[test]
+fn test_i18n() {
+    assert_eq!(normalize_locale("zh_CN.UTF-8"), Some("zh".into()));
+    assert_eq!(normalize_locale("zh-Hans-CN"), Some("zh".into()));
+    assert_eq!(normalize_locale("C"), None);
+    assert_eq!(
+        format("Version {version}", &[("version", "1.0.0")]),
+        "Version 1.0.0"
+    );
+    // 内置语言包的条目需要和英文一致
+    let keys = |json: &str| {
+        let mut keys: Vec<String> = serde_json::from_str::<Messages>(json)
+            .unwrap()
+            .into_keys()
+            .collect();
+        keys.sort();
+        keys
+    };
+    for (code, json) in BUILTIN {
+        assert_eq!(keys(json), keys(BUILTIN[0].1), "{}", code);
+    }
+}
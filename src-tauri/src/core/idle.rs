@@ -0,0 +1,99 @@
+use super::database::SqliteDB;
+use super::handle::Handle;
+use super::{i18n, plugin, record_cache, script};
+use crate::config::Config;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::Manager;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 已释放过，再次打开窗口前不重复释放
+static RELEASED: AtomicBool = AtomicBool::new(false);
+
+/// 最后一次有窗口显示的时间，窗口隐藏或关闭后不再更新
+fn last_active() -> &'static Mutex<Instant> {
+    static LAST_ACTIVE: OnceCell<Mutex<Instant>> = OnceCell::new();
+    LAST_ACTIVE.get_or_init(|| Mutex::new(Instant::now()))
+}
+
+/// 打开窗口时调用，释放过的资源在用到时重新加载
+pub fn mark_active() {
+    *last_active().lock() = Instant::now();
+    RELEASED.store(false, Ordering::SeqCst);
+}
+
+/// 已释放且还没有再打开窗口，长期持有的连接用完后不再保留
+pub fn is_released() -> bool {
+    RELEASED.load(Ordering::SeqCst)
+}
+
+/// minutes 为 0 时不释放
+fn should_release(idle: Duration, minutes: u64, any_visible: bool) -> bool {
+    minutes > 0 && !any_visible && idle >= Duration::from_secs(minutes * 60)
+}
+
+/// 窗口关闭一段时间后释放缓存，常驻托盘时只占用很少的内存
+pub fn start() {
+    thread::spawn(|| loop {
+        thread::sleep(CHECK_INTERVAL);
+        if RELEASED.load(Ordering::SeqCst) {
+            continue;
+        }
+        // 从窗口最后一次可见时开始计时，而不是从打开时
+        let visible = any_visible();
+        if visible {
+            *last_active().lock() = Instant::now();
+        }
+        let minutes = { Config::common().latest().idle_release_minutes.unwrap_or(10) };
+        let idle = last_active().lock().elapsed();
+        if should_release(idle, minutes, visible) {
+            release();
+        }
+    });
+}
+
+fn any_visible() -> bool {
+    let binding = Handle::global().app_handle.lock();
+    binding.as_ref().map_or(false, |app_handle| {
+        app_handle
+            .windows()
+            .values()
+            .any(|w| w.is_visible().unwrap_or(false))
+    })
+}
+
+/// 清空记录、语言包、脚本的缓存和已编译的插件，关闭隐藏的窗口，连同其中加载的缩略图一起释放
+/// 剪切板监听和后台任务长期持有的连接也关闭，有新内容或新任务时再打开
+pub fn release() {
+    RELEASED.store(true, Ordering::SeqCst);
+    record_cache::release();
+    i18n::reload();
+    script::clear_cache();
+    plugin::release();
+    SqliteDB::release_long_lived();
+    let windows = {
+        let binding = Handle::global().app_handle.lock();
+        binding
+            .as_ref()
+            .map(|app_handle| app_handle.windows())
+            .unwrap_or_default()
+    };
+    for window in windows.values() {
+        if !window.is_visible().unwrap_or(true) {
+            let _ = window.close();
+        }
+    }
+}
+
+#[test]
+fn test_should_release() {
+    let idle = Duration::from_secs(11 * 60);
+    assert!(should_release(idle, 10, false));
+    assert!(!should_release(idle, 10, true));
+    assert!(!should_release(idle, 0, false));
+    assert!(!should_release(Duration::from_secs(60), 10, false));
+}
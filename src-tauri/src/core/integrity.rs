@@ -0,0 +1,153 @@
+use super::database::SqliteDB;
+use super::handle::{Handle, MsgTypeEnum};
+use super::{record_cache, thumbnail};
+use crate::log_err;
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 等待剪切板监听和后台任务关闭连接的时间
+const SWAP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrphanCount {
+    pub table: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IntegrityReport {
+    /// PRAGMA integrity_check 发现的问题，最多 100 条
+    pub errors: Vec<String>,
+    /// 对应的记录已不存在的原始剪切板数据、关联等
+    pub orphans: Vec<OrphanCount>,
+    /// 没有对应记录的缩略图缓存
+    pub orphan_thumbnails: usize,
+    /// 压缩的内容无法解压的记录
+    pub unreadable_records: Vec<u64>,
+}
+
+impl IntegrityReport {
+    pub fn is_healthy(&self) -> bool {
+        self.errors.is_empty()
+            && self.orphans.is_empty()
+            && self.orphan_thumbnails == 0
+            && self.unreadable_records.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SalvageResult {
+    /// 复制到新数据库的行数
+    pub rows: usize,
+    /// 无法读取而跳过的行数
+    pub skipped: usize,
+    /// 损坏的数据库改名后保留在这里
+    pub corrupt_copy: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairResult {
+    pub removed_orphans: usize,
+    pub removed_thumbnails: usize,
+    pub reindexed: bool,
+    /// 重建索引后仍有问题时才会复制到新数据库
+    pub salvaged: Option<SalvageResult>,
+    /// 修复后重新检查的结果
+    pub report: IntegrityReport,
+}
+
+pub fn check() -> Result<IntegrityReport> {
+    let db = SqliteDB::new();
+    Ok(IntegrityReport {
+        errors: db.integrity_check()?,
+        orphans: db
+            .count_orphans()?
+            .into_iter()
+            .map(|(table, count)| OrphanCount { table, count })
+            .collect(),
+        orphan_thumbnails: thumbnail::find_orphans(&db)?.len(),
+        unreadable_records: db.find_unreadable_records()?,
+    })
+}
+
+/// 先重建索引，仍有问题时把能读取的行复制到新数据库并替换，最后清理孤立的数据
+pub fn repair() -> Result<RepairResult> {
+    let db = SqliteDB::new();
+    let mut reindexed = false;
+    let mut salvaged = None;
+    if !db.integrity_check()?.is_empty() {
+        // 索引损坏时 REINDEX 即可修复，表本身损坏时会失败
+        reindexed = db.reindex().is_ok();
+        if !reindexed || !db.integrity_check()?.is_empty() {
+            salvaged = Some(salvage(db)?);
+        }
+    }
+    let db = SqliteDB::new();
+    let removed_orphans = db.delete_orphans()?;
+    let removed_thumbnails = thumbnail::clean_orphans(&db)?;
+    record_cache::invalidate();
+    log_err!(Handle::notice_to_window(MsgTypeEnum::ChangeClipBoard, "ok"));
+    Ok(RepairResult {
+        removed_orphans,
+        removed_thumbnails,
+        reindexed,
+        salvaged,
+        report: check()?,
+    })
+}
+
+/// 在数据库文件名后加上后缀，-journal 等临时文件也一起改名
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// 损坏的数据库改名保留，新数据库放到原来的位置，之后打开的连接使用新数据库
+/// 剪切板监听和后台任务的连接先关闭，换好后再重新打开
+fn salvage(db: SqliteDB) -> Result<SalvageResult> {
+    let path = SqliteDB::path()?;
+    let fresh = with_suffix(&path, ".salvage");
+    if fresh.exists() {
+        fs::remove_file(&fresh)?;
+    }
+    let (rows, skipped) = db.salvage_to(&fresh)?;
+    // 改名前关闭连接，Windows 上无法改名打开中的文件
+    drop(db);
+    let now = chrono::Local::now().format("%Y%m%d%H%M%S");
+    let corrupt = with_suffix(&path, &format!(".corrupt-{}", now));
+    SqliteDB::with_long_lived_closed(SWAP_TIMEOUT, || {
+        fs::rename(&path, &corrupt)?;
+        let journal = with_suffix(&path, "-journal");
+        if journal.exists() {
+            log_err!(fs::rename(&journal, with_suffix(&corrupt, "-journal")));
+        }
+        fs::rename(&fresh, &path)?;
+        Ok(())
+    })?;
+    Ok(SalvageResult {
+        rows,
+        skipped,
+        corrupt_copy: corrupt.to_string_lossy().to_string(),
+    })
+}
+
+#[test]
+fn test_integrity_report() {
+    use super::database::SQLITE_FILE;
+    let mut report = IntegrityReport::default();
+    assert!(report.is_healthy());
+    report.orphans.push(OrphanCount {
+        table: "pasteboard_type".into(),
+        count: 2,
+    });
+    assert!(!report.is_healthy());
+    let path = Path::new("data").join(SQLITE_FILE);
+    assert_eq!(
+        with_suffix(&path, "-journal"),
+        Path::new("data").join(format!("{}-journal", SQLITE_FILE))
+    );
+}
@@ -0,0 +1,589 @@
+use super::cli;
+use super::database::{PageReq, SqliteDB};
+use super::handle::{Handle, MsgTypeEnum};
+use super::http_api::{self, constant_time_eq, Route};
+use super::native_messaging::{self, BrowserCapture};
+use super::osc52;
+use crate::config::Config;
+use crate::log_err;
+use crate::utils::{crypto_util, dirs, string_util};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+/// 单条消息最大长度，防止恶意长度导致一次分配过多内存
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+/// 连接令牌，每次启动重新生成，只有当前用户可以读取
+const TOKEN_FILE: &str = "ipc.token";
+/// 连接转发过来的 socket 时令牌的环境变量
+pub const TOKEN_ENV: &str = "LANAYA_IPC_TOKEN";
+
+/// 本地进程间通信，比 HTTP 接口延迟更低，开启 enable_ipc 后才监听
+/// 每条消息为 4 字节大端长度 + JSON，一个连接内可以连续发送多条请求
+/// 第一条必须是 {"op": "auth", "token": "<ipc.token 的内容>"}，否则关闭连接
+/// 请求：{"op": "search", "q": "...", "limit": 20} / {"op": "get", "id": 1} / {"op": "copy", "id": 1}
+///       {"op": "capture", "text": "...", "html": "...", "url": "...", "browser": "..."}
+///       {"op": "osc52", "data": "\u001b]52;c;<base64>\u0007"} / {"op": "add", "text": "..."}
+/// 响应：{"ok": true, "data": ...} / {"ok": false, "error": "..."}
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum IpcRequest {
+    Auth {
+        token: String,
+    },
+    Search {
+        q: Option<String>,
+        limit: Option<usize>,
+        cursor: Option<String>,
+        favorite: Option<bool>,
+    },
+    Get {
+        id: u64,
+    },
+    Copy {
+        id: u64,
+    },
+    /// 浏览器扩展通过 native messaging host 转发
+    Capture(BrowserCapture),
+    /// 远程终端、tmux 通过 SSH 转发的 OSC 52 转义序列
+    Osc52 {
+        data: String,
+    },
+    /// lanaya add，由运行中的实例写入数据库
+    Add {
+        text: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct IpcResponse {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// 和 HTTP 接口相同的操作交给 http_api 执行
+fn dispatch(req: IpcRequest) -> Result<Value> {
+    let route = match req {
+        IpcRequest::Auth { .. } => return Ok(Value::Null),
+        IpcRequest::Search {
+            q,
+            limit,
+            cursor,
+            favorite,
+        } => Route::Search(PageReq {
+            key: q,
+            page_size: limit,
+            cursor,
+            is_favorite: favorite,
+            ..PageReq::default()
+        }),
+        IpcRequest::Get { id } => Route::Get(id),
+        IpcRequest::Copy { id } => Route::Copy(id),
+        IpcRequest::Capture(capture) => return native_messaging::capture(capture),
+        IpcRequest::Osc52 { data } => return osc52::capture(&data),
+        IpcRequest::Add { text } => {
+            let id = cli::add_text(&SqliteDB::new(), text)?;
+            log_err!(Handle::update_systray());
+            log_err!(Handle::notice_to_window(MsgTypeEnum::ChangeClipBoard, "ok"));
+            return Ok(json!({ "id": id }));
+        }
+    };
+    http_api::execute(route)
+}
+
+/// 读取一条消息，连接正常关闭时返回 None
+pub fn read_frame<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut len_buf = [0u8; 4];
+    match reader.read_exact(&mut len_buf) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    let len = u32::from_be_bytes(len_buf) as usize;
+    if len > MAX_FRAME_LEN {
+        bail!("ipc frame too large: {} bytes", len);
+    }
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf)?;
+    Ok(Some(buf))
+}
+
+pub fn write_frame<W: Write>(writer: &mut W, data: &[u8]) -> Result<()> {
+    if data.len() > MAX_FRAME_LEN {
+        bail!("ipc frame too large: {} bytes", data.len());
+    }
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(data)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// 客户端发送一条请求并等待响应，失败时返回对方的错误信息
+pub fn request<S: Read + Write>(stream: &mut S, req: &Value) -> Result<Value> {
+    write_frame(stream, req.to_string().as_bytes())?;
+    let mut res: Value = match read_frame(stream)? {
+        Some(frame) => serde_json::from_slice(&frame)?,
+        // 作为 io 错误返回，调用方可以据此重连
+        None => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Lanaya closed the connection",
+            )
+            .into())
+        }
+    };
+    if res["ok"] != true {
+        bail!("{}", res["error"].as_str().unwrap_or("request failed"));
+    }
+    Ok(res["data"].take())
+}
+
+fn token_path() -> Result<PathBuf> {
+    Ok(dirs::app_home_dir()?.join(TOKEN_FILE))
+}
+
+/// 本机运行中的实例的令牌
+pub fn local_token() -> Result<String> {
+    match fs::read_to_string(token_path()?) {
+        Ok(token) => Ok(token.trim().to_string()),
+        Err(_) => bail!("Lanaya is not running or ipc is disabled"),
+    }
+}
+
+/// 连接运行中的实例并出示令牌
+pub fn connect() -> Result<Stream> {
+    let mut stream = platform::open()?;
+    authenticate(&mut stream, &local_token()?)?;
+    Ok(stream)
+}
+
+/// 连接指定的 socket，如 ssh -R 转发过来的，token 为对方实例的 ipc.token
+pub fn connect_at(path: &Path, token: &str) -> Result<Stream> {
+    let mut stream = platform::open_at(path)?;
+    authenticate(&mut stream, token)?;
+    Ok(stream)
+}
+
+fn authenticate<S: Read + Write>(stream: &mut S, token: &str) -> Result<()> {
+    request(stream, &json!({ "op": "auth", "token": token }))?;
+    Ok(())
+}
+
+/// 第一条消息是否为正确的令牌
+fn is_authorized(frame: &[u8], token: &str) -> bool {
+    match serde_json::from_slice::<IpcRequest>(frame) {
+        Ok(IpcRequest::Auth { token: given }) => constant_time_eq(&given, token),
+        _ => false,
+    }
+}
+
+fn error_response(error: &str) -> IpcResponse {
+    IpcResponse {
+        ok: false,
+        data: None,
+        error: Some(error.to_string()),
+    }
+}
+
+fn handle_frame(frame: &[u8]) -> IpcResponse {
+    let res = serde_json::from_slice::<IpcRequest>(frame)
+        .map_err(anyhow::Error::from)
+        .and_then(dispatch);
+    match res {
+        Ok(data) => IpcResponse {
+            ok: true,
+            data: Some(data),
+            error: None,
+        },
+        Err(err) => error_response(&err.to_string()),
+    }
+}
+
+/// 处理一个连接，直到对方关闭，令牌不对时回复错误后关闭
+fn serve<S: Read + Write>(mut stream: S, token: &str) {
+    let mut authorized = false;
+    loop {
+        let frame = match read_frame(&mut stream) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return,
+            Err(err) => {
+                println!("ipc read error: {}", err);
+                return;
+            }
+        };
+        let res = match authorized {
+            true => handle_frame(&frame),
+            false if is_authorized(&frame, token) => {
+                authorized = true;
+                handle_frame(&frame)
+            }
+            false => error_response("invalid or missing token"),
+        };
+        let res_bytes = serde_json::to_vec(&res).unwrap_or_default();
+        if let Err(err) = write_frame(&mut stream, &res_bytes) {
+            println!("ipc write error: {}", err);
+            return;
+        }
+        if !authorized {
+            return;
+        }
+    }
+}
+
+pub use platform::Stream;
+
+/// 开启 enable_ipc 时生成令牌并监听
+pub fn start() {
+    if !Config::common().latest().enable_ipc.unwrap_or(false) {
+        return;
+    }
+    thread::spawn(|| {
+        let token = string_util::to_hex(&crypto_util::random_bytes(24));
+        let res = token_path()
+            .and_then(|path| platform::write_private(&path, &token))
+            .and_then(|_| platform::listen(Arc::new(token)));
+        if let Err(err) = res {
+            println!("start ipc error: {}", err);
+        }
+    });
+}
+
+#[cfg(unix)]
+mod platform {
+    use crate::utils::dirs;
+    use anyhow::Result;
+    use std::fs;
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::thread;
+
+    const SOCKET_FILE: &str = "lanaya.sock";
+
+    pub type Stream = UnixStream;
+
+    pub fn open() -> Result<Stream> {
+        open_at(&dirs::app_home_dir()?.join(SOCKET_FILE))
+    }
+
+    pub fn open_at(path: &Path) -> Result<Stream> {
+        Ok(UnixStream::connect(path)?)
+    }
+
+    /// 创建时即为 0600，不存在其它用户可以读取的窗口
+    pub fn write_private(path: &Path, content: &str) -> Result<()> {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    /// 监听 ~/.config/lanaya/lanaya.sock，bind 时的 umask 保证创建出来就是 0600
+    pub fn listen(token: Arc<String>) -> Result<()> {
+        let path = dirs::app_home_dir()?.join(SOCKET_FILE);
+        // 上次异常退出遗留的 socket 文件会导致 bind 失败
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        let old_umask = unsafe { libc::umask(0o177) };
+        let listener = UnixListener::bind(&path);
+        unsafe { libc::umask(old_umask) };
+        let listener = listener?;
+        println!("ipc listening on {}", path.display());
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let token = token.clone();
+                    thread::spawn(move || super::serve(stream, &token));
+                }
+                Err(err) => println!("ipc accept error: {}", err),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use anyhow::{bail, Result};
+    use std::ffi::c_void;
+    use std::io::{self, Read, Write};
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::thread;
+
+    const PIPE_ACCESS_DUPLEX: u32 = 0x3;
+    const PIPE_REJECT_REMOTE_CLIENTS: u32 = 0x8;
+    const PIPE_UNLIMITED_INSTANCES: u32 = 255;
+    const ERROR_PIPE_CONNECTED: i32 = 535;
+    const INVALID_HANDLE_VALUE: isize = -1;
+    const SDDL_REVISION_1: u32 = 1;
+    const BUFFER_SIZE: u32 = 64 * 1024;
+    /// 只允许管道的创建者（当前用户）访问
+    const PIPE_SDDL: &str = "D:P(A;;GA;;;OW)";
+
+    #[repr(C)]
+    struct SecurityAttributes {
+        length: u32,
+        security_descriptor: *mut c_void,
+        inherit_handle: i32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateNamedPipeW(
+            name: *const u16,
+            open_mode: u32,
+            pipe_mode: u32,
+            max_instances: u32,
+            out_buffer_size: u32,
+            in_buffer_size: u32,
+            default_timeout: u32,
+            security_attributes: *const SecurityAttributes,
+        ) -> isize;
+        fn ConnectNamedPipe(pipe: isize, overlapped: *mut c_void) -> i32;
+        fn DisconnectNamedPipe(pipe: isize) -> i32;
+        fn ReadFile(
+            file: isize,
+            buffer: *mut u8,
+            len: u32,
+            read: *mut u32,
+            overlapped: *mut c_void,
+        ) -> i32;
+        fn WriteFile(
+            file: isize,
+            buffer: *const u8,
+            len: u32,
+            written: *mut u32,
+            overlapped: *mut c_void,
+        ) -> i32;
+        fn FlushFileBuffers(file: isize) -> i32;
+        fn CloseHandle(handle: isize) -> i32;
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            sddl: *const u16,
+            revision: u32,
+            security_descriptor: *mut *mut c_void,
+            size: *mut u32,
+        ) -> i32;
+    }
+
+    struct Pipe(isize);
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let mut read = 0u32;
+            let ok = unsafe {
+                ReadFile(
+                    self.0,
+                    buf.as_mut_ptr(),
+                    buf.len() as u32,
+                    &mut read,
+                    std::ptr::null_mut(),
+                )
+            };
+            if ok == 0 {
+                let err = io::Error::last_os_error();
+                // 对方关闭管道按 EOF 处理
+                return match err.raw_os_error() {
+                    Some(109) => Ok(0),
+                    _ => Err(err),
+                };
+            }
+            Ok(read as usize)
+        }
+    }
+
+    impl Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut written = 0u32;
+            let ok = unsafe {
+                WriteFile(
+                    self.0,
+                    buf.as_ptr(),
+                    buf.len() as u32,
+                    &mut written,
+                    std::ptr::null_mut(),
+                )
+            };
+            if ok == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(written as usize)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            unsafe {
+                FlushFileBuffers(self.0);
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Pipe {
+        fn drop(&mut self) {
+            unsafe {
+                DisconnectNamedPipe(self.0);
+                CloseHandle(self.0);
+            }
+        }
+    }
+
+    fn to_wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    /// 管道名带上用户名，不同用户互不干扰：\\.\pipe\lanaya-<user>
+    fn pipe_name() -> String {
+        let user = std::env::var("USERNAME").unwrap_or_default();
+        format!(r"\\.\pipe\lanaya-{}", user)
+    }
+
+    pub type Stream = std::fs::File;
+
+    /// 命名管道可以按文件打开
+    pub fn open() -> Result<Stream> {
+        open_at(Path::new(&pipe_name()))
+    }
+
+    pub fn open_at(path: &Path) -> Result<Stream> {
+        Ok(std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)?)
+    }
+
+    /// 令牌放在用户目录下，其它用户没有读取权限
+    pub fn write_private(path: &Path, content: &str) -> Result<()> {
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub fn listen(token: Arc<String>) -> Result<()> {
+        let name = to_wide(&pipe_name());
+        let sddl = to_wide(PIPE_SDDL);
+        let mut descriptor: *mut c_void = std::ptr::null_mut();
+        let ok = unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.as_ptr(),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                std::ptr::null_mut(),
+            )
+        };
+        if ok == 0 {
+            bail!(
+                "failed to create pipe security descriptor: {}",
+                io::Error::last_os_error()
+            );
+        }
+        let attributes = SecurityAttributes {
+            length: std::mem::size_of::<SecurityAttributes>() as u32,
+            security_descriptor: descriptor,
+            inherit_handle: 0,
+        };
+        println!("ipc listening on {}", pipe_name());
+        loop {
+            // 每个连接一个管道实例，连接后交给新线程处理，再创建下一个实例等待
+            let handle = unsafe {
+                CreateNamedPipeW(
+                    name.as_ptr(),
+                    PIPE_ACCESS_DUPLEX,
+                    PIPE_REJECT_REMOTE_CLIENTS,
+                    PIPE_UNLIMITED_INSTANCES,
+                    BUFFER_SIZE,
+                    BUFFER_SIZE,
+                    0,
+                    &attributes,
+                )
+            };
+            if handle == INVALID_HANDLE_VALUE {
+                bail!(
+                    "failed to create named pipe: {}",
+                    io::Error::last_os_error()
+                );
+            }
+            let connected = unsafe { ConnectNamedPipe(handle, std::ptr::null_mut()) } != 0
+                || io::Error::last_os_error().raw_os_error() == Some(ERROR_PIPE_CONNECTED);
+            let pipe = Pipe(handle);
+            if connected {
+                let token = token.clone();
+                thread::spawn(move || super::serve(pipe, &token));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_frame() {
+    let mut buf = vec![];
+    write_frame(&mut buf, br#"{"op":"get","id":3}"#).unwrap();
+    write_frame(&mut buf, br#"{"op":"search","q":"hi","limit":5}"#).unwrap();
+    let mut reader = std::io::Cursor::new(buf);
+    let frame = read_frame(&mut reader).unwrap().unwrap();
+    assert_eq!(
+        serde_json::from_slice::<IpcRequest>(&frame).unwrap(),
+        IpcRequest::Get { id: 3 }
+    );
+    let frame = read_frame(&mut reader).unwrap().unwrap();
+    assert_eq!(
+        serde_json::from_slice::<IpcRequest>(&frame).unwrap(),
+        IpcRequest::Search {
+            q: Some("hi".into()),
+            limit: Some(5),
+            cursor: None,
+            favorite: None
+        }
+    );
+    let frame = br#"{"op":"capture","text":"hi","url":"https://example.com"}"#;
+    assert_eq!(
+        serde_json::from_slice::<IpcRequest>(frame).unwrap(),
+        IpcRequest::Capture(BrowserCapture {
+            text: "hi".into(),
+            url: Some("https://example.com".into()),
+            ..BrowserCapture::default()
+        })
+    );
+    let frame = br#"{"op":"osc52","data":"\u001b]52;c;aGk=\u0007"}"#;
+    assert_eq!(
+        serde_json::from_slice::<IpcRequest>(frame).unwrap(),
+        IpcRequest::Osc52 {
+            data: "\x1b]52;c;aGk=\x07".into()
+        }
+    );
+    assert_eq!(
+        serde_json::from_slice::<IpcRequest>(br#"{"op":"add","text":"hi"}"#).unwrap(),
+        IpcRequest::Add { text: "hi".into() }
+    );
+    assert!(is_authorized(
+        br#"{"op":"auth","token":"secret"}"#,
+        "secret"
+    ));
+    assert!(!is_authorized(
+        br#"{"op":"auth","token":"guess"}"#,
+        "secret"
+    ));
+    assert!(!is_authorized(br#"{"op":"get","id":3}"#, "secret"));
+    assert!(read_frame(&mut reader).unwrap().is_none());
+    let mut huge = std::io::Cursor::new(u32::MAX.to_be_bytes().to_vec());
+    assert!(read_frame(&mut huge).is_err());
+}
@@ -0,0 +1,191 @@
+use super::database::{LongLivedDB, SqliteDB};
+use super::{idle, phash, thumbnail, webhook};
+use crate::config::Config;
+use anyhow::Result;
+use once_cell::sync::OnceCell;
+use parking_lot::{Condvar, Mutex};
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+
+/// 失败后的重试间隔（秒），用完后丢弃任务
+const RETRY_DELAYS: [u64; 4] = [2, 10, 60, 300];
+/// 没有新任务时也定时检查一次，处理到期的重试
+const POLL_SECS: u64 = 5;
+
+pub const KIND_THUMBNAIL: &str = "thumbnail";
+pub const KIND_WEBHOOK: &str = "webhook";
+pub const KIND_PHASH: &str = "phash";
+
+/// 持久化在 job 表中的后台任务，应用退出后下次启动继续执行
+#[derive(Debug, Clone, Default)]
+pub struct Job {
+    pub id: u64,
+    pub kind: String,
+    pub record_id: u64,
+    /// 任务参数，如 webhook 的地址
+    pub payload: String,
+    /// 已失败的次数
+    pub attempts: u32,
+}
+
+/// 重试也不会成功的错误，任务直接丢弃
+#[derive(Debug)]
+pub struct Permanent(pub String);
+
+impl fmt::Display for Permanent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Permanent {}
+
+fn signal() -> &'static (Mutex<bool>, Condvar) {
+    static SIGNAL: OnceCell<(Mutex<bool>, Condvar)> = OnceCell::new();
+    SIGNAL.get_or_init(|| (Mutex::new(false), Condvar::new()))
+}
+
+/// 唤醒后台线程立即处理新任务
+pub fn wake() {
+    let (pending, cvar) = signal();
+    *pending.lock() = true;
+    cvar.notify_one();
+}
+
+fn retry_delay(attempts: u32) -> Option<u64> {
+    RETRY_DELAYS.get(attempts as usize).copied()
+}
+
+/// 新记录入库后调用，只写入任务表，耗时的处理都在后台线程完成
+pub fn enqueue_post_processing(id: u64) {
+    let db = SqliteDB::new();
+    let record = match db.find_list_item_by_id(id) {
+        Ok(record) => record,
+        Err(err) => {
+            println!("enqueue job find record error: {}", err);
+            return;
+        }
+    };
+    let mut jobs = vec![];
+    // 加密记录的缩略图不落盘，无需预先生成
+    if (record.data_type == "image" || record.data_type == "svg") && !record.is_secure {
+        jobs.push((KIND_THUMBNAIL, String::new()));
+    }
+    let webhooks = Config::common()
+        .latest()
+        .webhooks
+        .clone()
+        .unwrap_or_default();
+    for webhook in webhooks {
+        jobs.push((KIND_WEBHOOK, webhook.url));
+    }
+    if jobs.is_empty() {
+        return;
+    }
+    for (kind, payload) in jobs {
+        if let Err(err) = db.enqueue_job(kind, id, &payload) {
+            println!("enqueue {} job error: {}", kind, err);
+        }
+    }
+    wake();
+}
+
+/// 数据库就绪后调用，旧版本保存的图片在后台补算感知哈希
+pub fn enqueue_phash_backfill() {
+    if SqliteDB::is_read_only() {
+        return;
+    }
+    match SqliteDB::new().enqueue_phash_backfill(KIND_PHASH) {
+        Ok(0) => {}
+        Ok(_) => wake(),
+        Err(err) => println!("enqueue phash jobs error: {}", err),
+    }
+}
+
+fn run(job: &Job) -> Result<()> {
+    match job.kind.as_str() {
+        KIND_THUMBNAIL => thumbnail::thumbnail(job.record_id, thumbnail::LIST_SIZE).map(|_| ()),
+        KIND_WEBHOOK => webhook::deliver(&job.payload, job.record_id),
+        KIND_PHASH => phash::backfill(job.record_id),
+        kind => Err(Permanent(format!("unknown job kind \"{}\"", kind)).into()),
+    }
+}
+
+/// 依次执行所有到期的任务
+fn drain(db: &SqliteDB) -> Result<()> {
+    while let Some(job) = db.next_due_job()? {
+        // 记录已被删除时任务没有意义
+        if db.find_list_item_by_id(job.record_id).is_err() {
+            db.delete_job(job.id)?;
+            continue;
+        }
+        let err = match run(&job) {
+            Ok(()) => {
+                db.delete_job(job.id)?;
+                continue;
+            }
+            Err(err) => err,
+        };
+        match retry_delay(job.attempts) {
+            Some(delay) if !err.is::<Permanent>() => {
+                println!(
+                    "{} job for record {} failed: {}, retry in {}s",
+                    job.kind, job.record_id, err, delay
+                );
+                db.reschedule_job(job.id, delay * 1000, &err.to_string())?;
+            }
+            _ => {
+                println!(
+                    "{} job for record {} dropped: {}",
+                    job.kind, job.record_id, err
+                );
+                db.delete_job(job.id)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn start() {
+    // 只读时任务无法写回结果
+    if SqliteDB::is_read_only() {
+        return;
+    }
+    thread::spawn(|| {
+        // 修复数据库或空闲释放时关闭，之后重新打开
+        let mut long_lived = LongLivedDB::default();
+        let (pending, cvar) = signal();
+        loop {
+            // 数据库被其它实例接管后不再写入
+            if SqliteDB::is_read_only() {
+                return;
+            }
+            if let Some(db) = long_lived.get() {
+                if let Err(err) = drain(db) {
+                    println!("job queue error: {}", err);
+                }
+            }
+            // 空闲释放后每次处理完即关闭，不长期占用
+            if idle::is_released() {
+                long_lived = LongLivedDB::default();
+            }
+            let mut pending = pending.lock();
+            if !*pending {
+                cvar.wait_for(&mut pending, Duration::from_secs(POLL_SECS));
+            }
+            *pending = false;
+        }
+    });
+}
+
+#[test]
+fn test_retry_delay() {
+    assert_eq!(retry_delay(0), Some(2));
+    assert_eq!(retry_delay(3), Some(300));
+    assert_eq!(retry_delay(4), None);
+    let err: anyhow::Error = Permanent("server responded 404".into()).into();
+    assert!(err.is::<Permanent>());
+    assert_eq!(err.to_string(), "server responded 404");
+    assert!(!anyhow::anyhow!("timeout").is::<Permanent>());
+}
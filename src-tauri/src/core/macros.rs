@@ -0,0 +1,200 @@
+use super::capture_notify;
+use super::clipboard::ClipBoardOprator;
+use super::database::SqliteDB;
+use super::handle::Handle;
+use super::i18n;
+use super::paste;
+use crate::config::Config;
+use anyhow::{bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// 每一步之间的默认等待时间，等目标应用处理完上一次粘贴
+const DEFAULT_STEP_DELAY: u64 = 300;
+/// 每一步等待时间的上限
+pub const MAX_STEP_DELAY: u64 = 10_000;
+const MAX_NAME_LEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MacroStep {
+    pub record_id: u64,
+    /// 粘贴后等待的毫秒数，为空时使用 macro_step_delay
+    pub delay_ms: Option<u64>,
+}
+
+/// 按顺序复制并粘贴的一组记录，如重复填写的表单
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ClipMacro {
+    pub id: u64,
+    pub name: String,
+    pub steps: Vec<MacroStep>,
+    pub create_time: u64,
+}
+
+impl ClipMacro {
+    pub fn validate(name: &str, steps: &[MacroStep]) -> Result<()> {
+        let len = name.trim().chars().count();
+        if len == 0 || len > MAX_NAME_LEN {
+            bail!(
+                "the macro name must be between 1 and {} characters",
+                MAX_NAME_LEN
+            );
+        }
+        if steps.is_empty() {
+            bail!("the macro has no steps");
+        }
+        if steps
+            .iter()
+            .any(|s| s.delay_ms.map_or(false, |d| d > MAX_STEP_DELAY))
+        {
+            bail!("the step delay must not exceed {} ms", MAX_STEP_DELAY);
+        }
+        Ok(())
+    }
+}
+
+/// 正在录制的宏：名称和已记录的步骤
+fn recording() -> &'static Mutex<Option<(String, Vec<MacroStep>)>> {
+    static RECORDING: OnceCell<Mutex<Option<(String, Vec<MacroStep>)>>> = OnceCell::new();
+    RECORDING.get_or_init(|| Mutex::new(None))
+}
+
+/// 正在回放，期间写入剪切板产生的记录不算作录制的步骤
+static REPLAYING: AtomicBool = AtomicBool::new(false);
+
+/// 开始录制，之后复制的每条记录都作为一步
+pub fn start_recording(name: String) -> Result<()> {
+    let mut recording = recording().lock();
+    if recording.is_some() {
+        bail!("a macro is already being recorded");
+    }
+    *recording = Some((name, vec![]));
+    Ok(())
+}
+
+pub fn is_recording() -> bool {
+    recording().lock().is_some()
+}
+
+/// 由剪切板监听调用，连续复制同一条记录只算一步
+pub fn on_captured(id: u64) {
+    if REPLAYING.load(Ordering::SeqCst) {
+        return;
+    }
+    if let Some((_, steps)) = recording().lock().as_mut() {
+        if steps.last().map(|s| s.record_id) != Some(id) {
+            steps.push(MacroStep {
+                record_id: id,
+                delay_ms: None,
+            });
+        }
+    }
+}
+
+/// 结束录制并保存，同名的宏会被覆盖
+pub fn stop_recording() -> Result<ClipMacro> {
+    let (name, steps) = match recording().lock().take() {
+        Some(res) => res,
+        None => bail!("no macro is being recorded"),
+    };
+    save(&name, &steps)
+}
+
+pub fn cancel_recording() {
+    recording().lock().take();
+}
+
+/// 保存时锁定步骤中的记录，不会被自动清理或直接删除
+pub fn save(name: &str, steps: &[MacroStep]) -> Result<ClipMacro> {
+    ClipMacro::validate(name, steps)?;
+    let db = SqliteDB::new();
+    ensure_steps_exist(&db, steps)?;
+    let ids: Vec<u64> = steps.iter().map(|s| s.record_id).collect();
+    db.set_locked(&ids, true)?;
+    let id = db.save_macro(name.trim(), steps)?;
+    db.find_macro(id)
+}
+
+fn ensure_steps_exist(db: &SqliteDB, steps: &[MacroStep]) -> Result<()> {
+    for (i, step) in steps.iter().enumerate() {
+        if db.find_list_item_by_id(step.record_id).is_err() {
+            bail!(
+                "step {} (record {}) was deleted, edit the macro and try again",
+                i + 1,
+                step.record_id
+            );
+        }
+    }
+    Ok(())
+}
+
+fn step_delay(step: &MacroStep) -> Duration {
+    let default = Config::common().latest().macro_step_delay;
+    let delay = step.delay_ms.or(default).unwrap_or(DEFAULT_STEP_DELAY);
+    Duration::from_millis(delay.min(MAX_STEP_DELAY))
+}
+
+/// 在后台线程中回放，有步骤的记录已被删除时不回放，避免只粘贴一部分
+pub fn replay(id: u64) -> Result<()> {
+    let db = SqliteDB::new();
+    let clip_macro = db.find_macro(id)?;
+    if is_recording() {
+        bail!("cannot replay a macro while recording");
+    }
+    ensure_steps_exist(&db, &clip_macro.steps)?;
+    if REPLAYING.swap(true, Ordering::SeqCst) {
+        bail!("a macro is already being replayed");
+    }
+    // 粘贴到之前获得焦点的应用
+    if let Some(window) = Handle::global().get_window() {
+        let _ = window.hide();
+    }
+    thread::spawn(move || {
+        for (i, step) in clip_macro.steps.iter().enumerate() {
+            match ClipBoardOprator::copy_by_id(step.record_id, None) {
+                Ok(_) => paste::paste(),
+                Err(err) => {
+                    capture_notify::notify(i18n::t_args(
+                        "notification.macro_stopped",
+                        &[
+                            ("name", &clip_macro.name),
+                            ("step", &(i + 1).to_string()),
+                            ("error", &err.to_string()),
+                        ],
+                    ));
+                    break;
+                }
+            }
+            thread::sleep(step_delay(step));
+        }
+        REPLAYING.store(false, Ordering::SeqCst);
+    });
+    Ok(())
+}
+
+#[test]
+fn test_macro_recording() {
+    start_recording("form".into()).unwrap();
+    assert!(start_recording("other".into()).is_err());
+    for id in [3, 3, 5, 3] {
+        on_captured(id);
+    }
+    let (name, steps) = recording().lock().take().unwrap();
+    assert_eq!(name, "form");
+    let ids: Vec<u64> = steps.iter().map(|s| s.record_id).collect();
+    assert_eq!(ids, vec![3, 5, 3]);
+    on_captured(7);
+    assert!(!is_recording());
+    assert!(ClipMacro::validate("form", &steps).is_ok());
+    assert!(ClipMacro::validate(" ", &steps).is_err());
+    assert!(ClipMacro::validate("form", &[]).is_err());
+    let slow = MacroStep {
+        record_id: 1,
+        delay_ms: Some(MAX_STEP_DELAY + 1),
+    };
+    assert!(ClipMacro::validate("form", &[slow]).is_err());
+}
@@ -1,6 +1,70 @@
+pub mod actions;
+pub mod analytics;
+pub mod appearance;
+pub mod audit;
+pub mod autostart;
+pub mod backup;
+pub mod biometric;
+pub mod bridge;
+pub mod capture_guard;
+pub mod capture_notify;
+pub mod cli;
 pub mod clipboard;
+pub mod cloud_clipboard;
+pub mod compress;
+pub mod crash;
 pub mod database;
+pub mod deep_link;
+pub mod diagnostics;
+pub mod espanso;
+pub mod expansion;
+pub mod external_editor;
 pub mod handle;
+pub mod http_api;
+pub mod i18n;
+pub mod idle;
+pub mod integrity;
+pub mod ipc;
+pub mod job_queue;
+pub mod macros;
+pub mod native_messaging;
+pub mod onboarding;
+pub mod osc52;
+pub mod pairing;
+pub mod palette;
+pub mod paste;
+pub mod pasteboard;
+pub mod phash;
+pub mod pin;
+pub mod plugin;
+pub mod position;
+pub mod preview;
+pub mod purge;
+pub mod query_builder;
+pub mod record_cache;
+pub mod retention;
+pub mod rules;
+pub mod screen_share;
+pub mod screenshot;
+pub mod script;
+pub mod secure_input;
+pub mod snippet;
+pub mod sound;
+pub mod source_app;
+pub mod stats;
+pub mod store;
+pub mod suggestion;
+pub mod svg;
+pub mod sync;
+pub mod sync_client;
 pub mod sysopt;
+pub mod thumbnail;
+pub mod time_machine;
 pub mod tray;
+pub mod trigger;
+pub mod updater;
+pub mod url_clean;
+pub mod vault;
+pub mod webhook;
 pub mod window_manager;
+pub mod window_state;
@@ -0,0 +1,357 @@
+use super::clipboard::ClipboardWatcher;
+use super::database::{InsertResult, Record, SqliteDB};
+use super::handle::{Handle, MsgTypeEnum};
+use super::ipc;
+use super::{retention, rules, screen_share, secure_input, url_clean};
+use crate::log_err;
+use crate::utils::string_util;
+use anyhow::{bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// 浏览器扩展中 connectNative 使用的名称
+pub const HOST_NAME: &str = "com.church.lanaya";
+/// 浏览器发来的单条消息上限
+const MAX_INCOMING_LEN: usize = 16 * 1024 * 1024;
+/// 发给浏览器的单条消息上限，Chrome 规定为 1 MB
+const MAX_OUTGOING_LEN: usize = 1024 * 1024;
+/// 扩展和剪切板监听在这段时间内记录到相同内容时只算一次
+const DEDUP_SECS: u64 = 5;
+/// 选中内容的 HTML 按 macOS 的类型名保存，再次复制时原样写回
+const HTML_TYPE: &str = "public.html";
+
+/// 浏览器扩展推送的复制内容，比剪切板多了页面地址和 HTML
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct BrowserCapture {
+    pub text: String,
+    pub html: Option<String>,
+    pub url: Option<String>,
+    /// 浏览器名称，作为来源应用保存
+    pub browser: Option<String>,
+}
+
+fn last_capture() -> &'static Mutex<Option<(String, Instant)>> {
+    static LAST: OnceCell<Mutex<Option<(String, Instant)>>> = OnceCell::new();
+    LAST.get_or_init(|| Mutex::new(None))
+}
+
+/// 记下刚记录的内容，另一边在短时间内已记录过相同内容时返回 false
+pub fn claim_capture(md5: &str) -> bool {
+    let mut last = last_capture().lock();
+    if let Some((last_md5, at)) = last.as_ref() {
+        if last_md5 == md5 && at.elapsed() < Duration::from_secs(DEDUP_SECS) {
+            return false;
+        }
+    }
+    *last = Some((md5.to_string(), Instant::now()));
+    true
+}
+
+/// 由运行中的实例处理扩展推送的内容，返回记录 id
+/// 暂停记录时和剪切板监听一样忽略
+pub fn capture(c: BrowserCapture) -> Result<Value> {
+    if !ClipboardWatcher::is_monitoring()
+        || SqliteDB::is_read_only()
+        || secure_input::should_pause()
+        || screen_share::should_pause()
+    {
+        bail!("capture is paused");
+    }
+    if c.text.trim().is_empty() {
+        bail!("nothing to capture");
+    }
+    let (content, original) = match url_clean::process_capture(&c.text) {
+        Some(cleaned) => (cleaned, Some(c.text.clone())),
+        None => (c.text.clone(), None),
+    };
+    let db = SqliteDB::new();
+    let md5 = string_util::md5(&content);
+    // 剪切板监听已经记录过时只补充页面信息
+    let res = match claim_capture(&md5) {
+        true => db.insert_if_not_exist(Record {
+            content,
+            data_type: "text".to_string(),
+            ..Default::default()
+        })?,
+        false => InsertResult::Updated(db.find_record_by_md5(md5)?.id),
+    };
+    let id = res.id();
+    if let Some(url) = c.url.as_ref().filter(|url| !url.is_empty()) {
+        db.set_source_url(id, url)?;
+    }
+    if let Some(browser) = c.browser.as_ref().filter(|b| !b.is_empty()) {
+        db.set_source_app(id, browser)?;
+    }
+    if let Some(original) = &original {
+        db.set_original_content(id, original)?;
+    }
+    // 剪切板监听在 macOS 上已经保存了原始类型
+    if let Some(html) = c.html.filter(|html| !html.is_empty()) {
+        if db.find_pasteboard_types(id)?.is_empty() {
+            db.save_pasteboard_types(id, &[(HTML_TYPE.to_string(), html.into_bytes())])?;
+        }
+    }
+    if let InsertResult::Inserted(_) = res {
+        log_err!(rules::apply(&db, id, c.browser.as_deref()));
+    }
+    ClipboardWatcher::notice_captured(&res);
+    retention::enforce(&db)?;
+    log_err!(Handle::update_systray());
+    log_err!(Handle::notice_to_window(MsgTypeEnum::ChangeClipBoard, "ok"));
+    Ok(json!({ "id": id }))
+}
+
+/// native messaging 的消息为 4 字节本机字节序长度 + JSON
+fn read_message<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut len_buf = [0u8; 4];
+    match reader.read_exact(&mut len_buf) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    let len = u32::from_ne_bytes(len_buf) as usize;
+    if len > MAX_INCOMING_LEN {
+        bail!("native message too large: {} bytes", len);
+    }
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf)?;
+    Ok(Some(buf))
+}
+
+fn write_message<W: Write>(writer: &mut W, data: &[u8]) -> Result<()> {
+    // 超出上限时浏览器会断开连接，改为返回错误
+    let too_large;
+    let data = if data.len() > MAX_OUTGOING_LEN {
+        too_large = json!({ "ok": false, "error": "response too large, use a smaller limit" })
+            .to_string()
+            .into_bytes();
+        &too_large
+    } else {
+        data
+    };
+    writer.write_all(&(data.len() as u32).to_ne_bytes())?;
+    writer.write_all(data)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// 浏览器启动 host 时的参数：Chrome 为 chrome-extension://<id>/，Firefox 为清单路径和扩展 id
+pub fn is_host_launch(args: &[String]) -> bool {
+    args.iter()
+        .any(|arg| arg.starts_with("chrome-extension://"))
+        || args
+            .first()
+            .map_or(false, |arg| arg.ends_with(&format!("{}.json", HOST_NAME)))
+}
+
+/// 在界面启动前调用：由浏览器启动时把消息转发给运行中的实例，处理完后退出进程
+pub fn run_host() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !is_host_launch(&args) {
+        return;
+    }
+    let mut stdin = io::stdin().lock();
+    let mut stdout = io::stdout().lock();
+    let mut conn = None;
+    loop {
+        let message = match read_message(&mut stdin) {
+            Ok(Some(message)) => message,
+            // 浏览器关闭端口时 stdin 结束
+            _ => std::process::exit(0),
+        };
+        let res = relay(&mut conn, &message).unwrap_or_else(|err| {
+            // 连接出错时下一条消息重新连接
+            conn = None;
+            json!({ "ok": false, "error": err.to_string() })
+                .to_string()
+                .into_bytes()
+        });
+        if write_message(&mut stdout, &res).is_err() {
+            std::process::exit(1);
+        }
+    }
+}
+
+fn relay(conn: &mut Option<ipc::Stream>, message: &[u8]) -> Result<Vec<u8>> {
+    if conn.is_none() {
+        match ipc::connect() {
+            Ok(stream) => *conn = Some(stream),
+            Err(_) => bail!("Lanaya is not running"),
+        }
+    }
+    let stream = conn.as_mut().unwrap();
+    ipc::write_frame(stream, message)?;
+    match ipc::read_frame(stream)? {
+        Some(res) => Ok(res),
+        None => bail!("Lanaya closed the connection"),
+    }
+}
+
+/// 注册到浏览器的 host 清单，Chrome 系以 origin 限制扩展，Firefox 以扩展 id 限制
+fn manifest(exe: &Path, extension_ids: &[String], firefox: bool) -> Value {
+    let mut manifest = json!({
+        "name": HOST_NAME,
+        "description": "Lanaya clipboard history",
+        "path": exe.display().to_string(),
+        "type": "stdio",
+    });
+    if firefox {
+        manifest["allowed_extensions"] = json!(extension_ids);
+    } else {
+        let origins: Vec<String> = extension_ids
+            .iter()
+            .map(|id| format!("chrome-extension://{}/", id))
+            .collect();
+        manifest["allowed_origins"] = json!(origins);
+    }
+    manifest
+}
+
+/// Firefox 的扩展 id 为邮箱格式或带花括号的 uuid，Chrome 的为 32 个小写字母
+fn is_firefox_id(id: &str) -> bool {
+    id.contains('@') || id.starts_with('{')
+}
+
+/// 为 Chrome 系浏览器和 Firefox 注册 host，返回写入的清单文件
+pub fn install(extension_ids: &[String]) -> Result<Vec<String>> {
+    if extension_ids.is_empty() {
+        bail!("missing extension id");
+    }
+    let exe = std::env::current_exe()?;
+    let (firefox, chrome): (Vec<String>, Vec<String>) = extension_ids
+        .iter()
+        .cloned()
+        .partition(|id| is_firefox_id(id));
+    let mut written = vec![];
+    if !chrome.is_empty() {
+        let content = manifest(&exe, &chrome, false).to_string();
+        written.extend(platform::install(&content, false)?);
+    }
+    if !firefox.is_empty() {
+        let content = manifest(&exe, &firefox, true).to_string();
+        written.extend(platform::install(&content, true)?);
+    }
+    Ok(written)
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::HOST_NAME;
+    use crate::utils::dirs;
+    use anyhow::Result;
+    use std::fs;
+    use winreg::enums::HKEY_CURRENT_USER;
+    use winreg::RegKey;
+
+    /// Windows 上清单放在应用目录，注册表中记录清单路径
+    pub fn install(content: &str, firefox: bool) -> Result<Vec<String>> {
+        let file = match firefox {
+            true => format!("{}.firefox.json", HOST_NAME),
+            false => format!("{}.json", HOST_NAME),
+        };
+        let path = dirs::app_home_dir()?.join(file);
+        fs::write(&path, content)?;
+        let keys: &[&str] = match firefox {
+            true => &[r"Software\Mozilla\NativeMessagingHosts"],
+            false => &[
+                r"Software\Google\Chrome\NativeMessagingHosts",
+                r"Software\Microsoft\Edge\NativeMessagingHosts",
+            ],
+        };
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        for key in keys {
+            let (key, _) = hkcu.create_subkey(format!(r"{}\{}", key, HOST_NAME))?;
+            key.set_value("", &path.display().to_string())?;
+        }
+        Ok(vec![path.display().to_string()])
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod platform {
+    use super::HOST_NAME;
+    use anyhow::{anyhow, Result};
+    use std::fs;
+    use tauri::api::path::home_dir;
+
+    /// 相对于用户目录，只写入已安装的浏览器
+    #[cfg(target_os = "macos")]
+    const CHROME_DIRS: [&str; 4] = [
+        "Library/Application Support/Google/Chrome",
+        "Library/Application Support/Chromium",
+        "Library/Application Support/Microsoft Edge",
+        "Library/Application Support/BraveSoftware/Brave-Browser",
+    ];
+    #[cfg(target_os = "macos")]
+    const FIREFOX_DIR: &str = "Library/Application Support/Mozilla/NativeMessagingHosts";
+    #[cfg(not(target_os = "macos"))]
+    const CHROME_DIRS: [&str; 4] = [
+        ".config/google-chrome",
+        ".config/chromium",
+        ".config/microsoft-edge",
+        ".config/BraveSoftware/Brave-Browser",
+    ];
+    #[cfg(not(target_os = "macos"))]
+    const FIREFOX_DIR: &str = ".mozilla/native-messaging-hosts";
+
+    pub fn install(content: &str, firefox: bool) -> Result<Vec<String>> {
+        let home = home_dir().ok_or_else(|| anyhow!("failed to get home dir"))?;
+        let dirs = match firefox {
+            true => vec![home.join(FIREFOX_DIR)],
+            false => CHROME_DIRS
+                .iter()
+                .map(|dir| home.join(dir))
+                .filter(|dir| dir.exists())
+                .map(|dir| dir.join("NativeMessagingHosts"))
+                .collect(),
+        };
+        let mut written = vec![];
+        for dir in dirs {
+            fs::create_dir_all(&dir)?;
+            let path = dir.join(format!("{}.json", HOST_NAME));
+            fs::write(&path, content)?;
+            written.push(path.display().to_string());
+        }
+        Ok(written)
+    }
+}
+
+#[test]
+fn test_native_messaging() {
+    let mut buf = vec![];
+    write_message(&mut buf, br#"{"op":"search","q":"hi"}"#).unwrap();
+    let mut reader = std::io::Cursor::new(buf);
+    assert_eq!(
+        read_message(&mut reader).unwrap().unwrap(),
+        br#"{"op":"search","q":"hi"}"#.to_vec()
+    );
+    assert!(read_message(&mut reader).unwrap().is_none());
+    let mut buf = vec![];
+    write_message(&mut buf, &vec![b'a'; MAX_OUTGOING_LEN + 1]).unwrap();
+    assert!(buf.len() < MAX_OUTGOING_LEN);
+    let args = |s: &str| -> Vec<String> { s.split(' ').map(String::from).collect() };
+    assert!(is_host_launch(&args(
+        "chrome-extension://abc/ --parent-window=0"
+    )));
+    assert!(is_host_launch(&args(
+        "/home/me/.mozilla/native-messaging-hosts/com.church.lanaya.json lanaya@example.com"
+    )));
+    assert!(!is_host_launch(&args("list --json")));
+    let exe = Path::new("/usr/bin/lanaya");
+    let chrome = manifest(exe, &["abcdefghijklmnopabcdefghijklmnop".into()], false);
+    assert_eq!(
+        chrome["allowed_origins"][0],
+        "chrome-extension://abcdefghijklmnopabcdefghijklmnop/"
+    );
+    let firefox = manifest(exe, &["lanaya@example.com".into()], true);
+    assert_eq!(firefox["allowed_extensions"][0], "lanaya@example.com");
+    assert!(is_firefox_id("{8a1b7f3e-0000-4000-8000-000000000000}"));
+    assert!(claim_capture("md5"));
+    assert!(!claim_capture("md5"));
+    assert!(claim_capture("other"));
+}
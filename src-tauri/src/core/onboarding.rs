@@ -0,0 +1,161 @@
+use super::i18n;
+use crate::config::{self, CommonConfig, Config};
+use anyhow::Result;
+use arboard::Clipboard;
+use serde::{Deserialize, Serialize};
+
+/// 设置向导中可以选择的配置，未选择的字段保持不变
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OnboardingChoice {
+    /// 打开主窗口的快捷键，format: {key}+{key}
+    pub global_shortcut: Option<String>,
+    pub record_limit: Option<u32>,
+    pub enable_auto_launch: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionStatus {
+    /// `accessibility` or `clipboard`
+    pub name: String,
+    /// `granted` or `denied` or `not_required`
+    pub status: String,
+    /// 未授权时提示用户如何处理
+    pub action: Option<String>,
+    /// 是否可以通过 open_permission_settings 打开对应的系统设置
+    pub can_open_settings: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OnboardingStatus {
+    pub first_launch: bool,
+    pub recommended: OnboardingChoice,
+    pub permissions: Vec<PermissionStatus>,
+}
+
+/// 只有新建配置文件时 onboarding_completed 为 false，老用户升级后不会再弹出向导
+pub fn is_first_launch() -> bool {
+    Config::common().latest().onboarding_completed == Some(false)
+}
+
+/// 向导中默认选中的配置
+pub fn recommended() -> OnboardingChoice {
+    let global_shortcut = CommonConfig::template()
+        .hotkeys
+        .unwrap_or_default()
+        .iter()
+        .find_map(|h| h.strip_prefix("global-shortcut:").map(String::from));
+    OnboardingChoice {
+        global_shortcut,
+        record_limit: Some(300),
+        enable_auto_launch: Some(true),
+    }
+}
+
+pub fn status() -> OnboardingStatus {
+    OnboardingStatus {
+        first_launch: is_first_launch(),
+        recommended: recommended(),
+        permissions: check_permissions(),
+    }
+}
+
+fn permission(name: &str, granted: Option<bool>, can_open_settings: bool) -> PermissionStatus {
+    let (status, action) = match granted {
+        None => ("not_required", None),
+        Some(true) => ("granted", None),
+        Some(false) => (
+            "denied",
+            Some(i18n::t(&format!("onboarding.{}_denied", name))),
+        ),
+    };
+    PermissionStatus {
+        name: name.into(),
+        status: status.into(),
+        action,
+        can_open_settings: can_open_settings && granted == Some(false),
+    }
+}
+
+/// 自动粘贴需要辅助功能权限，读取剪切板需要系统提供剪切板访问
+pub fn check_permissions() -> Vec<PermissionStatus> {
+    let clipboard = Clipboard::new().is_ok();
+    vec![
+        permission(
+            "accessibility",
+            platform::accessibility_granted(),
+            cfg!(target_os = "macos"),
+        ),
+        permission("clipboard", Some(clipboard), false),
+    ]
+}
+
+pub fn open_permission_settings(name: &str) -> Result<()> {
+    platform::open_settings(name)
+}
+
+/// 应用向导中的选择并标记为已完成，跳过向导时传入空的选择即可
+pub async fn complete(choice: OnboardingChoice) -> Result<()> {
+    let hotkeys = choice.global_shortcut.map(|keys| {
+        let current = { Config::common().latest().hotkeys.clone() };
+        let mut hotkeys: Vec<String> = current
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|h| !h.starts_with("global-shortcut:"))
+            .collect();
+        hotkeys.push(format!("global-shortcut:{}", keys));
+        hotkeys
+    });
+    let patch = CommonConfig {
+        hotkeys,
+        record_limit: choice.record_limit,
+        enable_auto_launch: choice.enable_auto_launch,
+        onboarding_completed: Some(true),
+        ..CommonConfig::default()
+    };
+    patch.validate()?;
+    config::modify_common_config(patch).await
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use anyhow::{bail, Result};
+    use std::process::Command;
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn AXIsProcessTrusted() -> bool;
+    }
+
+    pub fn accessibility_granted() -> Option<bool> {
+        Some(unsafe { AXIsProcessTrusted() })
+    }
+
+    /// 打开 系统设置 > 隐私与安全性 中对应的页面
+    pub fn open_settings(name: &str) -> Result<()> {
+        let pane = match name {
+            "accessibility" => "Privacy_Accessibility",
+            _ => bail!("unsupported permission \"{}\"", name),
+        };
+        Command::new("open")
+            .arg(format!(
+                "x-apple.systempreferences:com.apple.preference.security?{}",
+                pane
+            ))
+            .spawn()?;
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod platform {
+    use anyhow::{bail, Result};
+
+    /// 其它平台模拟按键不需要授权
+    pub fn accessibility_granted() -> Option<bool> {
+        None
+    }
+
+    pub fn open_settings(name: &str) -> Result<()> {
+        bail!("unsupported permission \"{}\"", name)
+    }
+}
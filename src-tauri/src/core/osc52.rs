@@ -0,0 +1,151 @@
+use super::ipc;
+use super::native_messaging::{self, BrowserCapture};
+use anyhow::{bail, Result};
+use base64::engine::general_purpose;
+use base64::Engine;
+use serde_json::{json, Value};
+use std::io::{self, Read, Write};
+
+/// OSC 52 写剪切板的转义序列：ESC ] 52 ; <选区> ; <base64> BEL 或 ESC \
+const PREFIX: &[u8] = b"\x1b]52;";
+/// 没有结束符时最多缓存的字节数
+const MAX_PENDING: usize = 16 * 1024 * 1024;
+/// 作为来源应用显示
+pub const SOURCE: &str = "OSC 52";
+
+/// 按字节流解析，序列可以被拆到多次输入中
+#[derive(Debug, Default)]
+pub struct Parser {
+    buf: Vec<u8>,
+}
+
+impl Parser {
+    /// 返回本次输入中完整序列的文本，查询剪切板的 `?` 和无法解码的内容忽略
+    pub fn feed(&mut self, data: &[u8]) -> Vec<String> {
+        for b in data {
+            // tmux passthrough（ESC P tmux; ... ESC \）中的 ESC 会写成两个
+            if *b == 0x1b && self.buf.last() == Some(&0x1b) {
+                continue;
+            }
+            self.buf.push(*b);
+        }
+        let mut texts = vec![];
+        loop {
+            let start = match find(&self.buf, PREFIX) {
+                Some(start) => start,
+                None => {
+                    // 只保留可能是前缀开头的部分
+                    let keep = self.buf.len().min(PREFIX.len() - 1);
+                    self.buf.drain(..self.buf.len() - keep);
+                    break;
+                }
+            };
+            self.buf.drain(..start);
+            let body_end = self.buf[PREFIX.len()..]
+                .iter()
+                .enumerate()
+                .find(|(i, b)| {
+                    **b == 0x07
+                        || (**b == 0x1b && self.buf.get(PREFIX.len() + i + 1) == Some(&b'\\'))
+                })
+                .map(|(i, b)| (PREFIX.len() + i, if *b == 0x07 { 1 } else { 2 }));
+            let (end, terminator_len) = match body_end {
+                Some(end) => end,
+                None => {
+                    if self.buf.len() > MAX_PENDING {
+                        self.buf.clear();
+                    }
+                    break;
+                }
+            };
+            if let Some(text) = decode(&self.buf[PREFIX.len()..end]) {
+                texts.push(text);
+            }
+            self.buf.drain(..end + terminator_len);
+        }
+        texts
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// body 为 <选区>;<base64>
+fn decode(body: &[u8]) -> Option<String> {
+    let split = body.iter().position(|b| *b == b';')?;
+    let payload = std::str::from_utf8(&body[split + 1..]).ok()?.trim();
+    if payload.is_empty() || payload == "?" {
+        return None;
+    }
+    let bytes = general_purpose::STANDARD.decode(payload).ok()?;
+    String::from_utf8(bytes)
+        .ok()
+        .filter(|text| !text.trim().is_empty())
+}
+
+/// IPC 中的 osc52 请求：data 为一个或多个完整的转义序列
+pub fn capture(data: &str) -> Result<Value> {
+    let mut ids = vec![];
+    for text in Parser::default().feed(data.as_bytes()) {
+        let res = native_messaging::capture(BrowserCapture {
+            text,
+            browser: Some(SOURCE.into()),
+            ..BrowserCapture::default()
+        })?;
+        ids.push(res["id"].clone());
+    }
+    Ok(json!({ "ids": ids }))
+}
+
+/// lanaya osc52：从标准输入读取终端输出，把其中的 OSC 52 内容发给运行中的实例
+/// passthrough 时原样输出到标准输出，可以串在管道中间
+pub fn forward_stdin(passthrough: bool) -> Result<()> {
+    let mut stream = match ipc::connect() {
+        Ok(stream) => stream,
+        Err(_) => bail!("Lanaya is not running"),
+    };
+    let mut parser = Parser::default();
+    let mut stdin = io::stdin().lock();
+    let mut stdout = io::stdout().lock();
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = stdin.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        if passthrough {
+            stdout.write_all(&buf[..n])?;
+            stdout.flush()?;
+        }
+        for text in parser.feed(&buf[..n]) {
+            let req = json!({ "op": "capture", "text": text, "browser": SOURCE });
+            // 暂停记录等错误不中断转发
+            if let Err(err) = ipc::request(&mut stream, &req) {
+                eprintln!("osc52: {}", err);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_osc52_parser() {
+    let mut parser = Parser::default();
+    // "hello" / "world"
+    assert_eq!(
+        parser.feed(b"log\x1b]52;c;aGVsbG8=\x07more\x1b]52;;d29ybGQ=\x1b\\"),
+        vec!["hello", "world"]
+    );
+    // 被拆成两段输入
+    assert!(parser.feed(b"\x1b]5").is_empty());
+    assert!(parser.feed(b"2;c;aGVs").is_empty());
+    assert_eq!(parser.feed(b"bG8=\x07"), vec!["hello"]);
+    // tmux passthrough
+    assert_eq!(
+        parser.feed(b"\x1bPtmux;\x1b\x1b]52;c;aGVsbG8=\x07\x1b\\"),
+        vec!["hello"]
+    );
+    assert!(parser.feed(b"\x1b]52;c;?\x07").is_empty());
+    assert!(parser.feed(b"\x1b]52;c;not base64!\x07").is_empty());
+    assert!(parser.buf.len() < PREFIX.len());
+}
@@ -0,0 +1,552 @@
+use super::database::SqliteDB;
+use super::handle::{Handle, MsgTypeEnum};
+use super::http_api::constant_time_eq;
+use super::sync::{self, SyncChange, SyncDevice};
+use crate::config::{self, CommonConfig, Config};
+use crate::log_err;
+use crate::utils::crypto_util::{self, SecretKey};
+use crate::utils::{dirs, json_util, string_util};
+use anyhow::{anyhow, bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use qrcode::render::svg;
+use qrcode::QrCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::UdpSocket;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// 配对码的有效时间
+const PAIRING_TTL_SECS: u64 = 300;
+/// 配对码输错的次数上限，超过后作废
+const MAX_PAIRING_FAILURES: u32 = 5;
+/// 推送内容的大小上限，图片以 base64 保存
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+/// 每台设备待取的记录数上限，超过后丢弃最早的
+const MAX_OUTBOX: usize = 20;
+/// 待取的记录保存的文件，在数据目录下
+const OUTBOX_FILE: &str = "sync_outbox.json";
+/// 请求时间和本机时间最多相差的毫秒数
+const MAX_CLOCK_SKEW_MILLIS: u64 = 5 * 60 * 1000;
+pub const DEVICE_HEADER: &str = "X-Lanaya-Device";
+pub const TIME_HEADER: &str = "X-Lanaya-Time";
+pub const SIGNATURE_HEADER: &str = "X-Lanaya-Signature";
+
+fn server() -> &'static Mutex<Option<Arc<Server>>> {
+    static SERVER: OnceCell<Mutex<Option<Arc<Server>>>> = OnceCell::new();
+    SERVER.get_or_init(|| Mutex::new(None))
+}
+
+/// 发送给各设备、等待对方取走的记录，按设备 id 保存，重启后仍然保留
+fn outbox() -> &'static Mutex<HashMap<String, Vec<SyncChange>>> {
+    static OUTBOX: OnceCell<Mutex<HashMap<String, Vec<SyncChange>>>> = OnceCell::new();
+    OUTBOX.get_or_init(|| {
+        let outbox = outbox_path()
+            .and_then(|path| json_util::read(&path))
+            .unwrap_or_default();
+        Mutex::new(outbox)
+    })
+}
+
+fn outbox_path() -> Result<PathBuf> {
+    Ok(dirs::app_data_dir()?.join(OUTBOX_FILE))
+}
+
+fn save_outbox(outbox: &HashMap<String, Vec<SyncChange>>) {
+    log_err!(outbox_path().and_then(|path| json_util::save(&path, outbox)));
+}
+
+/// 各设备最后一次请求的时间，更早的请求视为重放
+fn last_requests() -> &'static Mutex<HashMap<String, u64>> {
+    static LAST_REQUESTS: OnceCell<Mutex<HashMap<String, u64>>> = OnceCell::new();
+    LAST_REQUESTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 正在进行的配对：配对码、生成时间、输错的次数，只能使用一次
+fn pending() -> &'static Mutex<Option<(String, Instant, u32)>> {
+    static PENDING: OnceCell<Mutex<Option<(String, Instant, u32)>>> = OnceCell::new();
+    PENDING.get_or_init(|| Mutex::new(None))
+}
+
+/// 供手机等其它设备同步的接口，只监听 sync_server_address 对应的网卡
+/// POST /sync/pair                 body: { name, proof }，proof 为用二维码中的配对码对 name 做的 HMAC-SHA256
+///                                 返回 { device_id, secret }，secret 用配对码派生的密钥加密，配对码本身不经过网络
+/// POST /sync/push                 推送对方的记录，body: { changes: [SyncChange] }
+/// GET  /sync/pull?since=&after=&limit=
+///                                 拉取游标之后修改或删除过的记录，返回的 next_since、next_after 作为下次的游标
+/// GET  /sync/inbox                取走发送给该设备的记录，对方写入剪切板（另一台 Lanaya 见 sync_client）
+/// 除配对外，请求和响应的 body 都用配对时交换的密钥加密（见 crypto_util::SecretKey）
+/// 并带上 X-Lanaya-Device、X-Lanaya-Time（毫秒）和 X-Lanaya-Signature，签名的内容见 signing_payload
+#[derive(Debug, PartialEq)]
+enum Route {
+    Pair,
+    Push,
+    Pull {
+        since: u64,
+        after: String,
+        limit: Option<usize>,
+    },
+    Inbox,
+}
+
+/// 二维码中的内容为 payload，对方扫码后向 address 发起配对
+#[derive(Debug, Clone, Serialize)]
+pub struct PairingInfo {
+    pub address: String,
+    pub key: String,
+    pub expires_at: u64,
+    pub payload: String,
+    pub qr_svg: String,
+}
+
+#[derive(Deserialize)]
+struct PairReq {
+    name: String,
+    proof: String,
+}
+
+#[derive(Deserialize)]
+struct PushReq {
+    changes: Vec<SyncChange>,
+}
+
+/// 按最新配置启动或关闭服务，端口变化时重启
+pub fn refresh() -> Result<()> {
+    if let Some(old) = server().lock().take() {
+        old.unblock();
+    }
+    let (enable, port) = {
+        let config = Config::common().latest();
+        (
+            config.enable_sync_server.unwrap_or(false),
+            config.sync_server_port.unwrap_or(18091),
+        )
+    };
+    if !enable {
+        *pending().lock() = None;
+        return Ok(());
+    }
+    let address = listen_address();
+    let srv = Server::http((address.as_str(), port)).map_err(|err| {
+        anyhow!(
+            "failed to start sync server on {}:{}: {}",
+            address,
+            port,
+            err
+        )
+    })?;
+    let srv = Arc::new(srv);
+    *server().lock() = Some(srv.clone());
+    println!("sync server listening on {}:{}", address, port);
+    thread::spawn(move || {
+        for req in srv.incoming_requests() {
+            handle_request(req);
+        }
+    });
+    Ok(())
+}
+
+/// 本机在局域网中的地址，connect 只选择出口网卡，不会发送数据
+fn local_ip() -> String {
+    UdpSocket::bind(("0.0.0.0", 0))
+        .and_then(|socket| {
+            socket.connect(("8.8.8.8", 80))?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|_| "127.0.0.1".into())
+}
+
+/// sync_server_address 未设置时使用连接局域网的网卡
+fn listen_address() -> String {
+    let address = { Config::common().latest().sync_server_address.clone() };
+    address
+        .filter(|address| !address.is_empty())
+        .unwrap_or_else(local_ip)
+}
+
+/// 生成新的配对码和二维码，之前的配对码失效
+pub fn start_pairing() -> Result<PairingInfo> {
+    let (enable, port) = {
+        let config = Config::common().latest();
+        (
+            config.enable_sync_server.unwrap_or(false),
+            config.sync_server_port.unwrap_or(18091),
+        )
+    };
+    if !enable || server().lock().is_none() {
+        bail!("sync server is not running");
+    }
+    let key = string_util::to_hex(&crypto_util::random_bytes(32));
+    let address = format!("http://{}:{}", listen_address(), port);
+    let payload = json!({ "v": 2, "address": address, "key": key }).to_string();
+    let qr_svg = QrCode::new(payload.as_bytes())?
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build();
+    *pending().lock() = Some((key.clone(), Instant::now(), 0));
+    let expires_at = chrono::Local::now().timestamp_millis() as u64 + PAIRING_TTL_SECS * 1000;
+    Ok(PairingInfo {
+        address,
+        key,
+        expires_at,
+        payload,
+        qr_svg,
+    })
+}
+
+pub fn cancel_pairing() {
+    *pending().lock() = None;
+}
+
+/// 对方证明自己知道配对码，配对码不在网络上传输
+pub fn pairing_proof(key: &str, name: &str) -> String {
+    crypto_util::hmac_sha256_hex(key.as_bytes(), name.as_bytes())
+}
+
+/// 用配对码派生的密钥加密返回给对方的设备密钥
+pub fn pairing_transport_key(key: &str) -> SecretKey {
+    crypto_util::derive_key(key, b"lanaya-sync-pairing")
+}
+
+/// 签名的内容，双方按同样的格式计算
+pub fn signing_payload(method: &str, url: &str, time: u64, body: &str) -> Vec<u8> {
+    format!("{} {}\n{}\n{}", method, url, time, body).into_bytes()
+}
+
+fn now() -> u64 {
+    chrono::Local::now().timestamp_millis() as u64
+}
+
+/// 请求时间和本机相差不大，并且晚于该设备上一次的请求
+fn is_fresh(time: u64, last: Option<u64>, now: u64) -> bool {
+    time.max(now) - time.min(now) <= MAX_CLOCK_SKEW_MILLIS && last.map_or(true, |last| time > last)
+}
+
+/// 校验配对码，成功后作废并返回配对码，输错次数过多时也作废
+fn take_pairing_key(verify: impl Fn(&str) -> bool) -> Option<String> {
+    let mut pending = pending().lock();
+    let (key, created, failures) = pending.as_mut()?;
+    if created.elapsed() > Duration::from_secs(PAIRING_TTL_SECS) {
+        *pending = None;
+        return None;
+    }
+    if verify(key) {
+        return pending.take().map(|(key, _, _)| key);
+    }
+    *failures += 1;
+    if *failures >= MAX_PAIRING_FAILURES {
+        *pending = None;
+    }
+    None
+}
+
+fn pair(req: PairReq) -> Result<Value> {
+    let key = take_pairing_key(|key| constant_time_eq(&req.proof, &pairing_proof(key, &req.name)))
+        .ok_or(anyhow!("invalid or expired pairing key"))?;
+    let secret = SecretKey::random();
+    let device = SyncDevice {
+        id: string_util::to_hex(&crypto_util::random_bytes(8)),
+        name: req.name.trim().chars().take(64).collect(),
+        token: Some(secret.to_base64()),
+        paired_at: Some(now()),
+        ..SyncDevice::default()
+    };
+    let mut devices = { Config::common().latest().sync_devices.clone() }.unwrap_or_default();
+    devices.push(device.clone());
+    tauri::async_runtime::block_on(save_devices(devices))?;
+    log_err!(Handle::notice_to_window(
+        MsgTypeEnum::SyncDevicePaired,
+        device.name.clone()
+    ));
+    let secret = pairing_transport_key(&key).encrypt(&secret.to_base64());
+    Ok(json!({ "device_id": device.id, "secret": secret }))
+}
+
+/// 和设置页一样校验后保存
+async fn save_devices(devices: Vec<SyncDevice>) -> Result<()> {
+    let patch = CommonConfig {
+        sync_devices: Some(devices),
+        ..CommonConfig::default()
+    };
+    patch.validate()?;
+    config::modify_common_config(patch).await
+}
+
+/// 从配置中移除设备，之后该设备的令牌失效
+pub async fn unpair(id: &str) -> Result<()> {
+    let mut devices = { Config::common().latest().sync_devices.clone() }.unwrap_or_default();
+    devices.retain(|device| device.id != id);
+    save_devices(devices).await?;
+    let mut outbox = outbox().lock();
+    if outbox.remove(id).is_some() {
+        save_outbox(&outbox);
+    }
+    Ok(())
+}
+
+/// 把一条记录发送到已配对设备的剪切板，对方下次取 /sync/inbox 时收到
+/// 不受设备同步范围的限制，加密的记录不能发送
+pub fn send_to_device(device_id: &str, record_id: u64) -> Result<()> {
+    let paired = {
+        let config = Config::common().latest();
+        config.sync_devices.iter().flatten().any(|device| {
+            device.id == device_id && device.token.as_ref().map_or(false, |t| !t.is_empty())
+        })
+    };
+    if !paired {
+        bail!("the device is not paired");
+    }
+    if server().lock().is_none() {
+        bail!("sync server is not running");
+    }
+    let record = SqliteDB::new().find_by_id(record_id)?;
+    if record.is_secure {
+        bail!("encrypted records cannot be sent to other devices");
+    }
+    enqueue(
+        device_id,
+        SyncChange {
+            md5: record.md5,
+            data_type: record.data_type,
+            content: Some(record.content),
+            is_favorite: record.is_favorite,
+            tags: record.tags,
+            updated_at: chrono::Local::now().timestamp_millis() as u64,
+            deleted: false,
+        },
+    );
+    Ok(())
+}
+
+fn enqueue(device_id: &str, change: SyncChange) {
+    let mut outbox = outbox().lock();
+    push_change(outbox.entry(device_id.to_string()).or_default(), change);
+    save_outbox(&outbox);
+}
+
+/// 重复发送同一条记录时只保留最新的一次，超过 MAX_OUTBOX 时丢弃最早的
+fn push_change(queue: &mut Vec<SyncChange>, change: SyncChange) {
+    queue.retain(|c| c.md5 != change.md5);
+    queue.push(change);
+    if queue.len() > MAX_OUTBOX {
+        let overflow = queue.len() - MAX_OUTBOX;
+        queue.drain(..overflow);
+    }
+}
+
+/// 按发送顺序取走，最后一条为最近发送的
+fn take_outbox(device_id: &str) -> Vec<SyncChange> {
+    let mut outbox = outbox().lock();
+    let clips = outbox.remove(device_id).unwrap_or_default();
+    if !clips.is_empty() {
+        save_outbox(&outbox);
+    }
+    clips
+}
+
+fn header<'a>(req: &'a Request, name: &str) -> Option<&'a str> {
+    req.headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str().trim())
+}
+
+/// 按请求头找到设备并校验签名和时间，返回设备和共享的密钥
+/// 配对前的旧版本设备没有密钥，需要重新配对
+fn authenticate(req: &Request, body: &str) -> Option<(SyncDevice, SecretKey)> {
+    let id = header(req, DEVICE_HEADER)?;
+    let time: u64 = header(req, TIME_HEADER)?.parse().ok()?;
+    let signature = header(req, SIGNATURE_HEADER)?;
+    let devices = { Config::common().latest().sync_devices.clone() };
+    let device = devices
+        .unwrap_or_default()
+        .into_iter()
+        .find(|d| d.id == id)?;
+    let key = SecretKey::from_base64(device.token.as_deref()?).ok()?;
+    let payload = signing_payload(&req.method().to_string(), req.url(), time, body);
+    if !constant_time_eq(signature, &string_util::to_hex(&key.sign(&payload))) {
+        return None;
+    }
+    let mut last_requests = last_requests().lock();
+    if !is_fresh(time, last_requests.get(id).copied(), now()) {
+        return None;
+    }
+    last_requests.insert(device.id.clone(), time);
+    Some((device, key))
+}
+
+fn read_body(req: &mut Request) -> Result<String> {
+    if req.body_length().unwrap_or(0) > MAX_BODY_BYTES {
+        bail!("request body is too large");
+    }
+    let mut body = String::new();
+    req.as_reader()
+        .take(MAX_BODY_BYTES as u64 + 1)
+        .read_to_string(&mut body)?;
+    if body.len() > MAX_BODY_BYTES {
+        bail!("request body is too large");
+    }
+    Ok(body)
+}
+
+fn handle_request(mut req: Request) {
+    let (status, body) = match route(req.method(), req.url()) {
+        None => (404, error_body("not found")),
+        Some(Route::Pair) => match read_body(&mut req)
+            .and_then(|body| Ok(serde_json::from_str::<PairReq>(&body)?))
+            .and_then(pair)
+        {
+            Ok(body) => (200, body.to_string()),
+            Err(err) => (400, error_body(&err.to_string())),
+        },
+        Some(route) => match read_body(&mut req) {
+            Err(err) => (400, error_body(&err.to_string())),
+            Ok(raw) => match authenticate(&req, &raw) {
+                None => (401, error_body("invalid signature, pair the device again")),
+                Some((device, key)) => match execute(&raw, &route, &device, &key) {
+                    // 响应同样加密
+                    Ok(body) => (
+                        200,
+                        json!({ "data": key.encrypt(&body.to_string()) }).to_string(),
+                    ),
+                    Err(err) => (400, error_body(&err.to_string())),
+                },
+            },
+        },
+    };
+    let content_type = Header::from_bytes(
+        &b"Content-Type"[..],
+        &b"application/json; charset=utf-8"[..],
+    )
+    .unwrap();
+    let res = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(content_type);
+    if let Err(err) = req.respond(res) {
+        println!("sync server respond error: {}", err);
+    }
+}
+
+fn execute(raw: &str, route: &Route, device: &SyncDevice, key: &SecretKey) -> Result<Value> {
+    match *route {
+        Route::Push => {
+            let body: PushReq = serde_json::from_str(&key.decrypt(raw.trim())?)?;
+            let res = sync::apply_remote(body.changes, &device.id)?;
+            Ok(serde_json::to_value(res)?)
+        }
+        Route::Pull {
+            since,
+            ref after,
+            limit,
+        } => {
+            let changes = sync::changes_for(&device.id, since, after, limit)?;
+            // 下次从最后一条继续拉取，修改时间相同时按 md5 区分
+            let (next_since, next_after) = changes
+                .last()
+                .map_or((since, after.clone()), |c| (c.updated_at, c.md5.clone()));
+            Ok(json!({ "changes": changes, "next_since": next_since, "next_after": next_after }))
+        }
+        Route::Inbox => Ok(json!({ "clips": take_outbox(&device.id) })),
+        Route::Pair => bail!("already paired"),
+    }
+}
+
+fn error_body(msg: &str) -> String {
+    json_util::stringfy(&json!({ "error": msg })).unwrap_or_default()
+}
+
+fn route(method: &Method, url: &str) -> Option<Route> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match (method, segments.as_slice()) {
+        (Method::Post, ["sync", "pair"]) => Some(Route::Pair),
+        (Method::Post, ["sync", "push"]) => Some(Route::Push),
+        (Method::Get, ["sync", "inbox"]) => Some(Route::Inbox),
+        (Method::Get, ["sync", "pull"]) => {
+            let (mut since, mut after, mut limit) = (0, String::new(), None);
+            for (k, v) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+                match k {
+                    "since" => since = v.parse().ok()?,
+                    "after" => after = v.to_string(),
+                    "limit" => limit = v.parse().ok(),
+                    _ => {}
+                }
+            }
+            Some(Route::Pull {
+                since,
+                after,
+                limit,
+            })
+        }
+        _ => None,
+    }
+}
+
+#[test]
+fn test_sync_route() {
+    assert_eq!(route(&Method::Post, "/sync/pair"), Some(Route::Pair));
+    assert_eq!(route(&Method::Get, "/sync/push"), None);
+    assert_eq!(
+        route(
+            &Method::Get,
+            "/sync/pull?since=1700000000000&after=ab12&limit=50"
+        ),
+        Some(Route::Pull {
+            since: 1700000000000,
+            after: "ab12".into(),
+            limit: Some(50)
+        })
+    );
+    assert_eq!(
+        route(&Method::Get, "/sync/pull"),
+        Some(Route::Pull {
+            since: 0,
+            after: String::new(),
+            limit: None
+        })
+    );
+    assert_eq!(route(&Method::Get, "/sync/pull?since=abc"), None);
+    assert_eq!(route(&Method::Get, "/sync/inbox"), Some(Route::Inbox));
+}
+
+#[test]
+fn test_outbox_queue() {
+    let clip = |md5: &str| SyncChange {
+        md5: md5.into(),
+        ..SyncChange::default()
+    };
+    let mut queue = vec![];
+    for i in 0..MAX_OUTBOX + 2 {
+        push_change(&mut queue, clip(&i.to_string()));
+    }
+    push_change(&mut queue, clip("5"));
+    assert_eq!(queue.len(), MAX_OUTBOX);
+    assert_eq!(queue[0].md5, "2");
+    assert_eq!(queue.last().unwrap().md5, "5");
+}
+
+#[test]
+fn test_pairing_requests() {
+    let verify = |given: &'static str| move |key: &str| constant_time_eq(given, key);
+    *pending().lock() = Some(("secret".into(), Instant::now(), 0));
+    for _ in 0..MAX_PAIRING_FAILURES {
+        assert_eq!(take_pairing_key(verify("guess")), None);
+    }
+    assert_eq!(take_pairing_key(verify("secret")), None);
+    *pending().lock() = Some(("secret".into(), Instant::now(), 0));
+    assert_eq!(take_pairing_key(verify("secret")), Some("secret".into()));
+    assert_eq!(take_pairing_key(verify("secret")), None);
+    // 请求时间
+    let now = 1_700_000_000_000;
+    assert!(is_fresh(now - 1000, None, now));
+    assert!(is_fresh(now + 1000, Some(now), now));
+    assert!(!is_fresh(now, Some(now), now));
+    assert!(!is_fresh(now - MAX_CLOCK_SKEW_MILLIS - 1, None, now));
+}
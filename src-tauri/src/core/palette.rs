@@ -0,0 +1,160 @@
+use super::clipboard::ClipBoardOprator;
+use super::database::{QueryReq, Record, SqliteDB};
+use super::handle::Handle;
+use super::i18n;
+use super::paste;
+use super::plugin;
+use super::tray::Tray;
+use super::vault::Vault;
+use super::window_manager::WindowType;
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+const PALETTE_LABEL: &str = "palette";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PaletteItem {
+    /// `record` or `snippet` or `action` or `plugin`
+    pub kind: String,
+    pub id: String,
+    pub title: String,
+    pub subtitle: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct PaletteResults {
+    request_id: u64,
+    /// `history` or `snippets` or `actions`
+    section: String,
+    items: Vec<PaletteItem>,
+}
+
+// 面板中可执行的动作：(id, 标题)
+/// (id, 文案的 key)
+const ACTIONS: [(&str, &str); 4] = [
+    ("open_window", "palette.open_window"),
+    ("open_config", "palette.open_config"),
+    ("toggle_preview", "palette.toggle_preview"),
+    ("clear_history", "palette.clear_history"),
+];
+
+fn to_item(kind: &str, r: &Record) -> PaletteItem {
+    PaletteItem {
+        kind: kind.into(),
+        id: r.id.to_string(),
+        title: Tray::record_title(r),
+        subtitle: r.tags.clone(),
+    }
+}
+
+fn emit(request_id: u64, section: &str, items: Vec<PaletteItem>) {
+    if let Some(window) = Handle::global().get_window_by_label(PALETTE_LABEL) {
+        let res = window.emit(
+            "lanaya://palette-results",
+            PaletteResults {
+                request_id,
+                section: section.into(),
+                items,
+            },
+        );
+        if let Err(err) = res {
+            println!("emit palette results error: {}", err);
+        }
+    }
+}
+
+/// 依次推送历史记录、片段（收藏）和动作的搜索结果，前端根据 request_id 丢弃过期结果
+pub fn search(key: String, request_id: u64) -> Result<()> {
+    let db = SqliteDB::new();
+    let key = key.trim().to_string();
+    let query_key = if key.is_empty() {
+        None
+    } else {
+        Some(key.clone())
+    };
+    let history = db.find_by_key(QueryReq {
+        key: query_key.clone(),
+        limit: Some(20),
+        ..QueryReq::default()
+    })?;
+    emit(
+        request_id,
+        "history",
+        history.iter().map(|r| to_item("record", r)).collect(),
+    );
+    let snippets = db.find_by_key(QueryReq {
+        key: query_key,
+        limit: Some(20),
+        is_favorite: Some(true),
+        ..QueryReq::default()
+    })?;
+    emit(
+        request_id,
+        "snippets",
+        snippets.iter().map(|r| to_item("snippet", r)).collect(),
+    );
+    let key = key.to_lowercase();
+    let mut actions: Vec<PaletteItem> = ACTIONS
+        .iter()
+        .map(|(id, title)| (id, i18n::t(title)))
+        .filter(|(_, title)| title.to_lowercase().contains(&key))
+        .map(|(id, title)| PaletteItem {
+            kind: "action".into(),
+            id: id.to_string(),
+            title,
+            subtitle: String::new(),
+        })
+        .collect();
+    // 插件动作作用于最新一条记录，id 为 {plugin}/{action}
+    for (plugin_name, action) in plugin::actions() {
+        if action.title.to_lowercase().contains(&key) {
+            actions.push(PaletteItem {
+                kind: "plugin".into(),
+                id: format!("{}/{}", plugin_name, action.id),
+                title: action.title,
+                subtitle: plugin_name,
+            });
+        }
+    }
+    emit(request_id, "actions", actions);
+    Ok(())
+}
+
+/// 回车执行：记录和片段复制后粘贴到之前的应用，动作直接执行
+pub fn execute(kind: &str, id: &str) -> Result<()> {
+    if let Some(window) = Handle::global().get_window_by_label(PALETTE_LABEL) {
+        let _ = window.hide();
+    }
+    match kind {
+        "record" | "snippet" => {
+            ClipBoardOprator::copy_with_style(id.parse()?, None)?;
+            paste::paste_later();
+        }
+        "plugin" => {
+            let (plugin_name, action) = match id.split_once('/') {
+                Some(res) => res,
+                None => bail!("malformed plugin action \"{}\"", id),
+            };
+            let db = SqliteDB::new();
+            let latest = match db.find_latest_id()? {
+                Some(latest) => latest,
+                None => bail!("no record for plugin action"),
+            };
+            let mut record = db.find_by_id(latest)?;
+            record.content = Vault::global().reveal(&record)?;
+            if let Some(content) = plugin::run_action(plugin_name, action, &record)? {
+                ClipBoardOprator::set_text(content)?;
+                paste::paste_later();
+            }
+        }
+        "action" => match id {
+            "open_window" => Handle::open_window(WindowType::Main),
+            "open_config" => Handle::open_window(WindowType::Config),
+            "toggle_preview" => Handle::open_window(WindowType::Preview),
+            "clear_history" => SqliteDB::new().clear_data()?,
+            _ => bail!("unknown palette action \"{}\"", id),
+        },
+        _ => bail!("unknown palette item kind \"{}\"", kind),
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use super::clipboard::{ClipBoardOprator, PasteStyle};
+use super::database::{QueryReq, SqliteDB};
+use super::sound;
+use super::source_app;
+use super::time_machine;
+use crate::config::Config;
+use anyhow::{bail, Result};
+use arboard::Clipboard;
+use enigo::{Enigo, Key, KeyboardControllable};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+/// 打开列表窗口前处于前台的应用，即之后粘贴的目标
+fn target() -> &'static Mutex<Option<String>> {
+    static TARGET: OnceCell<Mutex<Option<String>>> = OnceCell::new();
+    TARGET.get_or_init(|| Mutex::new(None))
+}
+
+/// 在显示自己的窗口之前调用，记下粘贴的目标应用
+pub fn remember_target() {
+    *target().lock() = source_app::frontmost_app();
+}
+
+pub fn target_app() -> Option<String> {
+    target().lock().clone()
+}
+
+fn style_for(app: &str, styles: &HashMap<String, PasteStyle>) -> Option<PasteStyle> {
+    styles
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(app))
+        .map(|(_, style)| *style)
+}
+
+/// paste_style_by_app 中该应用的粘贴格式
+pub fn default_style(app: Option<&str>) -> Option<PasteStyle> {
+    let styles = Config::common().latest().paste_style_by_app.clone()?;
+    style_for(app?, &styles)
+}
+
+/// 模拟粘贴快捷键，粘贴到当前获得焦点的应用
+/// 调用前应先隐藏自己的窗口，等焦点回到之前的应用
+pub fn paste() {
+    thread::sleep(Duration::from_millis(100));
+    let mut enigo = Enigo::new();
+    #[cfg(target_os = "macos")]
+    let modifier = Key::Meta;
+    #[cfg(not(target_os = "macos"))]
+    let modifier = Key::Control;
+    // 由快捷键触发时用户可能还按着 Shift/Alt，先松开，避免变成其它快捷键
+    enigo.key_up(Key::Shift);
+    enigo.key_up(Key::Alt);
+    enigo.key_down(modifier);
+    enigo.key_click(Key::Layout('v'));
+    enigo.key_up(modifier);
+}
+
+/// 在后台线程中粘贴，不阻塞调用方
+pub fn paste_later() {
+    thread::spawn(paste);
+}
+
+/// 快速粘贴第 n 条（从 1 开始）最近记录
+pub fn paste_nth(n: usize) -> Result<()> {
+    let records = SqliteDB::new().find_by_key(QueryReq {
+        limit: Some(n),
+        ..QueryReq::default()
+    })?;
+    let record = match records.get(n - 1) {
+        Some(r) => r,
+        None => bail!("no record at position {}", n),
+    };
+    ClipBoardOprator::copy_by_id(record.id, None)?;
+    sound::play("paste");
+    let auto_paste = { Config::common().latest().quick_paste_auto_paste };
+    if auto_paste.unwrap_or(true) {
+        paste_later();
+    }
+    Ok(())
+}
+
+/// 去掉当前剪切板内容的格式，以纯文本粘贴
+pub fn paste_plain_text() -> Result<()> {
+    let mut clipboard = Clipboard::new()?;
+    let text = clipboard.get_text()?;
+    // 重新写入纯文本会清除 html、rtf 等其它格式，恢复时可以找回
+    time_machine::remember_current();
+    clipboard.set_text(text)?;
+    sound::play("paste");
+    paste_later();
+    Ok(())
+}
+
+#[test]
+fn test_paste_style_for_app() {
+    let styles = HashMap::from([
+        ("iTerm2".to_string(), PasteStyle::Plain),
+        ("Mail".to_string(), PasteStyle::Formatted),
+    ]);
+    assert_eq!(style_for("iterm2", &styles), Some(PasteStyle::Plain));
+    assert_eq!(style_for("Mail", &styles), Some(PasteStyle::Formatted));
+    assert_eq!(style_for("Terminal", &styles), None);
+}
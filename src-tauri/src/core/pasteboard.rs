@@ -0,0 +1,242 @@
+use super::clipboard::ImageDataDB;
+use super::database::{Record, SqliteDB};
+use crate::utils::{img_util, json_util, string_util};
+use anyhow::Result;
+
+const GIF_TYPE: &str = "com.compuserve.gif";
+const PNG_TYPE: &str = "public.png";
+
+/// 记录当前剪切板中除纯文本外的其它类型，再次复制时原样写回
+pub fn capture(db: &SqliteDB, record_id: u64) -> Result<()> {
+    if !platform::is_supported() {
+        return Ok(());
+    }
+    let types = platform::read_types();
+    if types.is_empty() {
+        return db.delete_pasteboard_types(record_id);
+    }
+    db.save_pasteboard_types(record_id, &types)
+}
+
+/// 按记录时保存的类型写回剪切板，没有保存过时返回 false
+/// record.content 需为明文
+pub fn restore(db: &SqliteDB, record: &Record) -> Result<bool> {
+    if !platform::is_supported() || record.is_secure {
+        return Ok(false);
+    }
+    // 动图由 set_image 按 GIF 写回
+    if record.data_type == "image"
+        && json_util::parse::<ImageDataDB>(&record.content)
+            .map_or(false, |image| image.gif.is_some())
+    {
+        return Ok(false);
+    }
+    let types = db.find_pasteboard_types(record.id)?;
+    if types.is_empty() {
+        return Ok(false);
+    }
+    let text = match record.data_type.as_str() {
+        "text" | "svg" => Some(record.content.as_str()),
+        _ => None,
+    };
+    platform::write_types(&types, text)?;
+    Ok(true)
+}
+
+/// 当前剪切板中图片的缩放比例（像素尺寸 / 逻辑尺寸），取不到时返回 None
+pub fn image_scale() -> Option<f64> {
+    platform::image_scale().filter(|scale| *scale >= 1.0)
+}
+
+/// 当前剪切板中的 GIF 动图，只有一帧的不算
+pub fn read_gif() -> Option<Vec<u8>> {
+    platform::read_type(GIF_TYPE).filter(|data| img_util::is_animated_gif(data))
+}
+
+/// 写入 GIF 和第一帧的 PNG，不支持的平台返回 false，由调用方写入第一帧
+pub fn write_gif(gif: &[u8], image: &ImageDataDB) -> Result<bool> {
+    if !platform::is_supported() {
+        return Ok(false);
+    }
+    let png = string_util::base64_decode(&image.base64);
+    platform::write_types(
+        &[
+            (GIF_TYPE.to_string(), gif.to_vec()),
+            (PNG_TYPE.to_string(), png),
+        ],
+        None,
+    )?;
+    Ok(true)
+}
+
+/// 按逻辑尺寸写入图片，不支持的平台返回 false，由调用方按像素尺寸写入
+pub fn write_image(image: &ImageDataDB) -> Result<bool> {
+    if !platform::is_supported() {
+        return Ok(false);
+    }
+    let png = string_util::base64_decode(&image.base64);
+    let width = image.width as f64 / image.scale;
+    let height = image.height as f64 / image.scale;
+    platform::write_image(&png, width, height)?;
+    Ok(true)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use anyhow::{bail, Result};
+    use objc::runtime::{Object, BOOL, YES};
+    use objc::{class, msg_send, sel, sel_impl};
+    use std::ffi::CString;
+
+    const PRESERVED_TYPES: [&str; 4] =
+        ["public.rtf", "public.html", "public.png", "public.file-url"];
+    /// 单个类型超过 20M 时不保存，避免数据库膨胀
+    const MAX_TYPE_SIZE: usize = 20 * 1024 * 1024;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct NSSize {
+        width: f64,
+        height: f64,
+    }
+
+    pub fn is_supported() -> bool {
+        true
+    }
+
+    unsafe fn ns_string(s: &str) -> *mut Object {
+        let s = CString::new(s).unwrap_or_default();
+        msg_send![class!(NSString), stringWithUTF8String: s.as_ptr()]
+    }
+
+    pub fn read_types() -> Vec<(String, Vec<u8>)> {
+        PRESERVED_TYPES
+            .iter()
+            .filter_map(|t| read_type(t).map(|data| (t.to_string(), data)))
+            .collect()
+    }
+
+    pub fn read_type(t: &str) -> Option<Vec<u8>> {
+        unsafe {
+            let pool: *mut Object = msg_send![class!(NSAutoreleasePool), new];
+            let pasteboard: *mut Object = msg_send![class!(NSPasteboard), generalPasteboard];
+            let data: *mut Object = msg_send![pasteboard, dataForType: ns_string(t)];
+            let mut res = None;
+            if !data.is_null() {
+                let len: usize = msg_send![data, length];
+                if len > 0 && len <= MAX_TYPE_SIZE {
+                    let bytes: *const u8 = msg_send![data, bytes];
+                    res = Some(std::slice::from_raw_parts(bytes, len).to_vec());
+                }
+            }
+            let _: () = msg_send![pool, drain];
+            res
+        }
+    }
+
+    pub fn write_types(types: &[(String, Vec<u8>)], text: Option<&str>) -> Result<()> {
+        let mut failed = vec![];
+        unsafe {
+            let pool: *mut Object = msg_send![class!(NSAutoreleasePool), new];
+            let pasteboard: *mut Object = msg_send![class!(NSPasteboard), generalPasteboard];
+            let _: isize = msg_send![pasteboard, clearContents];
+            for (t, data) in types {
+                let ns_data: *mut Object =
+                    msg_send![class!(NSData), dataWithBytes: data.as_ptr() length: data.len()];
+                let ok: BOOL = msg_send![pasteboard, setData: ns_data forType: ns_string(t)];
+                if ok != YES {
+                    failed.push(t.clone());
+                }
+            }
+            if let Some(text) = text {
+                let ok: BOOL = msg_send![pasteboard, setString: ns_string(text) forType: ns_string("public.utf8-plain-text")];
+                if ok != YES {
+                    failed.push("public.utf8-plain-text".to_string());
+                }
+            }
+            let _: () = msg_send![pool, drain];
+        }
+        if !failed.is_empty() {
+            bail!("failed to write pasteboard types: {}", failed.join(", "));
+        }
+        Ok(())
+    }
+
+    pub fn image_scale() -> Option<f64> {
+        unsafe {
+            let pool: *mut Object = msg_send![class!(NSAutoreleasePool), new];
+            let pasteboard: *mut Object = msg_send![class!(NSPasteboard), generalPasteboard];
+            let image: *mut Object = msg_send![class!(NSImage), alloc];
+            let image: *mut Object = msg_send![image, initWithPasteboard: pasteboard];
+            let mut scale = None;
+            if !image.is_null() {
+                let size: NSSize = msg_send![image, size];
+                let reps: *mut Object = msg_send![image, representations];
+                let count: usize = msg_send![reps, count];
+                if count > 0 && size.width > 0.0 {
+                    let rep: *mut Object = msg_send![reps, objectAtIndex: 0usize];
+                    let pixels: isize = msg_send![rep, pixelsWide];
+                    scale = Some(pixels as f64 / size.width);
+                }
+                let _: () = msg_send![image, release];
+            }
+            let _: () = msg_send![pool, drain];
+            scale
+        }
+    }
+
+    /// NSImage 设置逻辑尺寸后写入，其它应用粘贴时按原始大小显示
+    pub fn write_image(png: &[u8], width: f64, height: f64) -> Result<()> {
+        unsafe {
+            let pool: *mut Object = msg_send![class!(NSAutoreleasePool), new];
+            let data: *mut Object =
+                msg_send![class!(NSData), dataWithBytes: png.as_ptr() length: png.len()];
+            let image: *mut Object = msg_send![class!(NSImage), alloc];
+            let image: *mut Object = msg_send![image, initWithData: data];
+            if image.is_null() {
+                let _: () = msg_send![pool, drain];
+                bail!("failed to decode image");
+            }
+            let _: () = msg_send![image, setSize: NSSize { width, height }];
+            let pasteboard: *mut Object = msg_send![class!(NSPasteboard), generalPasteboard];
+            let _: isize = msg_send![pasteboard, clearContents];
+            let objects: *mut Object = msg_send![class!(NSArray), arrayWithObject: image];
+            let ok: BOOL = msg_send![pasteboard, writeObjects: objects];
+            let _: () = msg_send![image, release];
+            let _: () = msg_send![pool, drain];
+            if ok != YES {
+                bail!("failed to write image to pasteboard");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod platform {
+    use anyhow::Result;
+
+    pub fn is_supported() -> bool {
+        false
+    }
+
+    pub fn read_types() -> Vec<(String, Vec<u8>)> {
+        vec![]
+    }
+
+    pub fn read_type(_t: &str) -> Option<Vec<u8>> {
+        None
+    }
+
+    pub fn write_types(_types: &[(String, Vec<u8>)], _text: Option<&str>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn image_scale() -> Option<f64> {
+        None
+    }
+
+    pub fn write_image(_png: &[u8], _width: f64, _height: f64) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use super::clipboard::ImageDataDB;
+use super::database::{Record, SqliteDB};
+use crate::config::Config;
+use crate::utils::{img_util, json_util};
+use anyhow::Result;
+
+pub fn to_hex(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+fn parse_hex(hash: &str) -> Option<u64> {
+    u64::from_str_radix(hash, 16).ok()
+}
+
+fn threshold() -> u32 {
+    Config::common()
+        .latest()
+        .image_similarity_threshold
+        .unwrap_or(6)
+}
+
+pub fn is_dedupe_enabled() -> bool {
+    Config::common().latest().image_dedupe.unwrap_or(false)
+}
+
+/// 和 hash 相差不超过 max 位的图片 id，由近到远排序，exclude 为图片自身
+fn within(hashes: &[(u64, String)], hash: u64, max: u32, exclude: Option<u64>) -> Vec<u64> {
+    let mut similar: Vec<(u32, u64)> = hashes
+        .iter()
+        .filter(|(id, _)| Some(*id) != exclude)
+        .filter_map(|(id, other)| {
+            let distance = img_util::hamming_distance(hash, parse_hex(other)?);
+            (distance <= max).then_some((distance, *id))
+        })
+        .collect();
+    similar.sort();
+    similar.into_iter().map(|(_, id)| id).collect()
+}
+
+/// 新复制的图片在历史中是否已有视觉上相同的记录
+pub fn find_similar(db: &SqliteDB, hash: u64) -> Result<Option<u64>> {
+    let hashes = db.find_image_hashes()?;
+    Ok(within(&hashes, hash, threshold(), None).first().copied())
+}
+
+/// 为旧版本保存的图片补算哈希，由后台任务调用
+pub fn backfill(id: u64) -> Result<()> {
+    let db = SqliteDB::new();
+    let record = db.find_by_id(id)?;
+    let image: ImageDataDB = json_util::parse(&record.content).unwrap_or_default();
+    // 无法解码的图片也写入占位值，避免每次重试
+    let hash = img_util::base64_to_rgba8(&image.base64)
+        .and_then(|img| img_util::dhash(&img))
+        .map(to_hex)
+        .unwrap_or_else(|_| "-".into());
+    db.set_phash(id, &hash)
+}
+
+/// 和该图片视觉上相同的其它图片，按相似程度排序，旧图片在后台补算哈希之前不参与比较
+pub fn similar_images(id: u64) -> Result<Vec<Record>> {
+    let db = SqliteDB::new();
+    let hashes = db.find_image_hashes()?;
+    let hash = match hashes
+        .iter()
+        .find(|(i, _)| *i == id)
+        .and_then(|(_, h)| parse_hex(h))
+    {
+        Some(hash) => hash,
+        None => return Ok(vec![]),
+    };
+    within(&hashes, hash, threshold(), Some(id))
+        .into_iter()
+        .map(|other| db.find_list_item_by_id(other))
+        .collect()
+}
+
+#[test]
+fn test_similar_images() {
+    let hashes = vec![
+        (1, to_hex(0b1111)),
+        (2, to_hex(0b0111)),
+        (3, to_hex(u64::MAX)),
+        (4, "-".to_string()),
+    ];
+    assert_eq!(within(&hashes, 0b1111, 6, None), vec![1, 2]);
+    assert_eq!(within(&hashes, 0b1111, 6, Some(1)), vec![2]);
+    assert!(within(&hashes, 0, 2, None).is_empty());
+    assert_eq!(parse_hex(&to_hex(u64::MAX)), Some(u64::MAX));
+}
@@ -0,0 +1,28 @@
+use super::handle::{Handle, MsgTypeEnum};
+use crate::log_err;
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 固定后历史窗口保持在最前，失去焦点时不自动隐藏，方便连续拖动多条记录
+static PINNED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_pinned() -> bool {
+    PINNED.load(Ordering::SeqCst)
+}
+
+/// 已打开的窗口立即生效，之后新建的窗口在创建时读取
+pub fn set_pinned(pinned: bool) -> Result<()> {
+    PINNED.store(pinned, Ordering::SeqCst);
+    if let Some(window) = Handle::global().get_window() {
+        window.set_always_on_top(pinned)?;
+    }
+    log_err!(Handle::update_systray_select_item());
+    Handle::notice_to_window(MsgTypeEnum::ChangePinned, pinned)
+}
+
+/// 切换固定状态，返回切换后的状态
+pub fn toggle() -> Result<bool> {
+    let pinned = !is_pinned();
+    set_pinned(pinned)?;
+    Ok(pinned)
+}
@@ -0,0 +1,329 @@
+use super::database::Record;
+use crate::utils::{dirs, json_util};
+use anyhow::{anyhow, bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Component, Path};
+use std::sync::Arc;
+use wasmi::{Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+const MANIFEST_FILE: &str = "plugin.json";
+/// 每次调用可执行的指令上限，防止插件死循环卡住剪切板监听
+const FUEL_PER_CALL: u64 = 50_000_000;
+/// 插件返回内容的长度上限
+const MAX_OUTPUT_LEN: usize = 16 * 1024 * 1024;
+/// 每个实例线性内存的上限，超过时 memory.grow 失败
+const MAX_MEMORY_SIZE: usize = 64 * 1024 * 1024;
+pub const HOOKS: [&str; 3] = ["on_capture", "transform", "custom_action"];
+
+/// 插件目录下的 plugin.json
+/// {"name": "url-cleaner", "version": "0.1.0", "wasm": "plugin.wasm",
+///  "hooks": ["transform"], "actions": [{"id": "upper", "title": "To Upper Case"}]}
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Manifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    /// wasm 文件，相对插件目录
+    pub wasm: String,
+    #[serde(default)]
+    pub hooks: Vec<String>,
+    /// custom_action 提供的动作，展示在命令面板中
+    #[serde(default)]
+    pub actions: Vec<PluginAction>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PluginAction {
+    pub id: String,
+    pub title: String,
+}
+
+/// 加载结果，前端设置页展示
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginInfo {
+    pub dir: String,
+    pub manifest: Option<Manifest>,
+    pub error: Option<String>,
+}
+
+struct Plugin {
+    manifest: Manifest,
+    engine: Engine,
+    module: Module,
+}
+
+#[derive(Default)]
+struct PluginHost {
+    plugins: Vec<Arc<Plugin>>,
+    infos: Vec<PluginInfo>,
+    /// 空闲释放后为 false，下次调用插件时重新加载
+    loaded: bool,
+}
+
+fn host() -> &'static Mutex<PluginHost> {
+    static HOST: OnceCell<Mutex<PluginHost>> = OnceCell::new();
+    HOST.get_or_init(|| Mutex::new(PluginHost::default()))
+}
+
+impl Manifest {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() || self.wasm.is_empty() {
+            bail!("plugin name and wasm can not be empty");
+        }
+        // 只允许插件目录下的相对路径，绝对路径 join 后会替换掉插件目录
+        if Path::new(&self.wasm)
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            bail!("plugin wasm must be inside the plugin dir");
+        }
+        if let Some(hook) = self.hooks.iter().find(|h| !HOOKS.contains(&h.as_str())) {
+            bail!("unsupported plugin hook \"{}\"", hook);
+        }
+        if !self.actions.is_empty() && !self.has_hook("custom_action") {
+            bail!("plugin actions require the custom_action hook");
+        }
+        Ok(())
+    }
+
+    fn has_hook(&self, hook: &str) -> bool {
+        self.hooks.iter().any(|h| h == hook)
+    }
+}
+
+impl Plugin {
+    fn load(dir: &Path) -> Result<Self> {
+        let manifest: Manifest = json_util::read(&dir.join(MANIFEST_FILE))?;
+        manifest.validate()?;
+        let bytes = fs::read(dir.join(&manifest.wasm))?;
+        let mut config = wasmi::Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, &bytes[..]).map_err(|e| anyhow!("{}", e))?;
+        Ok(Plugin {
+            manifest,
+            engine,
+            module,
+        })
+    }
+
+    /// 调用插件导出的 hook，输入输出均为 UTF-8 JSON
+    /// 插件需导出 memory、alloc(len) -> ptr，hook(ptr, len) -> (out_ptr << 32 | out_len)
+    /// 每次调用都新建实例且不提供任何导入函数，插件无法访问文件、网络等
+    fn call(&self, hook: &str, input: &Value) -> Result<Option<Value>> {
+        let input = serde_json::to_vec(input)?;
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY_SIZE)
+            .instances(1)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store
+            .add_fuel(FUEL_PER_CALL)
+            .map_err(|e| anyhow!("{}", e))?;
+        let linker = <Linker<StoreLimits>>::new(&self.engine);
+        let instance = linker
+            .instantiate(&mut store, &self.module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| anyhow!("{}", e))?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| anyhow!("plugin does not export memory"))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&store, "alloc")
+            .map_err(|e| anyhow!("{}", e))?;
+        let func = instance
+            .get_typed_func::<(i32, i32), i64>(&store, hook)
+            .map_err(|e| anyhow!("{}", e))?;
+        let ptr = alloc
+            .call(&mut store, input.len() as i32)
+            .map_err(|e| anyhow!("{}", e))?;
+        memory
+            .write(&mut store, ptr as usize, &input)
+            .map_err(|e| anyhow!("{}", e))?;
+        let packed = func
+            .call(&mut store, (ptr, input.len() as i32))
+            .map_err(|e| anyhow!("{}", e))?;
+        let (out_ptr, out_len) = unpack(packed);
+        if out_len == 0 {
+            return Ok(None);
+        }
+        if out_len > MAX_OUTPUT_LEN {
+            bail!("plugin output too large: {} bytes", out_len);
+        }
+        let mut output = vec![0u8; out_len];
+        memory
+            .read(&store, out_ptr, &mut output)
+            .map_err(|e| anyhow!("{}", e))?;
+        Ok(Some(serde_json::from_slice(&output)?))
+    }
+}
+
+fn unpack(packed: i64) -> (usize, usize) {
+    let packed = packed as u64;
+    ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize)
+}
+
+/// 加载 plugins 目录下的所有插件，单个插件出错不影响其它插件
+pub fn load_all() -> Result<()> {
+    let plugins_dir = dirs::plugins_dir()?;
+    if !plugins_dir.exists() {
+        fs::create_dir_all(&plugins_dir)?;
+    }
+    let mut plugins = vec![];
+    let mut infos = vec![];
+    for entry in fs::read_dir(&plugins_dir)? {
+        let dir = entry?.path();
+        if !dir.is_dir() {
+            continue;
+        }
+        let dir_name = dir.display().to_string();
+        match Plugin::load(&dir) {
+            Ok(plugin) => {
+                println!(
+                    "load plugin {} {}",
+                    plugin.manifest.name, plugin.manifest.version
+                );
+                infos.push(PluginInfo {
+                    dir: dir_name,
+                    manifest: Some(plugin.manifest.clone()),
+                    error: None,
+                });
+                plugins.push(Arc::new(plugin));
+            }
+            Err(err) => {
+                println!("load plugin {} error: {}", dir_name, err);
+                infos.push(PluginInfo {
+                    dir: dir_name,
+                    manifest: None,
+                    error: Some(err.to_string()),
+                });
+            }
+        }
+    }
+    *host().lock() = PluginHost {
+        plugins,
+        infos,
+        loaded: true,
+    };
+    Ok(())
+}
+
+/// 释放已编译的插件，保留加载结果供设置页展示
+pub fn release() {
+    let mut host = host().lock();
+    host.plugins.clear();
+    host.loaded = false;
+}
+
+pub fn plugin_infos() -> Vec<PluginInfo> {
+    host().lock().infos.clone()
+}
+
+fn plugins_with_hook(hook: &str) -> Vec<Arc<Plugin>> {
+    if !host().lock().loaded {
+        if let Err(err) = load_all() {
+            println!("load plugins error: {}", err);
+        }
+    }
+    host()
+        .lock()
+        .plugins
+        .iter()
+        .filter(|p| p.manifest.has_hook(hook))
+        .cloned()
+        .collect()
+}
+
+/// 记录新复制的文本前调用：on_capture 返回 {"skip": true} 时不记录，
+/// transform 返回 {"content": "..."} 时替换内容，插件出错时忽略该插件
+pub fn process_capture(mut content: String) -> Option<String> {
+    for plugin in plugins_with_hook("on_capture") {
+        let input = json!({ "content": content, "data_type": "text" });
+        match plugin.call("on_capture", &input) {
+            Ok(Some(output)) if output["skip"].as_bool() == Some(true) => return None,
+            Ok(_) => {}
+            Err(err) => println!("plugin {} on_capture error: {}", plugin.manifest.name, err),
+        }
+    }
+    for plugin in plugins_with_hook("transform") {
+        let input = json!({ "content": content });
+        match plugin.call("transform", &input) {
+            Ok(Some(output)) => {
+                if let Some(transformed) = output["content"].as_str() {
+                    content = transformed.to_string();
+                }
+            }
+            Ok(None) => {}
+            Err(err) => println!("plugin {} transform error: {}", plugin.manifest.name, err),
+        }
+    }
+    Some(content)
+}
+
+/// 所有插件提供的动作：(plugin 名, 动作)
+pub fn actions() -> Vec<(String, PluginAction)> {
+    plugins_with_hook("custom_action")
+        .iter()
+        .flat_map(|p| {
+            p.manifest
+                .actions
+                .iter()
+                .map(|a| (p.manifest.name.clone(), a.clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// 对记录执行插件动作，返回 {"content": "..."} 时作为新的剪切板内容
+pub fn run_action(plugin_name: &str, action: &str, record: &Record) -> Result<Option<String>> {
+    let plugin = plugins_with_hook("custom_action")
+        .into_iter()
+        .find(|p| p.manifest.name == plugin_name)
+        .ok_or_else(|| anyhow!("plugin \"{}\" not found", plugin_name))?;
+    if !plugin.manifest.actions.iter().any(|a| a.id == action) {
+        bail!("plugin \"{}\" has no action \"{}\"", plugin_name, action);
+    }
+    let input = json!({
+        "action": action,
+        "record": {
+            "id": record.id,
+            "content": record.content,
+            "data_type": record.data_type,
+            "tags": record.tags,
+        },
+    });
+    let output = plugin.call("custom_action", &input)?;
+    Ok(output.and_then(|o| o["content"].as_str().map(String::from)))
+}
+
+#[test]
+fn test_manifest() {
+    let manifest: Manifest = serde_json::from_str(
+        r#"{"name": "upper", "version": "0.1.0", "wasm": "plugin.wasm",
+            "hooks": ["custom_action"], "actions": [{"id": "upper", "title": "To Upper Case"}]}"#,
+    )
+    .unwrap();
+    assert!(manifest.validate().is_ok());
+    let mut bad = manifest.clone();
+    bad.hooks = vec!["on_paste".into()];
+    assert!(bad.validate().is_err());
+    let mut bad = manifest.clone();
+    bad.hooks = vec![];
+    assert!(bad.validate().is_err());
+    let mut bad = manifest.clone();
+    bad.wasm = "../other.wasm".into();
+    assert!(bad.validate().is_err());
+    let mut bad = manifest.clone();
+    bad.wasm = "/tmp/other.wasm".into();
+    assert!(bad.validate().is_err());
+    let mut nested = manifest;
+    nested.wasm = "./build/plugin.wasm".into();
+    assert!(nested.validate().is_ok());
+    assert_eq!(unpack((16i64 << 32) | 5), (16, 5));
+}
@@ -0,0 +1,312 @@
+use super::window_state;
+use crate::config::Config;
+use anyhow::Result;
+use tauri::{LogicalSize, Monitor, PhysicalPosition, Window};
+
+/// 屏幕坐标，与 tauri Monitor::position 使用同一坐标系（物理像素）
+#[derive(Debug, Clone, Copy)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// 按配置摆放主窗口：center 居中，cursor 跟随鼠标，caret 跟随输入光标（取不到时退回鼠标位置）
+/// remember 回到该屏幕上次的位置，没有记录时居中
+/// 多屏时窗口出现在鼠标 / 光标所在的屏幕，居中时优先选前台窗口所在的屏幕
+pub fn place_main_window(window: &Window, width: f64, height: f64) -> Result<()> {
+    let mode = { Config::common().latest().popup_position.clone() };
+    let mode = mode.unwrap_or_else(|| "cursor".into());
+    let cursor = platform::cursor_position(window);
+    let anchor = match mode.as_str() {
+        "cursor" => cursor,
+        "caret" => platform::caret_position().or(cursor),
+        _ => None,
+    };
+    let target = match mode.as_str() {
+        "remember" => cursor,
+        _ => anchor,
+    };
+    let target = target.or_else(platform::focused_window_center).or(cursor);
+    let monitors = window.available_monitors()?;
+    let monitor = target
+        .and_then(|point| monitors.iter().find(|m| contains(m, point)).cloned())
+        .or(window.current_monitor()?)
+        .or_else(|| monitors.first().cloned());
+    let monitor = match monitor {
+        Some(monitor) => monitor,
+        None => return Ok(window.center()?),
+    };
+    // 按目标屏幕的缩放比例换算窗口的物理尺寸
+    let scale = monitor.scale_factor();
+    let area = monitor_rect(&monitor);
+    let remembered = match mode.as_str() {
+        "remember" => window_state::position_on(&monitor).map(|(x, y)| Point { x, y }),
+        _ => None,
+    };
+    let (x, y) = match anchor.or(remembered) {
+        Some(point) => clamp(point, width * scale, height * scale, area),
+        None => center_in(width * scale, height * scale, area),
+    };
+    window.set_position(PhysicalPosition::new(x as i32, y as i32))?;
+    // 在缩放比例不同的屏幕间移动后，重新按逻辑尺寸设置大小
+    window.set_size(LogicalSize::new(width, height))?;
+    Ok(())
+}
+
+fn monitor_rect(monitor: &Monitor) -> (f64, f64, f64, f64) {
+    let position = monitor.position();
+    let size = monitor.size();
+    (
+        position.x as f64,
+        position.y as f64,
+        size.width as f64,
+        size.height as f64,
+    )
+}
+
+fn contains(monitor: &Monitor, point: Point) -> bool {
+    let (x, y, width, height) = monitor_rect(monitor);
+    point.x >= x && point.x < x + width && point.y >= y && point.y < y + height
+}
+
+/// 在屏幕区域内居中
+pub fn center_in(width: f64, height: f64, area: (f64, f64, f64, f64)) -> (f64, f64) {
+    let (area_x, area_y, area_width, area_height) = area;
+    let x = area_x + ((area_width - width) / 2.0).max(0.0);
+    let y = area_y + ((area_height - height) / 2.0).max(0.0);
+    (x, y)
+}
+
+/// 以 point 为左上角放置窗口，超出屏幕的部分向内收回
+pub fn clamp(point: Point, width: f64, height: f64, area: (f64, f64, f64, f64)) -> (f64, f64) {
+    let (area_x, area_y, area_width, area_height) = area;
+    let x = point.x.min(area_x + area_width - width).max(area_x);
+    let y = point.y.min(area_y + area_height - height).max(area_y);
+    (x, y)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::Point;
+    use objc::{class, msg_send, sel, sel_impl};
+    use tauri::Window;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct NSPoint {
+        x: f64,
+        y: f64,
+    }
+
+    /// NSEvent 返回以主屏左下角为原点的逻辑坐标，这里转换为左上角原点的物理像素
+    pub fn cursor_position(window: &Window) -> Option<Point> {
+        let location: NSPoint = unsafe { msg_send![class!(NSEvent), mouseLocation] };
+        let primary = window.primary_monitor().ok()??;
+        let primary_height = primary.size().height as f64 / primary.scale_factor();
+        let logical = Point {
+            x: location.x,
+            y: primary_height - location.y,
+        };
+        let monitors = window.available_monitors().ok()?;
+        let scale = monitors
+            .iter()
+            .find(|m| {
+                let scale = m.scale_factor();
+                let x = m.position().x as f64 / scale;
+                let y = m.position().y as f64 / scale;
+                let width = m.size().width as f64 / scale;
+                let height = m.size().height as f64 / scale;
+                logical.x >= x && logical.x < x + width && logical.y >= y && logical.y < y + height
+            })
+            .map(|m| m.scale_factor())
+            .unwrap_or_else(|| primary.scale_factor());
+        Some(Point {
+            x: logical.x * scale,
+            y: logical.y * scale,
+        })
+    }
+
+    /// 需要辅助功能权限才能读取其它应用的输入光标，暂不支持
+    pub fn caret_position() -> Option<Point> {
+        None
+    }
+
+    /// 同样需要辅助功能权限，暂不支持
+    pub fn focused_window_center() -> Option<Point> {
+        None
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::Point;
+    use tauri::Window;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct POINT {
+        x: i32,
+        y: i32,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct RECT {
+        left: i32,
+        top: i32,
+        right: i32,
+        bottom: i32,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct GUITHREADINFO {
+        cb_size: u32,
+        flags: u32,
+        hwnd_active: isize,
+        hwnd_focus: isize,
+        hwnd_capture: isize,
+        hwnd_menu_owner: isize,
+        hwnd_move_size: isize,
+        hwnd_caret: isize,
+        rc_caret: RECT,
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetCursorPos(point: *mut POINT) -> i32;
+        fn GetGUIThreadInfo(thread_id: u32, info: *mut GUITHREADINFO) -> i32;
+        fn ClientToScreen(hwnd: isize, point: *mut POINT) -> i32;
+        fn GetForegroundWindow() -> isize;
+        fn GetWindowRect(hwnd: isize, rect: *mut RECT) -> i32;
+    }
+
+    pub fn cursor_position(_window: &Window) -> Option<Point> {
+        let mut point = POINT::default();
+        if unsafe { GetCursorPos(&mut point) } == 0 {
+            return None;
+        }
+        Some(Point {
+            x: point.x as f64,
+            y: point.y as f64,
+        })
+    }
+
+    /// 取前台线程的输入光标，返回光标左下角
+    pub fn caret_position() -> Option<Point> {
+        let mut info = GUITHREADINFO {
+            cb_size: std::mem::size_of::<GUITHREADINFO>() as u32,
+            ..Default::default()
+        };
+        if unsafe { GetGUIThreadInfo(0, &mut info) } == 0 || info.hwnd_caret == 0 {
+            return None;
+        }
+        let mut point = POINT {
+            x: info.rc_caret.left,
+            y: info.rc_caret.bottom,
+        };
+        if unsafe { ClientToScreen(info.hwnd_caret, &mut point) } == 0 {
+            return None;
+        }
+        Some(Point {
+            x: point.x as f64,
+            y: point.y as f64,
+        })
+    }
+
+    pub fn focused_window_center() -> Option<Point> {
+        let hwnd = unsafe { GetForegroundWindow() };
+        let mut rect = RECT::default();
+        if hwnd == 0 || unsafe { GetWindowRect(hwnd, &mut rect) } == 0 {
+            return None;
+        }
+        Some(Point {
+            x: (rect.left + rect.right) as f64 / 2.0,
+            y: (rect.top + rect.bottom) as f64 / 2.0,
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::Point;
+    use std::process::Command;
+    use tauri::Window;
+
+    /// X11 下通过 xdotool 获取鼠标位置，Wayland 不允许读取全局鼠标位置
+    pub fn cursor_position(_window: &Window) -> Option<Point> {
+        let output = Command::new("xdotool")
+            .args(["getmouselocation", "--shell"])
+            .output()
+            .ok()?;
+        let output = String::from_utf8_lossy(&output.stdout);
+        let mut x = None;
+        let mut y = None;
+        for line in output.lines() {
+            if let Some(value) = line.strip_prefix("X=") {
+                x = value.parse::<f64>().ok();
+            } else if let Some(value) = line.strip_prefix("Y=") {
+                y = value.parse::<f64>().ok();
+            }
+        }
+        Some(Point { x: x?, y: y? })
+    }
+
+    pub fn caret_position() -> Option<Point> {
+        None
+    }
+
+    pub fn focused_window_center() -> Option<Point> {
+        let output = Command::new("xdotool")
+            .args(["getactivewindow", "getwindowgeometry", "--shell"])
+            .output()
+            .ok()?;
+        let output = String::from_utf8_lossy(&output.stdout);
+        let value = |key: &str| {
+            output
+                .lines()
+                .find_map(|line| line.strip_prefix(key))
+                .and_then(|v| v.parse::<f64>().ok())
+        };
+        Some(Point {
+            x: value("X=")? + value("WIDTH=")? / 2.0,
+            y: value("Y=")? + value("HEIGHT=")? / 2.0,
+        })
+    }
+}
+
+#[test]
+fn test_clamp() {
+    let area = (0.0, 0.0, 1920.0, 1080.0);
+    let (x, y) = clamp(Point { x: 100.0, y: 100.0 }, 800.0, 600.0, area);
+    assert_eq!((x, y), (100.0, 100.0));
+    let (x, y) = clamp(
+        Point {
+            x: 1800.0,
+            y: 1000.0,
+        },
+        800.0,
+        600.0,
+        area,
+    );
+    assert_eq!((x, y), (1120.0, 480.0));
+    let area = (1920.0, 0.0, 1280.0, 720.0);
+    let (x, y) = clamp(
+        Point {
+            x: 1900.0,
+            y: -10.0,
+        },
+        800.0,
+        600.0,
+        area,
+    );
+    assert_eq!((x, y), (1920.0, 0.0));
+}
+
+#[test]
+fn test_center_in() {
+    let area = (1920.0, 0.0, 2560.0, 1440.0);
+    assert_eq!(center_in(800.0, 600.0, area), (2800.0, 420.0));
+    // 窗口比屏幕大时贴着左上角
+    assert_eq!(center_in(3000.0, 600.0, area), (1920.0, 420.0));
+}
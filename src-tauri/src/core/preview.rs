@@ -0,0 +1,122 @@
+use super::database::{Record, SqliteDB};
+use super::handle::Handle;
+use super::vault::Vault;
+use anyhow::Result;
+use serde::Serialize;
+
+const PREVIEW_LABEL: &str = "preview";
+/// 预览窗口首次推送的字符数，其余内容滚动时按需读取
+const FIRST_CHUNK_LEN: u64 = 64 * 1024;
+/// 单次读取的字符数上限
+const MAX_CHUNK_LEN: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreviewPayload {
+    pub id: u64,
+    pub data_type: String,
+    /// 文本记录只包含开头的一段
+    pub content: String,
+    pub create_time: u64,
+    /// 内容总字符数
+    pub total_length: u64,
+    /// 已推送的字符数，即下一段的 offset
+    pub next_offset: u64,
+    /// 关联的记录，content 为预览内容
+    pub related: Vec<Record>,
+}
+
+/// offset 和长度均按字符（而非字节）计算
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentChunk {
+    pub id: u64,
+    pub offset: u64,
+    pub content: String,
+    pub next_offset: u64,
+    pub total_length: u64,
+    pub has_more: bool,
+}
+
+/// 读取记录内容中 [offset, offset + len) 的字符，不用把整条大记录传给前端
+/// 加密记录需先解密，只能整体读出后再截取
+pub fn read_chunk(id: u64, offset: u64, len: u64) -> Result<ContentChunk> {
+    let db = SqliteDB::new();
+    let len = len.min(MAX_CHUNK_LEN);
+    let item = db.find_list_item_by_id(id)?;
+    let (content, total_length) = if item.is_secure {
+        let content = Vault::global().reveal(&db.find_by_id(id)?)?;
+        let total_length = content.chars().count() as u64;
+        let chunk = content
+            .chars()
+            .skip(offset as usize)
+            .take(len as usize)
+            .collect();
+        (chunk, total_length)
+    } else {
+        db.read_content_chunk(id, offset, len)?
+    };
+    let next_offset = offset + content.chars().count() as u64;
+    Ok(ContentChunk {
+        id,
+        offset,
+        content,
+        next_offset,
+        total_length,
+        has_more: next_offset < total_length,
+    })
+}
+
+fn load(id: u64) -> Result<PreviewPayload> {
+    let db = SqliteDB::new();
+    let item = db.find_list_item_by_id(id)?;
+    let related = db.find_linked_records(id)?;
+    // 图片和 SVG 需要完整数据才能显示
+    if item.data_type == "image" || item.data_type == "svg" {
+        let record = db.find_by_id(id)?;
+        let content = Vault::global().reveal(&record)?;
+        let total_length = content.chars().count() as u64;
+        return Ok(PreviewPayload {
+            id,
+            total_length,
+            next_offset: total_length,
+            content,
+            data_type: record.data_type,
+            create_time: record.create_time,
+            related,
+        });
+    }
+    let chunk = read_chunk(id, 0, FIRST_CHUNK_LEN)?;
+    Ok(PreviewPayload {
+        id,
+        content: chunk.content,
+        data_type: item.data_type,
+        create_time: item.create_time,
+        total_length: chunk.total_length,
+        next_offset: chunk.next_offset,
+        related,
+    })
+}
+
+/// 把记录内容推送到预览窗口，预览窗口未打开时忽略
+/// 读取和序列化放到后台线程，不阻塞主界面的选择
+pub fn show_record(id: u64) {
+    if Handle::global()
+        .get_window_by_label(PREVIEW_LABEL)
+        .is_none()
+    {
+        return;
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let payload = match load(id) {
+            Ok(payload) => payload,
+            Err(err) => {
+                println!("load preview error: {}", err);
+                return;
+            }
+        };
+        if let Some(window) = Handle::global().get_window_by_label(PREVIEW_LABEL) {
+            if let Err(err) = window.emit("lanaya://preview-record", payload) {
+                println!("emit preview error: {}", err);
+            }
+        }
+    });
+}
@@ -0,0 +1,125 @@
+use super::database::{Record, SqliteDB};
+use super::thumbnail;
+use super::vault::Vault;
+use crate::log_err;
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde::Serialize;
+
+/// 预览时最多返回的记录数
+const PREVIEW_LIMIT: usize = 100;
+
+/// 按正则或字符串匹配记录的内容，字符串不区分大小写
+pub enum Matcher {
+    Regex(Regex),
+    Contains(String),
+}
+
+impl Matcher {
+    pub fn new(pattern: &str, regex: bool) -> Result<Self> {
+        if pattern.is_empty() {
+            bail!("the pattern can not be empty");
+        }
+        Ok(match regex {
+            true => Matcher::Regex(Regex::new(pattern)?),
+            false => Matcher::Contains(pattern.to_lowercase()),
+        })
+    }
+
+    pub fn is_match(&self, content: &str) -> bool {
+        match self {
+            Matcher::Regex(re) => re.is_match(content),
+            Matcher::Contains(s) => content.to_lowercase().contains(s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PurgeResult {
+    /// 匹配的记录数，包括下面跳过的
+    pub matched: usize,
+    pub deleted: usize,
+    /// 匹配但已锁定的记录，需要先解锁
+    pub locked: usize,
+    /// 保险箱未解锁，无法检查内容的加密记录
+    pub unreadable: usize,
+    /// 匹配的记录 id（最多 PREVIEW_LIMIT 条），dry run 时用于预览
+    pub ids: Vec<u64>,
+    /// 回收站中匹配并粉碎的记录数
+    pub trashed: usize,
+}
+
+/// 内容、备注或原始链接匹配，加密记录在保险箱未解锁时返回 None
+fn matches(matcher: &Matcher, record: &Record, original: Option<&str>) -> Option<bool> {
+    let vault = Vault::global();
+    let content = match record.is_secure {
+        true if !vault.is_unlocked() => None,
+        _ => vault.reveal(record).ok(),
+    };
+    let matched = [content.as_deref(), record.note.as_deref(), original]
+        .into_iter()
+        .flatten()
+        .any(|text| matcher.is_match(text));
+    // 未解锁时只能按备注判断
+    match content {
+        None if !matched => None,
+        _ => Some(matched),
+    }
+}
+
+/// 删除内容（或清理跟踪参数前的原始链接、备注）匹配 pattern 的记录，回收站中的一并删除
+/// 匹配的通常是泄露的密码等敏感内容，删除时粉碎，不进入回收站，缩略图也一起粉碎
+pub fn purge_matching(pattern: &str, regex: bool, dry_run: bool) -> Result<PurgeResult> {
+    let matcher = Matcher::new(pattern, regex)?;
+    let db = SqliteDB::new();
+    let mut res = PurgeResult::default();
+    let mut targets = vec![];
+    for (record, original) in db.find_text_contents(false)? {
+        match matches(&matcher, &record, original.as_deref()) {
+            None => {
+                res.unreadable += 1;
+                continue;
+            }
+            Some(false) => continue,
+            Some(true) => {}
+        }
+        res.matched += 1;
+        if res.ids.len() < PREVIEW_LIMIT {
+            res.ids.push(record.id);
+        }
+        match record.is_locked {
+            true => res.locked += 1,
+            false => targets.push(record.id),
+        }
+    }
+    let mut trashed = vec![];
+    for (record, original) in db.find_text_contents(true)? {
+        match matches(&matcher, &record, original.as_deref()) {
+            Some(true) => trashed.push(record.id),
+            Some(false) => {}
+            None => res.unreadable += 1,
+        }
+    }
+    res.trashed = trashed.len();
+    if dry_run {
+        return Ok(res);
+    }
+    res.deleted = db.shred(&targets)?;
+    res.trashed = db.shred_trash(&trashed)?;
+    for id in targets.iter().chain(trashed.iter()) {
+        log_err!(thumbnail::shred(*id));
+    }
+    Ok(res)
+}
+
+#[test]
+fn test_purge_matcher() {
+    let contains = Matcher::new("Hunter2", false).unwrap();
+    assert!(contains.is_match("password: hunter2!"));
+    assert!(!contains.is_match("hunter 2"));
+    let regex = Matcher::new(r"sk-[A-Za-z0-9]{8,}", true).unwrap();
+    assert!(regex.is_match("key=sk-abcdEFGH1234"));
+    assert!(!regex.is_match("sk-short"));
+    assert!(Matcher::new("(", true).is_err());
+    assert!(Matcher::new("", false).is_err());
+}
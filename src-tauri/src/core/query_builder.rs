@@ -0,0 +1,98 @@
+use rusqlite::types::Value;
+use rusqlite::ParamsFromIter;
+
+/// 组合 where 条件和参数，占位符的序号按绑定的顺序生成，参数保留原本的类型
+#[derive(Debug, Default)]
+pub struct QueryBuilder {
+    conditions: Vec<String>,
+    params: Vec<Value>,
+}
+
+impl QueryBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 绑定一个参数，返回它的占位符，用于 select 的字段、limit 等 where 之外的位置
+    pub fn bind(&mut self, value: impl Into<Value>) -> String {
+        self.params.push(value.into());
+        format!("?{}", self.params.len())
+    }
+
+    /// 追加一个 and 条件，{0}、{1} 替换为 values 对应的占位符，可以重复引用
+    pub fn and(&mut self, condition: &str, values: Vec<Value>) -> &mut Self {
+        let mut condition = condition.to_string();
+        for (i, value) in values.into_iter().enumerate() {
+            let holder = self.bind(value);
+            condition = condition.replace(&format!("{{{}}}", i), &holder);
+        }
+        self.conditions.push(condition);
+        self
+    }
+
+    /// 任一参数满足 condition 即可，condition 中用 {0} 引用参数，values 为空时不追加
+    pub fn and_any(&mut self, condition: &str, values: Vec<Value>) -> &mut Self {
+        if values.is_empty() {
+            return self;
+        }
+        let any: Vec<String> = values
+            .into_iter()
+            .map(|value| condition.replace("{0}", &self.bind(value)))
+            .collect();
+        self.conditions.push(format!("({})", any.join(" or ")));
+        self
+    }
+
+    /// column in (...)，values 为空时不追加
+    pub fn and_in(&mut self, column: &str, values: Vec<Value>) -> &mut Self {
+        if values.is_empty() {
+            return self;
+        }
+        let holders: Vec<String> = values.into_iter().map(|value| self.bind(value)).collect();
+        self.conditions
+            .push(format!("{} in ({})", column, holders.join(",")));
+        self
+    }
+
+    /// 以 " and " 开头的条件，拼在 "where 1=1" 等已有条件之后，没有条件时为空
+    pub fn where_clause(&self) -> String {
+        self.conditions
+            .iter()
+            .map(|c| format!(" and {}", c))
+            .collect()
+    }
+
+    pub fn params(&self) -> ParamsFromIter<std::slice::Iter<'_, Value>> {
+        rusqlite::params_from_iter(self.params.iter())
+    }
+}
+
+/// 整数参数，u64 不能直接转换为 sqlite 的整数
+pub fn int(value: u64) -> Value {
+    Value::Integer(value as i64)
+}
+
+pub fn text(value: impl Into<String>) -> Value {
+    Value::Text(value.into())
+}
+
+#[test]
+fn test_query_builder() {
+    let mut q = QueryBuilder::new();
+    q.and("(content like {0} or preview like {0})", vec![text("%a%")])
+        .and(
+            "(create_time < {0} or (create_time = {0} and id < {1}))",
+            vec![int(5), int(3)],
+        )
+        .and_in("data_type", vec![text("text"), text("image")])
+        .and_in("id", vec![])
+        .and_any("tags like {0}", vec![text("%x%"), text("%y%")]);
+    let limit = q.bind(int(20));
+    assert_eq!(
+        q.where_clause(),
+        " and (content like ?1 or preview like ?1) and (create_time < ?2 or (create_time = ?2 and id < ?3)) and data_type in (?4,?5) and (tags like ?6 or tags like ?7)"
+    );
+    assert_eq!(limit, "?8");
+    assert_eq!(q.params[1], Value::Integer(5));
+    assert_eq!(q.params.len(), 8);
+}
@@ -0,0 +1,149 @@
+use super::database::Record;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+/// 缓存最近读取的记录条数
+const RECORD_CAPACITY: usize = 200;
+/// 缓存的查询结果个数，打开弹窗时的首页、常用搜索等
+const QUERY_CAPACITY: usize = 32;
+/// 超过该长度的记录（如大图）不缓存，避免占用过多内存
+const MAX_CACHED_CONTENT: usize = 256 * 1024;
+
+/// 容量很小，用按访问顺序排列的队列实现即可
+pub struct Lru<V> {
+    capacity: usize,
+    entries: VecDeque<(String, V)>,
+}
+
+impl<V: Clone> Lru<V> {
+    pub fn new(capacity: usize) -> Self {
+        Lru {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<V> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(index)?;
+        let value = entry.1.clone();
+        self.entries.push_front(entry);
+        Some(value)
+    }
+
+    pub fn put(&mut self, key: String, value: V) {
+        if let Some(index) = self.entries.iter().position(|(k, _)| *k == key) {
+            self.entries.remove(index);
+        }
+        self.entries.push_front((key, value));
+        self.entries.truncate(self.capacity);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// SQLite 前的内存缓存，记录有任何写入时整体失效
+/// generation 在每次失效时加一，查询期间发生过写入的结果不放入缓存
+struct RecordCache {
+    generation: u64,
+    /// 数据库文件的修改时间，用于发现命令行等其它进程的写入
+    db_modified: Option<SystemTime>,
+    records: Lru<Record>,
+    queries: Lru<Vec<Record>>,
+}
+
+fn cache() -> &'static Mutex<RecordCache> {
+    static CACHE: OnceCell<Mutex<RecordCache>> = OnceCell::new();
+    CACHE.get_or_init(|| {
+        Mutex::new(RecordCache {
+            generation: 0,
+            db_modified: None,
+            records: Lru::new(RECORD_CAPACITY),
+            queries: Lru::new(QUERY_CAPACITY),
+        })
+    })
+}
+
+impl RecordCache {
+    fn invalidate(&mut self) {
+        self.generation += 1;
+        self.records.clear();
+        self.queries.clear();
+    }
+}
+
+/// 写入提交后调用
+pub fn invalidate() {
+    cache().lock().invalidate();
+}
+
+/// 空闲时调用，连同队列已分配的容量一起释放
+pub fn release() {
+    let mut cache = cache().lock();
+    cache.invalidate();
+    cache.records.entries.shrink_to_fit();
+    cache.queries.entries.shrink_to_fit();
+}
+
+/// 读取缓存前调用，数据库文件被修改过时先失效
+pub fn check_modified(modified: Option<SystemTime>) {
+    let mut cache = cache().lock();
+    if modified.is_none() || cache.db_modified != modified {
+        cache.db_modified = modified;
+        cache.invalidate();
+    }
+}
+
+pub fn generation() -> u64 {
+    cache().lock().generation
+}
+
+pub fn get_record(id: u64) -> Option<Record> {
+    cache().lock().records.get(&id.to_string())
+}
+
+pub fn put_record(generation: u64, record: &Record) {
+    if record.content.len() > MAX_CACHED_CONTENT {
+        return;
+    }
+    let mut cache = cache().lock();
+    if cache.generation == generation {
+        cache.records.put(record.id.to_string(), record.clone());
+    }
+}
+
+pub fn get_query(key: &str) -> Option<Vec<Record>> {
+    cache().lock().queries.get(key)
+}
+
+pub fn put_query(generation: u64, key: String, records: &[Record]) {
+    let size: usize = records.iter().map(|r| r.content.len()).sum();
+    if size > MAX_CACHED_CONTENT * 4 {
+        return;
+    }
+    let mut cache = cache().lock();
+    if cache.generation == generation {
+        cache.queries.put(key, records.to_vec());
+    }
+}
+
+#[test]
+fn test_lru() {
+    let mut lru = Lru::new(2);
+    lru.put("a".into(), 1);
+    lru.put("b".into(), 2);
+    assert_eq!(lru.get("a"), Some(1));
+    // b 最久未访问，被淘汰
+    lru.put("c".into(), 3);
+    assert_eq!(lru.get("b"), None);
+    assert_eq!(lru.get("a"), Some(1));
+    assert_eq!(lru.get("c"), Some(3));
+    lru.put("c".into(), 4);
+    assert_eq!(lru.get("c"), Some(4));
+    lru.clear();
+    assert_eq!(lru.get("a"), None);
+}
@@ -0,0 +1,103 @@
+use super::database::{SqliteDB, UNDO_GRACE_SECS};
+use super::handle::{Handle, MsgTypeEnum};
+use super::sync;
+use super::time_machine;
+use crate::config::Config;
+use crate::log_err;
+use anyhow::Result;
+use serde::Serialize;
+use std::time::Duration;
+
+/// 可以单独限制条数的记录类型
+pub const RECORD_TYPES: [&str; 3] = ["text", "image", "svg"];
+/// 定时清理回收站的间隔，和撤销期限相同，过期的加密记录及时删除
+const TRASH_PURGE_INTERVAL: Duration = Duration::from_secs(UNDO_GRACE_SECS);
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashPurged {
+    pub purged: usize,
+    pub time: u64,
+}
+
+/// 先删除规则设置的已过期记录，再按类型、按总数删除最旧的未收藏记录，返回删除的条数
+/// 如 record_limit 为 1000、image 为 100 时，最多保留 100 张图片和 1000 条记录
+pub fn enforce(db: &SqliteDB) -> Result<usize> {
+    // 由负责写入的那台电脑清理
+    if SqliteDB::is_read_only() {
+        return Ok(0);
+    }
+    let config = { Config::common().latest().clone() };
+    let now = chrono::Local::now().timestamp_millis() as u64;
+    let mut deleted = db.delete_expired(now)?;
+    for (data_type, limit) in config.type_record_limits.unwrap_or_default() {
+        deleted += db.delete_over_limit_by_type(&data_type, limit as usize)?;
+    }
+    if let Some(limit) = config.record_limit {
+        deleted += db.delete_over_limit(limit as usize)?;
+    }
+    Ok(deleted)
+}
+
+/// 修改上限后立即清理一次，并刷新列表和托盘
+pub fn enforce_now() {
+    std::thread::spawn(|| match enforce(&SqliteDB::new()) {
+        Ok(0) => {}
+        Ok(_) => {
+            log_err!(Handle::update_systray());
+            log_err!(Handle::notice_to_window(MsgTypeEnum::ChangeClipBoard, "ok"));
+        }
+        Err(err) => println!("enforce record limits error: {}", err),
+    });
+}
+
+/// 回收站中在此之前删除的记录需要彻底删除，关闭 auto_purge_trash 时返回 None
+pub fn trash_purge_before(now: u64) -> Option<u64> {
+    let config = Config::common().latest();
+    match config.auto_purge_trash.unwrap_or(true) {
+        true => Some(purge_before(now, config.trash_retention_days.unwrap_or(0))),
+        false => None,
+    }
+}
+
+/// 至少保留到撤销期限之后
+fn purge_before(now: u64, retention_days: u32) -> u64 {
+    let keep = (UNDO_GRACE_SECS * 1000).max(retention_days as u64 * DAY_MILLIS);
+    now.saturating_sub(keep)
+}
+
+pub fn purge_trash(db: &SqliteDB) -> Result<usize> {
+    if SqliteDB::is_read_only() {
+        return Ok(0);
+    }
+    let now = chrono::Local::now().timestamp_millis() as u64;
+    log_err!(db.purge_sync_tombstones(now.saturating_sub(sync::TOMBSTONE_RETENTION_MILLIS)));
+    log_err!(db.purge_copy_history(now.saturating_sub(time_machine::HISTORY_RETENTION_MILLIS)));
+    // 关闭 auto_purge_trash 时也删除过期的加密记录
+    db.purge_trash(trash_purge_before(now).unwrap_or(0))
+}
+
+/// 定时清理回收站，每次清理后通知前端，没有删除时 purged 为 0
+pub fn start_trash_purge() {
+    std::thread::spawn(|| loop {
+        std::thread::sleep(TRASH_PURGE_INTERVAL);
+        match purge_trash(&SqliteDB::new()) {
+            Ok(purged) => {
+                let event = TrashPurged {
+                    purged,
+                    time: chrono::Local::now().timestamp_millis() as u64,
+                };
+                log_err!(Handle::notice_to_window(MsgTypeEnum::TrashPurged, event));
+            }
+            Err(err) => println!("purge trash error: {}", err),
+        }
+    });
+}
+
+#[test]
+fn test_trash_purge_before() {
+    let now = 10 * DAY_MILLIS;
+    assert_eq!(purge_before(now, 0), now - UNDO_GRACE_SECS * 1000);
+    assert_eq!(purge_before(now, 3), 7 * DAY_MILLIS);
+    assert_eq!(purge_before(now, 30), 0);
+}
@@ -0,0 +1,296 @@
+use super::database::SqliteDB;
+use super::retention::RECORD_TYPES;
+use crate::config::Config;
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// 自动分类规则：条件都满足时执行动作，按 priority 从高到低、相同时按配置中的顺序依次匹配
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct CaptureRule {
+    pub name: String,
+    /// 为空时视为启用
+    pub enabled: Option<bool>,
+    /// 优先级，越大越先匹配，为空时为 0
+    pub priority: Option<i32>,
+    /// 内容匹配的正则表达式，只对文本记录生效
+    pub pattern: Option<String>,
+    /// 来源应用名称，不区分大小写
+    pub source_app: Option<String>,
+    /// `text` or `image`
+    pub data_type: Option<String>,
+    /// 至少复制过多少次，如常用的地址复制几次后自动收藏
+    pub min_copy_count: Option<u64>,
+    /// 添加的标签
+    pub add_tags: Option<Vec<String>>,
+    /// 为 true 时加入收藏
+    pub favorite: Option<bool>,
+    /// 多少秒后自动删除，多条规则都设置时取最短的
+    pub expire_after_secs: Option<u64>,
+    /// 匹配后不再继续匹配后面的规则
+    pub stop: Option<bool>,
+}
+
+/// 参与匹配的记录信息
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RuleInput {
+    pub content: String,
+    pub data_type: String,
+    pub source_app: Option<String>,
+    #[serde(default)]
+    pub is_secure: bool,
+    /// 包括这一次在内的复制次数
+    #[serde(default = "default_copy_count")]
+    pub copy_count: u64,
+}
+
+fn default_copy_count() -> u64 {
+    1
+}
+
+/// 规则匹配的结果，dry run 时直接返回给设置页
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RuleOutcome {
+    /// 匹配到的规则名称，按匹配顺序
+    pub matched: Vec<String>,
+    pub tags: Vec<String>,
+    pub favorite: bool,
+    pub expire_after_secs: Option<u64>,
+}
+
+impl CaptureRule {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            bail!("rule name is required");
+        }
+        if let Some(pattern) = &self.pattern {
+            if let Err(err) = Regex::new(pattern) {
+                bail!("invalid pattern in rule \"{}\": {}", self.name, err);
+            }
+        }
+        if let Some(data_type) = &self.data_type {
+            if !RECORD_TYPES.contains(&data_type.as_str()) {
+                bail!(
+                    "unsupported data type \"{}\" in rule \"{}\"",
+                    data_type,
+                    self.name
+                );
+            }
+        }
+        if self.expire_after_secs == Some(0) {
+            bail!(
+                "expire_after_secs in rule \"{}\" must be positive",
+                self.name
+            );
+        }
+        Ok(())
+    }
+
+    fn matches(&self, input: &RuleInput) -> bool {
+        if !self.enabled.unwrap_or(true) {
+            return false;
+        }
+        if let Some(data_type) = &self.data_type {
+            if *data_type != input.data_type {
+                return false;
+            }
+        }
+        if self
+            .min_copy_count
+            .map_or(false, |min| input.copy_count < min)
+        {
+            return false;
+        }
+        if let Some(app) = self.source_app.as_ref().filter(|app| !app.is_empty()) {
+            match &input.source_app {
+                Some(source) if source.eq_ignore_ascii_case(app) => {}
+                _ => return false,
+            }
+        }
+        match self.pattern.as_ref().filter(|p| !p.is_empty()) {
+            // 加密记录和图片不匹配内容
+            Some(pattern) => {
+                input.data_type == "text"
+                    && !input.is_secure
+                    && Regex::new(pattern)
+                        .map(|re| re.is_match(&input.content))
+                        .unwrap_or(false)
+            }
+            None => true,
+        }
+    }
+}
+
+pub fn evaluate(rules: &[CaptureRule], input: &RuleInput) -> RuleOutcome {
+    let mut outcome = RuleOutcome::default();
+    let mut ordered: Vec<&CaptureRule> = rules.iter().collect();
+    // 稳定排序，优先级相同的保持配置中的顺序
+    ordered.sort_by_key(|rule| std::cmp::Reverse(rule.priority.unwrap_or(0)));
+    for rule in ordered.into_iter().filter(|rule| rule.matches(input)) {
+        outcome.matched.push(rule.name.clone());
+        for tag in rule.add_tags.iter().flatten() {
+            let tag = tag.trim();
+            if !tag.is_empty() && !outcome.tags.iter().any(|t| t == tag) {
+                outcome.tags.push(tag.to_string());
+            }
+        }
+        outcome.favorite |= rule.favorite.unwrap_or(false);
+        if let Some(secs) = rule.expire_after_secs {
+            outcome.expire_after_secs =
+                Some(outcome.expire_after_secs.map_or(secs, |s| s.min(secs)));
+        }
+        if rule.stop.unwrap_or(false) {
+            break;
+        }
+    }
+    outcome
+}
+
+/// 合并已有标签，标签以逗号分隔保存
+fn merge_tags(tags: &str, extra: &[String]) -> String {
+    let mut merged: Vec<&str> = tags.split(',').filter(|t| !t.is_empty()).collect();
+    for tag in extra {
+        if !merged.contains(&tag.as_str()) {
+            merged.push(tag);
+        }
+    }
+    merged.join(",")
+}
+
+/// 采集流程中新记录入库后调用，执行匹配到的规则
+pub fn apply(db: &SqliteDB, id: u64, source_app: Option<&str>) -> Result<()> {
+    let rules = { Config::common().latest().capture_rules.clone() };
+    let rules = match rules.filter(|rules| !rules.is_empty()) {
+        Some(rules) => rules,
+        None => return Ok(()),
+    };
+    let record = db.find_by_id(id)?;
+    let outcome = evaluate(
+        &rules,
+        &RuleInput {
+            content: record.content,
+            data_type: record.data_type,
+            source_app: source_app.map(String::from),
+            is_secure: record.is_secure,
+            copy_count: record.copy_count.unwrap_or(1),
+        },
+    );
+    if outcome.matched.is_empty() {
+        return Ok(());
+    }
+    if !outcome.tags.is_empty() {
+        let tags = merge_tags(&record.tags, &outcome.tags);
+        if tags != record.tags {
+            db.save_tags(id, tags)?;
+        }
+    }
+    if outcome.favorite && !record.is_favorite {
+        db.set_favorite(&[id], true)?;
+    }
+    if let Some(secs) = outcome.expire_after_secs {
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        db.set_expire_at(id, now + secs * 1000)?;
+    }
+    Ok(())
+}
+
+/// 规则测试器：不写数据库，rules 为空时使用当前配置
+pub fn dry_run(input: &RuleInput, rules: Option<Vec<CaptureRule>>) -> Result<RuleOutcome> {
+    let rules = match rules {
+        Some(rules) => rules,
+        None => Config::common()
+            .latest()
+            .capture_rules
+            .clone()
+            .unwrap_or_default(),
+    };
+    for rule in &rules {
+        rule.validate()?;
+    }
+    Ok(evaluate(&rules, input))
+}
+
+#[test]
+fn test_evaluate_rules() {
+    let rule = |name: &str| CaptureRule {
+        name: name.into(),
+        ..CaptureRule::default()
+    };
+    let rules = vec![
+        CaptureRule {
+            pattern: Some(r"^\d{6}$".into()),
+            add_tags: Some(vec!["otp".into()]),
+            expire_after_secs: Some(300),
+            ..rule("otp")
+        },
+        CaptureRule {
+            source_app: Some("Terminal".into()),
+            add_tags: Some(vec!["shell".into(), "otp".into()]),
+            expire_after_secs: Some(60),
+            stop: Some(true),
+            ..rule("terminal")
+        },
+        CaptureRule {
+            favorite: Some(true),
+            ..rule("all")
+        },
+    ];
+    let input = |content: &str, app: Option<&str>| RuleInput {
+        content: content.into(),
+        data_type: "text".into(),
+        source_app: app.map(String::from),
+        is_secure: false,
+        copy_count: 1,
+    };
+    let res = evaluate(&rules, &input("123456", Some("terminal")));
+    assert_eq!(res.matched, vec!["otp", "terminal"]);
+    assert_eq!(res.tags, vec!["otp", "shell"]);
+    assert_eq!(res.expire_after_secs, Some(60));
+    assert!(!res.favorite);
+    let res = evaluate(&rules, &input("hello", None));
+    assert_eq!(res.matched, vec!["all"]);
+    assert!(res.favorite);
+    let secure = RuleInput {
+        is_secure: true,
+        ..input("123456", None)
+    };
+    assert_eq!(evaluate(&rules, &secure).matched, vec!["all"]);
+    let frequent = vec![CaptureRule {
+        data_type: Some("text".into()),
+        min_copy_count: Some(3),
+        favorite: Some(true),
+        ..rule("frequent")
+    }];
+    assert!(!evaluate(&frequent, &input("1 Main St", None)).favorite);
+    let copied = RuleInput {
+        copy_count: 3,
+        ..input("1 Main St", None)
+    };
+    assert!(evaluate(&frequent, &copied).favorite);
+    let prioritized = vec![
+        CaptureRule {
+            source_app: Some("Terminal".into()),
+            add_tags: Some(vec!["cli".into()]),
+            ..rule("terminal")
+        },
+        CaptureRule {
+            pattern: Some(r"\b[A-Z]+-\d+\b".into()),
+            add_tags: Some(vec!["tickets".into()]),
+            priority: Some(10),
+            stop: Some(true),
+            ..rule("jira")
+        },
+    ];
+    let res = evaluate(&prioritized, &input("fix PROJ-42", Some("Terminal")));
+    assert_eq!(res.matched, vec!["jira"]);
+    assert_eq!(res.tags, vec!["tickets"]);
+    let res = evaluate(&prioritized, &input("ls -la", Some("Terminal")));
+    assert_eq!(res.tags, vec!["cli"]);
+    assert_eq!(merge_tags("a,otp", &["otp".into(), "b".into()]), "a,otp,b");
+    assert!(CaptureRule {
+        pattern: Some("(".into()),
+        ..rule("bad")
+    }
+    .validate()
+    .is_err());
+}
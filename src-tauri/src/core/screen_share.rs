@@ -0,0 +1,117 @@
+use crate::config::Config;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::time::{Duration, Instant};
+
+/// 只在共享或录制屏幕时运行的进程：Zoom 共享屏幕时的 CptHost、macOS 录屏的 screencapture、
+/// 有人通过屏幕共享连接本机时 macOS 启动的 screensharingd
+const SHARING_PROCESSES: [&str; 3] = ["CptHost", "screencapture", "screensharingd"];
+/// 检测需要列出进程，剪切板每秒都会检查，结果缓存一段时间
+const CACHE_SECS: u64 = 10;
+
+fn cache() -> &'static Mutex<Option<(Instant, bool)>> {
+    static CACHE: OnceCell<Mutex<Option<(Instant, bool)>>> = OnceCell::new();
+    CACHE.get_or_init(|| Mutex::new(None))
+}
+
+fn detect() -> bool {
+    let extra = { Config::common().latest().screen_share_processes.clone() };
+    let names: Vec<String> = SHARING_PROCESSES
+        .iter()
+        .map(|name| name.to_string())
+        .chain(extra.unwrap_or_default())
+        .map(|name| name.to_lowercase().trim_end_matches(".exe").to_string())
+        .collect();
+    if platform::is_remote_session() {
+        return true;
+    }
+    platform::process_names().iter().any(|process| {
+        let process = process.to_lowercase();
+        let process = process.trim_end_matches(".exe");
+        names.iter().any(|name| name == process)
+    })
+}
+
+/// 是否正在共享或录制屏幕，系统没有公开接口时按进程名判断
+pub fn is_sharing() -> bool {
+    let mut cache = cache().lock();
+    if let Some((checked_at, sharing)) = *cache {
+        if checked_at.elapsed() < Duration::from_secs(CACHE_SECS) {
+            return sharing;
+        }
+    }
+    let sharing = detect();
+    *cache = Some((Instant::now(), sharing));
+    sharing
+}
+
+/// 共享屏幕期间是否暂停记录剪切板
+pub fn should_pause() -> bool {
+    let enable = { Config::common().latest().pause_on_screen_share };
+    enable.unwrap_or(false) && is_sharing()
+}
+
+/// 共享屏幕期间是否隐藏弹窗中的内容预览
+pub fn should_hide_previews() -> bool {
+    let enable = { Config::common().latest().hide_previews_on_screen_share };
+    enable.unwrap_or(false) && is_sharing()
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    const SM_REMOTESESSION: i32 = 0x1000;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetSystemMetrics(index: i32) -> i32;
+    }
+
+    /// 通过远程桌面连接时整个桌面都在对方屏幕上
+    pub fn is_remote_session() -> bool {
+        unsafe { GetSystemMetrics(SM_REMOTESESSION) != 0 }
+    }
+
+    pub fn process_names() -> Vec<String> {
+        let output = match Command::new("tasklist")
+            .args(["/FO", "CSV", "/NH"])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+        {
+            Ok(output) => output,
+            Err(_) => return vec![],
+        };
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split(',').next())
+            .map(|name| name.trim_matches('"').to_string())
+            .collect()
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod platform {
+    use std::process::Command;
+
+    pub fn is_remote_session() -> bool {
+        false
+    }
+
+    pub fn process_names() -> Vec<String> {
+        // macOS 的 comm 默认是完整路径，-c 只取可执行文件名
+        #[cfg(target_os = "macos")]
+        let args: &[&str] = &["-A", "-c", "-o", "comm="];
+        #[cfg(not(target_os = "macos"))]
+        let args: &[&str] = &["-e", "-o", "comm="];
+        match Command::new("ps").args(args).output() {
+            Ok(output) => String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(|name| name.trim().to_string())
+                .collect(),
+            Err(_) => vec![],
+        }
+    }
+}
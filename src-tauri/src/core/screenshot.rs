@@ -0,0 +1,134 @@
+use super::clipboard::{ClipBoardOprator, ImageDataDB};
+use super::handle::Handle;
+use crate::log_err;
+use crate::utils::{dirs, img_util, string_util};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// 隐藏窗口后等待动画结束再截图，避免截到自己的窗口
+const HIDE_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScreenshotMode {
+    /// 框选区域
+    Region,
+    /// 点击选择窗口
+    Window,
+}
+
+/// 正在截图，重复按下快捷键时忽略
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+
+/// 系统自带或常见的截图工具，按顺序尝试，截图保存到 path
+/// Windows 的截图工具只能写入剪切板，不支持
+fn tools(mode: ScreenshotMode, path: &str) -> Vec<(&'static str, Vec<String>)> {
+    let args = |args: &[&str]| {
+        args.iter()
+            .map(|a| a.to_string())
+            .chain([path.to_string()])
+            .collect::<Vec<String>>()
+    };
+    match std::env::consts::OS {
+        "macos" => match mode {
+            ScreenshotMode::Region => vec![("screencapture", args(&["-i", "-s", "-x"]))],
+            ScreenshotMode::Window => vec![("screencapture", args(&["-i", "-w", "-x"]))],
+        },
+        "windows" => vec![],
+        _ => match mode {
+            ScreenshotMode::Region => vec![
+                ("gnome-screenshot", args(&["-a", "-f"])),
+                ("spectacle", args(&["-b", "-n", "-r", "-o"])),
+                ("scrot", args(&["-s", "-o"])),
+            ],
+            ScreenshotMode::Window => vec![
+                ("gnome-screenshot", args(&["-w", "-f"])),
+                ("spectacle", args(&["-b", "-n", "-a", "-o"])),
+                ("scrot", args(&["-u", "-o"])),
+            ],
+        },
+    }
+}
+
+/// 截图并放入剪切板，由剪切板监听记录到历史，取消截图时返回 false
+pub fn capture(mode: ScreenshotMode) -> Result<bool> {
+    if cfg!(target_os = "windows") {
+        bail!("screenshots are not supported on Windows, use Win+Shift+S instead");
+    }
+    if CAPTURING.swap(true, Ordering::SeqCst) {
+        return Ok(false);
+    }
+    let res = run(mode);
+    CAPTURING.store(false, Ordering::SeqCst);
+    res
+}
+
+/// 在后台线程中截图，供快捷键调用
+pub fn capture_in_background(mode: ScreenshotMode) {
+    thread::spawn(move || log_err!(capture(mode)));
+}
+
+fn run(mode: ScreenshotMode) -> Result<bool> {
+    if let Some(window) = Handle::global().get_window() {
+        if window.is_visible().unwrap_or(false) {
+            let _ = window.hide();
+            thread::sleep(HIDE_DELAY);
+        }
+    }
+    let path = dirs::temp_file_path("screenshot", "png")?;
+    let path_str = path.to_string_lossy().to_string();
+    let mut launched = false;
+    for (tool, args) in tools(mode, &path_str) {
+        // 工具不存在时继续尝试下一个，用户取消时也会返回失败
+        if let Ok(status) = Command::new(tool).args(&args).status() {
+            launched = true;
+            if status.success() || path.exists() {
+                break;
+            }
+        }
+    }
+    if !launched {
+        bail!("no screenshot tool available");
+    }
+    let saved = save(&path);
+    let _ = fs::remove_file(&path);
+    saved
+}
+
+fn save(path: &Path) -> Result<bool> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) if !bytes.is_empty() => bytes,
+        _ => return Ok(false),
+    };
+    let base64 = string_util::base64_encode(&bytes);
+    let img = img_util::base64_to_rgba8(&base64)?;
+    let data = ImageDataDB {
+        width: img.width,
+        height: img.height,
+        base64,
+        ..ImageDataDB::default()
+    };
+    // 由剪切板监听记录，和复制的图片一样去重、应用规则，暂停记录时不记录
+    ClipBoardOprator::set_image(data)?;
+    Ok(true)
+}
+
+#[test]
+fn test_screenshot_tools() {
+    if cfg!(target_os = "windows") {
+        return;
+    }
+    let region = tools(ScreenshotMode::Region, "/tmp/shot.png");
+    let window = tools(ScreenshotMode::Window, "/tmp/shot.png");
+    assert!(!region.is_empty());
+    assert_ne!(region, window);
+    for (_, args) in region.iter().chain(window.iter()) {
+        assert_eq!(args.last().map(|a| a.as_str()), Some("/tmp/shot.png"));
+    }
+}
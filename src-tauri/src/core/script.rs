@@ -0,0 +1,173 @@
+use crate::config::Config;
+use crate::utils::dirs;
+use anyhow::{anyhow, bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use rhai::{Dynamic, Engine, Scope, AST};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::time::{Instant, SystemTime};
+
+const SCRIPT_EXT: &str = "rhai";
+/// 单个脚本的资源上限
+const MAX_OPERATIONS: u64 = 5_000_000;
+const MAX_STRING_SIZE: usize = 16 * 1024 * 1024;
+const MAX_COLLECTION_SIZE: usize = 10_000;
+const MAX_CALL_LEVELS: usize = 32;
+
+/// scripts 目录下的脚本，enabled 由配置 capture_scripts 决定
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptInfo {
+    pub name: String,
+    pub enabled: bool,
+}
+
+/// 编译结果按修改时间缓存，脚本改动后自动重新编译
+fn cache() -> &'static Mutex<HashMap<String, (SystemTime, AST)>> {
+    static CACHE: OnceCell<Mutex<HashMap<String, (SystemTime, AST)>>> = OnceCell::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 空闲时调用，之后用到时重新编译
+pub fn clear_cache() {
+    cache().lock().clear();
+}
+
+fn new_engine(timeout_ms: u64) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.set_max_array_size(MAX_COLLECTION_SIZE);
+    engine.set_max_map_size(MAX_COLLECTION_SIZE);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    let start = Instant::now();
+    // 超时后中止脚本
+    engine.on_progress(move |_| {
+        if start.elapsed().as_millis() as u64 > timeout_ms {
+            Some(Dynamic::from("timeout"))
+        } else {
+            None
+        }
+    });
+    engine
+}
+
+fn compile(engine: &Engine, name: &str) -> Result<AST> {
+    let path = dirs::scripts_dir()?.join(format!("{}.{}", name, SCRIPT_EXT));
+    let modified = fs::metadata(&path)?.modified()?;
+    if let Some((time, ast)) = cache().lock().get(name) {
+        if *time == modified {
+            return Ok(ast.clone());
+        }
+    }
+    let ast = engine
+        .compile(fs::read_to_string(&path)?)
+        .map_err(|e| anyhow!("{}", e))?;
+    cache()
+        .lock()
+        .insert(name.to_string(), (modified, ast.clone()));
+    Ok(ast)
+}
+
+/// 执行脚本，脚本中可读取 content 和 data_type
+/// 返回字符串表示改写后的内容，返回 false 表示丢弃，其它值表示不改动
+pub fn run(engine: &Engine, ast: &AST, content: &str) -> Result<Option<String>> {
+    let mut scope = Scope::new();
+    scope.push("content", content.to_string());
+    scope.push_constant("data_type", "text".to_string());
+    let res: Dynamic = engine
+        .eval_ast_with_scope(&mut scope, ast)
+        .map_err(|e| anyhow!("{}", e))?;
+    if res.is::<bool>() && !res.as_bool().unwrap_or(true) {
+        return Ok(None);
+    }
+    if res.is_string() {
+        return Ok(Some(res.into_string().map_err(|e| anyhow!("{}", e))?));
+    }
+    Ok(Some(content.to_string()))
+}
+
+/// 记录新复制的文本前按顺序执行启用的脚本，返回 None 表示丢弃
+/// 脚本出错或超时时跳过该脚本
+pub fn process_capture(mut content: String) -> Option<String> {
+    let (scripts, timeout_ms) = {
+        let config = Config::common().latest();
+        (
+            config.capture_scripts.clone().unwrap_or_default(),
+            config.script_timeout_ms.unwrap_or(100) as u64,
+        )
+    };
+    for name in scripts {
+        let engine = new_engine(timeout_ms);
+        let res = compile(&engine, &name).and_then(|ast| run(&engine, &ast, &content));
+        match res {
+            Ok(Some(res)) => content = res,
+            Ok(None) => return None,
+            Err(err) => println!("capture script {} error: {}", name, err),
+        }
+    }
+    Some(content)
+}
+
+/// 列出 scripts 目录下的所有脚本
+pub fn list_scripts() -> Result<Vec<ScriptInfo>> {
+    let dir = dirs::scripts_dir()?;
+    if !dir.exists() {
+        fs::create_dir_all(&dir)?;
+    }
+    let enabled = Config::common()
+        .latest()
+        .capture_scripts
+        .clone()
+        .unwrap_or_default();
+    let mut scripts = vec![];
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(SCRIPT_EXT) {
+            continue;
+        }
+        if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+            scripts.push(ScriptInfo {
+                name: name.to_string(),
+                enabled: enabled.iter().any(|e| e == name),
+            });
+        }
+    }
+    scripts.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(scripts)
+}
+
+/// 脚本名只允许字母、数字、- 和 _，避免读取 scripts 目录以外的文件
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!("invalid script name \"{}\"", name);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_run_script() {
+    let engine = new_engine(1000);
+    let redact = engine
+        .compile(r#"content.replace("corp.internal", "***"); content"#)
+        .unwrap();
+    assert_eq!(
+        run(&engine, &redact, "ssh db.corp.internal").unwrap(),
+        Some("ssh db.***".to_string())
+    );
+    let reject = engine.compile(r#"!content.contains("password")"#).unwrap();
+    assert_eq!(run(&engine, &reject, "my password").unwrap(), None);
+    assert_eq!(
+        run(&engine, &reject, "hello").unwrap(),
+        Some("hello".to_string())
+    );
+    let endless = engine.compile("loop {}").unwrap();
+    assert!(run(&new_engine(10), &endless, "hello").is_err());
+    assert!(validate_name("redact-hosts").is_ok());
+    assert!(validate_name("../secret").is_err());
+}
@@ -0,0 +1,142 @@
+use crate::config::Config;
+
+/// 是否需要暂停记录剪切板：系统处于安全输入状态（密码框、UAC）或锁屏时
+pub fn should_pause() -> bool {
+    let enable = { Config::common().latest().pause_on_secure_input };
+    if !enable.unwrap_or(true) {
+        return false;
+    }
+    is_secure_input() || is_screen_locked()
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::ffi::{c_void, CString};
+
+    type CFTypeRef = *const c_void;
+
+    const K_CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+
+    #[link(name = "Carbon", kind = "framework")]
+    extern "C" {
+        fn IsSecureEventInputEnabled() -> u8;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFStringCreateWithCString(
+            alloc: CFTypeRef,
+            c_str: *const std::os::raw::c_char,
+            encoding: u32,
+        ) -> CFTypeRef;
+        fn CFDictionaryGetValue(dict: CFTypeRef, key: CFTypeRef) -> CFTypeRef;
+        fn CFBooleanGetValue(boolean: CFTypeRef) -> u8;
+        fn CFRelease(cf: CFTypeRef);
+    }
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGSessionCopyCurrentDictionary() -> CFTypeRef;
+    }
+
+    pub fn is_secure_input() -> bool {
+        unsafe { IsSecureEventInputEnabled() != 0 }
+    }
+
+    pub fn is_screen_locked() -> bool {
+        unsafe {
+            let dict = CGSessionCopyCurrentDictionary();
+            if dict.is_null() {
+                return false;
+            }
+            let key_name = CString::new("CGSSessionScreenIsLocked").unwrap();
+            let key = CFStringCreateWithCString(
+                std::ptr::null(),
+                key_name.as_ptr(),
+                K_CF_STRING_ENCODING_UTF8,
+            );
+            let value = CFDictionaryGetValue(dict, key);
+            let locked = !value.is_null() && CFBooleanGetValue(value) != 0;
+            CFRelease(key);
+            CFRelease(dict);
+            locked
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    const DESKTOP_SWITCHDESKTOP: u32 = 0x0100;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn OpenInputDesktop(flags: u32, inherit: i32, desired_access: u32) -> isize;
+        fn CloseDesktop(desktop: isize) -> i32;
+    }
+
+    /// UAC 安全桌面和锁屏都会切换到 Winlogon 桌面，此时无法打开当前输入桌面
+    fn is_input_desktop_accessible() -> bool {
+        unsafe {
+            let desktop = OpenInputDesktop(0, 0, DESKTOP_SWITCHDESKTOP);
+            if desktop == 0 {
+                return false;
+            }
+            CloseDesktop(desktop);
+            true
+        }
+    }
+
+    pub fn is_secure_input() -> bool {
+        !is_input_desktop_accessible()
+    }
+
+    pub fn is_screen_locked() -> bool {
+        !is_input_desktop_accessible()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use once_cell::sync::OnceCell;
+    use parking_lot::Mutex;
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    /// 剪切板每秒检查一次，loginctl 需要启动进程，结果缓存一段时间
+    const LOCKED_CACHE: Duration = Duration::from_secs(5);
+
+    fn cache() -> &'static Mutex<Option<(Instant, bool)>> {
+        static CACHE: OnceCell<Mutex<Option<(Instant, bool)>>> = OnceCell::new();
+        CACHE.get_or_init(|| Mutex::new(None))
+    }
+
+    pub fn is_secure_input() -> bool {
+        false
+    }
+
+    /// 通过 systemd-logind 的 LockedHint 判断是否锁屏
+    pub fn is_screen_locked() -> bool {
+        let mut cache = cache().lock();
+        if let Some((checked_at, locked)) = *cache {
+            if checked_at.elapsed() < LOCKED_CACHE {
+                return locked;
+            }
+        }
+        let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".into());
+        let locked = Command::new("loginctl")
+            .args(["show-session", &session, "-p", "LockedHint", "--value"])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim() == "yes")
+            .unwrap_or(false);
+        *cache = Some((Instant::now(), locked));
+        locked
+    }
+}
+
+pub fn is_secure_input() -> bool {
+    platform::is_secure_input()
+}
+
+pub fn is_screen_locked() -> bool {
+    platform::is_screen_locked()
+}
@@ -0,0 +1,191 @@
+use super::audit::AuditAction;
+use super::database::{Record, SqliteDB};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::PathBuf;
+
+/// 片段名称的最大字符数
+const NAME_LEN: usize = 40;
+/// 生成缩写时最多使用的单词数
+const TRIGGER_WORDS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnippetFormat {
+    /// VS Code 的 *.code-snippets
+    Vscode,
+    /// espanso 的 match 文件
+    Espanso,
+    /// TextExpander 可以导入的 CSV（缩写、内容、名称）
+    Textexpander,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SnippetExportReq {
+    pub format: SnippetFormat,
+    pub path: PathBuf,
+    /// 只导出带有这些标签的收藏，为空时导出全部收藏的文本
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snippet {
+    pub name: String,
+    /// 不带前缀的缩写，如 meeting-notes，espanso 中为 :meeting-notes
+    pub trigger: String,
+    pub body: String,
+    /// 从 espanso 导入时的原始触发词，同步时原样写回
+    pub espanso_trigger: Option<String>,
+}
+
+/// Lanaya 写入的 espanso match 文件的第一行
+pub const ESPANSO_HEADER: &str = "# generated by Lanaya";
+
+/// 第一行作为名称，前几个单词作为缩写，重复的缩写加上记录 id
+pub fn to_snippets(records: &[Record]) -> Vec<Snippet> {
+    let mut snippets: Vec<Snippet> = vec![];
+    for record in records {
+        let first_line = record.content.lines().find(|l| !l.trim().is_empty());
+        let first_line = first_line.unwrap_or_default().trim();
+        let name = match first_line.chars().count() > NAME_LEN {
+            true => first_line.chars().take(NAME_LEN).chain(['…']).collect(),
+            false if first_line.is_empty() => format!("clip {}", record.id),
+            false => first_line.to_string(),
+        };
+        let words: Vec<String> = first_line
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .take(TRIGGER_WORDS)
+            .map(|w| w.to_lowercase())
+            .collect();
+        let mut trigger = match words.is_empty() {
+            true => format!("clip{}", record.id),
+            false => words.join("-"),
+        };
+        if snippets.iter().any(|s| s.trigger == trigger) {
+            trigger = format!("{}-{}", trigger, record.id);
+        }
+        snippets.push(Snippet {
+            name,
+            trigger,
+            body: record.content.clone(),
+            espanso_trigger: None,
+        });
+    }
+    snippets
+}
+
+/// $ 在 VS Code 中表示占位符，需要转义
+fn vscode(snippets: &[Snippet]) -> Result<String> {
+    let mut map = Map::new();
+    for snippet in snippets {
+        let mut name = snippet.name.clone();
+        if map.contains_key(&name) {
+            name = format!("{} ({})", name, snippet.trigger);
+        }
+        let body: Vec<String> = snippet
+            .body
+            .lines()
+            .map(|line| line.replace('$', "\\$"))
+            .collect();
+        map.insert(
+            name,
+            json!({ "prefix": snippet.trigger, "body": body, "description": "Lanaya" }),
+        );
+    }
+    Ok(serde_json::to_string_pretty(&Value::Object(map))?)
+}
+
+/// JSON 的字符串也是合法的 YAML 双引号字符串，不需要引入 YAML 库
+fn espanso(snippets: &[Snippet]) -> Result<String> {
+    let mut res = format!("{}\nmatches:\n", ESPANSO_HEADER);
+    for snippet in snippets {
+        let trigger = match snippet.espanso_trigger.as_ref() {
+            Some(trigger) => trigger.clone(),
+            None => format!(":{}", snippet.trigger),
+        };
+        res.push_str(&format!(
+            "  - trigger: {}\n    replace: {}\n",
+            serde_json::to_string(&trigger)?,
+            serde_json::to_string(&snippet.body)?
+        ));
+    }
+    Ok(res)
+}
+
+fn csv_field(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+fn textexpander(snippets: &[Snippet]) -> Result<String> {
+    Ok(snippets
+        .iter()
+        .map(|s| {
+            format!(
+                "{},{},{}\n",
+                csv_field(&s.trigger),
+                csv_field(&s.body),
+                csv_field(&s.name)
+            )
+        })
+        .collect())
+}
+
+pub fn render(format: SnippetFormat, snippets: &[Snippet]) -> Result<String> {
+    match format {
+        SnippetFormat::Vscode => vscode(snippets),
+        SnippetFormat::Espanso => espanso(snippets),
+        SnippetFormat::Textexpander => textexpander(snippets),
+    }
+}
+
+/// 导出收藏的文本记录，返回导出的条数
+pub fn export(req: &SnippetExportReq) -> Result<usize> {
+    let db = SqliteDB::new();
+    let records = db.find_favorite_texts(req.tags.as_deref().unwrap_or_default())?;
+    let snippets = to_snippets(&records);
+    fs::write(&req.path, render(req.format, &snippets)?)?;
+    for record in &records {
+        db.audit(AuditAction::Exported, record.id);
+    }
+    Ok(snippets.len())
+}
+
+#[test]
+fn test_snippet_export() {
+    let record = |id: u64, content: &str| Record {
+        id,
+        content: content.to_string(),
+        ..Default::default()
+    };
+    let snippets = to_snippets(&[
+        record(1, "Meeting notes: $DATE\nagenda"),
+        record(2, "meeting notes, again"),
+        record(3, "  \n"),
+    ]);
+    let triggers: Vec<&str> = snippets.iter().map(|s| s.trigger.as_str()).collect();
+    assert_eq!(
+        triggers,
+        vec!["meeting-notes-date", "meeting-notes-again", "clip3"]
+    );
+    assert_eq!(snippets[2].name, "clip 3");
+    let code: Value = serde_json::from_str(&vscode(&snippets).unwrap()).unwrap();
+    assert_eq!(
+        code["Meeting notes: $DATE"]["body"],
+        json!(["Meeting notes: \\$DATE", "agenda"])
+    );
+    let yaml = espanso(&snippets[..1]).unwrap();
+    assert!(yaml.contains(
+        "  - trigger: \":meeting-notes-date\"\n    replace: \"Meeting notes: $DATE\\nagenda\"\n"
+    ));
+    let csv = textexpander(&[Snippet {
+        name: "quote".into(),
+        trigger: "q".into(),
+        body: "say \"hi\"".into(),
+        espanso_trigger: None,
+    }])
+    .unwrap();
+    assert_eq!(csv, "\"q\",\"say \"\"hi\"\"\",\"quote\"\n");
+}
@@ -0,0 +1,160 @@
+use super::capture_notify;
+use crate::config::Config;
+use crate::utils::dirs;
+use anyhow::{bail, Result};
+use std::fs;
+use std::thread;
+
+/// 可以播放提示音的事件
+pub const SOUND_EVENTS: [&str; 2] = ["capture", "paste"];
+/// 内置提示音，sounds 目录下的 <event>.wav 可以替换
+const BUILTIN: [(&str, &[u8]); 2] = [
+    ("capture", include_bytes!("../../sounds/capture.wav")),
+    ("paste", include_bytes!("../../sounds/paste.wav")),
+];
+
+fn load(event: &str) -> Option<Vec<u8>> {
+    if let Ok(path) = dirs::sounds_dir().map(|dir| dir.join(format!("{}.wav", event))) {
+        if path.exists() {
+            match fs::read(&path) {
+                Ok(bytes) => return Some(bytes),
+                Err(err) => println!("load sound {} error: {}", path.display(), err),
+            }
+        }
+    }
+    BUILTIN
+        .iter()
+        .find(|(name, _)| *name == event)
+        .map(|(_, bytes)| bytes.to_vec())
+}
+
+/// 按音量缩放 16 位 PCM 的采样，其它格式的文件原样播放
+fn scale_volume(mut wav: Vec<u8>, volume: u32) -> Result<Vec<u8>> {
+    if wav.len() < 12 || &wav[0..4] != b"RIFF" || &wav[8..12] != b"WAVE" {
+        bail!("not a wav file");
+    }
+    let u16_at = |wav: &[u8], i: usize| u16::from_le_bytes([wav[i], wav[i + 1]]);
+    let mut pcm16 = false;
+    let mut pos = 12;
+    while pos + 8 <= wav.len() {
+        let id = [wav[pos], wav[pos + 1], wav[pos + 2], wav[pos + 3]];
+        let size = u32::from_le_bytes([wav[pos + 4], wav[pos + 5], wav[pos + 6], wav[pos + 7]]);
+        let start = pos + 8;
+        let end = (start + size as usize).min(wav.len());
+        match &id {
+            b"fmt " if end - start >= 16 => {
+                // audio_format 为 1 表示 PCM
+                pcm16 = u16_at(&wav, start) == 1 && u16_at(&wav, start + 14) == 16;
+            }
+            b"data" if pcm16 => {
+                for i in (start..end - 1).step_by(2) {
+                    let sample = i16::from_le_bytes([wav[i], wav[i + 1]]) as i32;
+                    let scaled = (sample * volume as i32 / 100) as i16;
+                    wav[i..i + 2].copy_from_slice(&scaled.to_le_bytes());
+                }
+            }
+            _ => {}
+        }
+        // chunk 按偶数字节对齐
+        pos = start + size as usize + (size as usize & 1);
+    }
+    Ok(wav)
+}
+
+/// 在后台线程播放事件对应的提示音，未开启、未选中该事件或处于免打扰时段时忽略
+pub fn play(event: &'static str) {
+    let config = { Config::common().latest().clone() };
+    if !config.enable_sound_feedback.unwrap_or(false)
+        || !config
+            .sound_events
+            .unwrap_or_default()
+            .iter()
+            .any(|e| e == event)
+        || capture_notify::is_quiet_now()
+    {
+        return;
+    }
+    let volume = config.sound_volume.unwrap_or(50).min(100);
+    if volume == 0 {
+        return;
+    }
+    thread::spawn(move || {
+        let res = load(event)
+            .ok_or_else(|| anyhow::anyhow!("no sound for {}", event))
+            .and_then(|wav| scale_volume(wav, volume))
+            .and_then(|wav| platform::play_wav(event, &wav));
+        if let Err(err) = res {
+            println!("play sound {} error: {}", event, err);
+        }
+    });
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use anyhow::{bail, Result};
+
+    const SND_SYNC: u32 = 0x0000;
+    const SND_NODEFAULT: u32 = 0x0002;
+    const SND_MEMORY: u32 = 0x0004;
+
+    #[link(name = "winmm")]
+    extern "system" {
+        fn PlaySoundW(sound: *const u16, module: isize, flags: u32) -> i32;
+    }
+
+    /// SND_MEMORY 直接播放内存中的 wav，同步播放保证播放期间数据有效
+    pub fn play_wav(_event: &str, wav: &[u8]) -> Result<()> {
+        let ok = unsafe {
+            PlaySoundW(
+                wav.as_ptr() as *const u16,
+                0,
+                SND_MEMORY | SND_SYNC | SND_NODEFAULT,
+            )
+        };
+        if ok == 0 {
+            bail!("PlaySound failed");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod platform {
+    use anyhow::{bail, Result};
+    use std::process::Command;
+
+    /// 写到临时文件后交给系统自带的播放器
+    pub fn play_wav(event: &str, wav: &[u8]) -> Result<()> {
+        let path = std::env::temp_dir().join(format!("lanaya-{}.wav", event));
+        std::fs::write(&path, wav)?;
+        #[cfg(target_os = "macos")]
+        let players: [(&str, &[&str]); 1] = [("afplay", &[])];
+        #[cfg(not(target_os = "macos"))]
+        let players: [(&str, &[&str]); 2] = [("paplay", &[]), ("aplay", &["-q"])];
+        for (player, args) in players {
+            if let Ok(status) = Command::new(player).args(args).arg(&path).status() {
+                if status.success() {
+                    return Ok(());
+                }
+            }
+        }
+        bail!("no audio player available")
+    }
+}
+
+#[test]
+fn test_scale_volume() {
+    let wav = BUILTIN[0].1.to_vec();
+    let peak = |wav: &[u8]| {
+        wav[44..]
+            .chunks(2)
+            .map(|s| i16::from_le_bytes([s[0], s[1]]).unsigned_abs())
+            .max()
+            .unwrap_or(0)
+    };
+    let half = scale_volume(wav.clone(), 50).unwrap();
+    assert_eq!(half.len(), wav.len());
+    assert!(peak(&half) <= peak(&wav) / 2 + 1);
+    assert_eq!(peak(&scale_volume(wav.clone(), 0).unwrap()), 0);
+    assert!(scale_volume(b"not a wav".to_vec(), 50).is_err());
+}
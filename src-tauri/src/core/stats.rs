@@ -0,0 +1,114 @@
+use super::database::SqliteDB;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SourceStatsReq {
+    /// 毫秒时间戳，为空时不限制
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+impl SourceStatsReq {
+    fn range(&self) -> (u64, u64) {
+        (
+            self.start_time.unwrap_or(0),
+            self.end_time.unwrap_or(i64::MAX as u64),
+        )
+    }
+
+    fn limit(&self) -> usize {
+        self.limit.unwrap_or(10)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SourceCount {
+    pub name: String,
+    pub count: usize,
+}
+
+/// 复制次数最多的来源应用
+pub fn top_apps(req: &SourceStatsReq) -> Result<Vec<SourceCount>> {
+    let (start, end) = req.range();
+    let apps = SqliteDB::new().count_source_apps(start, end, req.limit())?;
+    Ok(apps
+        .into_iter()
+        .map(|(name, count)| SourceCount { name, count })
+        .collect())
+}
+
+/// url 记录中复制次数最多的域名
+pub fn top_domains(req: &SourceStatsReq) -> Result<Vec<SourceCount>> {
+    let (start, end) = req.range();
+    let urls = SqliteDB::new().find_url_contents(start, end)?;
+    Ok(count_domains(urls.iter().map(String::as_str), req.limit()))
+}
+
+fn count_domains<'a>(urls: impl Iterator<Item = &'a str>, limit: usize) -> Vec<SourceCount> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for host in urls.filter_map(url_host) {
+        *counts.entry(host).or_default() += 1;
+    }
+    let mut res: Vec<SourceCount> = counts
+        .into_iter()
+        .map(|(name, count)| SourceCount { name, count })
+        .collect();
+    res.sort_by(|a, b| b.count.cmp(&a.count).then(a.name.cmp(&b.name)));
+    res.truncate(limit);
+    res
+}
+
+/// 只认整条内容就是一个 http(s) 链接的记录，www. 前缀视为同一个域名
+fn url_host(content: &str) -> Option<String> {
+    let content = content.trim();
+    if content.contains(char::is_whitespace) {
+        return None;
+    }
+    let rest = content
+        .strip_prefix("https://")
+        .or_else(|| content.strip_prefix("http://"))?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?.split(':').next()?;
+    let host = host.to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    if host.is_empty() {
+        return None;
+    }
+    Some(host.to_string())
+}
+
+#[test]
+fn test_count_domains() {
+    assert_eq!(
+        url_host("https://www.GitHub.com/foo?a=1"),
+        Some("github.com".into())
+    );
+    assert_eq!(
+        url_host("http://user@example.com:8080#top"),
+        Some("example.com".into())
+    );
+    assert_eq!(url_host("see https://example.com"), None);
+    let urls = [
+        "https://a.com/1",
+        "https://b.com",
+        "https://a.com/2",
+        "ftp://c.com",
+    ];
+    let res = count_domains(urls.into_iter(), 10);
+    assert_eq!(
+        res,
+        vec![
+            SourceCount {
+                name: "a.com".into(),
+                count: 2
+            },
+            SourceCount {
+                name: "b.com".into(),
+                count: 1
+            },
+        ]
+    );
+}
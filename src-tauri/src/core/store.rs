@@ -0,0 +1,193 @@
+use super::database::{HistoryPage, InsertResult, PageReq, Record, SqliteDB};
+#[cfg(test)]
+use crate::utils::string_util;
+#[cfg(test)]
+use anyhow::anyhow;
+use anyhow::Result;
+#[cfg(test)]
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// HTTP 接口的搜索、读取和设置页的统计通过该 trait 访问记录，测试中用 MemoryStore 代替
+/// 其它功能仍直接使用 SqliteDB
+pub trait ClipboardStore {
+    /// 内容相同的记录只更新时间
+    fn insert(&self, record: Record) -> Result<InsertResult>;
+    fn get(&self, id: u64) -> Result<Record>;
+    /// 按 create_time、id 倒序分页
+    fn query(&self, req: PageReq) -> Result<HistoryPage>;
+    fn delete(&self, ids: &[u64]) -> Result<()>;
+    fn stats(&self) -> Result<StoreStats>;
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StoreStats {
+    pub records: usize,
+    pub favorites: usize,
+    pub images: usize,
+}
+
+pub fn open() -> Box<dyn ClipboardStore> {
+    Box::new(SqliteDB::new())
+}
+
+impl ClipboardStore for SqliteDB {
+    fn insert(&self, record: Record) -> Result<InsertResult> {
+        self.insert_if_not_exist(record)
+    }
+
+    fn get(&self, id: u64) -> Result<Record> {
+        self.find_by_id(id)
+    }
+
+    fn query(&self, req: PageReq) -> Result<HistoryPage> {
+        self.find_page(req)
+    }
+
+    fn delete(&self, ids: &[u64]) -> Result<()> {
+        self.delete_by_ids(ids)
+    }
+
+    fn stats(&self) -> Result<StoreStats> {
+        let (records, favorites, images) = self.count_records()?;
+        Ok(StoreStats {
+            records,
+            favorites,
+            images,
+        })
+    }
+}
+
+/// 只保存在内存中的存储，只支持关键字和收藏过滤
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore {
+    records: Mutex<Vec<Record>>,
+}
+
+#[cfg(test)]
+impl ClipboardStore for MemoryStore {
+    fn insert(&self, mut record: Record) -> Result<InsertResult> {
+        let mut records = self.records.lock();
+        let md5 = string_util::md5(&record.content);
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        if let Some(existing) = records.iter_mut().find(|r| r.md5 == md5) {
+            existing.create_time = now;
+            existing.copy_count = Some(existing.copy_count.unwrap_or(1) + 1);
+            return Ok(InsertResult::Updated(existing.id));
+        }
+        let id = records.iter().map(|r| r.id).max().unwrap_or(0) + 1;
+        record.id = id;
+        record.md5 = md5;
+        record.create_time = now;
+        record.first_seen_at = Some(now);
+        record.copy_count = Some(1);
+        records.push(record);
+        Ok(InsertResult::Inserted(id))
+    }
+
+    fn get(&self, id: u64) -> Result<Record> {
+        self.records
+            .lock()
+            .iter()
+            .find(|r| r.id == id)
+            .cloned()
+            .ok_or(anyhow!("record {} not found", id))
+    }
+
+    fn query(&self, req: PageReq) -> Result<HistoryPage> {
+        let page_size = req.page_size.unwrap_or(50).max(1);
+        let cursor = match &req.cursor {
+            Some(c) => {
+                let (time, id) = c.split_once('_').ok_or(anyhow!("invalid cursor"))?;
+                Some((time.parse::<u64>()?, id.parse::<u64>()?))
+            }
+            None => None,
+        };
+        let key = req.key.as_ref().map(|k| string_util::fold(k));
+        let mut records: Vec<Record> = self
+            .records
+            .lock()
+            .iter()
+            .filter(|r| match &key {
+                Some(key) => {
+                    (r.data_type == "text" && string_util::fold(&r.content).contains(key))
+                        || r.note
+                            .as_ref()
+                            .map_or(false, |n| string_util::fold(n).contains(key))
+                }
+                None => true,
+            })
+            .filter(|r| req.is_favorite.map_or(true, |fav| r.is_favorite == fav))
+            .filter(|r| cursor.map_or(true, |c| (r.create_time, r.id) < c))
+            .cloned()
+            .collect();
+        records.sort_by(|a, b| (b.create_time, b.id).cmp(&(a.create_time, a.id)));
+        let has_more = records.len() > page_size;
+        records.truncate(page_size);
+        let next_cursor = match has_more {
+            true => records
+                .last()
+                .map(|r| format!("{}_{}", r.create_time, r.id)),
+            false => None,
+        };
+        Ok(HistoryPage {
+            records,
+            next_cursor,
+            has_more,
+        })
+    }
+
+    fn delete(&self, ids: &[u64]) -> Result<()> {
+        self.records.lock().retain(|r| !ids.contains(&r.id));
+        Ok(())
+    }
+
+    fn stats(&self) -> Result<StoreStats> {
+        let records = self.records.lock();
+        Ok(StoreStats {
+            records: records.len(),
+            favorites: records.iter().filter(|r| r.is_favorite).count(),
+            images: records.iter().filter(|r| r.data_type == "image").count(),
+        })
+    }
+}
+
+#[test]
+fn test_memory_store() {
+    let store = MemoryStore::default();
+    let text = |content: &str| Record {
+        content: content.to_string(),
+        data_type: "text".to_string(),
+        ..Default::default()
+    };
+    let first = store.insert(text("hello")).unwrap();
+    assert_eq!(first, InsertResult::Inserted(1));
+    assert_eq!(
+        store.insert(text("hello")).unwrap(),
+        InsertResult::Updated(1)
+    );
+    store.insert(text("world")).unwrap();
+    store.insert(text("Hello again")).unwrap();
+    let page = store
+        .query(PageReq {
+            key: Some("hello".into()),
+            page_size: Some(1),
+            ..PageReq::default()
+        })
+        .unwrap();
+    assert_eq!(page.records.len(), 1);
+    assert!(page.has_more);
+    let next = store
+        .query(PageReq {
+            key: Some("hello".into()),
+            cursor: page.next_cursor,
+            ..PageReq::default()
+        })
+        .unwrap();
+    assert_eq!(next.records.len(), 1);
+    assert_ne!(next.records[0].id, page.records[0].id);
+    store.delete(&[1]).unwrap();
+    assert!(store.get(1).is_err());
+    assert_eq!(store.stats().unwrap().records, 2);
+}
@@ -0,0 +1,61 @@
+use super::capture_notify;
+use super::database::SqliteDB;
+use super::handle::{Handle, MsgTypeEnum};
+use super::i18n;
+use super::tray::Tray;
+use crate::config::Config;
+use crate::log_err;
+use anyhow::Result;
+use serde::Serialize;
+
+/// 经常复制或粘贴的记录，提示用户收藏
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FavoriteSuggestion {
+    pub id: u64,
+    /// 复制和粘贴的次数合计
+    pub uses: u64,
+    pub title: String,
+}
+
+/// 每条记录只提示一次，threshold 为 0 时不提示
+fn suggest(db: &SqliteDB, id: u64, threshold: u64) -> Result<Option<FavoriteSuggestion>> {
+    if threshold == 0 || SqliteDB::is_read_only() {
+        return Ok(None);
+    }
+    let uses = match db.take_favorite_suggestion(id, threshold)? {
+        Some(uses) => uses,
+        None => return Ok(None),
+    };
+    Ok(Some(FavoriteSuggestion {
+        id,
+        uses,
+        title: Tray::record_title(&db.find_list_item_by_id(id)?),
+    }))
+}
+
+/// 重复复制或粘贴记录后调用，达到次数时通知窗口，按配置发送系统通知
+pub fn on_used(id: u64) {
+    let config = { Config::common().latest().clone() };
+    let threshold = config.favorite_suggestion_threshold.unwrap_or(5);
+    let suggestion = match suggest(&SqliteDB::new(), id, threshold) {
+        Ok(Some(suggestion)) => suggestion,
+        Ok(None) => return,
+        Err(err) => {
+            println!("favorite suggestion error: {}", err);
+            return;
+        }
+    };
+    if config.favorite_suggestion_notification.unwrap_or(false) {
+        capture_notify::notify(i18n::t_args(
+            "notification.favorite_suggestion",
+            &[
+                ("title", &suggestion.title),
+                ("count", &suggestion.uses.to_string()),
+            ],
+        ));
+    }
+    log_err!(Handle::notice_to_window(
+        MsgTypeEnum::FavoriteSuggested,
+        suggestion
+    ));
+}
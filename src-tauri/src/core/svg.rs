@@ -0,0 +1,76 @@
+use super::clipboard::ImageDataDB;
+use crate::utils::{json_util, string_util};
+use anyhow::{anyhow, bail, Result};
+use resvg::{tiny_skia, usvg};
+
+/// 复制为 PNG 时的最大边长
+const MAX_RASTER_SIZE: u32 = 4096;
+/// 超过该长度的文本不当作 SVG 解析
+const MAX_SVG_LEN: usize = 10 * 1024 * 1024;
+
+/// 复制的文本是否是一个完整的 SVG 文档
+pub fn is_svg(text: &str) -> bool {
+    let text = text.trim();
+    if text.len() > MAX_SVG_LEN || !text.ends_with("</svg>") {
+        return false;
+    }
+    // 允许 XML 声明、注释和 DOCTYPE 出现在 <svg 之前
+    let head = text.trim_start_matches('\u{feff}');
+    head.starts_with("<svg")
+        || ((head.starts_with("<?xml") || head.starts_with("<!")) && head.contains("<svg"))
+}
+
+/// 渲染为 PNG，max 为最大边长，比原始尺寸小时等比缩小，返回 PNG 和像素尺寸
+pub fn rasterize(svg: &str, max: u32) -> Result<(Vec<u8>, u32, u32)> {
+    let tree = usvg::Tree::from_str(svg, &usvg::Options::default())?;
+    let (width, height) = (tree.size.width(), tree.size.height());
+    if width <= 0.0 || height <= 0.0 {
+        bail!("svg has no size");
+    }
+    let scale = (max as f64 / width.max(height)).min(1.0);
+    let (w, h) = (
+        ((width * scale).ceil() as u32).max(1),
+        ((height * scale).ceil() as u32).max(1),
+    );
+    let mut pixmap = tiny_skia::Pixmap::new(w, h).ok_or_else(|| anyhow!("invalid svg size"))?;
+    resvg::render(
+        &tree,
+        usvg::FitTo::Original,
+        tiny_skia::Transform::from_scale(scale as f32, scale as f32),
+        pixmap.as_mut(),
+    )
+    .ok_or_else(|| anyhow!("failed to render svg"))?;
+    Ok((pixmap.encode_png()?, w, h))
+}
+
+/// 列表中的预览只保存尺寸，图片由 thumb:// 按需渲染
+pub fn preview(svg: &str) -> Result<String> {
+    let tree = usvg::Tree::from_str(svg, &usvg::Options::default())?;
+    json_util::stringfy(&ImageDataDB {
+        width: tree.size.width().ceil() as usize,
+        height: tree.size.height().ceil() as usize,
+        ..ImageDataDB::default()
+    })
+}
+
+/// 复制为 PNG 时使用的图片
+pub fn to_image(svg: &str) -> Result<ImageDataDB> {
+    let (png, width, height) = rasterize(svg, MAX_RASTER_SIZE)?;
+    Ok(ImageDataDB {
+        width: width as usize,
+        height: height as usize,
+        base64: string_util::base64_encode(&png),
+        ..ImageDataDB::default()
+    })
+}
+
+#[test]
+fn test_is_svg() {
+    assert!(is_svg(r#"<svg xmlns="http://www.w3.org/2000/svg"></svg>"#));
+    assert!(is_svg(
+        "<?xml version=\"1.0\"?>\n<svg viewBox=\"0 0 1 1\"><rect/></svg>\n"
+    ));
+    assert!(!is_svg("<svg> is a tag"));
+    assert!(!is_svg("<div><svg></svg></div>"));
+    assert!(!is_svg("<?xml version=\"1.0\"?><note></note>"));
+}
@@ -0,0 +1,490 @@
+use super::database::{Record, SqliteDB};
+use super::handle::{Handle, MsgTypeEnum};
+use super::retention::RECORD_TYPES;
+use crate::config::{CommonConfig, Config};
+use crate::log_err;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// 冲突处理策略：`last_writer_wins` 按修改时间取较新的一边，`union` 合并两边，`manual` 放入待处理队列
+pub const SYNC_POLICIES: [&str; 3] = ["last_writer_wins", "union", "manual"];
+
+/// 其它设备上一条记录的状态，两边按内容的 md5 对应
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct SyncChange {
+    pub md5: String,
+    pub data_type: String,
+    /// 对方已有该记录时可以为空
+    pub content: Option<String>,
+    pub is_favorite: bool,
+    /// 逗号分隔，和本地保存的格式相同
+    pub tags: String,
+    /// 收藏、标签或删除的最后修改时间（毫秒）
+    pub updated_at: u64,
+    pub deleted: bool,
+}
+
+/// 当前记录和同步相关的状态
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncState {
+    pub id: u64,
+    pub is_favorite: bool,
+    pub tags: String,
+    pub updated_at: u64,
+}
+
+/// 待手动处理的冲突，local、remote 为该字段两边的值
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SyncConflict {
+    pub id: u64,
+    pub record_id: u64,
+    /// `favorite`、`tags` 或 `deleted`
+    pub field: String,
+    pub local: Value,
+    pub remote: Value,
+    pub device: String,
+    pub create_time: u64,
+}
+
+/// 已配对的设备，同步范围的条件同时满足时才传输该记录
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct SyncDevice {
+    pub id: String,
+    pub name: String,
+    /// 为 true 时只同步收藏的记录
+    pub favorites_only: Option<bool>,
+    /// 只同步带有其中任一标签的记录，为空时不限制
+    pub tags: Option<Vec<String>>,
+    /// 同步的记录类型：`text` / `image`，为空时不限制
+    pub data_types: Option<Vec<String>>,
+    /// 配对时交换的密钥（base64），用于请求签名和加解密 body
+    pub token: Option<String>,
+    pub paired_at: Option<u64>,
+}
+
+/// 本机作为客户端配对过的其它电脑（对方的同步接口）
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct SyncRemote {
+    /// 本机在对方设备列表中显示的名字
+    pub name: String,
+    /// 对方同步接口的地址，如 http://192.168.1.2:18091
+    pub address: String,
+    /// 对方分配给本机的设备 id
+    pub device_id: String,
+    /// 配对时对方返回的密钥（base64）
+    pub token: String,
+    pub paired_at: Option<u64>,
+}
+
+impl SyncRemote {
+    pub fn validate(&self) -> Result<()> {
+        if !self.address.starts_with("http://") && !self.address.starts_with("https://") {
+            bail!("sync remote address must start with http:// or https://");
+        }
+        if self.device_id.trim().is_empty() || self.token.trim().is_empty() {
+            bail!("sync remote \"{}\" is not paired", self.name);
+        }
+        Ok(())
+    }
+}
+
+impl SyncDevice {
+    pub fn validate(&self) -> Result<()> {
+        if self.id.trim().is_empty() {
+            bail!("sync device id is required");
+        }
+        for data_type in self.data_types.iter().flatten() {
+            if !RECORD_TYPES.contains(&data_type.as_str()) {
+                bail!(
+                    "unsupported data type \"{}\" for sync device \"{}\"",
+                    data_type,
+                    self.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn tags(&self) -> Vec<&str> {
+        self.tags
+            .iter()
+            .flatten()
+            .map(|tag| tag.trim())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    }
+
+    /// 和 SqliteDB::find_sync_changes 中的条件一致
+    fn allows(&self, data_type: &str, is_favorite: bool, tags: &str) -> bool {
+        if let Some(types) = self.data_types.as_ref().filter(|t| !t.is_empty()) {
+            if !types.iter().any(|t| t == data_type) {
+                return false;
+            }
+        }
+        if self.favorites_only.unwrap_or(false) && !is_favorite {
+            return false;
+        }
+        let wanted = self.tags();
+        wanted.is_empty() || tags.split(',').any(|tag| wanted.contains(&tag))
+    }
+}
+
+/// 未配置的设备不限制同步范围
+pub fn find_device(id: &str) -> SyncDevice {
+    let devices = { Config::common().latest().sync_devices.clone() };
+    devices
+        .unwrap_or_default()
+        .into_iter()
+        .find(|device| device.id == id)
+        .unwrap_or_else(|| SyncDevice {
+            id: id.into(),
+            ..SyncDevice::default()
+        })
+}
+
+/// 删除标记保留的时间，超过这个时间没有同步的设备不会收到删除
+pub const TOMBSTONE_RETENTION_MILLIS: u64 = 30 * 24 * 60 * 60 * 1000;
+
+/// 在游标 (since, after) 之后新增、修改或删除过、且在设备同步范围内的记录
+/// 按修改时间和 md5 升序，加密记录不同步
+pub fn changes_for(
+    device: &str,
+    since: u64,
+    after: &str,
+    limit: Option<usize>,
+) -> Result<Vec<SyncChange>> {
+    let device = find_device(device);
+    SqliteDB::new().find_sync_changes(
+        since,
+        after,
+        device.data_types.as_deref(),
+        device.favorites_only.unwrap_or(false),
+        &device.tags(),
+        limit.unwrap_or(200),
+    )
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ApplyResult {
+    pub inserted: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub conflicts: usize,
+}
+
+/// 一个字段合并后的结果
+#[derive(Debug, Clone, PartialEq)]
+enum Resolution<T> {
+    Keep,
+    Apply(T),
+    Conflict,
+}
+
+fn policy(config: &CommonConfig, field: &str) -> String {
+    // 和默认配置保持一致
+    match field {
+        "favorite" => config
+            .sync_favorite_policy
+            .clone()
+            .unwrap_or_else(|| "last_writer_wins".into()),
+        "tags" => config
+            .sync_tag_policy
+            .clone()
+            .unwrap_or_else(|| "union".into()),
+        _ => config
+            .sync_deletion_policy
+            .clone()
+            .unwrap_or_else(|| "manual".into()),
+    }
+}
+
+pub fn validate_policy(policy: &str) -> Result<()> {
+    if !SYNC_POLICIES.contains(&policy) {
+        bail!("unsupported sync conflict policy \"{}\"", policy);
+    }
+    Ok(())
+}
+
+fn merge_tags(local: &str, remote: &str) -> String {
+    let mut tags: Vec<&str> = local.split(',').filter(|t| !t.is_empty()).collect();
+    for tag in remote.split(',').filter(|t| !t.is_empty()) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags.join(",")
+}
+
+fn resolve_favorite(policy: &str, local: &SyncState, remote: &SyncChange) -> Resolution<bool> {
+    if local.is_favorite == remote.is_favorite {
+        return Resolution::Keep;
+    }
+    match policy {
+        "union" => Resolution::Apply(true),
+        "manual" => Resolution::Conflict,
+        _ if remote.updated_at > local.updated_at => Resolution::Apply(remote.is_favorite),
+        _ => Resolution::Keep,
+    }
+}
+
+fn resolve_tags(policy: &str, local: &SyncState, remote: &SyncChange) -> Resolution<String> {
+    if local.tags == remote.tags {
+        return Resolution::Keep;
+    }
+    match policy {
+        "union" => {
+            let merged = merge_tags(&local.tags, &remote.tags);
+            if merged == local.tags {
+                Resolution::Keep
+            } else {
+                Resolution::Apply(merged)
+            }
+        }
+        "manual" => Resolution::Conflict,
+        _ if remote.updated_at > local.updated_at => Resolution::Apply(remote.tags.clone()),
+        _ => Resolution::Keep,
+    }
+}
+
+/// union 时任一边保留就不删除
+fn resolve_deletion(policy: &str, local: &SyncState, remote: &SyncChange) -> Resolution<()> {
+    if !remote.deleted {
+        return Resolution::Keep;
+    }
+    match policy {
+        "union" => Resolution::Keep,
+        "manual" => Resolution::Conflict,
+        _ if remote.updated_at > local.updated_at => Resolution::Apply(()),
+        _ => Resolution::Keep,
+    }
+}
+
+fn conflict(
+    local: &SyncState,
+    field: &str,
+    local_value: Value,
+    remote_value: Value,
+    device: &str,
+) -> SyncConflict {
+    SyncConflict {
+        id: 0,
+        record_id: local.id,
+        field: field.into(),
+        local: local_value,
+        remote: remote_value,
+        device: device.into(),
+        create_time: chrono::Local::now().timestamp_millis() as u64,
+    }
+}
+
+/// 合并其它设备发来的记录状态，按配置的策略处理收藏、标签和删除的冲突
+pub fn apply_remote(changes: Vec<SyncChange>, device: &str) -> Result<ApplyResult> {
+    let config = { Config::common().latest().clone() };
+    let scope = find_device(device);
+    let db = SqliteDB::new();
+    let mut res = ApplyResult::default();
+    for change in changes {
+        let local = match db.find_sync_state(&change.md5)? {
+            Some(local) => local,
+            None => {
+                // 本地没有的记录直接新增，已删除或不在同步范围内的不新增
+                if !scope.allows(&change.data_type, change.is_favorite, &change.tags) {
+                    continue;
+                }
+                if let (false, Some(content)) = (change.deleted, change.content.clone()) {
+                    insert_remote(&db, &change, content)?;
+                    res.inserted += 1;
+                }
+                continue;
+            }
+        };
+        let resolution = match resolve_deletion(&policy(&config, "deleted"), &local, &change) {
+            // 锁定的记录需要先解锁才能删除，交给用户处理
+            Resolution::Apply(()) if db.is_locked(local.id)? => Resolution::Conflict,
+            resolution => resolution,
+        };
+        match resolution {
+            Resolution::Apply(()) => {
+                db.delete_by_ids(&[local.id])?;
+                res.deleted += 1;
+                continue;
+            }
+            Resolution::Conflict => {
+                db.add_sync_conflict(&conflict(
+                    &local,
+                    "deleted",
+                    false.into(),
+                    true.into(),
+                    device,
+                ))?;
+                res.conflicts += 1;
+                continue;
+            }
+            Resolution::Keep if change.deleted => continue,
+            Resolution::Keep => {}
+        }
+        let mut updated = false;
+        match resolve_favorite(&policy(&config, "favorite"), &local, &change) {
+            Resolution::Apply(value) => {
+                db.set_favorite(&[local.id], value)?;
+                updated = true;
+            }
+            Resolution::Conflict => {
+                let c = conflict(
+                    &local,
+                    "favorite",
+                    local.is_favorite.into(),
+                    change.is_favorite.into(),
+                    device,
+                );
+                db.add_sync_conflict(&c)?;
+                res.conflicts += 1;
+            }
+            Resolution::Keep => {}
+        }
+        match resolve_tags(&policy(&config, "tags"), &local, &change) {
+            Resolution::Apply(tags) => {
+                db.save_tags(local.id, tags)?;
+                updated = true;
+            }
+            Resolution::Conflict => {
+                let c = conflict(
+                    &local,
+                    "tags",
+                    local.tags.clone().into(),
+                    change.tags.clone().into(),
+                    device,
+                );
+                db.add_sync_conflict(&c)?;
+                res.conflicts += 1;
+            }
+            Resolution::Keep => {}
+        }
+        if updated {
+            // 沿用对方的修改时间，避免被当成本地的新修改再同步回去
+            db.set_updated_at(local.id, change.updated_at.max(local.updated_at))?;
+            res.updated += 1;
+        }
+    }
+    if res != ApplyResult::default() {
+        log_err!(Handle::update_systray());
+        log_err!(Handle::notice_to_window(MsgTypeEnum::ChangeClipBoard, "ok"));
+    }
+    Ok(res)
+}
+
+fn insert_remote(db: &SqliteDB, change: &SyncChange, content: String) -> Result<()> {
+    let res = db.insert_if_not_exist(Record {
+        content,
+        data_type: change.data_type.clone(),
+        is_favorite: change.is_favorite,
+        ..Default::default()
+    })?;
+    if !change.tags.is_empty() {
+        db.save_tags(res.id(), change.tags.clone())?;
+    }
+    db.set_updated_at(res.id(), change.updated_at)?;
+    Ok(())
+}
+
+pub fn list_conflicts() -> Result<Vec<SyncConflict>> {
+    SqliteDB::new().find_sync_conflicts()
+}
+
+/// use_remote 为 true 时采用对方的值，否则保留本地的值
+pub fn resolve_conflict(id: u64, use_remote: bool) -> Result<()> {
+    let db = SqliteDB::new();
+    let conflict = db.find_sync_conflict(id)?;
+    if use_remote {
+        match conflict.field.as_str() {
+            "favorite" => db.set_favorite(
+                &[conflict.record_id],
+                conflict.remote.as_bool().unwrap_or(false),
+            )?,
+            "tags" => db.save_tags(
+                conflict.record_id,
+                conflict.remote.as_str().unwrap_or_default().to_string(),
+            )?,
+            "deleted" => db.delete_by_ids(&[conflict.record_id])?,
+            field => bail!("unknown conflict field \"{}\"", field),
+        }
+        log_err!(Handle::update_systray());
+        log_err!(Handle::notice_to_window(MsgTypeEnum::ChangeClipBoard, "ok"));
+    }
+    db.delete_sync_conflict(id)
+}
+
+#[test]
+fn test_resolve_sync_conflicts() {
+    let local = SyncState {
+        id: 1,
+        is_favorite: false,
+        tags: "a,b".into(),
+        updated_at: 100,
+    };
+    let remote = |updated_at: u64| SyncChange {
+        md5: "md5".into(),
+        is_favorite: true,
+        tags: "b,c".into(),
+        updated_at,
+        ..SyncChange::default()
+    };
+    assert_eq!(
+        resolve_favorite("last_writer_wins", &local, &remote(200)),
+        Resolution::Apply(true)
+    );
+    assert_eq!(
+        resolve_favorite("last_writer_wins", &local, &remote(50)),
+        Resolution::Keep
+    );
+    assert_eq!(
+        resolve_favorite("union", &local, &remote(50)),
+        Resolution::Apply(true)
+    );
+    assert_eq!(
+        resolve_favorite("manual", &local, &remote(200)),
+        Resolution::Conflict
+    );
+    assert_eq!(
+        resolve_tags("union", &local, &remote(50)),
+        Resolution::Apply("a,b,c".into())
+    );
+    assert_eq!(
+        resolve_tags("last_writer_wins", &local, &remote(200)),
+        Resolution::Apply("b,c".into())
+    );
+    let deleted = SyncChange {
+        deleted: true,
+        ..remote(200)
+    };
+    assert_eq!(
+        resolve_deletion("last_writer_wins", &local, &deleted),
+        Resolution::Apply(())
+    );
+    assert_eq!(
+        resolve_deletion("union", &local, &deleted),
+        Resolution::Keep
+    );
+    assert_eq!(
+        resolve_deletion("manual", &local, &remote(200)),
+        Resolution::Keep
+    );
+    let laptop = SyncDevice {
+        id: "laptop".into(),
+        tags: Some(vec!["work".into()]),
+        data_types: Some(vec!["text".into()]),
+        ..SyncDevice::default()
+    };
+    assert!(laptop.allows("text", false, "a,work"));
+    assert!(!laptop.allows("text", false, "workshop"));
+    assert!(!laptop.allows("image", true, "work"));
+    let favorites = SyncDevice {
+        favorites_only: Some(true),
+        ..SyncDevice::default()
+    };
+    assert!(favorites.allows("image", true, ""));
+    assert!(!favorites.allows("text", false, ""));
+    assert!(validate_policy("union").is_ok());
+    assert!(validate_policy("newest").is_err());
+}
@@ -0,0 +1,197 @@
+use super::bridge::hostname;
+use super::capture_notify;
+use super::clipboard::{ClipBoardOprator, ImageDataDB};
+use super::i18n;
+use super::pairing::{self, DEVICE_HEADER, SIGNATURE_HEADER, TIME_HEADER};
+use super::sync::{SyncChange, SyncRemote};
+use crate::config::{self, CommonConfig, Config};
+use crate::utils::crypto_util::SecretKey;
+use crate::utils::{img_util, json_util, string_util};
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 取收件箱的间隔，请求失败后逐次加倍，最长 MAX_BACKOFF
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+const TIMEOUT_SECS: u64 = 10;
+
+/// 对方设置页二维码中的内容
+#[derive(Deserialize)]
+struct PairingPayload {
+    v: u32,
+    address: String,
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct PairRes {
+    device_id: String,
+    secret: String,
+}
+
+#[derive(Deserialize)]
+struct InboxRes {
+    clips: Vec<SyncChange>,
+}
+
+/// 用对方二维码中的内容配对，name 为本机在对方显示的名字，为空时使用主机名，同一地址重新配对时替换
+pub fn pair_with(payload: &str, name: &str) -> Result<SyncRemote> {
+    let name = match name.trim() {
+        "" => hostname(),
+        name => name.to_string(),
+    };
+    let name = name.as_str();
+    let payload: PairingPayload =
+        json_util::parse(payload.trim()).map_err(|_| anyhow!("invalid pairing code"))?;
+    if payload.v != 2 {
+        bail!("the other device runs an incompatible version, update both devices and try again");
+    }
+    let address = payload.address.trim_end_matches('/').to_string();
+    let body = json!({ "name": name, "proof": pairing::pairing_proof(&payload.key, name) });
+    let res = ureq::post(&format!("{}/sync/pair", address))
+        .timeout(Duration::from_secs(TIMEOUT_SECS))
+        .send_json(body)
+        .map_err(request_error)?;
+    let res: PairRes = res.into_json()?;
+    let token = pairing::pairing_transport_key(&payload.key).decrypt(&res.secret)?;
+    let remote = SyncRemote {
+        name: name.to_string(),
+        address,
+        device_id: res.device_id,
+        token,
+        paired_at: Some(chrono::Local::now().timestamp_millis() as u64),
+    };
+    let mut remotes = { Config::common().latest().sync_remotes.clone() }.unwrap_or_default();
+    remotes.retain(|r| r.address != remote.address);
+    remotes.push(remote.clone());
+    tauri::async_runtime::block_on(save_remotes(remotes))?;
+    Ok(remote)
+}
+
+/// 只删除本机保存的密钥，对方的设备列表中需要另外取消配对
+pub async fn remove(device_id: &str) -> Result<()> {
+    let mut remotes = { Config::common().latest().sync_remotes.clone() }.unwrap_or_default();
+    remotes.retain(|r| r.device_id != device_id);
+    save_remotes(remotes).await
+}
+
+async fn save_remotes(remotes: Vec<SyncRemote>) -> Result<()> {
+    let patch = CommonConfig {
+        sync_remotes: Some(remotes),
+        ..CommonConfig::default()
+    };
+    patch.validate()?;
+    config::modify_common_config(patch).await
+}
+
+fn request_error(err: ureq::Error) -> anyhow::Error {
+    match err {
+        ureq::Error::Status(401, _) => anyhow!("the other device rejected the request, pair again"),
+        ureq::Error::Status(status, res) => {
+            let msg = res
+                .into_json::<Value>()
+                .ok()
+                .and_then(|v| v["error"].as_str().map(String::from))
+                .unwrap_or_default();
+            anyhow!("the other device responded {} {}", status, msg)
+        }
+        err => err.into(),
+    }
+}
+
+/// 签名后请求对方的接口，返回解密后的响应
+fn get(remote: &SyncRemote, url: &str) -> Result<String> {
+    let key = SecretKey::from_base64(&remote.token)?;
+    let time = chrono::Local::now().timestamp_millis() as u64;
+    let signature = string_util::to_hex(&key.sign(&pairing::signing_payload("GET", url, time, "")));
+    let res = ureq::get(&format!("{}{}", remote.address, url))
+        .timeout(Duration::from_secs(TIMEOUT_SECS))
+        .set(DEVICE_HEADER, &remote.device_id)
+        .set(TIME_HEADER, &time.to_string())
+        .set(SIGNATURE_HEADER, &signature)
+        .call()
+        .map_err(request_error)?;
+    let body: Value = res.into_json()?;
+    let data = body["data"]
+        .as_str()
+        .ok_or(anyhow!("unexpected response from the other device"))?;
+    key.decrypt(data)
+}
+
+/// 取走对方发送给本机的记录，最后发送的一条写入剪切板，由剪切板监听记录
+fn poll(remote: &SyncRemote) -> Result<()> {
+    let inbox: InboxRes = json_util::parse(&get(remote, "/sync/inbox")?)?;
+    let clip = match inbox.clips.into_iter().last() {
+        Some(clip) => clip,
+        None => return Ok(()),
+    };
+    let content = clip.content.unwrap_or_default();
+    match clip.data_type.as_str() {
+        "image" => {
+            // 对方发来的内容先检查能否解码，写入剪切板时不再检查
+            let image: ImageDataDB = json_util::parse(&content)?;
+            string_util::try_base64_decode(&image.base64)?;
+            img_util::base64_to_rgba8(&image.base64)?;
+            ClipBoardOprator::set_image(image)?
+        }
+        _ => ClipBoardOprator::set_text(content)?,
+    }
+    capture_notify::notify(i18n::t_args(
+        "notification.received_from",
+        &[("name", &remote.address)],
+    ));
+    Ok(())
+}
+
+/// 失败次数对应的等待时间
+fn backoff(failures: u32) -> Duration {
+    POLL_INTERVAL
+        .saturating_mul(2u32.saturating_pow(failures))
+        .min(MAX_BACKOFF)
+}
+
+/// 定时取各个配对过的电脑的收件箱，没有配对时只检查配置
+pub fn start() {
+    thread::spawn(|| {
+        // 设备 id 对应的连续失败次数和下次请求的时间
+        let mut schedule: HashMap<String, (u32, Instant)> = HashMap::new();
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let remotes = { Config::common().latest().sync_remotes.clone() }.unwrap_or_default();
+            schedule.retain(|id, _| remotes.iter().any(|r| &r.device_id == id));
+            for remote in remotes {
+                let (failures, next) = schedule
+                    .get(&remote.device_id)
+                    .copied()
+                    .unwrap_or((0, Instant::now()));
+                if Instant::now() < next {
+                    continue;
+                }
+                let failures = match poll(&remote) {
+                    Ok(()) => 0,
+                    Err(err) => {
+                        // 只在第一次失败时输出，对方关机时不刷屏
+                        if failures == 0 {
+                            println!("poll {} inbox error: {}", remote.address, err);
+                        }
+                        failures + 1
+                    }
+                };
+                let next = Instant::now() + backoff(failures);
+                schedule.insert(remote.device_id.clone(), (failures, next));
+            }
+        }
+    });
+}
+
+#[test]
+fn test_poll_backoff() {
+    assert_eq!(backoff(0), POLL_INTERVAL);
+    assert_eq!(backoff(2), POLL_INTERVAL * 4);
+    assert_eq!(backoff(20), MAX_BACKOFF);
+    assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+}
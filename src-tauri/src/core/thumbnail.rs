@@ -0,0 +1,246 @@
+use super::clipboard::ImageDataDB;
+use super::database::SqliteDB;
+use super::svg;
+use super::vault::Vault;
+use crate::utils::{dirs, json_util, string_util};
+use anyhow::{bail, Result};
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::imageops::FilterType;
+use image::{AnimationDecoder, Frame};
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+use tauri::http::{Request, Response, ResponseBuilder};
+use tauri::AppHandle;
+
+pub const PROTOCOL: &str = "thumb";
+const DEFAULT_SIZE: u32 = 256;
+/// 列表中图片使用的尺寸，新图片入库后在后台预先生成
+pub const LIST_SIZE: u32 = 512;
+const MAX_SIZE: u32 = 1024;
+/// 尺寸按 64 取整，避免同一张图缓存太多份
+const SIZE_STEP: u32 = 64;
+const JPEG_QUALITY: u8 = 80;
+/// 动图缩略图最多保留的帧数
+const MAX_GIF_FRAMES: usize = 200;
+
+/// 解析 thumb://localhost/<id>?size=<n>，Windows 上为 https://thumb.localhost/<id>?size=<n>
+fn parse_uri(uri: &str) -> Option<(u64, u32)> {
+    let rest = uri.split_once("localhost/")?.1;
+    let (id, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let id = id.trim_end_matches('/').parse().ok()?;
+    let size = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == "size")
+        .and_then(|(_, v)| v.parse::<u32>().ok())
+        .unwrap_or(DEFAULT_SIZE);
+    Some((id, normalize_size(size)))
+}
+
+fn normalize_size(size: u32) -> u32 {
+    let size = size.clamp(SIZE_STEP, MAX_SIZE);
+    (size + SIZE_STEP - 1) / SIZE_STEP * SIZE_STEP
+}
+
+/// 缩略图缓存的目录名，在图片目录下
+pub const CACHE_DIR: &str = "thumbs";
+
+fn thumbs_dir() -> Result<PathBuf> {
+    Ok(dirs::app_data_img_dir()?.join(CACHE_DIR))
+}
+
+fn cache_path(id: u64, size: u32, ext: &str) -> Result<PathBuf> {
+    Ok(thumbs_dir()?.join(format!("{}_{}.{}", id, size, ext)))
+}
+
+/// 逐帧缩小的动图缩略图
+fn animated_thumbnail(gif: &[u8], size: u32) -> Result<Vec<u8>> {
+    let frames = GifDecoder::new(Cursor::new(gif))?
+        .into_frames()
+        .take(MAX_GIF_FRAMES)
+        .collect::<image::ImageResult<Vec<Frame>>>()?;
+    let mut bytes = vec![];
+    {
+        let mut encoder = GifEncoder::new(&mut bytes);
+        encoder.set_repeat(Repeat::Infinite)?;
+        for frame in frames {
+            let delay = frame.delay();
+            let img = image::DynamicImage::ImageRgba8(frame.into_buffer());
+            let img = match img.width() > size || img.height() > size {
+                true => img.resize(size, size, FilterType::Triangle),
+                false => img,
+            };
+            encoder.encode_frame(Frame::from_parts(img.into_rgba8(), 0, 0, delay))?;
+        }
+    }
+    Ok(bytes)
+}
+
+/// 由原图生成缩略图，加密记录需保险箱已解锁且不落盘
+pub fn thumbnail(id: u64, size: u32) -> Result<Vec<u8>> {
+    let db = SqliteDB::new();
+    // 先查列表项，命中缓存时不用读取原图
+    let item = db.find_list_item_by_id(id)?;
+    if item.data_type != "image" && item.data_type != "svg" {
+        bail!("record {} is not an image", id);
+    }
+    for ext in ["jpg", "gif", "png"] {
+        let path = cache_path(id, size, ext)?;
+        if path.exists() {
+            if !item.is_secure {
+                return Ok(fs::read(path)?);
+            }
+            // 生成缓存后才被加密的记录
+            fs::remove_file(&path)?;
+        }
+    }
+    let record = db.find_by_id(id)?;
+    let content = Vault::global().reveal(&record)?;
+    // SVG 保留透明背景，缓存为 PNG
+    if record.data_type == "svg" {
+        let (bytes, _, _) = svg::rasterize(&content, size)?;
+        if !record.is_secure {
+            fs::create_dir_all(thumbs_dir()?)?;
+            fs::write(cache_path(id, size, "png")?, &bytes)?;
+        }
+        return Ok(bytes);
+    }
+    let data: ImageDataDB = json_util::parse(&content)?;
+    let (bytes, path) = match &data.gif {
+        Some(gif) => (
+            animated_thumbnail(&string_util::base64_decode(gif), size)?,
+            cache_path(id, size, "gif")?,
+        ),
+        None => {
+            let img = image::load_from_memory(&string_util::base64_decode(&data.base64))?;
+            // 原图比目标尺寸小时不放大
+            let img = match img.width() > size || img.height() > size {
+                true => img.resize(size, size, FilterType::Triangle),
+                false => img,
+            };
+            let mut bytes = vec![];
+            image::DynamicImage::ImageRgb8(img.into_rgb8()).write_to(
+                &mut Cursor::new(&mut bytes),
+                image::ImageOutputFormat::Jpeg(JPEG_QUALITY),
+            )?;
+            (bytes, cache_path(id, size, "jpg")?)
+        }
+    };
+    if !record.is_secure {
+        fs::create_dir_all(thumbs_dir()?)?;
+        fs::write(path, &bytes)?;
+    }
+    Ok(bytes)
+}
+
+/// 已不存在的记录的缩略图
+pub fn find_orphans(db: &SqliteDB) -> Result<Vec<PathBuf>> {
+    let dir = thumbs_dir()?;
+    let mut res = vec![];
+    if !dir.exists() {
+        return Ok(res);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let id = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.split('_').next())
+            .and_then(|id| id.parse::<u64>().ok());
+        let exists = match id {
+            Some(id) => db.find_by_id(id).is_ok(),
+            None => false,
+        };
+        if !exists {
+            res.push(path);
+        }
+    }
+    Ok(res)
+}
+
+/// 删除已不存在的记录的缩略图
+pub fn clean_orphans(db: &SqliteDB) -> Result<usize> {
+    let orphans = find_orphans(db)?;
+    for path in orphans.iter() {
+        fs::remove_file(path)?;
+    }
+    Ok(orphans.len())
+}
+
+/// 删除全部缩略图，恢复备份后 id 对应的记录已经不同
+pub fn clear() -> Result<()> {
+    let dir = thumbs_dir()?;
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+/// 用 0 覆盖记录的缩略图后删除
+pub fn shred(id: u64) -> Result<()> {
+    let dir = thumbs_dir()?;
+    if !dir.exists() {
+        return Ok(());
+    }
+    let prefix = format!("{}_", id);
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let matched = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .map_or(false, |name| name.starts_with(&prefix));
+        if matched {
+            let len = fs::metadata(&path)?.len() as usize;
+            fs::write(&path, vec![0u8; len])?;
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn clear() -> Result<()> {
+    let dir = thumbs_dir()?;
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+/// 列表中的图片通过该协议按需加载缩略图，不把原图传给 webview
+pub fn handle_protocol(
+    _app: &AppHandle,
+    request: &Request,
+) -> Result<Response, Box<dyn std::error::Error>> {
+    let (id, size) = match parse_uri(request.uri()) {
+        Some(res) => res,
+        None => return ResponseBuilder::new().status(400).body(vec![]),
+    };
+    match thumbnail(id, size) {
+        Ok(bytes) => ResponseBuilder::new()
+            .status(200)
+            .mimetype(match bytes.get(..4) {
+                Some(b"GIF8") => "image/gif",
+                Some(b"\x89PNG") => "image/png",
+                _ => "image/jpeg",
+            })
+            .header("Cache-Control", "max-age=86400")
+            .body(bytes),
+        Err(err) => {
+            println!("thumbnail {} error: {}", id, err);
+            ResponseBuilder::new().status(404).body(vec![])
+        }
+    }
+}
+
+#[test]
+fn test_parse_uri() {
+    assert_eq!(parse_uri("thumb://localhost/12?size=200"), Some((12, 256)));
+    assert_eq!(parse_uri("https://thumb.localhost/12"), Some((12, 256)));
+    assert_eq!(
+        parse_uri("thumb://localhost/12?size=5000"),
+        Some((12, 1024))
+    );
+    assert_eq!(parse_uri("thumb://localhost/12?size=1"), Some((12, 64)));
+    assert_eq!(parse_uri("thumb://localhost/abc"), None);
+}
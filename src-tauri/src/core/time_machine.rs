@@ -0,0 +1,108 @@
+use super::clipboard::ClipBoardOprator;
+use super::database::SqliteDB;
+use crate::utils::string_util;
+use anyhow::{bail, Result};
+use arboard::Clipboard;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 最多保留的被覆盖的剪切板内容
+const MAX_STACK: usize = 20;
+/// 复制历史保留的时间，更早的时间点只能按记录最近一次复制的时间查找
+pub const HISTORY_RETENTION_MILLIS: u64 = 90 * 24 * 60 * 60 * 1000;
+
+/// 被 Lanaya 覆盖前的剪切板内容
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClipState {
+    /// 已记录的内容，恢复时按原始类型写回
+    Record(u64),
+    /// 未记录的文本，如暂停记录时复制的内容
+    Text(String),
+}
+
+fn stack() -> &'static Mutex<Vec<ClipState>> {
+    static STACK: OnceCell<Mutex<Vec<ClipState>>> = OnceCell::new();
+    STACK.get_or_init(|| Mutex::new(vec![]))
+}
+
+/// 正在恢复，期间写入剪切板不再入栈
+static RESTORING: AtomicBool = AtomicBool::new(false);
+
+fn push(stack: &mut Vec<ClipState>, state: ClipState) {
+    if stack.last() == Some(&state) {
+        return;
+    }
+    stack.push(state);
+    if stack.len() > MAX_STACK {
+        stack.remove(0);
+    }
+}
+
+fn current_state() -> Option<ClipState> {
+    let text = Clipboard::new().ok()?.get_text().ok()?;
+    if text.trim().is_empty() {
+        return None;
+    }
+    match SqliteDB::new().find_record_by_md5(string_util::md5(&text)) {
+        Ok(record) => Some(ClipState::Record(record.id)),
+        Err(_) => Some(ClipState::Text(text)),
+    }
+}
+
+/// Lanaya 写入剪切板前调用，记下当前的内容，图片等非文本内容不记录
+pub fn remember_current() {
+    if RESTORING.load(Ordering::SeqCst) {
+        return;
+    }
+    if let Some(state) = current_state() {
+        push(&mut stack().lock(), state);
+    }
+}
+
+fn write(state: &ClipState) -> Result<()> {
+    match state {
+        ClipState::Record(id) => ClipBoardOprator::restore_by_id(*id),
+        // 恢复时也不记录
+        ClipState::Text(text) => ClipBoardOprator::set_transient_text(text.clone()),
+    }
+}
+
+/// 恢复被 Lanaya 覆盖前的剪切板内容，可以连续恢复更早的内容
+pub fn restore_previous() -> Result<()> {
+    let state = match stack().lock().pop() {
+        Some(state) => state,
+        None => bail!("no previous clipboard to restore"),
+    };
+    RESTORING.store(true, Ordering::SeqCst);
+    let res = write(&state);
+    RESTORING.store(false, Ordering::SeqCst);
+    res
+}
+
+/// 把剪切板恢复为 time（毫秒时间戳）时的内容，返回恢复的记录 id，不算作一次粘贴
+pub fn restore_at(time: u64) -> Result<u64> {
+    let id = match SqliteDB::new().find_active_at(time)? {
+        Some(id) => id,
+        None => bail!("no record was copied before that time"),
+    };
+    ClipBoardOprator::restore_by_id(id)?;
+    Ok(id)
+}
+
+#[test]
+fn test_clip_state_stack() {
+    let mut stack = vec![];
+    push(&mut stack, ClipState::Record(1));
+    push(&mut stack, ClipState::Record(1));
+    push(&mut stack, ClipState::Text("draft".into()));
+    assert_eq!(
+        stack,
+        vec![ClipState::Record(1), ClipState::Text("draft".into())]
+    );
+    for id in 0..MAX_STACK as u64 {
+        push(&mut stack, ClipState::Record(id + 10));
+    }
+    assert_eq!(stack.len(), MAX_STACK);
+    assert_eq!(stack[0], ClipState::Record(10));
+}
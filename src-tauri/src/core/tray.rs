@@ -1,8 +1,16 @@
+use super::clipboard::{ClipBoardOprator, ImageDataDB};
+use super::database::{QueryReq, Record, SqliteDB};
 use super::handle::Handle;
+use super::i18n;
+use super::pin;
+use super::vault::SECURE_MASK;
 use super::window_manager::WindowType;
 use crate::config;
 use crate::config::{CommonConfig, Config};
+use crate::log_err;
+use crate::utils::{hotkey_util, json_util};
 use anyhow::Result;
+use tauri::api::notification::Notification;
 use tauri::{
     AppHandle, CustomMenuItem, Manager, SystemTrayEvent, SystemTrayMenu, SystemTrayMenuItem,
     SystemTraySubmenu,
@@ -10,42 +18,155 @@ use tauri::{
 
 pub struct Tray {}
 
+/// 托盘图标的实现方式，Linux 下依桌面环境而定
+/// Tauri 在 Linux 上的托盘本身就是 libappindicator（StatusNotifierItem），这里只判断能否显示，不另外实现托盘
+#[allow(unused)]
+#[derive(Debug, PartialEq)]
+pub enum TrayBackend {
+    Native,
+    StatusNotifier,
+    XEmbed,
+    Unavailable,
+}
+
 impl Tray {
+    /// 没有托盘时（如未装扩展的 GNOME）只能通过快捷键打开，提示用户并显示设置窗口
+    pub fn ensure_reachable(app_handle: &AppHandle) {
+        if platform::detect_backend() != TrayBackend::Unavailable {
+            return;
+        }
+        let common = { Config::common().latest().clone() };
+        let shortcut = common
+            .hotkeys
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|h| hotkey_util::parse_hotkey(h).ok())
+            .find(|(func, _)| func == "global-shortcut")
+            .map(|(_, keys)| hotkey_util::get_short_cut_name(keys, true))
+            .unwrap_or_default();
+        let body = i18n::t_args("notification.tray_unavailable", &[("shortcut", &shortcut)]);
+        let identifier = app_handle.config().tauri.bundle.identifier.clone();
+        log_err!(Notification::new(identifier)
+            .title("Lanaya")
+            .body(body)
+            .show());
+        Handle::show_window(WindowType::Config);
+    }
+
     pub fn tray_menu(app_handle: &AppHandle) -> SystemTrayMenu {
-        let zh = { Config::common().latest().language == Some("zh".into()) };
         let version = app_handle.package_info().version.to_string();
-        if zh {
-            SystemTrayMenu::new()
-                .add_item(CustomMenuItem::new("open_window", "显示界面"))
-                .add_item(CustomMenuItem::new("hide_window", "隐藏界面").accelerator("Esc"))
-                .add_native_item(SystemTrayMenuItem::Separator)
-                .add_submenu(SystemTraySubmenu::new(
-                    "语言",
-                    SystemTrayMenu::new()
-                        .add_item(CustomMenuItem::new("language_zh", "简体中文"))
-                        .add_item(CustomMenuItem::new("language_en", "English")),
-                ))
-                .add_item(CustomMenuItem::new("more_config", "更多设置"))
-                .add_native_item(SystemTrayMenuItem::Separator)
-                .add_item(CustomMenuItem::new("app_version", format!("版本 {version}")).disabled())
-                .add_item(CustomMenuItem::new("quit", "退出").accelerator("CmdOrControl+Q"))
+        let menu = Self::recent_items(SystemTrayMenu::new());
+        // 每种语言用它自己的名字显示
+        let mut language_menu = SystemTrayMenu::new();
+        for language in i18n::available_languages() {
+            language_menu = language_menu.add_item(CustomMenuItem::new(
+                format!("language_{}", language),
+                i18n::tr(&language, "language.name"),
+            ));
+        }
+        menu.add_item(CustomMenuItem::new(
+            "open_window",
+            i18n::t("tray.show_window"),
+        ))
+        .add_item(
+            CustomMenuItem::new("hide_window", i18n::t("tray.hide_window")).accelerator("Esc"),
+        )
+        .add_item(CustomMenuItem::new(
+            "pin_window",
+            i18n::t("tray.pin_window"),
+        ))
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_submenu(SystemTraySubmenu::new(
+            i18n::t("tray.language"),
+            language_menu,
+        ))
+        .add_item(CustomMenuItem::new(
+            "more_config",
+            i18n::t("tray.more_config"),
+        ))
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(
+            CustomMenuItem::new(
+                "app_version",
+                i18n::t_args("tray.version", &[("version", &version)]),
+            )
+            .disabled(),
+        )
+        .add_item(CustomMenuItem::new("quit", i18n::t("tray.quit")).accelerator("CmdOrControl+Q"))
+    }
+
+    // 最近记录和收藏，点击后直接复制到剪切板
+    fn recent_items(menu: SystemTrayMenu) -> SystemTrayMenu {
+        let count = { Config::common().latest().tray_recent_count.unwrap_or(10) } as usize;
+        // 数据库还在初始化时先不显示，就绪后会重新生成菜单
+        if count == 0 || !SqliteDB::is_ready() {
+            return menu;
+        }
+        let db = SqliteDB::new();
+        let recent = db
+            .find_by_key(QueryReq {
+                limit: Some(count),
+                ..QueryReq::default()
+            })
+            .unwrap_or_default();
+        let favorites = db
+            .find_by_key(QueryReq {
+                limit: Some(count),
+                is_favorite: Some(true),
+                ..QueryReq::default()
+            })
+            .unwrap_or_default();
+        if recent.is_empty() && favorites.is_empty() {
+            return menu;
+        }
+        let mut menu = menu;
+        for r in recent.iter() {
+            menu = menu.add_item(CustomMenuItem::new(
+                format!("record_{}", r.id),
+                Self::record_title(r),
+            ));
+        }
+        if !favorites.is_empty() {
+            let mut favorite_menu = SystemTrayMenu::new();
+            for r in favorites.iter() {
+                favorite_menu = favorite_menu.add_item(CustomMenuItem::new(
+                    format!("favorite_{}", r.id),
+                    Self::record_title(r),
+                ));
+            }
+            menu = menu.add_submenu(SystemTraySubmenu::new(
+                i18n::t("tray.favorites"),
+                favorite_menu,
+            ));
+        }
+        menu.add_native_item(SystemTrayMenuItem::Separator)
+    }
+
+    /// 记录的单行标题，取第一行非空内容并截断
+    pub fn record_title(r: &Record) -> String {
+        if r.is_secure {
+            return SECURE_MASK.to_string();
+        }
+        // SVG 的列表内容也是图片尺寸
+        if r.data_type == "image" || r.data_type == "svg" {
+            let image: ImageDataDB = json_util::parse(&r.content).unwrap_or_default();
+            return format!(
+                "[{}] {}x{}",
+                i18n::t("tray.image"),
+                image.width,
+                image.height
+            );
+        }
+        let line = r
+            .content
+            .lines()
+            .map(|l| l.trim())
+            .find(|l| !l.is_empty())
+            .unwrap_or("");
+        if line.chars().count() > 30 {
+            format!("{}…", line.chars().take(30).collect::<String>())
         } else {
-            SystemTrayMenu::new()
-                .add_item(CustomMenuItem::new("open_window", "Show Window"))
-                .add_item(CustomMenuItem::new("hide_window", "Hide Window").accelerator("Esc"))
-                .add_native_item(SystemTrayMenuItem::Separator)
-                .add_submenu(SystemTraySubmenu::new(
-                    "Language",
-                    SystemTrayMenu::new()
-                        .add_item(CustomMenuItem::new("language_zh", "简体中文"))
-                        .add_item(CustomMenuItem::new("language_en", "English")),
-                ))
-                .add_item(CustomMenuItem::new("more_config", "More Config"))
-                .add_native_item(SystemTrayMenuItem::Separator)
-                .add_item(
-                    CustomMenuItem::new("app_version", format!("Version {version}")).disabled(),
-                )
-                .add_item(CustomMenuItem::new("quit", "Quit").accelerator("CmdOrControl+Q"))
+            line.to_string()
         }
     }
 
@@ -58,19 +179,14 @@ impl Tray {
     }
 
     pub fn update_select_item(app_handle: &AppHandle) -> Result<()> {
-        let language = { Config::common().latest().language.clone() };
-
+        let current = i18n::current_language();
         let tray = app_handle.tray_handle();
-
-        if let Some(language) = language {
-            if language == "zh" {
-                let _ = tray.get_item("language_zh").set_selected(true);
-                let _ = tray.get_item("language_en").set_selected(false);
-            } else {
-                let _ = tray.get_item("language_en").set_selected(true);
-                let _ = tray.get_item("language_zh").set_selected(false);
-            }
+        for language in i18n::available_languages() {
+            let _ = tray
+                .get_item(&format!("language_{}", language))
+                .set_selected(language == current);
         }
+        let _ = tray.get_item("pin_window").set_selected(pin::is_pinned());
         Ok(())
     }
 
@@ -86,9 +202,14 @@ impl Tray {
                         window.hide().unwrap();
                     }
                 }
-                "language_zh" => change_language("zh".into()),
-                "language_en" => change_language("en".into()),
+                "pin_window" => log_err!(pin::toggle()),
+                id if id.starts_with("language_") => {
+                    change_language(id.trim_start_matches("language_").into())
+                }
                 "more_config" => Handle::open_window(WindowType::Config),
+                id if id.starts_with("record_") || id.starts_with("favorite_") => {
+                    copy_from_tray(id)
+                }
                 "quit" => {
                     app_handle.exit(0);
                     std::process::exit(0);
@@ -120,3 +241,87 @@ fn change_language(mode: String) {
         }
     });
 }
+
+// 托盘中点击记录，复制回剪切板
+fn copy_from_tray(menu_id: &str) {
+    let id = menu_id
+        .split_once('_')
+        .and_then(|(_, id)| id.parse::<u64>().ok());
+    if let Some(id) = id {
+        if let Err(err) = ClipBoardOprator::copy_by_id(id, None) {
+            println!("copy_from_tray: {}", err);
+        }
+    }
+}
+
+/// 有 StatusNotifierWatcher 时使用 AppIndicator，
+/// 否则 X11 下 libappindicator 会退回 XEmbed 托盘，GNOME 和 Wayland 下都没有托盘
+#[allow(unused)]
+pub fn choose_backend(has_watcher: bool, session_type: &str, desktop: &str) -> TrayBackend {
+    if has_watcher {
+        return TrayBackend::StatusNotifier;
+    }
+    let is_x11 = session_type.eq_ignore_ascii_case("x11");
+    let is_gnome = desktop.to_uppercase().split(':').any(|d| d == "GNOME");
+    if is_x11 && !is_gnome {
+        return TrayBackend::XEmbed;
+    }
+    TrayBackend::Unavailable
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{choose_backend, TrayBackend};
+    use std::process::Command;
+
+    fn has_status_notifier_watcher() -> bool {
+        let output = Command::new("dbus-send")
+            .args([
+                "--session",
+                "--dest=org.freedesktop.DBus",
+                "--type=method_call",
+                "--print-reply",
+                "/org/freedesktop/DBus",
+                "org.freedesktop.DBus.NameHasOwner",
+                "string:org.kde.StatusNotifierWatcher",
+            ])
+            .output();
+        match output {
+            Ok(output) => String::from_utf8_lossy(&output.stdout).contains("boolean true"),
+            // 没有 dbus-send 时无法判断，当作可用
+            Err(_) => true,
+        }
+    }
+
+    pub fn detect_backend() -> TrayBackend {
+        let session_type = std::env::var("XDG_SESSION_TYPE").unwrap_or_default();
+        let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+        choose_backend(has_status_notifier_watcher(), &session_type, &desktop)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use super::TrayBackend;
+
+    pub fn detect_backend() -> TrayBackend {
+        TrayBackend::Native
+    }
+}
+
+#[test]
+fn test_choose_backend() {
+    assert_eq!(
+        choose_backend(true, "wayland", "GNOME"),
+        TrayBackend::StatusNotifier
+    );
+    assert_eq!(choose_backend(false, "x11", "XFCE"), TrayBackend::XEmbed);
+    assert_eq!(
+        choose_backend(false, "x11", "ubuntu:GNOME"),
+        TrayBackend::Unavailable
+    );
+    assert_eq!(
+        choose_backend(false, "wayland", "KDE"),
+        TrayBackend::Unavailable
+    );
+}
@@ -0,0 +1,310 @@
+use super::database::SqliteDB;
+use super::expansion;
+use super::handle::{Handle, MsgTypeEnum};
+use super::window_manager::WindowType;
+use crate::config::Config;
+use crate::log_err;
+use anyhow::{bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 两次按下的最大间隔
+const DOUBLE_TAP_INTERVAL: Duration = Duration::from_millis(400);
+/// 和弦前缀按下后等待下一个键的时间
+const CHORD_TIMEOUT: Duration = Duration::from_millis(1500);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriggerKind {
+    DoubleTap,
+    Chord,
+}
+
+/// 高级触发方式
+/// format: double-tap:{func}:{key}+{key} 或 chord:{func}:{key}+{key}
+/// 如 double-tap:global-shortcut:17+67 表示连按两次 Ctrl+C 打开主窗口
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    pub kind: TriggerKind,
+    pub func: String,
+    pub keys: BTreeSet<u32>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TriggerEvent {
+    Fired(String),
+    /// 和弦完成，附带前缀之后按下的键
+    ChordCompleted(String, u32),
+}
+
+pub fn parse_trigger(trigger: &str) -> Result<Trigger> {
+    let parts: Vec<&str> = trigger.splitn(3, ':').collect();
+    if parts.len() != 3 || parts[1].is_empty() {
+        bail!("malformed trigger \"{}\"", trigger);
+    }
+    let kind = match parts[0] {
+        "double-tap" => TriggerKind::DoubleTap,
+        "chord" => TriggerKind::Chord,
+        _ => bail!("unsupported trigger kind \"{}\"", parts[0]),
+    };
+    let mut keys = BTreeSet::new();
+    for key in parts[2].split('+') {
+        match key.parse::<u32>() {
+            Ok(code) => keys.insert(code),
+            Err(_) => bail!("malformed trigger \"{}\"", trigger),
+        };
+    }
+    Ok(Trigger {
+        kind,
+        func: parts[1].to_string(),
+        keys,
+    })
+}
+
+fn is_modifier(code: u32) -> bool {
+    matches!(code, 16 | 17 | 18 | 91)
+}
+
+/// 按键状态机，只关心按键编码（与前端 keyCode 一致）和时间
+#[derive(Default)]
+pub struct TriggerMachine {
+    triggers: Vec<Trigger>,
+    pressed: BTreeSet<u32>,
+    last_tap: Option<(usize, Instant)>,
+    pending_chord: Option<(usize, Instant)>,
+}
+
+impl TriggerMachine {
+    pub fn new(triggers: Vec<Trigger>) -> Self {
+        TriggerMachine {
+            triggers,
+            ..TriggerMachine::default()
+        }
+    }
+
+    pub fn on_key_down(&mut self, code: u32, now: Instant) -> Option<TriggerEvent> {
+        // 按住不放时系统会重复发送按下事件，忽略
+        if !self.pressed.insert(code) {
+            return None;
+        }
+        if let Some((idx, at)) = self.pending_chord.take() {
+            if now.duration_since(at) <= CHORD_TIMEOUT && !is_modifier(code) {
+                let func = self.triggers[idx].func.clone();
+                return Some(TriggerEvent::ChordCompleted(func, code));
+            }
+        }
+        let idx = self.triggers.iter().position(|t| t.keys == self.pressed)?;
+        match self.triggers[idx].kind {
+            TriggerKind::DoubleTap => match self.last_tap.take() {
+                Some((last, at))
+                    if last == idx && now.duration_since(at) <= DOUBLE_TAP_INTERVAL =>
+                {
+                    Some(TriggerEvent::Fired(self.triggers[idx].func.clone()))
+                }
+                _ => {
+                    self.last_tap = Some((idx, now));
+                    None
+                }
+            },
+            TriggerKind::Chord => {
+                self.pending_chord = Some((idx, now));
+                None
+            }
+        }
+    }
+
+    pub fn on_key_up(&mut self, code: u32) {
+        self.pressed.remove(&code);
+    }
+}
+
+fn machine() -> &'static Mutex<TriggerMachine> {
+    static MACHINE: OnceCell<Mutex<TriggerMachine>> = OnceCell::new();
+    MACHINE.get_or_init(|| Mutex::new(TriggerMachine::default()))
+}
+
+/// 重新加载配置中的触发方式和文本扩展的触发词，有配置时才启动全局按键监听
+pub fn refresh() {
+    let triggers = { Config::common().latest().triggers.clone() };
+    let triggers: Vec<Trigger> = triggers
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|t| match parse_trigger(t) {
+            Ok(t) => Some(t),
+            Err(err) => {
+                println!("err: {}", err);
+                None
+            }
+        })
+        .collect();
+    let enable = !triggers.is_empty() || expansion::is_enabled();
+    *machine().lock() = TriggerMachine::new(triggers);
+    expansion::reload();
+    if enable {
+        start_listener();
+    }
+}
+
+fn start_listener() {
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    thread::spawn(|| {
+        let res = rdev::listen(|event| {
+            expansion::on_event(&event);
+            let res = match event.event_type {
+                rdev::EventType::KeyPress(key) => key_to_code(key)
+                    .and_then(|code| machine().lock().on_key_down(code, Instant::now())),
+                rdev::EventType::KeyRelease(key) => {
+                    if let Some(code) = key_to_code(key) {
+                        machine().lock().on_key_up(code);
+                    }
+                    None
+                }
+                _ => None,
+            };
+            if let Some(trigger_event) = res {
+                // 执行动作放到其它线程，避免阻塞系统按键事件
+                thread::spawn(move || dispatch(trigger_event));
+            }
+        });
+        if let Err(err) = res {
+            println!("listen key event error: {:?}", err);
+        }
+    });
+}
+
+fn dispatch(event: TriggerEvent) {
+    match event {
+        TriggerEvent::Fired(func) => match Handle::hotkey_action(&func) {
+            Some(action) => action(),
+            None => println!("unknown trigger func \"{}\"", func),
+        },
+        TriggerEvent::ChordCompleted(func, code) => match func.as_str() {
+            "search-tag" => log_err!(search_tag(code)),
+            _ => match Handle::hotkey_action(&func) {
+                Some(action) => action(),
+                None => println!("unknown trigger func \"{}\"", func),
+            },
+        },
+    }
+}
+
+/// 和弦后按下的字母选择以该字母开头的标签，打开主窗口并按标签过滤
+fn search_tag(code: u32) -> Result<()> {
+    let letter = match char::from_u32(code) {
+        Some(c) if c.is_ascii_alphanumeric() => c.to_ascii_lowercase(),
+        _ => return Ok(()),
+    };
+    let tags = SqliteDB::new().find_all_tags()?;
+    let tag = tags
+        .into_iter()
+        .find(|t| t.to_lowercase().starts_with(letter));
+    if let Some(tag) = tag {
+        Handle::show_window(WindowType::Main);
+        Handle::notice_to_window(MsgTypeEnum::Search, format!("t:{}", tag))?;
+    }
+    Ok(())
+}
+
+fn key_to_code(key: rdev::Key) -> Option<u32> {
+    use rdev::Key::*;
+    let code = match key {
+        ShiftLeft | ShiftRight => 16,
+        ControlLeft | ControlRight => 17,
+        Alt | AltGr => 18,
+        MetaLeft | MetaRight => 91,
+        Backspace => 8,
+        Tab => 9,
+        Return => 13,
+        Escape => 27,
+        Space => 32,
+        LeftArrow => 37,
+        UpArrow => 38,
+        RightArrow => 39,
+        DownArrow => 40,
+        Num0 => 48,
+        Num1 => 49,
+        Num2 => 50,
+        Num3 => 51,
+        Num4 => 52,
+        Num5 => 53,
+        Num6 => 54,
+        Num7 => 55,
+        Num8 => 56,
+        Num9 => 57,
+        KeyA => 65,
+        KeyB => 66,
+        KeyC => 67,
+        KeyD => 68,
+        KeyE => 69,
+        KeyF => 70,
+        KeyG => 71,
+        KeyH => 72,
+        KeyI => 73,
+        KeyJ => 74,
+        KeyK => 75,
+        KeyL => 76,
+        KeyM => 77,
+        KeyN => 78,
+        KeyO => 79,
+        KeyP => 80,
+        KeyQ => 81,
+        KeyR => 82,
+        KeyS => 83,
+        KeyT => 84,
+        KeyU => 85,
+        KeyV => 86,
+        KeyW => 87,
+        KeyX => 88,
+        KeyY => 89,
+        KeyZ => 90,
+        _ => return None,
+    };
+    Some(code)
+}
+
+#[test]
+fn test_trigger_machine() {
+    let double_tap = parse_trigger("double-tap:global-shortcut:17+67").unwrap();
+    let chord = parse_trigger("chord:search-tag:17+18+86").unwrap();
+    let mut machine = TriggerMachine::new(vec![double_tap, chord]);
+    let start = Instant::now();
+    let at = |ms: u64| start + Duration::from_millis(ms);
+
+    // Ctrl 按住，C 连按两次
+    assert_eq!(machine.on_key_down(17, at(0)), None);
+    assert_eq!(machine.on_key_down(67, at(10)), None);
+    // 重复的按下事件被忽略
+    assert_eq!(machine.on_key_down(67, at(20)), None);
+    machine.on_key_up(67);
+    assert_eq!(
+        machine.on_key_down(67, at(200)),
+        Some(TriggerEvent::Fired("global-shortcut".into()))
+    );
+    machine.on_key_up(67);
+    // 间隔太久不触发
+    assert_eq!(machine.on_key_down(67, at(1000)), None);
+    machine.on_key_up(67);
+    assert_eq!(machine.on_key_down(67, at(2000)), None);
+    machine.on_key_up(67);
+    machine.on_key_up(17);
+
+    // Ctrl+Alt+V 之后按 W
+    assert_eq!(machine.on_key_down(17, at(3000)), None);
+    assert_eq!(machine.on_key_down(18, at(3000)), None);
+    assert_eq!(machine.on_key_down(86, at(3010)), None);
+    machine.on_key_up(86);
+    machine.on_key_up(18);
+    machine.on_key_up(17);
+    assert_eq!(
+        machine.on_key_down(87, at(3500)),
+        Some(TriggerEvent::ChordCompleted("search-tag".into(), 87))
+    );
+    assert!(parse_trigger("chord:search-tag").is_err());
+    assert!(parse_trigger("hold:search-tag:17").is_err());
+}
@@ -0,0 +1,265 @@
+use super::handle::{Handle, MsgTypeEnum};
+use crate::config::Config;
+use crate::log_err;
+use crate::utils::{dirs, json_util};
+use anyhow::{anyhow, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// 可以选择的更新渠道
+pub const CHANNELS: [&str; 2] = ["stable", "beta"];
+/// 后台检查更新的间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+/// 启动后等待一段时间再检查，避免和启动时的其它任务抢占网络
+const FIRST_CHECK_DELAY: Duration = Duration::from_secs(60);
+/// 分批推送：发布后的这段时间内，后台检查按分组逐步提示，手动检查不受影响
+const ROLLOUT_MILLIS: u64 = 72 * 60 * 60 * 1000;
+const ROLLOUT_BUCKETS: u64 = 100;
+const STATE_FILE: &str = "updater.json";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UpdateInfo {
+    pub version: String,
+    pub notes: Option<String>,
+    pub channel: String,
+    /// 发布时间（毫秒），update.json 中没有 pub_date 时为空
+    pub pub_date: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UpdateStatus {
+    pub channel: String,
+    pub last_checked: Option<u64>,
+    pub available: Option<UpdateInfo>,
+    /// 已在后台下载并安装，重启后生效
+    pub installed: bool,
+    /// 推迟提醒到该时间，期间后台检查到的更新不提示
+    pub deferred_until: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// 重启后仍然保留的状态
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct Saved {
+    /// 分批推送的分组，第一次启动时随机生成
+    bucket: u64,
+    deferred_until: Option<u64>,
+}
+
+fn saved_path() -> Result<PathBuf> {
+    Ok(dirs::app_data_dir()?.join(STATE_FILE))
+}
+
+fn saved() -> &'static Mutex<Saved> {
+    static SAVED: OnceCell<Mutex<Saved>> = OnceCell::new();
+    SAVED.get_or_init(|| {
+        let saved = saved_path()
+            .and_then(|path| json_util::read(&path))
+            .unwrap_or_else(|_| {
+                let saved = Saved {
+                    bucket: rand::thread_rng().gen_range(0..ROLLOUT_BUCKETS),
+                    deferred_until: None,
+                };
+                log_err!(saved_path().and_then(|path| json_util::save(&path, &saved)));
+                saved
+            });
+        Mutex::new(saved)
+    })
+}
+
+fn state() -> &'static Mutex<UpdateStatus> {
+    static STATE: OnceCell<Mutex<UpdateStatus>> = OnceCell::new();
+    STATE.get_or_init(|| {
+        Mutex::new(UpdateStatus {
+            deferred_until: saved().lock().deferred_until,
+            ..UpdateStatus::default()
+        })
+    })
+}
+
+pub fn channel() -> String {
+    Config::common()
+        .latest()
+        .update_channel
+        .clone()
+        .unwrap_or_else(|| CHANNELS[0].into())
+}
+
+/// update.json 中各渠道的平台名，stable 使用默认的 darwin-aarch64 等，beta 为 darwin-aarch64-beta
+fn target(channel: &str) -> Option<String> {
+    if channel == CHANNELS[0] {
+        return None;
+    }
+    let os = match std::env::consts::OS {
+        "macos" => "darwin",
+        os => os,
+    };
+    Some(format!("{}-{}-{}", os, std::env::consts::ARCH, channel))
+}
+
+pub fn status() -> UpdateStatus {
+    let mut status = state().lock().clone();
+    status.channel = channel();
+    status
+}
+
+/// 修改渠道后之前检查到的更新不再适用
+pub fn on_channel_changed() {
+    let mut status = state().lock();
+    status.available = None;
+    status.error = None;
+}
+
+fn now() -> u64 {
+    chrono::Local::now().timestamp_millis() as u64
+}
+
+fn builder(channel: &str) -> Result<tauri::updater::UpdateBuilder<tauri::Wry>> {
+    let app_handle = Handle::global()
+        .app_handle
+        .lock()
+        .clone()
+        .ok_or(anyhow!("the app is not ready"))?;
+    // 不触发内置的更新对话框，由设置页面或通知提示
+    let builder = tauri::updater::builder(app_handle).skip_events();
+    Ok(match target(channel) {
+        Some(target) => builder.target(target),
+        None => builder,
+    })
+}
+
+/// 在当前渠道检查更新，并更新状态
+pub async fn check() -> Result<UpdateStatus> {
+    let channel = channel();
+    let res = builder(&channel)?.check().await;
+    {
+        let mut status = state().lock();
+        status.last_checked = Some(now());
+        match &res {
+            Ok(update) => {
+                status.error = None;
+                status.available = match update.is_update_available() {
+                    true => Some(UpdateInfo {
+                        version: update.latest_version().to_string(),
+                        notes: update.body().cloned(),
+                        channel: channel.clone(),
+                        pub_date: update
+                            .date()
+                            .map(|date| date.unix_timestamp().max(0) as u64 * 1000),
+                    }),
+                    false => None,
+                };
+            }
+            Err(err) => status.error = Some(err.to_string()),
+        }
+    }
+    res?;
+    Ok(status())
+}
+
+/// 下载并安装当前渠道的更新，Windows 上会退出并启动安装程序
+pub async fn install() -> Result<()> {
+    let channel = channel();
+    let update = builder(&channel)?.check().await?;
+    if !update.is_update_available() {
+        return Ok(());
+    }
+    update.download_and_install().await?;
+    let mut status = state().lock();
+    status.installed = true;
+    status.available = None;
+    Ok(())
+}
+
+/// 推迟提醒，hours 为 0 时取消推迟，重启后仍然有效
+pub fn defer(hours: u64) {
+    let deferred_until = match hours {
+        0 => None,
+        hours => Some(now() + hours * 60 * 60 * 1000),
+    };
+    state().lock().deferred_until = deferred_until;
+    let saved = {
+        let mut saved = saved().lock();
+        saved.deferred_until = deferred_until;
+        saved.clone()
+    };
+    log_err!(saved_path().and_then(|path| json_util::save(&path, &saved)));
+}
+
+fn is_deferred(status: &UpdateStatus, now: u64) -> bool {
+    status.deferred_until.map_or(false, |until| now < until)
+}
+
+/// 分组 bucket 在发布 bucket% 的推送时间后才提示
+fn is_rolled_out(info: &UpdateInfo, bucket: u64, now: u64) -> bool {
+    info.pub_date.map_or(true, |pub_date| {
+        now >= pub_date + ROLLOUT_MILLIS / ROLLOUT_BUCKETS * bucket.min(ROLLOUT_BUCKETS - 1)
+    })
+}
+
+/// 后台定时检查，发现更新时通知窗口
+/// 开启 auto_download_updates 时在 macOS、Linux 上直接下载安装，重启后生效
+/// Windows 的安装程序会关闭正在运行的应用，只提示不自动安装
+pub fn start() {
+    thread::spawn(|| {
+        thread::sleep(FIRST_CHECK_DELAY);
+        loop {
+            let config = { Config::common().latest().clone() };
+            if config.auto_check_updates.unwrap_or(true) {
+                log_err!(check_in_background(
+                    config.auto_download_updates.unwrap_or(false)
+                ));
+            }
+            thread::sleep(CHECK_INTERVAL);
+        }
+    });
+}
+
+fn check_in_background(auto_download: bool) -> Result<()> {
+    let current = tauri::async_runtime::block_on(check())?;
+    let bucket = saved().lock().bucket;
+    let rolled_out = current
+        .available
+        .as_ref()
+        .map_or(false, |info| is_rolled_out(info, bucket, now()));
+    if !rolled_out || current.installed || is_deferred(&current, now()) {
+        return Ok(());
+    }
+    if auto_download && !cfg!(target_os = "windows") {
+        tauri::async_runtime::block_on(install())?;
+    }
+    // 前端根据 installed 提示重启或安装
+    Handle::notice_to_window(MsgTypeEnum::UpdateAvailable, status())?;
+    Ok(())
+}
+
+#[test]
+fn test_update_channel() {
+    assert_eq!(target("stable"), None);
+    let beta = target("beta").unwrap();
+    assert!(beta.ends_with(&format!("-{}-beta", std::env::consts::ARCH)));
+    assert!(!beta.starts_with("macos"));
+    let mut status = UpdateStatus {
+        deferred_until: Some(100),
+        ..UpdateStatus::default()
+    };
+    assert!(is_deferred(&status, 99));
+    assert!(!is_deferred(&status, 100));
+    status.deferred_until = None;
+    assert!(!is_deferred(&status, 0));
+    let info = UpdateInfo {
+        pub_date: Some(1000),
+        ..UpdateInfo::default()
+    };
+    assert!(is_rolled_out(&info, 0, 1000));
+    let half = 1000 + ROLLOUT_MILLIS / 2;
+    assert!(!is_rolled_out(&info, 50, half - 1));
+    assert!(is_rolled_out(&info, 50, half));
+    assert!(is_rolled_out(&info, 99, 1000 + ROLLOUT_MILLIS));
+    assert!(is_rolled_out(&UpdateInfo::default(), 99, 0));
+}
@@ -0,0 +1,108 @@
+use crate::config::Config;
+use anyhow::{bail, Result};
+
+/// 默认去掉的跟踪参数，以 * 结尾的按前缀匹配
+pub const DEFAULT_PARAMS: [&str; 14] = [
+    "utm_*", "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "igshid",
+    "mc_cid", "mc_eid", "_hsenc", "_hsmi", "mkt_tok",
+];
+
+pub fn validate_param(param: &str) -> Result<()> {
+    let name = param.strip_suffix('*').unwrap_or(param);
+    if name.is_empty()
+        || name.contains(['=', '&', '?', '#', '*'])
+        || name.contains(char::is_whitespace)
+    {
+        bail!("invalid url cleaning parameter \"{}\"", param);
+    }
+    Ok(())
+}
+
+/// 参数名不区分大小写
+fn is_tracking(key: &str, params: &[String]) -> bool {
+    let key = key.to_lowercase();
+    params.iter().any(|param| {
+        let param = param.to_lowercase();
+        match param.strip_suffix('*') {
+            Some(prefix) => key.starts_with(prefix),
+            None => key == param,
+        }
+    })
+}
+
+/// 整条内容是 http(s) 链接时去掉其中的跟踪参数，没有变化时返回 None
+pub fn clean(content: &str, params: &[String]) -> Option<String> {
+    let url = content.trim();
+    if !(url.starts_with("https://") || url.starts_with("http://"))
+        || url.contains(char::is_whitespace)
+    {
+        return None;
+    }
+    let (url, fragment) = match url.split_once('#') {
+        Some((url, fragment)) => (url, Some(fragment)),
+        None => (url, None),
+    };
+    let (base, query) = url.split_once('?')?;
+    let pairs: Vec<&str> = query.split('&').filter(|p| !p.is_empty()).collect();
+    let kept: Vec<&str> = pairs
+        .iter()
+        .copied()
+        .filter(|pair| !is_tracking(pair.split('=').next().unwrap_or(pair), params))
+        .collect();
+    if kept.len() == pairs.len() {
+        return None;
+    }
+    let mut cleaned = base.to_string();
+    if !kept.is_empty() {
+        cleaned.push('?');
+        cleaned.push_str(&kept.join("&"));
+    }
+    if let Some(fragment) = fragment {
+        cleaned.push('#');
+        cleaned.push_str(fragment);
+    }
+    Some(cleaned)
+}
+
+/// 采集流程中使用，未开启时返回 None，开启时返回清理后的链接
+pub fn process_capture(content: &str) -> Option<String> {
+    let (enabled, extra) = {
+        let config = Config::common().latest();
+        (
+            config.enable_url_cleaning.unwrap_or(false),
+            config.url_clean_params.clone().unwrap_or_default(),
+        )
+    };
+    if !enabled {
+        return None;
+    }
+    let mut params: Vec<String> = DEFAULT_PARAMS.iter().map(|p| p.to_string()).collect();
+    params.extend(extra);
+    clean(content, &params)
+}
+
+#[test]
+fn test_clean_url() {
+    let params: Vec<String> = DEFAULT_PARAMS.iter().map(|p| p.to_string()).collect();
+    assert_eq!(
+        clean(
+            "https://example.com/a?id=1&utm_source=x&UTM_Medium=y&fbclid=z#top",
+            &params
+        ),
+        Some("https://example.com/a?id=1#top".into())
+    );
+    assert_eq!(
+        clean("https://example.com/?gclid=1", &params),
+        Some("https://example.com/".into())
+    );
+    assert_eq!(clean("https://example.com/?id=1", &params), None);
+    assert_eq!(clean("see https://example.com/?gclid=1", &params), None);
+    let extra = vec!["ref".to_string()];
+    assert_eq!(
+        clean("http://example.com?ref=home&q=2", &extra),
+        Some("http://example.com?q=2".into())
+    );
+    assert!(validate_param("utm_*").is_ok());
+    assert!(validate_param("a=b").is_err());
+    assert!(validate_param("*").is_err());
+}
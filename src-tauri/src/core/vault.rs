@@ -0,0 +1,215 @@
+use crate::config::{self, CommonConfig, Config};
+use crate::core::biometric;
+use crate::core::clipboard::ImageDataDB;
+use crate::core::database::{Record, SqliteDB};
+use crate::core::thumbnail;
+use crate::utils::crypto_util::{self, SecretKey};
+use crate::utils::{dirs, json_util, string_util};
+use anyhow::{bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::Window;
+
+/// 列表中 secure 记录展示的占位内容
+pub const SECURE_MASK: &str = "••••••••";
+const VERIFY_TEXT: &str = "lanaya-vault";
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+struct VaultFile {
+    salt: String,
+    verifier: String,
+}
+
+/// 加密前的原始数据，content、content_preview 和清理前的原始链接一起加密存入 content 字段
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+struct SecurePayload {
+    content: String,
+    content_preview: Option<String>,
+    #[serde(default)]
+    original_content: Option<String>,
+}
+
+pub struct Vault {
+    key: Arc<Mutex<Option<SecretKey>>>,
+}
+
+impl Vault {
+    pub fn global() -> &'static Vault {
+        static VAULT: OnceCell<Vault> = OnceCell::new();
+
+        VAULT.get_or_init(|| Vault {
+            key: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    #[allow(unused)]
+    pub fn is_initialized() -> bool {
+        dirs::vault_path().map(|p| p.exists()).unwrap_or(false)
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.key.lock().is_some()
+    }
+
+    /// 解锁保险箱，第一次解锁时使用该口令初始化
+    pub fn unlock(&self, passphrase: &str) -> Result<()> {
+        if passphrase.is_empty() {
+            bail!("passphrase can not be empty");
+        }
+        let path = dirs::vault_path()?;
+        let key = if path.exists() {
+            let file: VaultFile = json_util::read(&path)?;
+            let salt = string_util::try_base64_decode(&file.salt)?;
+            let key = crypto_util::derive_key(passphrase, &salt);
+            let verifier = string_util::try_base64_decode(&file.verifier)?;
+            if !key.verify(VERIFY_TEXT.as_bytes(), &verifier) {
+                bail!("wrong passphrase");
+            }
+            key
+        } else {
+            let salt = crypto_util::random_bytes(16);
+            let key = crypto_util::derive_key(passphrase, &salt);
+            json_util::save(
+                &path,
+                &VaultFile {
+                    salt: string_util::base64_encode(&salt),
+                    verifier: string_util::base64_encode(&key.sign(VERIFY_TEXT.as_bytes())),
+                },
+            )?;
+            key
+        };
+        *self.key.lock() = Some(key);
+        Ok(())
+    }
+
+    pub fn lock(&self) {
+        *self.key.lock() = None;
+    }
+
+    /// 开启生物识别解锁：需要先用口令解锁，再把密钥存入系统钥匙串
+    pub async fn enable_biometric(&self, window: &Window) -> Result<()> {
+        let key = self.key()?;
+        biometric::verify(window, "enable biometric unlock for Lanaya").await?;
+        biometric::save_secret(&key.to_base64())?;
+        config::modify_common_config(CommonConfig {
+            enable_biometric_unlock: Some(true),
+            ..CommonConfig::default()
+        })
+        .await
+    }
+
+    pub async fn disable_biometric(&self) -> Result<()> {
+        biometric::delete_secret()?;
+        config::modify_common_config(CommonConfig {
+            enable_biometric_unlock: Some(false),
+            ..CommonConfig::default()
+        })
+        .await
+    }
+
+    /// 生物识别失败时返回错误，前端应回退到输入口令
+    pub async fn unlock_with_biometric(&self, window: &Window) -> Result<()> {
+        let enable = { Config::common().latest().enable_biometric_unlock };
+        if !enable.unwrap_or(false) {
+            bail!("biometric unlock is not enabled");
+        }
+        biometric::verify(window, "unlock Lanaya secure records").await?;
+        let key = SecretKey::from_base64(&biometric::load_secret()?)?;
+        *self.key.lock() = Some(key);
+        Ok(())
+    }
+
+    fn key(&self) -> Result<SecretKey> {
+        match self.key.lock().as_ref() {
+            Some(key) => Ok(key.clone()),
+            None => bail!("vault is locked"),
+        }
+    }
+
+    /// 备份中的口令文件和本机的是否使用同一个口令，本机还没有设置口令时也视为相同
+    pub fn is_same_vault(content: &str) -> bool {
+        let local: VaultFile = match dirs::vault_path().and_then(|path| json_util::read(&path)) {
+            Ok(local) => local,
+            Err(_) => return true,
+        };
+        match json_util::parse::<VaultFile>(content) {
+            Ok(other) => other.salt == local.salt && other.verifier == local.verifier,
+            Err(_) => false,
+        }
+    }
+
+    /// 加密记录并把列表预览替换为占位内容，已生成的缩略图一起粉碎
+    pub fn secure_record(&self, id: u64) -> Result<()> {
+        let key = self.key()?;
+        let db = SqliteDB::new();
+        let record = db.find_by_id(id)?;
+        if record.is_secure {
+            return Ok(());
+        }
+        let payload = SecurePayload {
+            content_preview: db.find_preview_by_id(id)?,
+            original_content: db.find_original_content(id)?,
+            content: record.content,
+        };
+        let content = key.encrypt(&json_util::stringfy(&payload)?);
+        let content_preview = Self::mask_preview(&record.data_type, &payload);
+        db.update_secure(id, true, content, content_preview, None)?;
+        thumbnail::shred(id)
+    }
+
+    /// 解密记录，恢复原始内容和预览
+    pub fn unsecure_record(&self, id: u64) -> Result<()> {
+        let key = self.key()?;
+        let db = SqliteDB::new();
+        let record = db.find_by_id(id)?;
+        if !record.is_secure {
+            return Ok(());
+        }
+        let payload: SecurePayload = json_util::parse(&key.decrypt(&record.content)?)?;
+        db.update_secure(
+            id,
+            false,
+            payload.content,
+            payload.content_preview.unwrap_or_default(),
+            payload.original_content.as_deref(),
+        )
+    }
+
+    /// 返回 secure 记录的明文内容，未解锁时报错
+    pub fn reveal(&self, record: &Record) -> Result<String> {
+        if !record.is_secure {
+            return Ok(record.content.clone());
+        }
+        let key = self.key()?;
+        let payload: SecurePayload = json_util::parse(&key.decrypt(&record.content)?)?;
+        Ok(payload.content)
+    }
+
+    /// 清理跟踪参数前的原始链接，secure 记录未解锁时报错
+    pub fn reveal_original(&self, record: &Record) -> Result<Option<String>> {
+        if !record.is_secure {
+            return SqliteDB::new().find_original_content(record.id);
+        }
+        let key = self.key()?;
+        let payload: SecurePayload = json_util::parse(&key.decrypt(&record.content)?)?;
+        Ok(payload.original_content)
+    }
+
+    fn mask_preview(data_type: &str, payload: &SecurePayload) -> String {
+        if data_type != "image" {
+            return SECURE_MASK.to_string();
+        }
+        // 图片仅保留宽高，前端仍可解析
+        let image: ImageDataDB = json_util::parse(&payload.content).unwrap_or_default();
+        json_util::stringfy(&ImageDataDB {
+            width: image.width,
+            height: image.height,
+            scale: image.scale,
+            base64: String::new(),
+            gif: None,
+        })
+        .unwrap_or_default()
+    }
+}
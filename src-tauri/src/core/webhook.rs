@@ -0,0 +1,143 @@
+use super::database::{Record, SqliteDB};
+use super::job_queue::Permanent;
+use crate::config::Config;
+use crate::utils::crypto_util;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+
+const TIMEOUT_SECS: u64 = 10;
+
+/// 新记录匹配过滤条件时，POST 到指定地址
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Webhook {
+    pub url: String,
+    /// 签名密钥，设置后带上 X-Lanaya-Signature: sha256=<hmac>
+    pub secret: Option<String>,
+    /// 内容包含该关键字时才发送，不区分大小写
+    pub keyword: Option<String>,
+    /// 只发送某种类型的记录：`text` or `image`
+    pub data_type: Option<String>,
+}
+
+impl Webhook {
+    pub fn validate(&self) -> Result<()> {
+        if !self.url.starts_with("http://") && !self.url.starts_with("https://") {
+            bail!("webhook url must start with http:// or https://");
+        }
+        if let Some(data_type) = &self.data_type {
+            if !["text", "image"].contains(&data_type.as_str()) {
+                bail!("unsupported webhook data type \"{}\"", data_type);
+            }
+        }
+        Ok(())
+    }
+
+    fn matches(&self, record: &Record) -> bool {
+        // 加密记录不外发
+        if record.is_secure {
+            return false;
+        }
+        if let Some(data_type) = &self.data_type {
+            if *data_type != record.data_type {
+                return false;
+            }
+        }
+        match &self.keyword {
+            Some(keyword) if !keyword.is_empty() => {
+                record.data_type == "text"
+                    && record
+                        .content
+                        .to_lowercase()
+                        .contains(&keyword.to_lowercase())
+            }
+            _ => true,
+        }
+    }
+}
+
+/// 由后台任务队列调用，只发送一次，失败后由队列负责重试
+pub fn deliver(url: &str, id: u64) -> Result<()> {
+    // 任务入队后 webhook 可能已从配置中删除
+    let webhook = match Config::common()
+        .latest()
+        .webhooks
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|w| w.url == url)
+    {
+        Some(webhook) => webhook,
+        None => return Ok(()),
+    };
+    let record = SqliteDB::new().find_by_id(id)?;
+    if !webhook.matches(&record) {
+        return Ok(());
+    }
+    let body = json!({
+        "event": "record.created",
+        "record": {
+            "id": record.id,
+            "content": record.content,
+            "data_type": record.data_type,
+            "tags": record.tags,
+            "create_time": record.create_time,
+        },
+    })
+    .to_string();
+    let mut req = ureq::post(&webhook.url)
+        .timeout(Duration::from_secs(TIMEOUT_SECS))
+        .set("Content-Type", "application/json")
+        .set("X-Lanaya-Event", "record.created");
+    if let Some(secret) = webhook.secret.as_ref().filter(|s| !s.is_empty()) {
+        let signature = crypto_util::hmac_sha256_hex(secret.as_bytes(), body.as_bytes());
+        req = req.set("X-Lanaya-Signature", &format!("sha256={}", signature));
+    }
+    match req.send_string(&body) {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(status, _)) if !should_retry(status) => {
+            Err(Permanent(format!("server responded {}", status)).into())
+        }
+        Err(ureq::Error::Status(status, _)) => bail!("server responded {}", status),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// 网络错误、限流和服务端错误才重试，其它 4xx 重试也不会成功
+fn should_retry(status: u16) -> bool {
+    status == 408 || status == 429 || status >= 500
+}
+
+#[test]
+fn test_webhook_matches() {
+    let record = Record {
+        content: "Meeting at 10AM".into(),
+        data_type: "text".into(),
+        ..Default::default()
+    };
+    let webhook = Webhook {
+        url: "https://example.com/hook".into(),
+        keyword: Some("meeting".into()),
+        ..Default::default()
+    };
+    assert!(webhook.validate().is_ok());
+    assert!(webhook.matches(&record));
+    assert!(!webhook.matches(&Record {
+        is_secure: true,
+        ..record.clone()
+    }));
+    let image_only = Webhook {
+        data_type: Some("image".into()),
+        ..webhook.clone()
+    };
+    assert!(!image_only.matches(&record));
+    assert!(Webhook {
+        url: "ftp://example.com".into(),
+        ..webhook
+    }
+    .validate()
+    .is_err());
+    assert!(should_retry(503));
+    assert!(!should_retry(404));
+}
@@ -1,6 +1,8 @@
 pub enum WindowType {
     Config,
     Main,
+    Preview,
+    Palette,
 }
 pub struct WindowInfo {
     pub label: String,
@@ -51,4 +53,36 @@ impl WindowInfo {
             skip_taskbar: false,
         }
     }
+    pub fn preview() -> Self {
+        WindowInfo {
+            label: "preview".into(),
+            title: "Preview".into(),
+            url: "/preview".into(),
+            width: 480.0,
+            height: 600.0,
+            resizable: true,
+            // minimizable: false,
+            fullscreenable: false,
+            always_on_top: true,
+            transparent: false,
+            decorations: true,
+            skip_taskbar: false,
+        }
+    }
+    pub fn palette() -> Self {
+        WindowInfo {
+            label: "palette".into(),
+            title: "Lanaya".into(),
+            url: "/palette".into(),
+            width: 600.0,
+            height: 360.0,
+            resizable: false,
+            // minimizable: false,
+            fullscreenable: false,
+            always_on_top: true,
+            transparent: true,
+            decorations: false,
+            skip_taskbar: true,
+        }
+    }
 }
@@ -0,0 +1,149 @@
+use crate::log_err;
+use crate::utils::{dirs, json_util};
+use anyhow::Result;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use tauri::{Monitor, Window, WindowEvent};
+
+const STATE_FILE: &str = "window_state.json";
+
+/// 窗口左上角相对于所在屏幕左上角的位置（物理像素），屏幕排列变化后仍然有效
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub struct Offset {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// 主窗口的位置和前端的布局，单独保存，不写入配置文件
+/// 主窗口的大小跟随内容，不能拖动调整，不保存
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct WindowState {
+    /// 按屏幕记住的位置，popup_position 为 remember 时使用
+    #[serde(default)]
+    pub positions: HashMap<String, Offset>,
+    /// 由前端决定内容，如最近的收藏、标签过滤条件，不含搜索的文字
+    #[serde(default)]
+    pub layout: Map<String, Value>,
+}
+
+/// 内存中的状态和是否有未写入的修改
+fn state() -> &'static Mutex<(WindowState, bool)> {
+    static STATE: OnceCell<Mutex<(WindowState, bool)>> = OnceCell::new();
+    STATE.get_or_init(|| {
+        let state = state_path()
+            .and_then(|path| Ok(fs::read_to_string(path)?))
+            .and_then(|content| json_util::parse(&content))
+            .unwrap_or_default();
+        Mutex::new((state, false))
+    })
+}
+
+fn state_path() -> Result<PathBuf> {
+    Ok(dirs::app_home_dir()?.join(STATE_FILE))
+}
+
+/// 屏幕的名称，取不到时用位置和尺寸区分
+fn monitor_key(name: Option<&str>, position: (i32, i32), size: (u32, u32)) -> String {
+    match name.filter(|n| !n.is_empty()) {
+        Some(name) => name.to_string(),
+        None => format!("{},{} {}x{}", position.0, position.1, size.0, size.1),
+    }
+}
+
+fn key_of(monitor: &Monitor) -> String {
+    let (position, size) = (monitor.position(), monitor.size());
+    monitor_key(
+        monitor.name().map(|n| n.as_str()),
+        (position.x, position.y),
+        (size.width, size.height),
+    )
+}
+
+/// 在该屏幕上次的位置，换算为屏幕坐标
+pub fn position_on(monitor: &Monitor) -> Option<(f64, f64)> {
+    let offset = *state().lock().0.positions.get(&key_of(monitor))?;
+    let origin = monitor.position();
+    Some((origin.x as f64 + offset.x, origin.y as f64 + offset.y))
+}
+
+pub fn layout() -> Map<String, Value> {
+    state().lock().0.layout.clone()
+}
+
+/// 合并前端的布局，值为 null 时删除该项
+pub fn save_layout(patch: Map<String, Value>) {
+    let mut state = state().lock();
+    for (key, value) in patch {
+        match value {
+            Value::Null => state.0.layout.remove(&key),
+            value => state.0.layout.insert(key, value),
+        };
+    }
+    state.1 = true;
+}
+
+fn flush() -> Result<()> {
+    let content = {
+        let mut state = state().lock();
+        if !state.1 {
+            return Ok(());
+        }
+        state.1 = false;
+        json_util::stringfy(&state.0)?
+    };
+    fs::write(state_path()?, content)?;
+    Ok(())
+}
+
+/// 移动时更新内存中的状态，失去焦点或关闭时写入文件
+pub fn track(window: &Window) {
+    let window = window.clone();
+    window.clone().on_window_event(move |event| match event {
+        WindowEvent::Moved(position) => {
+            if let Ok(Some(monitor)) = window.current_monitor() {
+                let origin = monitor.position();
+                let offset = Offset {
+                    x: (position.x - origin.x) as f64,
+                    y: (position.y - origin.y) as f64,
+                };
+                let mut state = state().lock();
+                state.0.positions.insert(key_of(&monitor), offset);
+                state.1 = true;
+            }
+        }
+        WindowEvent::Focused(false)
+        | WindowEvent::CloseRequested { .. }
+        | WindowEvent::Destroyed => {
+            log_err!(flush());
+        }
+        _ => {}
+    });
+}
+
+#[test]
+fn test_window_state() {
+    assert_eq!(
+        monitor_key(Some("DELL U2720Q"), (0, 0), (3840, 2160)),
+        "DELL U2720Q"
+    );
+    assert_eq!(
+        monitor_key(Some(""), (-1920, 0), (1920, 1080)),
+        "-1920,0 1920x1080"
+    );
+    assert_eq!(monitor_key(None, (0, 0), (2560, 1440)), "0,0 2560x1440");
+    let mut patch = Map::new();
+    patch.insert("filter".into(), Value::String("f:".into()));
+    patch.insert("columns".into(), serde_json::json!([120, 480]));
+    save_layout(patch);
+    let mut patch = Map::new();
+    patch.insert("columns".into(), Value::Null);
+    save_layout(patch);
+    let layout = layout();
+    assert_eq!(layout.get("filter"), Some(&Value::String("f:".into())));
+    assert!(!layout.contains_key("columns"));
+}
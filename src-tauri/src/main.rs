@@ -9,6 +9,7 @@ use window_shadows::set_shadow;
 use crate::config::Config;
 use crate::core::clipboard;
 use crate::core::database::SqliteDB;
+use crate::core::handle::MsgTypeEnum;
 use crate::core::sysopt;
 use crate::core::tray;
 mod cmds;
@@ -17,31 +18,155 @@ mod core;
 mod utils;
 
 fn main() {
+    unsafe {
+        log_err!(utils::dirs::init_portable_flag());
+    }
+    core::crash::install();
+    // 由浏览器扩展启动时只转发消息，不启动界面
+    core::native_messaging::run_host();
+    // lanaya list / get / add 等命令不启动界面
+    core::cli::run();
+    tauri_plugin_deep_link::prepare("com.church.lanaya");
     let app = tauri::Builder::default()
         .setup(|app| {
             set_up(app);
             Ok(())
         })
+        // 重复启动时不再创建新的监听和数据库连接，参数转发给已运行的实例
+        .plugin(tauri_plugin_single_instance::init(|_app, argv, _cwd| {
+            core::deep_link::handle_args(argv);
+        }))
+        .register_uri_scheme_protocol(core::thumbnail::PROTOCOL, core::thumbnail::handle_protocol)
         .system_tray(SystemTray::new())
         .on_system_tray_event(core::tray::Tray::on_system_tray_event)
         .invoke_handler(tauri::generate_handler![
             cmds::get_common_config,
             cmds::set_common_config,
+            cmds::get_settings,
+            cmds::get_keymap,
+            cmds::patch_settings,
             cmds::change_language,
             cmds::change_record_limit,
             cmds::change_auto_launch,
+            cmds::set_autostart,
+            cmds::is_autostart_enabled,
             cmds::change_theme_mode,
+            cmds::get_appearance,
+            cmds::set_appearance,
             cmds::change_hotkeys,
+            cmds::set_global_shortcut,
+            cmds::get_hotkey_conflicts,
+            cmds::change_audit_log,
             cmds::clear_data,
             cmds::insert_record,
             cmds::insert_if_not_exist,
             cmds::find_all_record,
             cmds::mark_favorite,
+            cmds::set_favorite,
+            cmds::set_locked,
+            cmds::set_note,
             cmds::save_tags,
             cmds::find_by_key,
+            cmds::get_history_page,
+            cmds::get_timeline,
+            cmds::get_top_apps,
+            cmds::get_top_domains,
+            cmds::test_capture_rules,
+            cmds::get_original_content,
+            cmds::should_hide_previews,
+            cmds::link_records,
+            cmds::unlink_records,
+            cmds::get_linked_records,
+            cmds::copy_with_links,
+            cmds::create_backup,
+            cmds::restore_backup,
+            cmds::apply_sync_changes,
+            cmds::get_sync_changes,
+            cmds::list_sync_conflicts,
+            cmds::resolve_sync_conflict,
+            cmds::start_pairing,
+            cmds::cancel_pairing,
+            cmds::unpair_sync_device,
+            cmds::send_to_device,
+            cmds::pair_with_remote,
+            cmds::remove_sync_remote,
+            cmds::get_source_url,
+            cmds::find_similar_images,
+            cmds::compress_existing_content,
+            cmds::check_database_integrity,
+            cmds::repair_database,
+            cmds::get_event_stats,
+            cmds::purge_analytics,
+            cmds::get_store_stats,
+            cmds::get_crash_reports,
+            cmds::clear_crash_reports,
+            cmds::get_update_status,
+            cmds::check_for_update,
+            cmds::install_update,
+            cmds::defer_update,
+            cmds::export_snippets,
+            cmds::sync_espanso,
+            cmds::set_expansion_trigger,
+            cmds::get_expansion_triggers,
+            cmds::start_macro_recording,
+            cmds::stop_macro_recording,
+            cmds::cancel_macro_recording,
+            cmds::save_macro,
+            cmds::get_macros,
+            cmds::delete_macro,
+            cmds::replay_macro,
+            cmds::paste_records,
+            cmds::get_full_content,
             cmds::delete_over_limit,
             cmds::write_to_clip,
+            cmds::copy_record,
+            cmds::copy_record_as,
+            cmds::get_paste_target_app,
             cmds::delete_by_id,
+            cmds::delete_by_ids,
+            cmds::shred_records,
+            cmds::purge_matching,
+            cmds::restore_clipboard_at,
+            cmds::restore_previous_clipboard,
+            cmds::take_screenshot,
+            cmds::undo_last_operation,
+            cmds::list_trash,
+            cmds::restore_from_trash,
+            cmds::unlock_vault,
+            cmds::lock_vault,
+            cmds::is_vault_unlocked,
+            cmds::is_biometric_available,
+            cmds::enable_biometric_unlock,
+            cmds::disable_biometric_unlock,
+            cmds::unlock_vault_with_biometric,
+            cmds::set_record_secure,
+            cmds::reveal_record,
+            cmds::find_audit_log,
+            cmds::clear_audit_log,
+            cmds::toggle_preview_window,
+            cmds::preview_record,
+            cmds::read_content_chunk,
+            cmds::palette_search,
+            cmds::palette_execute,
+            cmds::get_plugins,
+            cmds::reload_plugins,
+            cmds::get_capture_scripts,
+            cmds::take_pending_search,
+            cmds::is_database_ready,
+            cmds::get_onboarding_status,
+            cmds::check_permissions,
+            cmds::open_permission_settings,
+            cmds::complete_onboarding,
+            cmds::export_settings,
+            cmds::import_settings,
+            cmds::get_custom_actions,
+            cmds::run_custom_action,
+            cmds::open_in_editor,
+            cmds::toggle_pin,
+            cmds::is_window_pinned,
+            cmds::get_window_layout,
+            cmds::save_window_layout,
+            cmds::run_diagnostics,
         ])
         .build(tauri::generate_context!())
         .expect("error while build tauri application");
@@ -51,6 +176,7 @@ fn main() {
             api.prevent_exit();
         }
         tauri::RunEvent::Exit => {
+            SqliteDB::release_owner();
             app_handle.exit(0);
         }
         _ => {}
@@ -62,11 +188,54 @@ fn set_up(app: &mut App) {
     app.set_activation_policy(tauri::ActivationPolicy::Accessory);
     let window = app.get_window("main").unwrap();
     set_shadow(&window, true).expect("Unsupported platform!");
+    window.on_window_event(|event| {
+        if let tauri::WindowEvent::ThemeChanged(theme) = event {
+            core::appearance::on_system_theme_changed(*theme);
+        }
+    });
+    core::window_state::track(&window);
     core::handle::Handle::global().init(app.app_handle());
     log_err!(Config::init_config());
+    config::watch_config_file();
+    core::crash::submit_pending();
+    // 托盘先不带最近记录显示，数据库就绪后再补上
     log_err!(tray::Tray::update_systray(&app.app_handle()));
+    log_err!(core::plugin::load_all());
+    tray::Tray::ensure_reachable(&app.app_handle());
     log_err!(sysopt::Sysopt::global().init_launch());
-    let _ = core::handle::Handle::refresh_global_shortcut();
-    SqliteDB::init();
+    log_err!(core::handle::Handle::refresh_global_shortcut());
+    core::trigger::refresh();
     clipboard::ClipboardWatcher::start();
+    core::cloud_clipboard::start();
+    log_err!(core::http_api::init_token());
+    log_err!(core::http_api::refresh());
+    log_err!(core::pairing::refresh());
+    core::sync_client::start();
+    core::ipc::start();
+    core::job_queue::start();
+    core::updater::start();
+    core::retention::start_trash_purge();
+    core::espanso::start();
+    core::idle::start();
+    core::deep_link::register();
+    // 建表、迁移放到后台，窗口和托盘不用等待，查询会等到数据库就绪
+    std::thread::spawn(|| {
+        if let Err(err) = SqliteDB::init() {
+            // 只读打开时没有可用的数据库，提示后退出
+            core::capture_notify::notify(err.to_string());
+            if let Some(app_handle) = core::handle::Handle::global().app_handle.lock().as_ref() {
+                app_handle.exit(1);
+            }
+            return;
+        }
+        log_err!(core::handle::Handle::update_systray());
+        log_err!(core::handle::Handle::notice_to_window(
+            MsgTypeEnum::DatabaseReady,
+            true
+        ));
+        log_err!(core::thumbnail::clean_orphans(&SqliteDB::new()));
+        core::job_queue::enqueue_phash_backfill();
+        // 没有运行中的实例时，lanaya copy <id> 由本进程处理
+        core::cli::handle_args(&std::env::args().collect::<Vec<String>>());
+    });
 }
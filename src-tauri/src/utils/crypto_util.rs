@@ -0,0 +1,134 @@
+use super::string_util;
+use anyhow::{bail, Result};
+use crypto::aes::{self, KeySize};
+use crypto::hmac::Hmac;
+use crypto::mac::{Mac, MacResult};
+use crypto::pbkdf2::pbkdf2;
+use crypto::sha2::Sha256;
+use crypto::symmetriccipher::SynchronousStreamCipher;
+use rand::RngCore;
+
+const PBKDF2_ROUNDS: u32 = 100_000;
+const IV_LEN: usize = 16;
+const MAC_LEN: usize = 32;
+
+/// 由口令派生出的密钥，enc 用于 AES-256-CTR 加密，mac 用于 HMAC-SHA256 校验
+#[derive(Clone)]
+pub struct SecretKey {
+    enc: [u8; 32],
+    mac: [u8; 32],
+}
+
+pub fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+/// HMAC-SHA256 签名，输出十六进制
+pub fn hmac_sha256_hex(key: &[u8], data: &[u8]) -> String {
+    let mut hmac = Hmac::new(Sha256::new(), key);
+    hmac.input(data);
+    string_util::to_hex(hmac.result().code())
+}
+
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> SecretKey {
+    let mut output = [0u8; 64];
+    let mut hmac = Hmac::new(Sha256::new(), passphrase.as_bytes());
+    pbkdf2(&mut hmac, salt, PBKDF2_ROUNDS, &mut output);
+    let mut enc = [0u8; 32];
+    let mut mac = [0u8; 32];
+    enc.copy_from_slice(&output[..32]);
+    mac.copy_from_slice(&output[32..]);
+    SecretKey { enc, mac }
+}
+
+impl SecretKey {
+    pub fn to_base64(&self) -> String {
+        let mut bytes = self.enc.to_vec();
+        bytes.extend_from_slice(&self.mac);
+        string_util::base64_encode(&bytes)
+    }
+
+    /// 随机生成，用于和配对的设备共享
+    pub fn random() -> Self {
+        let bytes = random_bytes(64);
+        let mut enc = [0u8; 32];
+        let mut mac = [0u8; 32];
+        enc.copy_from_slice(&bytes[..32]);
+        mac.copy_from_slice(&bytes[32..]);
+        SecretKey { enc, mac }
+    }
+
+    pub fn from_base64(data: &str) -> Result<Self> {
+        let bytes = string_util::try_base64_decode(data)?;
+        if bytes.len() != 64 {
+            bail!("invalid secret key");
+        }
+        let mut enc = [0u8; 32];
+        let mut mac = [0u8; 32];
+        enc.copy_from_slice(&bytes[..32]);
+        mac.copy_from_slice(&bytes[32..]);
+        Ok(SecretKey { enc, mac })
+    }
+
+    pub fn sign(&self, data: &[u8]) -> Vec<u8> {
+        let mut hmac = Hmac::new(Sha256::new(), &self.mac);
+        hmac.input(data);
+        hmac.result().code().to_vec()
+    }
+
+    /// 定长时间比较，避免时序攻击
+    pub fn verify(&self, data: &[u8], signature: &[u8]) -> bool {
+        let mut hmac = Hmac::new(Sha256::new(), &self.mac);
+        hmac.input(data);
+        hmac.result() == MacResult::new(signature)
+    }
+
+    /// 输出格式: base64(iv + 密文 + hmac)
+    pub fn encrypt(&self, plain: &str) -> String {
+        let mut payload = random_bytes(IV_LEN);
+        let mut cipher = aes::ctr(KeySize::KeySize256, &self.enc, &payload);
+        let mut encrypted = vec![0u8; plain.len()];
+        cipher.process(plain.as_bytes(), &mut encrypted);
+        payload.extend_from_slice(&encrypted);
+        let signature = self.sign(&payload);
+        payload.extend_from_slice(&signature);
+        string_util::base64_encode(&payload)
+    }
+
+    pub fn decrypt(&self, data: &str) -> Result<String> {
+        let payload = string_util::try_base64_decode(data)?;
+        if payload.len() < IV_LEN + MAC_LEN {
+            bail!("invalid encrypted content");
+        }
+        let (body, signature) = payload.split_at(payload.len() - MAC_LEN);
+        if !self.verify(body, signature) {
+            bail!("encrypted content verify failed");
+        }
+        let (iv, encrypted) = body.split_at(IV_LEN);
+        let mut cipher = aes::ctr(KeySize::KeySize256, &self.enc, iv);
+        let mut plain = vec![0u8; encrypted.len()];
+        cipher.process(encrypted, &mut plain);
+        Ok(String::from_utf8(plain)?)
+    }
+}
+
+#[test]
+fn test_encrypt_decrypt() {
+    let key = derive_key("passphrase", b"salt");
+    let encrypted = key.encrypt("hello lanaya");
+    assert_ne!(encrypted, "hello lanaya");
+    assert_eq!(key.decrypt(&encrypted).unwrap(), "hello lanaya");
+    let other = derive_key("other", b"salt");
+    assert!(other.decrypt(&encrypted).is_err());
+}
+
+#[test]
+fn test_hmac_sha256_hex() {
+    // RFC 4231 test case 2
+    assert_eq!(
+        hmac_sha256_hex(b"Jefe", b"what do ya want for nothing?"),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+}
@@ -1,32 +1,54 @@
+use super::{crypto_util, string_util};
 use anyhow::Result;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 use tauri::api::path::home_dir;
+use tauri::utils::platform::current_exe;
 
 static APP_DIR: &str = "lanaya";
 static CONFIG_FILE: &str = "config.json";
+static VAULT_FILE: &str = "vault.json";
+/// 临时文件目录，相对于应用目录，备份时不包含
+pub static TEMP_DIR: &str = "tmp";
+/// 程序目录下存在该文件时以便携模式运行
+static PORTABLE_FILE: &str = "PORTABLE";
+static PORTABLE_ARG: &str = "--portable";
+
+/// 便携模式下配置、数据库、图片都放在程序所在目录
+pub static mut PORTABLE_FLAG: bool = false;
+
+fn app_exe_dir() -> Result<PathBuf> {
+    let app_exe = dunce::canonicalize(current_exe()?)?;
+    let app_dir = app_exe
+        .parent()
+        .ok_or(anyhow::anyhow!("failed to get the portable app dir"))?;
+    Ok(PathBuf::from(app_dir))
+}
+
+/// initialize portable flag
+/// 需要在读取任何配置和数据之前调用
+pub unsafe fn init_portable_flag() -> Result<()> {
+    if std::env::args().any(|arg| arg == PORTABLE_ARG) {
+        PORTABLE_FLAG = true;
+        return Ok(());
+    }
+    let app_dir = app_exe_dir()?;
+    if app_dir.join(PORTABLE_FILE).exists() || app_dir.join(".config").join(PORTABLE_FILE).exists()
+    {
+        PORTABLE_FLAG = true;
+    }
+    Ok(())
+}
+
+pub fn is_portable() -> bool {
+    unsafe { PORTABLE_FLAG }
+}
 
 /// get the app home dir
 pub fn app_home_dir() -> Result<PathBuf> {
-    #[cfg(target_os = "windows")]
-    unsafe {
-        use tauri::utils::platform::current_exe;
-
-        if !PORTABLE_FLAG {
-            Ok(home_dir()
-                .ok_or(anyhow::anyhow!("failed to get app home dir"))?
-                .join(".config")
-                .join(APP_DIR))
-        } else {
-            let app_exe = current_exe()?;
-            let app_exe = dunce::canonicalize(app_exe)?;
-            let app_dir = app_exe
-                .parent()
-                .ok_or(anyhow::anyhow!("failed to get the portable app dir"))?;
-            Ok(PathBuf::from(app_dir).join(".config").join(APP_DIR))
-        }
+    if is_portable() {
+        return Ok(app_exe_dir()?.join(".config").join(APP_DIR));
     }
-
-    #[cfg(not(target_os = "windows"))]
     Ok(home_dir()
         .ok_or(anyhow::anyhow!("failed to get the app home dir"))?
         .join(".config")
@@ -34,7 +56,6 @@ pub fn app_home_dir() -> Result<PathBuf> {
 }
 
 /// logs dir
-#[allow(unused)]
 pub fn app_logs_dir() -> Result<PathBuf> {
     Ok(app_home_dir()?.join("logs"))
 }
@@ -43,6 +64,11 @@ pub fn config_path() -> Result<PathBuf> {
     Ok(app_home_dir()?.join(CONFIG_FILE))
 }
 
+/// secure records 的口令校验文件
+pub fn vault_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join(VAULT_FILE))
+}
+
 #[allow(unused)]
 pub fn app_data_dir() -> Result<PathBuf> {
     Ok(app_home_dir()?.join("data"))
@@ -52,6 +78,58 @@ pub fn app_data_img_dir() -> Result<PathBuf> {
     Ok(app_data_dir()?.join("img"))
 }
 
+/// 插件目录，每个插件一个子目录
+pub fn plugins_dir() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join("plugins"))
+}
+
+/// 处理新复制内容的脚本目录
+pub fn scripts_dir() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join("scripts"))
+}
+
+/// 自定义语言包目录，<language>.json
+pub fn locales_dir() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join("locales"))
+}
+
+/// 自定义提示音目录，<event>.wav
+pub fn sounds_dir() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join("sounds"))
+}
+
+/// 临时文件目录（外部编辑、截图），不放在其它用户也能写入的系统临时目录，Unix 上只有本用户可以访问
+pub fn app_temp_dir() -> Result<PathBuf> {
+    let dir = app_home_dir()?.join(TEMP_DIR);
+    create_private_dir(&dir)?;
+    Ok(dir)
+}
+
+/// 临时目录下还不存在的文件路径，文件名带随机后缀
+pub fn temp_file_path(prefix: &str, ext: &str) -> Result<PathBuf> {
+    let suffix = string_util::to_hex(&crypto_util::random_bytes(8));
+    Ok(app_temp_dir()?.join(format!("{}-{}.{}", prefix, suffix, ext)))
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    if !dir.exists() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+    }
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    Ok(())
+}
+
 #[test]
 fn test() {
     println!("app_home_dir: {:?}", app_home_dir());
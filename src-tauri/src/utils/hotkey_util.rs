@@ -1,3 +1,44 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::fmt;
+
+/// 全局快捷键注册失败的诊断信息，suggestions 为可用的其它组合
+#[derive(Debug, Clone, Serialize)]
+pub struct HotkeyConflict {
+    pub func: String,
+    pub keys: Vec<u32>,
+    pub shortcut: String,
+    pub reason: String,
+    pub suggestions: Vec<Vec<u32>>,
+}
+
+#[derive(Debug)]
+pub struct HotkeyRegisterError(pub Vec<HotkeyConflict>);
+
+impl fmt::Display for HotkeyRegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg: Vec<String> = self
+            .0
+            .iter()
+            .map(|c| {
+                let mut msg = format!("{} ({}): {}", c.func, c.shortcut, c.reason);
+                if !c.suggestions.is_empty() {
+                    let names: Vec<String> = c
+                        .suggestions
+                        .iter()
+                        .map(|keys| get_short_cut_name(keys.clone(), true))
+                        .collect();
+                    msg += &format!(", try {}", names.join(" / "));
+                }
+                msg
+            })
+            .collect();
+        write!(f, "failed to register hotkeys: {}", msg.join("; "))
+    }
+}
+
+impl std::error::Error for HotkeyRegisterError {}
+
 /// keyCode to keyName
 /// @param {Number} keyCode
 /// @return {String} keyName
@@ -63,6 +104,10 @@ fn modifier_code_to_name(modifier_code: u32) -> String {
     .to_string()
 }
 
+pub fn is_modifier(key_code: u32) -> bool {
+    !modifier_code_to_name(key_code).is_empty()
+}
+
 pub fn get_short_cut_name(key_code_arr: Vec<u32>, is_first_word_upper_case: bool) -> String {
     let mut key_str = String::new();
     let mut modifier = String::new();
@@ -94,6 +139,67 @@ pub fn get_short_cut_name(key_code_arr: Vec<u32>, is_first_word_upper_case: bool
     modifier + &key_str + &normal_key
 }
 
+/// 解析配置中的快捷键
+/// format: {func}:{keyCode}+{keyCode}，keyCode 为空表示未设置
+pub fn parse_hotkey(hotkey: &str) -> Result<(String, Vec<u32>)> {
+    let (func, keys) = match hotkey.split_once(':') {
+        Some(res) => res,
+        None => bail!("malformed hotkey \"{}\"", hotkey),
+    };
+    if func.is_empty() {
+        bail!("malformed hotkey \"{}\"", hotkey);
+    }
+    if keys.is_empty() {
+        return Ok((func.to_string(), vec![]));
+    }
+    let mut key_codes = vec![];
+    for key in keys.split('+') {
+        match key.parse::<u32>() {
+            Ok(code) => key_codes.push(code),
+            Err(_) => bail!("malformed hotkey \"{}\"", hotkey),
+        }
+    }
+    Ok((func.to_string(), key_codes))
+}
+
+/// 快捷键被占用时可以尝试的组合：保留主键，换一组修饰键
+pub fn alternative_combos(keys: &[u32]) -> Vec<Vec<u32>> {
+    let normal: Vec<u32> = keys.iter().copied().filter(|k| !is_modifier(*k)).collect();
+    if normal.is_empty() {
+        return vec![];
+    }
+    let mut current: Vec<u32> = keys.iter().copied().filter(|k| is_modifier(*k)).collect();
+    current.sort_unstable();
+    let candidates: [&[u32]; 6] = [
+        &[16, 91],
+        &[18, 91],
+        &[16, 17],
+        &[17, 18],
+        &[16, 18, 91],
+        &[16, 17, 18],
+    ];
+    candidates
+        .iter()
+        .filter(|modifiers| **modifiers != current.as_slice())
+        .map(|modifiers| [*modifiers, normal.as_slice()].concat())
+        .collect()
+}
+
+/// 找出按键组合相同的两个快捷键，按键顺序不影响
+pub fn find_conflict(hotkeys: &[(String, Vec<u32>)]) -> Option<(String, String)> {
+    let mut seen: Vec<(&String, Vec<u32>)> = vec![];
+    for (func, keys) in hotkeys {
+        let mut keys = keys.clone();
+        keys.sort_unstable();
+        keys.dedup();
+        if let Some((other, _)) = seen.iter().find(|(_, k)| *k == keys) {
+            return Some(((*other).clone(), func.clone()));
+        }
+        seen.push((func, keys));
+    }
+    None
+}
+
 fn capitalized(name: &str, is_first_word_upper_case: bool) -> String {
     let name = name.to_lowercase();
     if !is_first_word_upper_case {
@@ -119,3 +225,40 @@ fn test() {
     let short_cut_name = get_short_cut_name(hot_key_arr, true);
     println!("{}", short_cut_name);
 }
+
+#[test]
+fn test_parse_hotkey() {
+    let (func, keys) = parse_hotkey("global-shortcut:16+67+91").unwrap();
+    assert_eq!(func, "global-shortcut");
+    assert_eq!(keys, vec![16, 67, 91]);
+    assert_eq!(parse_hotkey("clear-history:").unwrap().1, Vec::<u32>::new());
+    assert!(parse_hotkey("global-shortcut").is_err());
+    assert!(parse_hotkey("global-shortcut:16+a").is_err());
+}
+
+#[test]
+fn test_find_conflict() {
+    let hotkeys = vec![
+        ("global-shortcut".to_string(), vec![16, 67, 91]),
+        ("clear-clipboard".to_string(), vec![17, 88]),
+    ];
+    assert_eq!(find_conflict(&hotkeys), None);
+    let mut hotkeys = hotkeys;
+    hotkeys.push(("toggle-monitoring".to_string(), vec![91, 67, 16]));
+    assert_eq!(
+        find_conflict(&hotkeys),
+        Some((
+            "global-shortcut".to_string(),
+            "toggle-monitoring".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_alternative_combos() {
+    let combos = alternative_combos(&[91, 16, 67]);
+    assert_eq!(combos.len(), 5);
+    assert!(combos.iter().all(|c| c.last() == Some(&67)));
+    assert!(!combos.contains(&vec![16, 91, 67]));
+    assert!(alternative_combos(&[17, 16]).is_empty());
+}
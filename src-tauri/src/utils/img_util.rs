@@ -50,3 +50,127 @@ pub fn base64_to_rgba8(base64: &str) -> Result<ImageData> {
         Err(_) => Err(anyhow::anyhow!("decode image error")),
     }
 }
+
+/// GIF 中的帧数，只数到 2 帧，格式不对时返回 None
+fn gif_frame_count(data: &[u8]) -> Option<usize> {
+    if data.len() < 13 || !(data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")) {
+        return None;
+    }
+    // 全局颜色表
+    let mut pos = 13 + color_table_len(data[10]);
+    let mut frames = 0;
+    loop {
+        match *data.get(pos)? {
+            // 扩展块：标识、标签、数据子块
+            0x21 => pos = skip_sub_blocks(data, pos + 2)?,
+            // 图像描述符：10 字节、局部颜色表、LZW 最小码长、数据子块
+            0x2C => {
+                frames += 1;
+                if frames > 1 {
+                    return Some(frames);
+                }
+                pos += 10 + color_table_len(*data.get(pos + 9)?) + 1;
+                pos = skip_sub_blocks(data, pos)?;
+            }
+            0x3B => return Some(frames),
+            _ => return None,
+        }
+    }
+}
+
+fn color_table_len(flags: u8) -> usize {
+    match flags & 0x80 {
+        0 => 0,
+        _ => 3 * (1 << ((flags & 0x07) + 1)),
+    }
+}
+
+fn skip_sub_blocks(data: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *data.get(pos)? as usize;
+        pos += 1 + len;
+        if len == 0 {
+            return Some(pos);
+        }
+    }
+}
+
+/// 有多帧的 GIF 才需要保存原始数据
+pub fn is_animated_gif(data: &[u8]) -> bool {
+    gif_frame_count(data).map_or(false, |frames| frames > 1)
+}
+
+/// 差值哈希（dHash）：缩小为 9x8 的灰度图，比较每行相邻像素的亮度
+/// 重新编码、轻微缩放后的同一张图片哈希相同或只差几位
+pub fn dhash(img: &ImageData) -> Result<u64> {
+    let rgba = image::RgbaImage::from_raw(img.width as u32, img.height as u32, img.bytes.to_vec())
+        .ok_or_else(|| anyhow::anyhow!("invalid image data"))?;
+    let gray = image::imageops::grayscale(&rgba);
+    let small = image::imageops::resize(&gray, 9, 8, image::imageops::FilterType::Triangle);
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let bit = small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | bit as u64;
+        }
+    }
+    Ok(hash)
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+#[test]
+fn test_dhash() {
+    // 从左到右变亮的渐变图，放大一倍后哈希不变
+    let gradient = |width: usize, height: usize| {
+        let mut bytes = Vec::with_capacity(width * height * 4);
+        for _ in 0..height {
+            for x in 0..width {
+                let v = (x * 255 / width) as u8;
+                bytes.extend_from_slice(&[v, v, v, 255]);
+            }
+        }
+        ImageData {
+            width,
+            height,
+            bytes: bytes.into(),
+        }
+    };
+    let small = dhash(&gradient(90, 80)).unwrap();
+    let large = dhash(&gradient(180, 160)).unwrap();
+    assert_eq!(hamming_distance(small, large), 0);
+    // 反向的渐变每一位都不同
+    let mut reversed = gradient(90, 80);
+    reversed.bytes = reversed
+        .bytes
+        .chunks(4)
+        .flat_map(|p| [255 - p[0], 255 - p[1], 255 - p[2], 255])
+        .collect::<Vec<u8>>()
+        .into();
+    assert_eq!(hamming_distance(small, dhash(&reversed).unwrap()), 64);
+}
+
+#[test]
+fn test_animated_gif() {
+    // 1x1 的 GIF，两色全局颜色表
+    let mut gif = b"GIF89a\x01\x00\x01\x00\x80\x00\x00".to_vec();
+    gif.extend_from_slice(&[0, 0, 0, 255, 255, 255]);
+    let frame = [
+        0x21, 0xF9, 0x04, 0x00, 0x0A, 0x00, 0x00, 0x00, // 图形控制扩展
+        0x2C, 0, 0, 0, 0, 1, 0, 1, 0, 0x00, // 图像描述符
+        0x02, 0x02, 0x44, 0x01, 0x00, // 图像数据
+    ];
+    let mut single = gif.clone();
+    single.extend_from_slice(&frame);
+    single.push(0x3B);
+    assert_eq!(gif_frame_count(&single), Some(1));
+    assert!(!is_animated_gif(&single));
+    gif.extend_from_slice(&frame);
+    gif.extend_from_slice(&frame);
+    gif.push(0x3B);
+    assert!(is_animated_gif(&gif));
+    assert!(!is_animated_gif(b"\x89PNG\r\n"));
+    assert!(!is_animated_gif(&gif[..30]));
+}
@@ -12,3 +12,16 @@ macro_rules! log_err {
         }
     };
 }
+
+#[macro_export]
+macro_rules! wrap_err {
+    ($stat: expr) => {
+        match $stat {
+            Ok(a) => Ok(a),
+            Err(err) => {
+                println!("err: {}", err);
+                Err(err.to_string())
+            }
+        }
+    };
+}
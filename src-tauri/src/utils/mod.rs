@@ -1,6 +1,8 @@
+pub mod crypto_util;
 pub mod dirs;
 pub mod hotkey_util;
 pub mod img_util;
 pub mod json_util;
 pub mod log_print;
+pub mod process_util;
 pub mod string_util;
@@ -24,35 +24,227 @@ pub fn base64_decode(base64: &str) -> Vec<u8> {
     general_purpose::STANDARD.decode(base64).unwrap()
 }
 
-// 如果content中 包含key的话，就把key用<b>key</b>高亮起来
+/// 解码外部传入的内容，格式不对时返回错误
+pub fn try_base64_decode(base64: &str) -> anyhow::Result<Vec<u8>> {
+    Ok(general_purpose::STANDARD.decode(base64.trim())?)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// 如果content中 包含key的话，就把key用<b>key</b>高亮起来，和搜索一样忽略大小写和变音符号
 pub fn highlight(key: &str, content: &str) -> String {
+    let key = fold(key);
+    if key.is_empty() {
+        return escape_html(content);
+    }
+    let (folded, spans) = fold_with_spans(content);
     let mut res = String::new();
     let mut start = 0;
-    let mut end;
-
-    while let Some(i) = content[start..].to_lowercase().find(&key.to_lowercase()) {
-        end = start + i;
-        res.push_str(&content[start..end]);
-        res.push_str(&format!(
-            "[highlight]{}[/highlight]",
-            &content[end..end + key.len()]
-        ));
-        start = end + key.len();
-    }
-    res.push_str(&content[start..]);
-    res = escape_html(&res);
-    res = res
-        .replace("[highlight]", "<b>")
-        .replace("[/highlight]", "</b>");
+    let mut from = 0;
+    while let Some(i) = folded[from..].find(&key) {
+        let (begin, end) = (spans[from + i].0, spans[from + i + key.len() - 1].1);
+        from += i + key.len();
+        // ß 展开的 ss 被上一次命中用掉了一半
+        if begin < start {
+            continue;
+        }
+        res.push_str(&escape_html(&content[start..begin]));
+        res.push_str(&format!("<b>{}</b>", escape_html(&content[begin..end])));
+        start = end;
+    }
+    res.push_str(&escape_html(&content[start..]));
+    res
+}
+
+/// 折叠后的内容，以及其中每个字节对应的原始字符的字节范围
+fn fold_with_spans(content: &str) -> (String, Vec<(usize, usize)>) {
+    let mut folded = String::new();
+    let mut spans: Vec<(usize, usize)> = vec![];
+    for (i, c) in content.char_indices() {
+        let before = folded.len();
+        push_folded(&mut folded, c);
+        spans.extend((before..folded.len()).map(|_| (i, i + c.len_utf8())));
+    }
+    (folded, spans)
+}
+
+/// 截取第一个命中位置附近的内容，保留命中之前的 before 个字符，共 len 个字符
+/// 和 highlight 一样按折叠后的形式查找，没有命中时返回 None
+pub fn snippet(key: &str, content: &str, before: usize, len: usize) -> Option<String> {
+    let key = fold(key);
+    if key.is_empty() {
+        return None;
+    }
+    let (folded, spans) = fold_with_spans(content);
+    let begin = spans[folded.find(&key)?].0;
+    let start = match before {
+        0 => begin,
+        n => content[..begin]
+            .char_indices()
+            .rev()
+            .nth(n - 1)
+            .map_or(0, |(i, _)| i),
+    };
+    Some(content[start..].chars().take(len).collect())
+}
+
+// 搜索时比较的形式：转为小写并去掉常见的变音符号，如 Café -> cafe、straße -> strasse
+pub fn fold(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        push_folded(&mut res, c);
+    }
     res
 }
 
+fn push_folded(res: &mut String, c: char) {
+    for lower in c.to_lowercase() {
+        match fold_char(lower) {
+            Some(base) => res.push_str(base),
+            None => res.push(lower),
+        }
+    }
+}
+
+fn fold_char(c: char) -> Option<&'static str> {
+    let base = match c {
+        // 组合用的变音符号
+        '\u{300}'..='\u{36f}' => "",
+        'à'..='å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è'..='ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì'..='ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò'..='ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ſ' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'þ' => "th",
+        'ù'..='ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        'ς' => "σ",
+        _ => return None,
+    };
+    Some(base)
+}
+
+// 列表预览：跳过空行、去掉行尾空白，取开头的非空行，最多 max_chars 个字符
+pub fn smart_preview(content: &str, max_chars: usize) -> String {
+    let mut res = String::new();
+    let mut len = 0;
+    for line in content.lines().map(str::trim_end) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if !res.is_empty() {
+            res.push('\n');
+            len += 1;
+        }
+        if len >= max_chars {
+            break;
+        }
+        let line: String = line.chars().take(max_chars - len).collect();
+        len += line.chars().count();
+        res.push_str(&line);
+    }
+    res.trim_end().to_string()
+}
+
 fn escape_html(html: &str) -> String {
     html.replace("<", "&lt;").replace(">", "&gt;")
 }
 
+// 纯文本转成 html，保留换行
+pub fn text_to_html(text: &str) -> String {
+    let escaped = escape_html(&text.replace('&', "&amp;"));
+    format!("<div>{}</div>", escaped.replace('\n', "<br>"))
+}
+
+// url 百分号解码，`+` 视为空格
+pub fn url_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => res.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => {
+                        res.push(b);
+                        i += 2;
+                    }
+                    Err(_) => res.push(b'%'),
+                }
+            }
+            b => res.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&res).to_string()
+}
+
 #[test]
 fn test_highlight() {
     let res = highlight("hello", "hello worldhello");
     println!("{}", res);
 }
+
+#[test]
+fn test_fold_search() {
+    assert_eq!(fold("Café"), "cafe");
+    assert_eq!(fold("STRASSE"), fold("straße"));
+    assert_eq!(fold("Crème Brûlée"), "creme brulee");
+    // 分解形式的变音符号
+    assert_eq!(fold("Cafe\u{301}"), "cafe");
+    assert_eq!(
+        highlight("cafe", "Un Café <b>CAFE</b>"),
+        "Un <b>Café</b> &lt;b&gt;<b>CAFE</b>&lt;/b&gt;"
+    );
+    assert_eq!(
+        highlight("strasse", "Hauptstraße 1"),
+        "Haupt<b>straße</b> 1"
+    );
+    assert_eq!(highlight("s", "ß"), "<b>ß</b>");
+    assert_eq!(highlight("", "<a>"), "&lt;a&gt;");
+}
+
+#[test]
+fn test_snippet() {
+    // 折叠后的长度和原文不同时也截取到原文中的命中位置
+    let content = format!("{}Hauptstraße 1", "ß".repeat(10));
+    assert_eq!(
+        snippet("strasse", &content, 2, 8),
+        Some("ptstraße".to_string())
+    );
+    assert_eq!(snippet("CAFE", "Un café", 0, 4), Some("café".to_string()));
+    assert_eq!(
+        snippet("cafe", "Un café", 10, 100),
+        Some("Un café".to_string())
+    );
+    assert_eq!(snippet("tea", "Un café", 10, 100), None);
+}
+
+#[test]
+fn test_smart_preview() {
+    assert_eq!(
+        smart_preview("\n\n  fn main() {  \n\n\n  }\n", 100),
+        "  fn main() {\n  }"
+    );
+    assert_eq!(smart_preview("abc\ndef", 5), "abc\nd");
+    assert_eq!(smart_preview("中文内容", 2), "中文");
+}
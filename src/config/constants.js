@@ -18,6 +18,10 @@ export const themeOptions = [
     name: "Dark",
     value: "dark",
   },
+  {
+    name: "System",
+    value: "system",
+  },
 ];
 
 export const recordLimitOptions = [
@@ -27,20 +31,69 @@ export const recordLimitOptions = [
   { name: "300", value: 300 },
 ];
 
+// 0 表示不单独限制
+export const imageLimitOptions = [
+  { name: "-", value: 0 },
+  { name: "20", value: 20 },
+  { name: "50", value: 50 },
+  { name: "100", value: 100 },
+];
+
+// 恢复备份时如何处理当前的记录，name 为 i18n key
+export const restoreStrategyOptions = [
+  { name: "config.common.restore_replace", value: "replace" },
+  { name: "config.common.restore_keep_newest", value: "keep_newest" },
+  { name: "config.common.restore_union", value: "union_by_hash" },
+];
+
+export const snippetFormatOptions = [
+  { name: "VS Code", value: "vscode", file: "lanaya.code-snippets" },
+  { name: "espanso", value: "espanso", file: "lanaya.yml" },
+  { name: "TextExpander", value: "textexpander", file: "lanaya.csv" },
+];
+
+export const updateChannelOptions = [
+  { name: "config.about.channel_stable", value: "stable" },
+  { name: "config.about.channel_beta", value: "beta" },
+];
+
 export const hotkeys_func_enum = {
   COPY: "copy",
   QUICK_COPY: "quick-copy",
   MOVE_SELECTED: "move-selected",
+  MOVE_UP: "move-up",
+  MOVE_DOWN: "move-down",
+  DELETE: "delete",
   CLOSE_WINDOW: "close-window",
   GLOBAL_SHORTCUT: "global-shortcut",
   CLEAR_HISTORY: "clear-history",
+  QUICK_SEARCH: "quick-search",
+  PASTE_PLAIN_TEXT: "paste-plain-text",
+  SEARCH_TAG: "search-tag",
+  TOGGLE_MONITORING: "toggle-monitoring",
+  CLEAR_CLIPBOARD: "clear-clipboard",
+  FAVORITE_LATEST: "favorite-latest",
+  OPEN_SNIPPETS: "open-snippets",
+  RESTORE_CLIPBOARD: "restore-clipboard",
+  SCREENSHOT_REGION: "screenshot-region",
+  SCREENSHOT_WINDOW: "screenshot-window",
+  TOGGLE_PIN: "toggle-pin",
+};
+
+// 高级触发方式，配置在 triggers 中
+export const trigger_kind_enum = {
+  DOUBLE_TAP: "double-tap",
+  CHORD: "chord",
 };
 
 /**
  *  { keymap: ["⏎"], tips: "hotkeys.copy" },
     { keymap: ["⌘"], tips: "hotkeys.quick-copy" },
-    { keymap: ["↑", "↓"], tips: "hotkeys.move-selected" },
+    { keymap: ["↑"], tips: "hotkeys.move-up" },
+    { keymap: ["↓"], tips: "hotkeys.move-down" },
+    { keymap: ["⌃", "Del"], tips: "hotkeys.delete" },
     { keymap: ["Esc"], tips: "hotkeys.close-window" },
+ * 与后端 default 按键方案一致，读取 keymap 失败时使用
  */
 export const defaultHotkeys = [
   {
@@ -52,11 +105,25 @@ export const defaultHotkeys = [
     keys: [91],
   },
   {
-    func: hotkeys_func_enum.MOVE_SELECTED,
-    keys: [38, 40],
+    func: hotkeys_func_enum.MOVE_UP,
+    keys: [38],
+  },
+  {
+    func: hotkeys_func_enum.MOVE_DOWN,
+    keys: [40],
+  },
+  {
+    func: hotkeys_func_enum.DELETE,
+    keys: [17, 46],
   },
   {
     func: hotkeys_func_enum.CLOSE_WINDOW,
     keys: [27],
   },
 ];
+
+export const keymapProfileOptions = [
+  { name: "Default", value: "default" },
+  { name: "Vim", value: "vim" },
+  { name: "Emacs", value: "emacs" },
+];
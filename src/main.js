@@ -4,6 +4,7 @@ import App from "./App.vue";
 import router from "./router/router.js";
 import { listenLanguageChange } from "./service/globalListener";
 import { i18n, setLanguage } from "./i18n";
+import { initAppearance } from "./service/appearance";
 const app = createApp(App);
 app.use(router);
 app.use(i18n);
@@ -11,3 +12,4 @@ app.mount("#app");
 listenLanguageChange((data) => {
   setLanguage(data);
 });
+initAppearance();
@@ -2,10 +2,14 @@ import { createRouter, createWebHistory } from "vue-router";
 
 import Main from "../views/Main.vue";
 import Config from "../views/Config.vue";
+import Preview from "../views/Preview.vue";
+import Palette from "../views/Palette.vue";
 
 const routes = [
   { path: "/", component: Main },
   { path: "/config", component: Config },
+  { path: "/preview", component: Preview },
+  { path: "/palette", component: Palette },
 ];
 
 const router = createRouter({
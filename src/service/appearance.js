@@ -0,0 +1,22 @@
+import { getAppearance } from "./cmds";
+import { listenThemeChange } from "./globalListener";
+
+// 外观设置保存在后端，所有窗口启动时读取并跟随变化
+export const applyAppearance = (appearance) => {
+  const root = document.documentElement;
+  root.classList.toggle("dark", appearance.theme === "dark");
+  root.style.setProperty("--accent-color", appearance.accent_color);
+  root.style.setProperty("--list-font-size", `${appearance.font_size}px`);
+  root.style.opacity = appearance.window_opacity;
+};
+
+export const initAppearance = async () => {
+  try {
+    applyAppearance(await getAppearance());
+  } catch (err) {
+    console.error(err);
+  }
+  await listenThemeChange((appearance) => {
+    applyAppearance(appearance);
+  });
+};
@@ -1,4 +1,4 @@
-import { invoke } from "@tauri-apps/api/tauri";
+import { invoke, convertFileSrc } from "@tauri-apps/api/tauri";
 import { sendNotice } from "@/service/msg";
 
 export async function getCommonConfig() {
@@ -9,6 +9,18 @@ export async function setCommonConfig(config) {
   return invoke("set_common_config", { config });
 }
 
+export async function getSettings() {
+  return invoke("get_settings");
+}
+
+export async function getKeymap() {
+  return invoke("get_keymap");
+}
+
+export async function patchSettings(patch) {
+  return invoke("patch_settings", { patch });
+}
+
 export async function setLanguage(language) {
   return invoke("change_language", { language });
 }
@@ -18,17 +30,41 @@ export async function setRecordLimit(limit) {
 }
 
 export async function setAutoLaunch(enable) {
-  return invoke("change_auto_launch", { enable });
+  return invoke("set_autostart", { enable });
+}
+
+export async function isAutoLaunchEnabled() {
+  return invoke("is_autostart_enabled");
 }
 
 export async function setThemeMode(themeMode) {
   return invoke("change_theme_mode", { themeMode });
 }
 
+export async function getAppearance() {
+  return invoke("get_appearance");
+}
+
+export async function setAppearance(patch) {
+  return invoke("set_appearance", { patch });
+}
+
 export async function setHotkeys(hotkeys) {
   return invoke("change_hotkeys", { hotkeys });
 }
 
+export async function setAuditLog(enable, limit) {
+  return invoke("change_audit_log", { enable, limit });
+}
+
+export async function setGlobalShortcut(keys) {
+  return invoke("set_global_shortcut", { keys });
+}
+
+export async function getHotkeyConflicts() {
+  return invoke("get_hotkey_conflicts");
+}
+
 export async function clearData() {
   return invoke("clear_data");
 }
@@ -49,6 +85,45 @@ export async function markFavorite(id) {
   return invoke("mark_favorite", { id });
 }
 
+export async function setFavorite(ids, value) {
+  return invoke("set_favorite", { ids, value });
+}
+
+// 粉碎删除，无法撤销
+export async function shredRecords(ids) {
+  return invoke("shred_records", { ids });
+}
+
+// 先 dryRun 预览匹配的条数，确认后再删除
+export async function purgeMatching(pattern, regex = false, dryRun = true) {
+  return invoke("purge_matching", { pattern, regex, dryRun });
+}
+
+// time 为毫秒时间戳
+export async function restoreClipboardAt(time) {
+  return invoke("restore_clipboard_at", { time });
+}
+
+export async function restorePreviousClipboard() {
+  return invoke("restore_previous_clipboard");
+}
+
+export async function takeScreenshot(mode) {
+  return invoke("take_screenshot", { mode });
+}
+
+export async function setLocked(ids, value) {
+  return invoke("set_locked", { ids, value });
+}
+
+export async function setNote(id, note) {
+  return invoke("set_note", { id, note });
+}
+
+export async function clearNote(id) {
+  return invoke("set_note", { id, note: null });
+}
+
 export async function saveTags(id, tags) {
   return invoke("save_tags", { id, tags: tags.join(",") });
 }
@@ -57,6 +132,24 @@ export async function findByKey(query) {
   return invoke("find_by_key", { query });
 }
 
+// 图片记录的缩略图地址，由后端 thumb 协议按需生成
+export function thumbnailUrl(id, size) {
+  return `${convertFileSrc(String(id), "thumb")}?size=${size}`;
+}
+
+export async function getHistoryPage(query) {
+  return invoke("get_history_page", { query });
+}
+
+// req: { days, max_days, limit_per_day, key, is_favorite, tags, preview_length, lazy_images }
+export async function getTimeline(req = {}) {
+  return invoke("get_timeline", { req });
+}
+
+export async function getFullContent(id) {
+  return invoke("get_full_content", { id });
+}
+
 export async function deleteOverLimit(limit) {
   return invoke("delete_over_limit", { limit });
 }
@@ -66,6 +159,370 @@ export async function writeToClip(id) {
   sendNotice("", "Copy!");
 }
 
+export async function copyRecord(id, format = "plain") {
+  return invoke("copy_record", { id, format });
+}
+
 export async function deleteById(id) {
   return invoke("delete_by_id", { id });
 }
+
+export async function deleteByIds(ids) {
+  return invoke("delete_by_ids", { ids });
+}
+
+// 撤销最近一次删除或清空，返回恢复的条数
+export async function undoLastOperation() {
+  return invoke("undo_last_operation");
+}
+
+// 回收站中的记录，加密记录的 content 为 null
+export async function listTrash() {
+  return invoke("list_trash");
+}
+
+// 从回收站恢复记录，返回恢复的条数
+export async function restoreFromTrash(ids) {
+  return invoke("restore_from_trash", { ids });
+}
+
+export async function unlockVault(passphrase) {
+  return invoke("unlock_vault", { passphrase });
+}
+
+export async function lockVault() {
+  return invoke("lock_vault");
+}
+
+export async function isVaultUnlocked() {
+  return invoke("is_vault_unlocked");
+}
+
+export async function isBiometricAvailable() {
+  return invoke("is_biometric_available");
+}
+
+export async function enableBiometricUnlock() {
+  return invoke("enable_biometric_unlock");
+}
+
+export async function disableBiometricUnlock() {
+  return invoke("disable_biometric_unlock");
+}
+
+export async function unlockVaultWithBiometric() {
+  return invoke("unlock_vault_with_biometric");
+}
+
+export async function setRecordSecure(id, secure) {
+  return invoke("set_record_secure", { id, secure });
+}
+
+export async function revealRecord(id) {
+  return invoke("reveal_record", { id });
+}
+
+export async function findAuditLog(query) {
+  return invoke("find_audit_log", { query });
+}
+
+export async function clearAuditLog() {
+  return invoke("clear_audit_log");
+}
+
+export async function togglePreviewWindow() {
+  return invoke("toggle_preview_window");
+}
+
+export async function previewRecord(id) {
+  return invoke("preview_record", { id });
+}
+
+export async function readContentChunk(id, offset, len) {
+  return invoke("read_content_chunk", { id, offset, len });
+}
+
+export async function paletteSearch(key, requestId) {
+  return invoke("palette_search", { key, requestId });
+}
+
+export async function paletteExecute(kind, id) {
+  return invoke("palette_execute", { kind, id });
+}
+
+export async function getPlugins() {
+  return invoke("get_plugins");
+}
+
+export async function reloadPlugins() {
+  return invoke("reload_plugins");
+}
+
+export async function getCaptureScripts() {
+  return invoke("get_capture_scripts");
+}
+
+export async function takePendingSearch() {
+  return invoke("take_pending_search");
+}
+
+export async function isDatabaseReady() {
+  return invoke("is_database_ready");
+}
+
+export async function getOnboardingStatus() {
+  return invoke("get_onboarding_status");
+}
+
+export async function checkPermissions() {
+  return invoke("check_permissions");
+}
+
+export async function openPermissionSettings(name) {
+  return invoke("open_permission_settings", { name });
+}
+
+export async function completeOnboarding(choice = {}) {
+  return invoke("complete_onboarding", { choice });
+}
+
+export async function exportSettings(path) {
+  return invoke("export_settings", { path });
+}
+
+// dryRun 时只返回会修改的配置项，不会应用
+export async function importSettings(path, dryRun = true) {
+  return invoke("import_settings", { path, dryRun });
+}
+
+// req: { start_time, end_time, limit }，时间为毫秒时间戳
+export async function getTopApps(req = {}) {
+  return invoke("get_top_apps", { req });
+}
+
+export async function getTopDomains(req = {}) {
+  return invoke("get_top_domains", { req });
+}
+
+export async function testCaptureRules(input, rules = null) {
+  return invoke("test_capture_rules", { input, rules });
+}
+
+export async function getOriginalContent(id) {
+  return invoke("get_original_content", { id });
+}
+
+export async function linkRecords(a, b) {
+  return invoke("link_records", { a, b });
+}
+
+export async function unlinkRecords(a, b) {
+  return invoke("unlink_records", { a, b });
+}
+
+export async function getLinkedRecords(id) {
+  return invoke("get_linked_records", { id });
+}
+
+export async function copyWithLinks(id, separator = null) {
+  return invoke("copy_with_links", { id, separator });
+}
+
+export async function shouldHidePreviews() {
+  return invoke("should_hide_previews");
+}
+
+export async function createBackup(path) {
+  return invoke("create_backup", { path });
+}
+
+export async function restoreBackup(path, strategy = "replace") {
+  return invoke("restore_backup", { path, strategy });
+}
+
+export async function applySyncChanges(device, changes) {
+  return invoke("apply_sync_changes", { device, changes });
+}
+
+export async function getSyncChanges(device, since = 0, after = null, limit = null) {
+  return invoke("get_sync_changes", { device, since, after, limit });
+}
+
+export async function listSyncConflicts() {
+  return invoke("list_sync_conflicts");
+}
+
+export async function resolveSyncConflict(id, useRemote) {
+  return invoke("resolve_sync_conflict", { id, useRemote });
+}
+
+export async function startPairing() {
+  return invoke("start_pairing");
+}
+
+export async function cancelPairing() {
+  return invoke("cancel_pairing");
+}
+
+export async function unpairSyncDevice(id) {
+  return invoke("unpair_sync_device", { id });
+}
+
+export async function sendToDevice(deviceId, recordId) {
+  return invoke("send_to_device", { deviceId, recordId });
+}
+
+export async function pairWithRemote(payload, name) {
+  return invoke("pair_with_remote", { payload, name });
+}
+
+export async function removeSyncRemote(deviceId) {
+  return invoke("remove_sync_remote", { deviceId });
+}
+
+export async function getSourceUrl(id) {
+  return invoke("get_source_url", { id });
+}
+
+export async function findSimilarImages(id) {
+  return invoke("find_similar_images", { id });
+}
+
+export async function compressExistingContent() {
+  return invoke("compress_existing_content");
+}
+
+export async function checkDatabaseIntegrity() {
+  return invoke("check_database_integrity");
+}
+
+export async function repairDatabase() {
+  return invoke("repair_database");
+}
+
+export async function getEventStats(req = {}) {
+  return invoke("get_event_stats", { req });
+}
+
+export async function purgeAnalytics() {
+  return invoke("purge_analytics");
+}
+
+export async function getStoreStats() {
+  return invoke("get_store_stats");
+}
+
+export async function getCrashReports() {
+  return invoke("get_crash_reports");
+}
+
+export async function clearCrashReports() {
+  return invoke("clear_crash_reports");
+}
+
+export async function getUpdateStatus() {
+  return invoke("get_update_status");
+}
+
+export async function checkForUpdate() {
+  return invoke("check_for_update");
+}
+
+export async function installUpdate() {
+  return invoke("install_update");
+}
+
+export async function deferUpdate(hours) {
+  return invoke("defer_update", { hours });
+}
+
+export async function exportSnippets(req) {
+  return invoke("export_snippets", { req });
+}
+
+export async function syncEspanso() {
+  return invoke("sync_espanso");
+}
+
+export async function setExpansionTrigger(id, trigger) {
+  return invoke("set_expansion_trigger", { id, trigger });
+}
+
+export async function getExpansionTriggers() {
+  return invoke("get_expansion_triggers");
+}
+
+export async function startMacroRecording(name) {
+  return invoke("start_macro_recording", { name });
+}
+
+export async function stopMacroRecording() {
+  return invoke("stop_macro_recording");
+}
+
+export async function cancelMacroRecording() {
+  return invoke("cancel_macro_recording");
+}
+
+export async function saveMacro(name, steps) {
+  return invoke("save_macro", { name, steps });
+}
+
+export async function getMacros() {
+  return invoke("get_macros");
+}
+
+export async function deleteMacro(id) {
+  return invoke("delete_macro", { id });
+}
+
+export async function replayMacro(id) {
+  return invoke("replay_macro", { id });
+}
+
+// separator 为空时使用设置中的 multi_paste_separator
+export async function pasteRecords(ids, separator = null, autoPaste = false) {
+  return invoke("paste_records", { ids, separator, autoPaste });
+}
+
+// style: "formatted" | "plain"，为空时使用目标应用的默认格式
+export async function copyRecordAs(id, style = null) {
+  return invoke("copy_record_as", { id, style });
+}
+
+export async function getPasteTargetApp() {
+  return invoke("get_paste_target_app");
+}
+
+export async function getCustomActions(dataType = null) {
+  return invoke("get_custom_actions", { dataType });
+}
+
+export async function runCustomAction(name, id) {
+  return invoke("run_custom_action", { name, id });
+}
+
+export async function openInEditor(id, asNew = false) {
+  return invoke("open_in_editor", { id, asNew });
+}
+
+export async function togglePin() {
+  return invoke("toggle_pin");
+}
+
+export async function isWindowPinned() {
+  return invoke("is_window_pinned");
+}
+
+export async function getWindowLayout() {
+  return invoke("get_window_layout");
+}
+
+// 合并保存，值为 null 时删除该项
+export async function saveWindowLayout(layout) {
+  return invoke("save_window_layout", { layout });
+}
+
+export async function runDiagnostics(path = null) {
+  return invoke("run_diagnostics", { path });
+}
@@ -22,6 +22,41 @@ export const listenHotkeysChange = async (consumer) => {
   return unListen;
 };
 
+export const listenKeymapChange = async (consumer) => {
+  const unListen = await listen("lanaya://change-keymap", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenHotkeyConflict = async (consumer) => {
+  const unListen = await listen("lanaya://hotkey-conflict", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenThemeChange = async (consumer) => {
+  const unListen = await listen("lanaya://change-theme", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenConfigReloaded = async (consumer) => {
+  const unListen = await listen("lanaya://config-reloaded", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenDatabaseReady = async (consumer) => {
+  const unListen = await listen("lanaya://database-ready", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
 export const listenClipboardChange = async (consumer) => {
   const unListen = await listen("lanaya://change-clipboard", async (event) => {
     consumer(event.payload);
@@ -29,9 +64,100 @@ export const listenClipboardChange = async (consumer) => {
   return unListen;
 };
 
+export const listenCapturePausedChange = async (consumer) => {
+  const unListen = await listen("lanaya://change-capture-paused", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenScreenSharingChange = async (consumer) => {
+  const unListen = await listen("lanaya://change-screen-sharing", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenRecordAdded = async (consumer) => {
+  const unListen = await listen("lanaya://record-added", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenRecordUpdated = async (consumer) => {
+  const unListen = await listen("lanaya://record-updated", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenRecordDeleted = async (consumer) => {
+  const unListen = await listen("lanaya://record-deleted", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenPreviewRecord = async (consumer) => {
+  const unListen = await listen("lanaya://preview-record", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenPaletteResults = async (consumer) => {
+  const unListen = await listen("lanaya://palette-results", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenSearch = async (consumer) => {
+  const unListen = await listen("lanaya://search", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
 export const listenWindowBlur = async (consumer) => {
   const unlistenBlur = await listen("tauri://blur", async (event) => {
     consumer(event);
   });
   return unlistenBlur;
 };
+
+export const listenSyncDevicePaired = async (consumer) => {
+  const unListen = await listen("lanaya://sync-device-paired", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenUpdateAvailable = async (consumer) => {
+  const unListen = await listen("lanaya://update-available", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenTrashPurged = async (consumer) => {
+  const unListen = await listen("lanaya://trash-purged", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenFavoriteSuggested = async (consumer) => {
+  const unListen = await listen("lanaya://favorite-suggested", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
+
+export const listenPinChanged = async (consumer) => {
+  const unListen = await listen("lanaya://change-pinned", async (event) => {
+    consumer(event.payload);
+  });
+  return unListen;
+};
@@ -1,7 +1,7 @@
 import {
   clearData,
   insertIfNotExist,
-  findAllRecord,
+  getHistoryPage,
   markFavorite,
   saveTags as saveTagsCmd,
   findByKey,
@@ -27,7 +27,8 @@ pub struct QueryReq {
 async function selectPage(searchKey = "", isFavorite = undefined, limit = 300) {
   // 如果 searchKey 以f:开头，那么就是查询收藏的记录
   if (searchKey === "") {
-    return await findAllRecord(limit);
+    let page = await getHistoryPage({ page_size: limit, lazy_images: true });
+    return page.records;
   }
   if (searchKey.startsWith("f:")) {
     isFavorite = true;
@@ -35,6 +36,7 @@ async function selectPage(searchKey = "", isFavorite = undefined, limit = 300) {
   }
   let query = {
     limit,
+    lazy_images: true,
   };
   if (searchKey.startsWith("t:")) {
     query.tags = searchKey.substring(2).split(",").filter(Boolean);
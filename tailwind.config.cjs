@@ -1,6 +1,8 @@
 /** @type {import('tailwindcss').Config} */
 module.exports = {
   content: ["./index.html", "./src/**/*.{vue,js}"],
+  // 深色模式由后端的外观设置决定，见 service/appearance.js
+  darkMode: "class",
   theme: {
     extend: {},
   },